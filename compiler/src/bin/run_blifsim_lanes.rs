@@ -0,0 +1,23 @@
+use bee::common::config::*;
+use bee::testing::blifsim::compare_blif_sim_to_fsim_lanes;
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct LaneArgs {
+    #[clap(flatten)]
+    pub bee_args: Args,
+
+    /// Seed of the random stimuli in lane 0. Lane `l` uses `seed + l`
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+}
+
+fn main() -> std::io::Result<()> {
+    let args = LaneArgs::parse();
+    match compare_blif_sim_to_fsim_lanes(args.bee_args, args.seed) {
+        Ok(_) => { println!("Test Success!"); }
+        _     => { println!("Test Failed!");  }
+    }
+    Ok(())
+}
@@ -0,0 +1,17 @@
+use bee::common::config::*;
+use bee::testing::equiv::prove_equivalence;
+use clap::Parser;
+
+fn main() -> std::io::Result<()> {
+    let args = Args::parse();
+    match prove_equivalence(args) {
+        Ok(_) => {
+            println!("Test Success!");
+            return Ok(());
+        }
+        Err(e) => {
+            println!("Test Failed!");
+            return Err(e);
+        }
+    }
+}
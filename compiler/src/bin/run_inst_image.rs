@@ -0,0 +1,37 @@
+use bee::common::config::*;
+use bee::common::inst_image::*;
+use bee::testing::try_new_circuit;
+use clap::Parser;
+use std::fs;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct ImageArgs {
+    #[clap(flatten)]
+    pub bee_args: Args,
+
+    /// Disassemble this instruction image instead of writing the images of the design.
+    /// The platform config is taken from the design (use --compiled to skip compiling)
+    #[arg(long)]
+    pub disasm: Option<String>,
+}
+
+fn main() -> std::io::Result<()> {
+    let args = ImageArgs::parse();
+    let circuit = try_new_circuit(&args.bee_args)?;
+
+    match &args.disasm {
+        Some(path) => {
+            let image = fs::read(path)?;
+            for dinst in disassemble_image(&image, &circuit.platform_cfg)?.iter() {
+                println!("{}", dinst.disasm());
+            }
+        }
+        None => {
+            for path in save_instruction_images(&circuit)?.iter() {
+                println!("Wrote {}", path);
+            }
+        }
+    }
+    return Ok(());
+}
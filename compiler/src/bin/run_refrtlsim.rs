@@ -1,5 +1,6 @@
 use bee::rtlsim::ref_rtlsim_testharness;
 use bee::common::config::*;
+use bee::common::clocks::parse_clock_args;
 use clap::Parser;
 
 fn main() -> std::io::Result<()> {
@@ -13,6 +14,8 @@ fn main() -> std::io::Result<()> {
         &args.input_stimuli_path,
         &sim_dir,
         &sim_output_file,
+        &args.ref_sim,
+        &parse_clock_args(&args.clocks)?,
     )?;
     Ok(())
 }
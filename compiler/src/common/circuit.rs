@@ -6,6 +6,7 @@ use crate::common::hwgraph::*;
 use crate::fsim::board::Board;
 use crate::rtlsim::vcdparser::FourStateBit;
 use indexmap::IndexMap;
+use serde::Serialize;
 use std::fmt::Debug;
 use std::collections::VecDeque;
 use petgraph::{
@@ -17,13 +18,40 @@ use petgraph::{
 };
 
 
+/// Savings of the netlist optimization passes (`passes::optimize`)
+/// and the LUTs added when lowering the netlist (`passes::lower_gates`)
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct OptStats {
+    /// `ConstLut` inputs folded into LUT tables
+    pub const_inputs_folded: u32,
+
+    /// LUTs that turned out to be constant
+    pub const_luts: u32,
+
+    pub buffers_absorbed: u32,
+    pub inverters_absorbed: u32,
+
+    /// Duplicate LUTs merged by structural hashing
+    pub luts_merged: u32,
+
+    pub nodes_removed: u32,
+
+    /// Gate enables split out into mux LUTs
+    pub gate_enables_split: u32,
+}
+
 #[derive(Default, Clone)]
 pub struct Circuit {
     pub compiler_cfg: CompilerConfig,
     pub platform_cfg: PlatformConfig,
     pub kaminpar_cfg: KaMinParConfig,
     pub graph: HWGraph,
-    pub emul:  EmulatorMapping
+    pub emul:  EmulatorMapping,
+    pub opt_stats: OptStats,
+
+    /// Number of schedules in which each (source, destination) edge was on a
+    /// critical inter-module path (`passes::repartition`)
+    pub partition_feedback: IndexMap<(String, String), u32>
 }
 
 fn set_debug(graph: &mut HWGraph, nidx: NodeIndex, check: NodeCheckState) {
@@ -575,6 +603,49 @@ impl Circuit {
             &format!("{}/{}.{}.dot", ccfg.output_dir, ccfg.top_module, pfx),
             &format!("{}/{}.{}.pdf", ccfg.output_dir, ccfg.top_module, pfx));
     }
+
+    /// Save the graph in dot form without rendering it
+    pub fn save_graph_dot(self: &Self, pfx: &str) -> std::io::Result<()> {
+        let ccfg = &self.compiler_cfg;
+        return write_string_to_file(
+            format!("{:?}", self),
+            &format!("{}/{}.{}.dot", ccfg.output_dir, ccfg.top_module, pfx));
+    }
+
+    /// Save the nodes (primitive, placement, rank & pc) and the edges of
+    /// the graph in JSON form
+    pub fn save_graph_json(self: &Self, pfx: &str) -> std::io::Result<()> {
+        let mut nodes = vec![];
+        for nidx in self.graph.node_indices() {
+            let node = self.graph.node_weight(nidx).unwrap();
+            let info = node.info();
+            nodes.push(serde_json::json!({
+                "idx":    nidx.index(),
+                "prim":   node.prim,
+                "module": info.coord.module,
+                "proc":   info.coord.proc,
+                "asap":   info.rank.asap,
+                "alap":   info.rank.alap,
+                "pc":     info.pc
+            }));
+        }
+        let mut edges = vec![];
+        for eidx in self.graph.edge_indices() {
+            let (src, dst) = self.graph.edge_endpoints(eidx).unwrap();
+            let edge = self.graph.edge_weight(eidx).unwrap();
+            edges.push(serde_json::json!({
+                "src":    src.index(),
+                "dst":    dst.index(),
+                "signal": edge.signal.name()
+            }));
+        }
+        let json = serde_json::json!({ "nodes": nodes, "edges": edges });
+
+        let ccfg = &self.compiler_cfg;
+        return write_string_to_file(
+            serde_json::to_string_pretty(&json)?,
+            &format!("{}/{}.{}.json", ccfg.output_dir, ccfg.top_module, pfx));
+    }
 }
 
 impl Debug for Circuit {
@@ -0,0 +1,57 @@
+use crate::common::error::CompileError;
+use crate::common::primitive::Bit;
+use crate::rtlsim::rtlsim_utils::InputStimuliMap;
+use indexmap::IndexMap;
+
+/// Parse the `--clocks <clock net>=<period>` arguments.
+/// The period is in cycles of the fastest (base) clock
+pub fn parse_clock_args(clocks: &Vec<String>) -> Result<IndexMap<String, u32>, CompileError> {
+    let mut ret: IndexMap<String, u32> = IndexMap::new();
+    for arg in clocks.iter() {
+        let (name, period) = match arg.split_once('=') {
+            Some(kv) => kv,
+            None => {
+                return Err(CompileError::invalid_config("clocks", arg,
+                    "expected <clock net>=<period>"));
+            }
+        };
+        let period: u32 = match period.parse() {
+            Ok(p) if p >= 1 => p,
+            _ => {
+                return Err(CompileError::invalid_config("clocks", arg,
+                    &format!("period of clock {} should be an integer of at least 1", name)));
+            }
+        };
+        ret.insert(name.to_string(), period);
+    }
+    return Ok(ret);
+}
+
+/// Name of the input that enables the registers of the `clock` domain
+pub fn clock_enable_name(clock: &str) -> String {
+    format!("{}$en", clock)
+}
+
+/// Whether the edge of a clock with `period` fires at the end of base `cycle`
+pub fn clock_fires(period: u32, cycle: u32) -> Bit {
+    if cycle % period == period - 1 { 1 } else { 0 }
+}
+
+/// Add the clock enable stimuli of every clock domain slower than the base clock.
+/// The stimuli are as long as the longest stimuli already in `input_stimuli`
+pub fn add_clock_enable_stimuli(
+    input_stimuli: &mut InputStimuliMap,
+    clocks: &IndexMap<String, u32>
+) {
+    let mut cycles = 0;
+    for (_, vals) in input_stimuli.iter() {
+        cycles = cycles.max(vals.len() as u32);
+    }
+    for (clock, period) in clocks.iter() {
+        if *period <= 1 {
+            continue;
+        }
+        let vals = (0..cycles).map(|c| clock_fires(*period, c) as u64).collect();
+        input_stimuli.insert(clock_enable_name(clock), vals);
+    }
+}
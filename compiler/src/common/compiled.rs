@@ -0,0 +1,227 @@
+use crate::common::{
+    circuit::Circuit,
+    config::*,
+    error::CompileError,
+    hwgraph::*,
+    mapping::EmulatorMapping,
+    network::Coordinate,
+    primitive::*,
+    topology::GlobalNetworkTopology,
+    utils::write_string_to_file
+};
+use indexmap::IndexMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use twox_hash::xxh3::hash64;
+use std::fs;
+
+/// Bumped whenever the layout of `CompiledDesign` changes
+pub const COMPILED_FORMAT_VERSION: u32 = 2;
+
+/// `PlatformConfig` without the derived network topology
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CompiledPlatform {
+    pub num_mods: u32,
+    pub num_procs: u32,
+    pub max_steps: u32,
+    pub lut_inputs: u32,
+    pub inter_proc_nw_lat: u32,
+    pub inter_mod_nw_lat: u32,
+    pub imem_lat: u32,
+    pub dmem_rd_lat: u32,
+    pub dmem_wr_lat: u32,
+    pub sram_width: u32,
+    pub sram_entries: u32,
+    pub sram_rd_ports: u32,
+    pub sram_wr_ports: u32,
+    pub sram_rd_lat: u32,
+    pub sram_wr_lat: u32,
+    pub sram_ip_pl: u32,
+    pub sram_slots: u32,
+    pub topology: TopologyKind
+}
+
+impl CompiledPlatform {
+    pub fn from(pcfg: &PlatformConfig) -> Self {
+        CompiledPlatform {
+            num_mods:          pcfg.num_mods,
+            num_procs:         pcfg.num_procs,
+            max_steps:         pcfg.max_steps,
+            lut_inputs:        pcfg.lut_inputs,
+            inter_proc_nw_lat: pcfg.inter_proc_nw_lat,
+            inter_mod_nw_lat:  pcfg.inter_mod_nw_lat,
+            imem_lat:          pcfg.imem_lat,
+            dmem_rd_lat:       pcfg.dmem_rd_lat,
+            dmem_wr_lat:       pcfg.dmem_wr_lat,
+            sram_width:        pcfg.sram_width,
+            sram_entries:      pcfg.sram_entries,
+            sram_rd_ports:     pcfg.sram_rd_ports,
+            sram_wr_ports:     pcfg.sram_wr_ports,
+            sram_rd_lat:       pcfg.sram_rd_lat,
+            sram_wr_lat:       pcfg.sram_wr_lat,
+            sram_ip_pl:        pcfg.sram_ip_pl,
+            sram_slots:        pcfg.sram_slots,
+            topology:          pcfg.topology.kind()
+        }
+    }
+
+    pub fn to_platform_cfg(self: &Self) -> Result<PlatformConfig, CompileError> {
+        Ok(PlatformConfig {
+            num_mods:          self.num_mods,
+            num_procs:         self.num_procs,
+            max_steps:         self.max_steps,
+            lut_inputs:        self.lut_inputs,
+            inter_proc_nw_lat: self.inter_proc_nw_lat,
+            inter_mod_nw_lat:  self.inter_mod_nw_lat,
+            imem_lat:          self.imem_lat,
+            dmem_rd_lat:       self.dmem_rd_lat,
+            dmem_wr_lat:       self.dmem_wr_lat,
+            sram_width:        self.sram_width,
+            sram_entries:      self.sram_entries,
+            sram_rd_ports:     self.sram_rd_ports,
+            sram_wr_ports:     self.sram_wr_ports,
+            sram_rd_lat:       self.sram_rd_lat,
+            sram_wr_lat:       self.sram_wr_lat,
+            sram_ip_pl:        self.sram_ip_pl,
+            sram_slots:        self.sram_slots,
+            topology: GlobalNetworkTopology::new(self.topology, self.num_mods, self.num_procs)?
+        })
+    }
+}
+
+/// Top level IO of the design
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompiledIO {
+    pub name: String,
+    pub output: bool,
+    pub coord: Coordinate,
+    pub pc: u32
+}
+
+/// # CompiledDesign
+/// - Everything the functional simulator and the drivers need to run a
+///   compiled design without recompiling it from the blif file
+/// - Saved as `<output_dir>/<top>.compiled.json` by `testing::try_new_circuit`
+///   and loaded back with `--compiled <file>`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompiledDesign {
+    /// `COMPILED_FORMAT_VERSION` of the writer
+    pub version: u32,
+
+    pub top_module: String,
+
+    /// `netlist_hash` of the blif file that was compiled
+    pub netlist_hash: u64,
+
+    pub platform: CompiledPlatform,
+
+    /// Clock periods in base cycles indexed by the clock net name
+    pub clocks: IndexMap<String, u32>,
+
+    /// Initial SRAM content image paths indexed by the SRAM blackbox name
+    pub sram_init: IndexMap<String, String>,
+
+    pub io: Vec<CompiledIO>,
+
+    /// Instructions, signal map, SRAM mappings and host_steps
+    pub emul: EmulatorMapping
+}
+
+/// Hash of the blif file contents
+pub fn netlist_hash(blif_file_path: &str) -> Result<u64, CompileError> {
+    let bytes = fs::read(blif_file_path)?;
+    return Ok(hash64(&bytes));
+}
+
+impl CompiledDesign {
+    pub fn from(circuit: &Circuit, netlist_hash: u64) -> Self {
+        let mut io = vec![];
+        for nidx in circuit.graph.node_indices() {
+            let node = circuit.graph.node_weight(nidx).unwrap();
+            if node.is() == Primitive::Input || node.is() == Primitive::Output {
+                io.push(CompiledIO {
+                    name:   node.name().to_string(),
+                    output: node.is() == Primitive::Output,
+                    coord:  node.info().coord,
+                    pc:     node.info().pc
+                });
+            }
+        }
+        CompiledDesign {
+            version:      COMPILED_FORMAT_VERSION,
+            top_module:   circuit.compiler_cfg.top_module.clone(),
+            netlist_hash: netlist_hash,
+            platform:     CompiledPlatform::from(&circuit.platform_cfg),
+            clocks:       circuit.compiler_cfg.clocks.clone(),
+            sram_init:    circuit.compiler_cfg.sram_init.clone(),
+            io:           io,
+            emul:         circuit.emul.clone()
+        }
+    }
+
+    pub fn save(self: &Self, path: &str) -> Result<(), CompileError> {
+        write_string_to_file(serde_json::to_string(self)?, path)?;
+        return Ok(());
+    }
+
+    pub fn load(path: &str) -> Result<Self, CompileError> {
+        let text = fs::read_to_string(path)?;
+        let design: CompiledDesign = match serde_json::from_str(&text) {
+            Ok(d) => d,
+            Err(e) => {
+                return Err(CompileError::Parse { path: path.to_string(), msg: format!("{}", e) });
+            }
+        };
+        if design.version != COMPILED_FORMAT_VERSION {
+            return Err(CompileError::Parse {
+                path: path.to_string(),
+                msg: format!("compiled design format version {}, expect {}",
+                    design.version, COMPILED_FORMAT_VERSION)
+            });
+        }
+        return Ok(design);
+    }
+
+    /// Rebuild a `Circuit` that can be simulated. The netlist is not part of
+    /// the compiled design, so the graph only contains the top level IOs.
+    /// The signal map of every other signal has no node index
+    pub fn to_circuit(self: &Self, output_dir: &str) -> Result<Circuit, CompileError> {
+        let mut circuit = Circuit::default();
+        circuit.platform_cfg = self.platform.to_platform_cfg()?;
+        circuit.compiler_cfg = CompilerConfig {
+            top_module: self.top_module.clone(),
+            output_dir: output_dir.to_string(),
+            clocks:     self.clocks.clone(),
+            sram_init:  self.sram_init.clone(),
+            ..CompilerConfig::default()
+        };
+        circuit.emul = self.emul.clone();
+
+        let mut io_idx: IndexMap<String, NodeIndex> = IndexMap::new();
+        for sig in self.io.iter() {
+            let prim = if sig.output {
+                CircuitPrimitive::Output { name: sig.name.clone() }
+            } else {
+                CircuitPrimitive::Input { name: sig.name.clone() }
+            };
+            let mut node = HWNode::new(prim);
+            node.info_mut().coord = sig.coord;
+            node.info_mut().pc = sig.pc;
+            node.info_mut().scheduled = true;
+            io_idx.insert(sig.name.clone(), circuit.graph.add_node(node));
+        }
+
+        // Point the signal map of the IOs to the rebuilt nodes
+        for (_, mmap) in circuit.emul.module_mappings.iter_mut() {
+            for (_, pmap) in mmap.proc_mappings.iter_mut() {
+                for (sig, nmi) in pmap.signal_map.iter_mut() {
+                    match io_idx.get(sig) {
+                        Some(nidx) => { nmi.idx = Some(*nidx); }
+                        None => {}
+                    }
+                }
+            }
+        }
+        return Ok(circuit);
+    }
+}
@@ -1,157 +1,25 @@
 use crate::common::primitive::*;
 use crate::common::network::*;
+use crate::common::topology::*;
 use strum::EnumCount;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use derivative::Derivative;
 use std::fmt::Debug;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use indexmap::IndexMap;
 
-#[derive(Clone, Default, Serialize)]
-pub struct GlobalNetworkTopology {
-    pub edges: IndexMap<Coordinate, Coordinate>,
-    pub inter_mod_paths: IndexMap<(u32, u32), Vec<NetworkPath>>
-}
-
-impl GlobalNetworkTopology {
-    pub fn new(num_mods: u32, num_procs: u32) -> Self {
-        let mut ret = GlobalNetworkTopology::default();
-        if num_mods == 1 {
-            return ret;
-        }
-        let num_mods_1 = num_mods - 1;
-        let grp_sz = num_procs / num_mods_1;
-
-        assert!(num_mods_1 & (num_mods_1 - 1) == 0, "num_mods should be 2^n + 1");
-        assert!(num_procs  & (num_procs - 1)  == 0, "num_procs should be 2^n + 1");
-        assert!(num_procs >= num_mods_1, "num_procs {} < num_mods - 1 {}", num_procs, num_mods_1);
-
-        for m in 0..num_mods_1 {
-            for p in 0..num_procs {
-                let r = p % grp_sz;
-                let q = (p - r) / grp_sz;
-                let src = Coordinate { module: m, proc: p };
-                let dst = if q == m {
-                    let dm = num_mods_1;
-                    let dp = p;
-                    Coordinate { module: dm, proc: dp }
-                } else {
-                    let dm = q;
-                    let dp = m * grp_sz + r;
-                    Coordinate { module: dm, proc: dp }
-                };
-                ret.edges.insert(src, dst);
-                ret.edges.insert(dst, src);
-                ret.add_path(src, dst);
-                ret.add_path(dst, src);
-            }
-        }
-        return ret;
-    }
-
-    fn add_path(self: &mut Self, src: Coordinate, dst: Coordinate) {
-        if !self.inter_mod_paths.contains_key(&(src.module, dst.module)) {
-            self.inter_mod_paths.insert((src.module, dst.module), vec![]);
-        }
-        if !self.inter_mod_paths.contains_key(&(dst.module, src.module)) {
-            self.inter_mod_paths.insert((dst.module, src.module), vec![]);
-        }
-        let paths = self.inter_mod_paths.get_mut(&(src.module, dst.module)).unwrap();
-        paths.push(NetworkPath::new(src, dst));
-    }
-
-    /// Returns a Vec<NetworkPath> where the path connects some processor in
-    /// src.module to some processor in dst.module
-    pub fn inter_mod_paths(self: &Self, src: Coordinate, dst: Coordinate) -> Vec<NetworkPath> {
-        let paths = self.inter_mod_paths.get(&(src.module, dst.module)).unwrap();
-        return paths.to_vec();
-    }
-
-    /// Returns a Vec<NetworkRoute> where the route connects src.module to dst.module
-    /// while hopping to one intermediate module
-    pub fn inter_mod_routes(self: &Self, src: Coordinate, dst: Coordinate) -> Vec<NetworkRoute> {
-        let mut ret: Vec<NetworkRoute> = vec![];
-        let mut src_to_inter: IndexMap<u32, Vec<NetworkPath>> = IndexMap::new();
-        let mut inter_to_dst: IndexMap<u32, Vec<NetworkPath>> = IndexMap::new();
-        for ((m1, m2), paths) in self.inter_mod_paths.iter() {
-            if *m1 == src.module && *m2 != dst.module {
-                if !src_to_inter.contains_key(m2) {
-                    src_to_inter.insert(*m2, vec![]);
-                }
-                src_to_inter.get_mut(m2).unwrap().append(&mut paths.clone());
-            }
-            if *m1 != src.module && *m2 == dst.module {
-                if !inter_to_dst.contains_key(m2) {
-                    inter_to_dst.insert(*m1, vec![]);
-                }
-                inter_to_dst.get_mut(m1).unwrap().append(&mut paths.clone());
-            }
-        }
-        for imod in src_to_inter.keys() {
-            for s2i_path in src_to_inter.get(imod).unwrap().iter() {
-                for i2d_path in inter_to_dst.get(imod).unwrap().iter() {
-                    let route = if s2i_path.dst == i2d_path.src {
-                        NetworkRoute::from([*s2i_path, *i2d_path])
-                    } else {
-                        NetworkRoute::from([*s2i_path,
-                                           NetworkPath::new(
-                                               s2i_path.dst,
-                                               i2d_path.src),
-                                           *i2d_path])
-                    };
-                    ret.push(route);
-                }
-            }
-        }
-        return ret;
-    }
-}
-
-impl Debug for GlobalNetworkTopology {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let indent: &str = "    ";
-
-        write!(f, "digraph {{\n")?;
-
-        let mut map: IndexMap<Coordinate, u32> = IndexMap::new();
-
-        for (i, (src, _)) in self.edges.iter().enumerate() {
-            map.insert(*src, i as u32);
-
-            write!(
-                f,
-                "{}{} [ label = \"{:?}\" ]\n",
-                indent,
-                i,
-                src
-            )?;
-        }
-        for (i, (_, dst)) in self.edges.iter().enumerate() {
-            write!(
-                f,
-                "{}{} {} {} ",
-                indent,
-                i,
-                "->",
-                map.get(dst).unwrap()
-            )?;
-            writeln!(f, "[ ]")?;
-        }
-
-        write!(f, "}}")
-    }
-}
-
-
-#[derive(Debug, Clone, Serialize)]
+#[derive(clap::Args, Debug, Clone, Serialize)]
 pub struct KaMinParConfig {
     /// Random seed for partitioner
+    #[arg(long = "kaminpar-seed", default_value_t = 123)]
     pub seed: u64,
 
-    /// Partitioner hyperparameter
+    /// Partitioner hyperparameter (allowed imbalance of the partitions)
+    #[arg(long = "kaminpar-epsilon", default_value_t = 0.03)]
     pub epsilon: f64,
 
     /// Number of threads to perform partitioning
+    #[arg(long = "kaminpar-nthreads", default_value_t = 16)]
     pub nthreads: u32,
 }
 
@@ -177,7 +45,34 @@ pub struct CompilerConfig {
     pub dbg_tail_length: u32,
 
     /// Number of nodes scheduled per PC for that PC to be classified as a tail
-    pub dbg_tail_threshold: u32
+    pub dbg_tail_threshold: u32,
+
+    /// Initial SRAM content image paths indexed by the SRAM blackbox name
+    pub sram_init: IndexMap<String, String>,
+
+    /// Clock periods in base cycles indexed by the clock net name.
+    /// Undeclared clocks run at the base (fastest) clock
+    pub clocks: IndexMap<String, u32>,
+
+    /// Always remap the combinational logic to `lut_inputs`-input LUTs.
+    /// Otherwise `lut_map` only runs when a LUT has more than `lut_inputs` inputs
+    pub lut_map: bool,
+
+    /// Path to the partition constraints file (`common::constraints`)
+    pub partition_constraints: Option<String>,
+
+    /// Graph partitioner used by the `partition` pass
+    pub partitioner: PartitionerKind,
+
+    /// Maximum number of times to repartition & reschedule with the critical
+    /// inter-module edges of the previous schedules weighted up
+    pub repartition_iters: u32,
+
+    /// Stop repartitioning after this many seconds
+    pub repartition_time_limit: Option<u64>,
+
+    /// Passes to run and the checks & dumps around them
+    pub pass_cfg: PassManagerConfig
 }
 
 #[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -240,6 +135,11 @@ pub struct PlatformConfig {
     /// This is to cut the critical path in HW implementations
     pub sram_ip_pl: u32,
 
+    /// Number of target SRAMs (or ports of a multi-ported target SRAM) that
+    /// can be packed into the address space of a SRAM processor.
+    /// The SRAMProcessor in the emulator RTL holds a single target SRAM
+    pub sram_slots: u32,
+
     /// Global network topology
     #[derivative(Debug="ignore")]
     pub topology: GlobalNetworkTopology
@@ -264,6 +164,7 @@ impl Default for PlatformConfig {
             sram_rd_lat: 1,
             sram_wr_lat: 1,
             sram_ip_pl: 1,
+            sram_slots: 1,
             topology: GlobalNetworkTopology::default()
         }
     }
@@ -313,16 +214,21 @@ impl PlatformConfig {
         1 << self.lut_inputs
     }
 
+    /// number of bits for a single instruction (excluding the module & processor index)
+    pub fn inst_bits(self: &Self) -> u32 {
+        self.opcode_bits() +
+            self.lut_bits() +
+            self.lut_inputs * (self.index_bits() + 1) +
+            self.switch_bits() +
+            3
+    }
+
     pub fn total_procs(self: &Self) -> u32 {
         self.num_mods * self.num_procs
     }
 
     fn nw_path_lat(self: &Self, path: &NetworkPath) -> u32 {
-        match path.tpe {
-            PathTypes::ProcessorInternal => 0,
-            PathTypes::InterProcessor    => self.inter_proc_nw_lat,
-            PathTypes::InterModule       => self.inter_mod_nw_lat
-        }
+        self.topology.path_lat(path, self)
     }
 
     /// Fetch and decode latency
@@ -417,9 +323,55 @@ impl PlatformConfig {
         self.sram_rdwr_mode_offset() + self.sram_entries
     }
 
+    /// Number of unique SRAM input IDs used by a single target SRAM slot
+    pub fn sram_slot_input_ids(self: &Self) -> u32 {
+        self.sram_other_offset() + 1
+    }
+
+    /// Returns the (slot, slot local ID) pair given a unique SRAM input ID
+    pub fn index_to_sram_input_slot(self: &Self, idx: u32) -> (u32, u32) {
+        let n = self.sram_slot_input_ids();
+        (idx / n, idx % n)
+    }
+
+    /// Returns the (slot, bit index) pair given a unique SRAM output ID
+    pub fn index_to_sram_output_slot(self: &Self, idx: u32) -> (u32, u32) {
+        (idx / self.sram_width, idx % self.sram_width)
+    }
+
+    /// Slots of a sram processor are accessed in two phases within a target cycle.
+    /// First, each slot performs its write one after another (a read for the
+    /// write mask followed by the write). Returns the pc at which slot `slot`
+    /// starts its write.
+    pub fn sram_slot_wr_step(self: &Self, slot: u32) -> Cycle {
+        slot * self.sram_rd_en_step()
+    }
+
+    /// After all the writes are issued, each slot sends out its read.
+    /// Returns the pc at which the read of slot `slot` is sent out.
+    pub fn sram_slot_rd_req_step(self: &Self, nslots: u32, slot: u32) -> Cycle {
+        self.sram_slot_wr_step(nslots.max(1) - 1) + self.sram_wr_lat + slot
+    }
+
+    /// Returns the first pc at which the read data of slot `slot` is valid
+    /// when the sram processor has `nslots` slots
+    pub fn sram_slot_rd_step(self: &Self, nslots: u32, slot: u32) -> Cycle {
+        self.sram_slot_rd_req_step(nslots, slot) + self.sram_rd_lat
+    }
+
+    /// Minimum number of host steps required to access `nslots` slots
+    pub fn sram_slot_steps(self: &Self, nslots: u32) -> Cycle {
+        if nslots == 0 {
+            0
+        } else {
+            self.sram_slot_rd_step(nslots, nslots - 1) + 1
+        }
+    }
+
     /// To emulate SRAMs, the `operands` fields are used to set a unique ID
     /// for each SRAM input bit type.
-    /// This function returns the type of the bit and its index given a unique ID
+    /// This function returns the type of the bit and its index given a
+    /// slot local ID (see `index_to_sram_input_slot`)
     pub fn index_to_sram_input_type(self: &Self, idx: u32) -> (Primitive, u32) {
         if idx >= self.sram_other_offset() {
             assert!(false, "Unknown index to sram input type: {}", idx);
@@ -446,6 +398,241 @@ impl PlatformConfig {
     }
 }
 
+/// Compiler passes that can be scheduled by the pass manager (`passes::runner`)
+#[derive(ValueEnum, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompilerPass {
+    Dce,
+    LutMap,
+    ConstProp,
+    AbsorbBuffers,
+    Strash,
+    ClockDomains,
+    LowerGates,
+    SplitLargeSrams,
+    PrepartitionRankOrder,
+    Partition,
+    InitRankOrder,
+    SplitRegNodes,
+    SplitSramNodes,
+    ReplicateConsts,
+    DistributeIo,
+    FindRankOrder,
+    CheckRankOrder,
+    CheckConnectivity,
+    ScheduleInstructions,
+    CompactSchedule,
+    MapInstructions,
+    VerifyProgram,
+    PrintStats
+}
+
+impl CompilerPass {
+    /// Passes that are run when no pass list is given
+    pub fn default_pipeline() -> Vec<CompilerPass> {
+        vec![
+            CompilerPass::Dce,
+            CompilerPass::LutMap,
+            CompilerPass::ConstProp,
+            CompilerPass::AbsorbBuffers,
+            CompilerPass::Strash,
+            CompilerPass::ClockDomains,
+            CompilerPass::LowerGates,
+            CompilerPass::SplitLargeSrams,
+            CompilerPass::PrepartitionRankOrder,
+            CompilerPass::Partition,
+            CompilerPass::InitRankOrder,
+            CompilerPass::SplitRegNodes,
+            CompilerPass::SplitSramNodes,
+            CompilerPass::ReplicateConsts,
+            CompilerPass::DistributeIo,
+            CompilerPass::FindRankOrder,
+            CompilerPass::CheckRankOrder,
+            CompilerPass::ScheduleInstructions,
+            CompilerPass::CompactSchedule,
+            CompilerPass::MapInstructions,
+            CompilerPass::VerifyProgram,
+            CompilerPass::PrintStats
+        ]
+    }
+
+    /// Name of the pass as used on the command line
+    pub fn name(self: &Self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+
+    /// Parse a pass list file. Passes are separated by whitespace or commas
+    /// and everything after a `#` in a line is ignored
+    pub fn parse_pass_list(text: &str) -> Result<Vec<CompilerPass>, String> {
+        let mut ret = vec![];
+        for line in text.lines() {
+            let line = match line.split_once('#') {
+                Some((l, _)) => l,
+                None => line
+            };
+            for tok in line.split(|c: char| c.is_whitespace() || c == ',').filter(|x| *x != "") {
+                ret.push(CompilerPass::from_str(tok, true)?);
+            }
+        }
+        return Ok(ret);
+    }
+}
+
+/// Sanity checks that the pass manager can run in between passes
+#[derive(ValueEnum, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum PassCheck {
+    /// `check_connectivity`
+    Connectivity,
+
+    /// `check_rank_order`. Only valid once the ranks are set
+    RankOrder
+}
+
+/// Format of the `HWGraph` dumps of the pass manager
+#[derive(ValueEnum, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum GraphDumpFormat {
+    #[default]
+    Dot,
+    Json
+}
+
+/// # PassManagerConfig
+/// - Which compiler passes to run and in what order
+/// - Checks to run and graphs to dump in between passes
+#[derive(clap::Args, Serialize, Debug, Clone)]
+pub struct PassManagerConfig {
+    /// Compiler passes to run in order. Runs the full pipeline when empty
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub passes: Vec<CompilerPass>,
+
+    /// File listing the compiler passes to run in order (overrides --passes)
+    #[arg(long)]
+    pub pass_file: Option<String>,
+
+    /// Run the --checks after these passes
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub check_after: Vec<CompilerPass>,
+
+    /// Run the --checks after every pass
+    #[arg(long, default_value_t = false)]
+    pub check_all: bool,
+
+    /// Checks to run after the passes in --check-after
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = vec![PassCheck::Connectivity])]
+    pub checks: Vec<PassCheck>,
+
+    /// Dump the graph before these passes
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub dump_before: Vec<CompilerPass>,
+
+    /// Dump the graph after these passes
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub dump_after: Vec<CompilerPass>,
+
+    /// Format of the graph dumps
+    #[arg(long, value_enum, default_value_t = GraphDumpFormat::Dot)]
+    pub dump_format: GraphDumpFormat,
+
+    /// Save the per pass statistics in <top>.passes.json
+    #[arg(long, default_value_t = false)]
+    pub save_pass_report: bool,
+}
+
+impl Default for PassManagerConfig {
+    fn default() -> Self {
+        PassManagerConfig {
+            passes: vec![],
+            pass_file: None,
+            check_after: vec![],
+            check_all: false,
+            checks: vec![PassCheck::Connectivity],
+            dump_before: vec![],
+            dump_after: vec![],
+            dump_format: GraphDumpFormat::Dot,
+            save_pass_report: false
+        }
+    }
+}
+
+impl PassManagerConfig {
+    /// Passes to run in order
+    pub fn pipeline(self: &Self) -> std::io::Result<Vec<CompilerPass>> {
+        match &self.pass_file {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)?;
+                return CompilerPass::parse_pass_list(&text).map_err(|e| {
+                    std::io::Error::other(format!("{}: {}", path, e))
+                });
+            }
+            None => {}
+        }
+        if self.passes.is_empty() {
+            return Ok(CompilerPass::default_pipeline());
+        } else {
+            return Ok(self.passes.clone());
+        }
+    }
+
+    /// Whether the checks should run after `pass`
+    pub fn should_check_after(self: &Self, pass: &CompilerPass) -> bool {
+        self.check_all || self.check_after.contains(pass)
+    }
+}
+
+/// Global network topologies (`common::topology`)
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TopologyKind {
+    /// Fully connected modules plus a hub module. num_mods should be 2^n + 1
+    #[default]
+    Hub,
+
+    /// 2D mesh (`num_mods` should not be a prime above 3)
+    Mesh,
+
+    /// 2D mesh with wraparound links
+    Torus,
+
+    Ring,
+
+    /// Every pair of modules is directly connected
+    Crossbar
+}
+
+/// Graph partitioners (`passes::partitioner`)
+#[derive(ValueEnum, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PartitionerKind {
+    /// Pure Rust multilevel partitioner
+    Multilevel,
+
+    /// KaMinPar. Requires the `kaminpar` cargo feature
+    #[value(name = "kaminpar")]
+    KaMinPar
+}
+
+impl Default for PartitionerKind {
+    fn default() -> Self {
+        if cfg!(feature = "kaminpar") {
+            PartitionerKind::KaMinPar
+        } else {
+            PartitionerKind::Multilevel
+        }
+    }
+}
+
+/// Reference RTL simulators used to generate the golden waveform.
+/// Defaults to Icarus Verilog as it is freely available.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq)]
+pub enum RefSimulator {
+    VCS,
+    Verilator,
+    #[default]
+    Icarus
+}
+
 #[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
 pub struct Args {
@@ -473,19 +660,26 @@ pub struct Args {
     #[arg(long)]
     pub vcd: Option<String>,
 
+    /// Reference RTL simulator to run when no reference waveform is provided
+    #[arg(long, value_enum, default_value_t = RefSimulator::default())]
+    pub ref_sim: RefSimulator,
+
     /// Hierarchy path to the instance under emulation
     #[arg(long, default_value = "testharness.top")]
     pub instance_path: String,
 
     /// clock starts low
+    /// (only used when the reference waveform has no testbench timing metadata)
     #[arg(short, long, default_value_t = false)]
     pub clock_start_low: bool,
 
     /// timesteps per cycle
+    /// (only used when the reference waveform has no testbench timing metadata)
     #[arg(long, default_value_t = 2)]
     pub timesteps_per_cycle: u32,
 
     /// number of cycles to skip when parsing reference rtl sim vcd
+    /// (only used when the reference waveform has no testbench timing metadata)
     #[arg(long, default_value_t = 4)]
     pub ref_skip_cycles: u32,
 
@@ -509,6 +703,10 @@ pub struct Args {
     #[arg(long, default_value_t = 8)]
     pub num_procs: u32,
 
+    /// global network topology connecting the modules
+    #[arg(long, value_enum, default_value_t = TopologyKind::Hub)]
+    pub topology: TopologyKind,
+
     /// maximum number of instructions per processor
     #[arg(long, default_value_t = 128)]
     pub max_steps: u32,
@@ -565,6 +763,58 @@ pub struct Args {
     #[arg(long, default_value_t = 1)]
     pub sram_ip_pl: u32,
 
+    /// Number of target SRAMs that can be packed into a SRAM processor.
+    /// Only the functional and blif simulators support more than one. The
+    /// hardware flows (metasim, swsim, alveo) reject it at compile time
+    #[arg(long, default_value_t = 1)]
+    pub sram_slots: u32,
+
+    /// Initial SRAM contents as <SRAM blackbox name>=<image path>.
+    /// `.bin` images are read like `$readmemb`, others like `$readmemh`
+    #[arg(long)]
+    pub sram_init: Vec<String>,
+
+    /// Clock domains as <clock net>=<period>. The period is in cycles of the
+    /// fastest clock, which every undeclared clock is assumed to run at
+    #[arg(long)]
+    pub clocks: Vec<String>,
+
+    /// Map the (gate level) combinational logic to `lut_inputs`-input LUTs
+    /// even when it already fits
+    #[arg(long, default_value_t = false)]
+    pub lut_map: bool,
+
+    /// Partition constraints file pinning signals or hierarchies to
+    /// modules and processors
+    #[arg(long)]
+    pub partition_constraints: Option<String>,
+
+    /// Graph partitioner. Defaults to KaMinPar when built with the
+    /// `kaminpar` feature
+    #[arg(long, value_enum, default_value_t = PartitionerKind::default())]
+    pub partitioner: PartitionerKind,
+
+    /// Repartition & reschedule up to this many times, keeping the schedule
+    /// with the fewest host steps
+    #[arg(long, default_value_t = 0)]
+    pub repartition_iters: u32,
+
+    /// Time budget in seconds for repartitioning (repartitions until the
+    /// budget runs out when --repartition-iters is 0)
+    #[arg(long)]
+    pub repartition_time_limit: Option<u64>,
+
+    /// Load a compiled design (`<top>.compiled.json`) instead of compiling
+    /// the blif file
+    #[arg(long)]
+    pub compiled: Option<String>,
+
+    #[command(flatten)]
+    pub kaminpar_cfg: KaMinParConfig,
+
+    #[command(flatten)]
+    pub pass_cfg: PassManagerConfig,
+
     /// debug tail length
     #[arg(long, default_value_t = 10)]
     pub dbg_tail_length: u32,
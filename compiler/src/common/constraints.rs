@@ -0,0 +1,188 @@
+use crate::common::config::{CompilerConfig, PlatformConfig};
+use crate::common::error::CompileError;
+use crate::common::network::Coordinate;
+use regex::Regex;
+use std::fs;
+
+/// How a rule selects nodes by name
+#[derive(Debug, Clone)]
+pub enum NameMatcher {
+    /// Hierarchy prefix of the flattened net name (e.g. `tile.core.`)
+    Prefix(String),
+    Regex(Regex)
+}
+
+impl NameMatcher {
+    pub fn matches(self: &Self, name: &str) -> bool {
+        match self {
+            NameMatcher::Prefix(p) => name.starts_with(p.as_str()),
+            NameMatcher::Regex(r)  => r.is_match(name)
+        }
+    }
+}
+
+/// Nodes selected by `matcher` are placed on one of the `modules`, and on
+/// one of the `procs` of that module when given
+#[derive(Debug, Clone)]
+pub struct PartitionRule {
+    pub matcher: NameMatcher,
+    pub modules: Vec<u32>,
+    pub procs: Option<Vec<u32>>,
+
+    /// Line of the rule in the constraints file
+    pub line: usize
+}
+
+/// # PartitionConstraints
+/// - Rules that pin nodes to modules and processors during partitioning
+/// - One rule per line. Everything after a `#` is ignored
+///   ```text
+///   # <prefix|regex> <pattern> module=<ids> [proc=<ids>]
+///   prefix tile.core0.    module=0-3
+///   regex  ^io_(in|out)_  module=4 proc=0-7,12
+///   ```
+/// - `<ids>` is a comma separated list of ids or inclusive ranges
+/// - A node follows the first rule that matches its name
+#[derive(Debug, Clone, Default)]
+pub struct PartitionConstraints {
+    pub rules: Vec<PartitionRule>
+}
+
+/// Parse `0-3,5,7-8`
+fn parse_id_set(text: &str) -> Result<Vec<u32>, String> {
+    let mut ret = vec![];
+    for tok in text.split(',').filter(|x| *x != "") {
+        let (lo, hi) = match tok.split_once('-') {
+            Some((lo, hi)) => (lo, hi),
+            None => (tok, tok)
+        };
+        let lo: u32 = lo.parse().map_err(|_| format!("invalid id {}", lo))?;
+        let hi: u32 = hi.parse().map_err(|_| format!("invalid id {}", hi))?;
+        if lo > hi {
+            return Err(format!("empty range {}", tok));
+        }
+        for i in lo..=hi {
+            if !ret.contains(&i) {
+                ret.push(i);
+            }
+        }
+    }
+    if ret.is_empty() {
+        return Err(format!("empty id set {}", text));
+    }
+    return Ok(ret);
+}
+
+fn parse_rule(line: &str) -> Result<PartitionRule, String> {
+    let toks: Vec<&str> = line.split_whitespace().collect();
+    if toks.len() < 3 {
+        return Err("expected <prefix|regex> <pattern> module=<ids> [proc=<ids>]".to_string());
+    }
+    let matcher = match toks[0] {
+        "prefix" => NameMatcher::Prefix(toks[1].to_string()),
+        "regex"  => NameMatcher::Regex(Regex::new(toks[1]).map_err(|e| format!("{}", e))?),
+        _ => { return Err(format!("unknown matcher {}", toks[0])); }
+    };
+
+    let mut modules = None;
+    let mut procs = None;
+    for tok in toks[2..].iter() {
+        match tok.split_once('=') {
+            Some(("module", ids)) => { modules = Some(parse_id_set(ids)?); }
+            Some(("proc", ids))   => { procs = Some(parse_id_set(ids)?); }
+            _ => { return Err(format!("unknown target {}", tok)); }
+        }
+    }
+    let modules = match modules {
+        Some(m) => m,
+        None => { return Err("missing module=<ids>".to_string()); }
+    };
+    return Ok(PartitionRule { matcher: matcher, modules: modules, procs: procs, line: 0 });
+}
+
+impl PartitionConstraints {
+    pub fn parse(text: &str, path: &str) -> Result<Self, CompileError> {
+        let mut ret = PartitionConstraints::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = match line.split_once('#') {
+                Some((l, _)) => l,
+                None => line
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_rule(line) {
+                Ok(mut rule) => {
+                    rule.line = i + 1;
+                    ret.rules.push(rule);
+                }
+                Err(msg) => {
+                    return Err(CompileError::Parse {
+                        path: path.to_string(),
+                        msg: format!("line {}: {}", i + 1, msg)
+                    });
+                }
+            }
+        }
+        return Ok(ret);
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, CompileError> {
+        let text = fs::read_to_string(path)?;
+        return PartitionConstraints::parse(&text, path);
+    }
+
+    /// Constraints of `ccfg.partition_constraints`. Empty when not given
+    pub fn from_config(ccfg: &CompilerConfig) -> Result<Self, CompileError> {
+        match &ccfg.partition_constraints {
+            Some(path) => PartitionConstraints::from_file(path),
+            None => Ok(PartitionConstraints::default())
+        }
+    }
+
+    /// All the module & processor ids exist on the platform
+    pub fn check(self: &Self, pcfg: &PlatformConfig) -> Result<(), CompileError> {
+        for rule in self.rules.iter() {
+            for m in rule.modules.iter() {
+                if *m >= pcfg.num_mods {
+                    return Err(CompileError::invalid_config(
+                        "partition constraint module", m,
+                        &format!("line {}: num_mods is {}", rule.line, pcfg.num_mods)));
+                }
+            }
+            match &rule.procs {
+                Some(procs) => {
+                    for p in procs.iter() {
+                        if *p >= pcfg.num_procs {
+                            return Err(CompileError::invalid_config(
+                                "partition constraint proc", p,
+                                &format!("line {}: num_procs is {}", rule.line, pcfg.num_procs)));
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+        return Ok(());
+    }
+
+    /// Index of the first rule matching `name`
+    pub fn rule_of(self: &Self, name: &str) -> Option<usize> {
+        self.rules.iter().position(|r| r.matcher.matches(name))
+    }
+
+    /// A node named `name` may be placed at `coord`
+    pub fn allows(self: &Self, name: &str, coord: &Coordinate) -> bool {
+        match self.rule_of(name) {
+            Some(r) => {
+                let rule = &self.rules[r];
+                let proc_ok = match &rule.procs {
+                    Some(procs) => procs.contains(&coord.proc),
+                    None => true
+                };
+                rule.modules.contains(&coord.module) && proc_ok
+            }
+            None => true
+        }
+    }
+}
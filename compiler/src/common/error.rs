@@ -0,0 +1,183 @@
+use crate::common::primitive::Primitive;
+use crate::common::network::Coordinate;
+use crate::common::verify::ProgramViolation;
+use std::fmt::{Debug, Display};
+
+/// # CompileError
+/// - Errors that stop the compilation of a design
+/// - `is_capacity` tells apart errors caused by the design not fitting on the
+///   platform (can be fixed by changing the config) from errors in the inputs
+///   and compiler bugs
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    /// The blif file could not be read or is not a flattened netlist
+    Parse { path: String, msg: String },
+
+    /// File system errors (output directory, SRAM images, dumps)
+    Io { msg: String },
+
+    /// Platform or compiler config value that the compiler can't work with
+    InvalidConfig { param: String, value: String, reason: String },
+
+    /// The schedule needs more host steps than `max_steps`
+    ScheduleExceedsMaxSteps {
+        pc: u32,
+        max_steps: u32,
+        rank: u32,
+        scheduled: usize,
+        total: usize
+    },
+
+    /// More IO nodes want the same processor than there are free processors
+    NotEnoughProcsForIO {
+        direction: Primitive,
+        coord: Coordinate,
+        nodes: Vec<String>,
+        free_procs: usize
+    },
+
+    /// A node reads more operands than there are LUT inputs
+    TooManyOperands {
+        node: String,
+        coord: Coordinate,
+        operands: u32,
+        lut_inputs: u32
+    },
+
+    /// The SRAM processor input or output index of a node does not fit in
+    /// the operands of an instruction
+    SRAMIndexOutOfRange {
+        node: String,
+        coord: Coordinate,
+        idx: u32,
+        max_idx: u64
+    },
+
+    /// A target SRAM does not fit in the SRAM processor of any module
+    SRAMDoesNotFit {
+        sram: String,
+        width: u32,
+        entries: u64,
+        sram_width: u32,
+        sram_entries: u32
+    },
+
+    /// No module has enough free SRAM processor slots for a target SRAM
+    NotEnoughSRAMSlots {
+        sram: String,
+        slots: u32,
+        sram_slots: u32
+    },
+
+    /// An input of a node that is not one of its operands
+    UnknownOperand {
+        node: String,
+        coord: Coordinate,
+        operand: String
+    },
+
+    /// The emulator program breaks the rules checked by `verify_program`
+    InvalidProgram { violations: Vec<ProgramViolation> },
+
+    /// A pass hit an internal invariant (compiler bug)
+    Internal { pass: String, msg: String }
+}
+
+impl CompileError {
+    /// True when the design does not fit on the platform
+    pub fn is_capacity(self: &Self) -> bool {
+        match self {
+            CompileError::ScheduleExceedsMaxSteps { .. } |
+            CompileError::NotEnoughProcsForIO     { .. } |
+            CompileError::TooManyOperands         { .. } |
+            CompileError::SRAMIndexOutOfRange     { .. } |
+            CompileError::SRAMDoesNotFit          { .. } |
+            CompileError::NotEnoughSRAMSlots      { .. } => true,
+            _ => false
+        }
+    }
+
+    pub fn invalid_config<T: Debug>(param: &str, value: T, reason: &str) -> Self {
+        CompileError::InvalidConfig {
+            param: param.to_string(),
+            value: format!("{:?}", value),
+            reason: reason.to_string()
+        }
+    }
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::Parse { path, msg } => {
+                write!(f, "Failed to parse {}: {}", path, msg)
+            }
+            CompileError::Io { msg } => {
+                write!(f, "{}", msg)
+            }
+            CompileError::InvalidConfig { param, value, reason } => {
+                write!(f, "Invalid {} {}: {}", param, value, reason)
+            }
+            CompileError::ScheduleExceedsMaxSteps { pc, max_steps, rank, scheduled, total } => {
+                write!(f, "Schedule failed at rank {}: {} nodes out of {} nodes scheduled, pc {} max_steps {}",
+                    rank, scheduled, total, pc, max_steps)
+            }
+            CompileError::NotEnoughProcsForIO { direction, coord, nodes, free_procs } => {
+                write!(f, "Not enough free processor for IO {:?}: {} nodes at {:?} ({:?}), {} free processors",
+                    direction, nodes.len(), coord, nodes, free_procs)
+            }
+            CompileError::TooManyOperands { node, coord, operands, lut_inputs } => {
+                write!(f, "{} at {:?} has {} operands, more than lut_inputs {}",
+                    node, coord, operands, lut_inputs)
+            }
+            CompileError::SRAMIndexOutOfRange { node, coord, idx, max_idx } => {
+                write!(f, "SRAM unique input idx {} > max_op_num {} for {} at {:?}",
+                    idx, max_idx, node, coord)
+            }
+            CompileError::SRAMDoesNotFit { sram, width, entries, sram_width, sram_entries } => {
+                write!(f, "SRAM {} ({} bits x {} entries) does not fit in a module SRAM ({} bits x {} entries)",
+                    sram, width, entries, sram_width, sram_entries)
+            }
+            CompileError::NotEnoughSRAMSlots { sram, slots, sram_slots } => {
+                write!(f, "SRAM {} needs {} SRAM processor slots, but no module has that many free out of sram_slots {}",
+                    sram, slots, sram_slots)
+            }
+            CompileError::UnknownOperand { node, coord, operand } => {
+                write!(f, "{} at {:?} has no operand driven by {}", node, coord, operand)
+            }
+            CompileError::InvalidProgram { violations } => {
+                write!(f, "Emulator program has {} violations", violations.len())?;
+                for v in violations.iter().take(8) {
+                    write!(f, "\n  {}", v)?;
+                }
+                if violations.len() > 8 {
+                    write!(f, "\n  ...")?;
+                }
+                return Ok(());
+            }
+            CompileError::Internal { pass, msg } => {
+                write!(f, "Internal compiler error in {}: {}", pass, msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+impl From<std::io::Error> for CompileError {
+    fn from(e: std::io::Error) -> Self {
+        CompileError::Io { msg: format!("{}", e) }
+    }
+}
+
+impl From<serde_json::Error> for CompileError {
+    fn from(e: serde_json::Error) -> Self {
+        CompileError::Io { msg: format!("{}", e) }
+    }
+}
+
+impl From<CompileError> for std::io::Error {
+    fn from(e: CompileError) -> Self {
+        std::io::Error::other(e)
+    }
+}
@@ -1,7 +1,7 @@
 use crate::common::network::*;
 use crate::common::primitive::*;
 use petgraph::graph::{Graph, NodeIndex};
-use serde::Serialize;
+use serde::{Deserialize, Deserializer, Serialize};
 use strum_macros::EnumCount as EnumCountMacro;
 use serde::ser::SerializeStruct;
 use serde::Serializer;
@@ -55,6 +55,10 @@ pub struct NodeInfo {
     /// index to the allocated imem slot
     pub pc: u32,
 
+    /// Index of the target SRAM within the module's sram processor
+    /// (only meaningful for SRAM nodes)
+    pub sram_slot: u32,
+
     /// Debug information
     pub debug: DebugInfo
 }
@@ -75,13 +79,43 @@ impl Serialize for NodeInfo {
     }
 }
 
+/// Inverse of the `Serialize` implementation. Fields that are not
+/// serialized are set to their default values
+impl<'de> Deserialize<'de> for NodeInfo {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Node {
+            module: u32,
+            proc: u32,
+            #[serde(rename = "rank.asap")]
+            asap: u32,
+            #[serde(rename = "rank.alap")]
+            alap: u32,
+            scheduled: bool,
+            pc: u32
+        }
+        let node = Node::deserialize(deserializer)?;
+        Ok(NodeInfo {
+            coord: Coordinate { module: node.module, proc: node.proc },
+            rank: RankInfo { asap: node.asap, alap: node.alap, mob: 0 },
+            scheduled: node.scheduled,
+            pc: node.pc,
+            ..NodeInfo::default()
+        })
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct NodeMapInfo {
     /// Info filled in by the compiler
     pub info: NodeInfo,
 
-    /// Petgraph node index
-    pub idx: NodeIndex,
+    /// Petgraph node index. `None` when the node is not part of the graph
+    /// (e.g. the internal signals of a loaded compiled design)
+    pub idx: Option<NodeIndex>,
 }
 
 impl Serialize for NodeMapInfo {
@@ -96,6 +130,21 @@ impl Serialize for NodeMapInfo {
     }
 }
 
+/// `idx` is not serialized, so it is set to `None`
+impl<'de> Deserialize<'de> for NodeMapInfo {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Info {
+            info: NodeInfo
+        }
+        let info = Info::deserialize(deserializer)?;
+        Ok(NodeMapInfo { info: info.info, idx: None })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct HWNode {
     /// CircuitPrimitive from the blif_parser
@@ -170,9 +219,10 @@ pub enum SignalType {
     SRAMRdWrAddr { name: String, idx: u32 }
 }
 
-impl Debug for SignalType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let name = match self {
+impl SignalType {
+    /// Name of the net carrying this signal
+    pub fn name(&self) -> &str {
+        match self {
             SignalType::NOP                          => "",
             SignalType::Wire         { name        } => name,
             SignalType::SRAMRdEn     { name        } => name,
@@ -185,8 +235,13 @@ impl Debug for SignalType {
             SignalType::SRAMRdWrEn   { name        } => name,
             SignalType::SRAMRdWrAddr { name, idx:_ } => name,
             SignalType::SRAMRdWrMode { name        } => name,
-        };
-        write!(f, "{}", name)
+        }
+    }
+}
+
+impl Debug for SignalType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
     }
 }
 
@@ -0,0 +1,185 @@
+use crate::common::{
+    circuit::Circuit,
+    config::PlatformConfig,
+    error::CompileError,
+    instruction::*,
+    mapping::EmulatorMapping,
+    primitive::*,
+};
+use bitvec::prelude::*;
+use indexmap::IndexMap;
+use std::fs;
+
+/// Bytes per instruction in the image. Each instruction is pushed to the
+/// FPGA as a single 512 bit DMA beat
+pub const INST_BEAT_BYTES: usize = 64;
+
+/// Bits of an instruction beat that are used: the instruction followed by
+/// the processor and module index
+pub fn inst_beat_bits(pcfg: &PlatformConfig) -> u32 {
+    pcfg.inst_bits() + pcfg.num_proc_bits() + pcfg.num_mod_bits()
+}
+
+/// # encode_inst_beat
+/// - Encodes `inst` for processor `proc` in module `module` into the beat
+///   that the instruction bridge consumes
+/// - `to_bits` followed by the processor & module index (MSB first), stored
+///   with the last bit in the LSB of byte 0
+pub fn encode_inst_beat(inst: &Instruction, pcfg: &PlatformConfig, module: u32, proc: u32) -> Vec<u8> {
+    let mut bitbuf = inst.to_bits(pcfg);
+    for x in 0..pcfg.num_proc_bits() {
+        let sl = pcfg.num_proc_bits() - x - 1;
+        bitbuf.push((proc >> sl) & 1 == 1);
+    }
+    for x in 0..pcfg.num_mod_bits() {
+        let sl = pcfg.num_mod_bits() - x - 1;
+        bitbuf.push((module >> sl) & 1 == 1);
+    }
+    assert!(bitbuf.len() <= INST_BEAT_BYTES * 8,
+        "Instruction beat has {} bits, more than {}", bitbuf.len(), INST_BEAT_BYTES * 8);
+
+    let mut beat = vec![0u8; INST_BEAT_BYTES];
+    for (i, bit) in bitbuf.iter().rev().enumerate() {
+        if *bit {
+            beat[i / 8] |= 1 << (i % 8);
+        }
+    }
+    return beat;
+}
+
+/// Inverse of `encode_inst_beat`. Returns the instruction, processor index
+/// and module index, or why the beat isn't a valid instruction
+pub fn decode_inst_beat(beat: &[u8], pcfg: &PlatformConfig) -> Result<(Instruction, u32, u32), String> {
+    let inst_bits = pcfg.inst_bits() as usize;
+    let proc_bits = pcfg.num_proc_bits() as usize;
+    let mod_bits  = pcfg.num_mod_bits() as usize;
+    let tot_bits  = inst_bits + proc_bits + mod_bits;
+
+    let raw = beat.view_bits::<Lsb0>();
+    let mut bitbuf: BitVec = BitVec::new();
+    for i in (0..tot_bits).rev() {
+        bitbuf.push(raw[i]);
+    }
+
+    let mut pidx = 0;
+    for i in 0..proc_bits {
+        pidx = (pidx << 1) | (bitbuf[inst_bits + i] as u32);
+    }
+    let mut midx = 0;
+    for i in 0..mod_bits {
+        midx = (midx << 1) | (bitbuf[inst_bits + proc_bits + i] as u32);
+    }
+    bitbuf.truncate(inst_bits);
+
+    let inst = Instruction::from_bits(&bitbuf, pcfg)?;
+    return Ok((inst, pidx, midx));
+}
+
+/// Instructions of each module in the order that they are pushed to the
+/// FPGA: `host_steps` instructions per processor, processors in order
+pub fn module_instructions(emul: &EmulatorMapping) -> IndexMap<u32, Vec<Instruction>> {
+    let mut ret: IndexMap<u32, Vec<Instruction>> = IndexMap::new();
+    for (m, mmap) in emul.module_mappings.iter() {
+        let mut procs: Vec<&u32> = mmap.proc_mappings.keys().collect();
+        procs.sort();
+
+        let mut insts = vec![];
+        for p in procs.iter() {
+            insts.extend(mmap.proc_mappings.get(*p).unwrap().instructions.clone());
+        }
+        ret.insert(*m, insts);
+    }
+    ret.sort_keys();
+    return ret;
+}
+
+/// Instruction image of `module`: the beats of `insts` (`module_instructions`) back to back
+pub fn module_image(insts: &Vec<Instruction>, pcfg: &PlatformConfig, module: u32, host_steps: u32) -> Vec<u8> {
+    let mut image = Vec::with_capacity(insts.len() * INST_BEAT_BYTES);
+    for (i, inst) in insts.iter().enumerate() {
+        let proc = i as u32 / host_steps;
+        image.extend(encode_inst_beat(inst, pcfg, module, proc));
+    }
+    return image;
+}
+
+/// Save the instruction image of each module as `<output_dir>/<top>.mod-<m>.img`.
+/// Returns the paths of the images
+pub fn save_instruction_images(circuit: &Circuit) -> Result<Vec<String>, CompileError> {
+    let ccfg = &circuit.compiler_cfg;
+    let mut paths = vec![];
+    for (m, insts) in module_instructions(&circuit.emul).iter() {
+        let image = module_image(insts, &circuit.platform_cfg, *m, circuit.emul.host_steps);
+        let path = format!("{}/{}.mod-{}.img", ccfg.output_dir, ccfg.top_module, m);
+        fs::write(&path, image)?;
+        paths.push(path);
+    }
+    return Ok(paths);
+}
+
+/// A decoded instruction of an image
+#[derive(Debug, Clone)]
+pub struct DisasmInst {
+    pub module: u32,
+    pub proc: u32,
+
+    /// Position of the instruction within its processor
+    pub pc: u32,
+
+    pub inst: Instruction
+}
+
+/// # disassemble_image
+/// - Decodes an image written by `save_instruction_images`
+/// - `pc` counts the instructions of each processor in the order they appear
+pub fn disassemble_image(image: &[u8], pcfg: &PlatformConfig) -> Result<Vec<DisasmInst>, CompileError> {
+    if image.len() % INST_BEAT_BYTES != 0 {
+        return Err(CompileError::invalid_config("instruction image size", image.len(),
+            &format!("expect a multiple of {} bytes", INST_BEAT_BYTES)));
+    }
+
+    let mut ret = vec![];
+    let mut pcs: IndexMap<(u32, u32), u32> = IndexMap::new();
+    for (i, beat) in image.chunks(INST_BEAT_BYTES).enumerate() {
+        let (inst, proc, module) = match decode_inst_beat(beat, pcfg) {
+            Ok(x) => x,
+            Err(msg) => {
+                return Err(CompileError::invalid_config("instruction image beat", i, &msg));
+            }
+        };
+        let pc = match pcs.get_mut(&(module, proc)) {
+            Some(pc) => { *pc += 1; *pc }
+            None => { pcs.insert((module, proc), 0); 0 }
+        };
+        ret.push(DisasmInst { module: module, proc: proc, pc: pc, inst: inst });
+    }
+    return Ok(ret);
+}
+
+impl DisasmInst {
+    /// One line listing, e.g.
+    /// `m 0 p 3 pc 12: Lut lut 0x96 ops [l5 l0 s3] sw 2 local fwd`.
+    /// NOPs only list the switch info as they can still forward bits
+    pub fn disasm(self: &Self) -> String {
+        let inst = &self.inst;
+        let mut ret = format!("m {} p {} pc {}: {:?}", self.module, self.proc, self.pc, inst.opcode);
+        if inst.opcode != Opcode::NOP {
+            let mut ops = vec![];
+            for op in inst.operands.iter() {
+                ops.push(format!("{}{}", if op.local { "l" } else { "s" }, op.rs));
+            }
+            ret.push_str(&format!(" lut {:#x} ops [{}]", inst.lut, ops.join(" ")));
+        }
+        ret.push_str(&format!(" sw {}", inst.sinfo.idx));
+        if inst.sinfo.local {
+            ret.push_str(" local");
+        }
+        if inst.sinfo.fwd {
+            ret.push_str(" fwd");
+        }
+        if inst.mem {
+            ret.push_str(" mem");
+        }
+        return ret;
+    }
+}
@@ -16,7 +16,7 @@ pub struct Operand {
     pub idx: Bits,
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct SwitchInfo {
     /// Set when the `local` field has been already set (for correctness checks)
     pub local_set: bool,
@@ -34,7 +34,7 @@ pub struct SwitchInfo {
     pub fwd: bool
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct Instruction {
     /// This instruction is performing something
     pub valid: bool,
@@ -107,4 +107,58 @@ impl Instruction {
         bit_vec.push(self.mem        );
         return bit_vec;
     }
+
+    /// Inverse of `to_bits`. Operands that are not used by the instruction
+    /// are decoded as `rs: 0, local: true`, which is how `to_bits` pads them.
+    /// Fails on opcodes that the processor doesn't know.
+    pub fn from_bits(bits: &BitVec, cfg: &PlatformConfig) -> Result<Self, String> {
+        assert!(bits.len() == cfg.inst_bits() as usize,
+            "Instruction has {} bits, expect {}", bits.len(), cfg.inst_bits());
+
+        let mut offset = 0;
+        let mut take = |nbits: u32| -> u64 {
+            let mut x: u64 = 0;
+            for i in 0..nbits as usize {
+                x = (x << 1) | (bits[offset + i] as u64);
+            }
+            offset += nbits as usize;
+            return x;
+        };
+
+        let opcode_raw = take(cfg.opcode_bits()) as u32;
+        let opcode = match Opcode::from_repr(opcode_raw) {
+            Some(op) => op,
+            None => {
+                return Err(format!("Unknown opcode {}", opcode_raw));
+            }
+        };
+        let lut = take(cfg.lut_bits());
+
+        let mut operands: Vec<Operand> = vec![Operand::default(); cfg.lut_inputs as usize];
+        for opidx in (0..cfg.lut_inputs).rev() {
+            let rs = take(cfg.index_bits()) as Bits;
+            let local = take(1) == 1;
+            operands[opidx as usize] = Operand { rs: rs, local: local, idx: opidx };
+        }
+
+        let sinfo_idx = take(cfg.switch_bits()) as Bits;
+        let sinfo_local = take(1) == 1;
+        let sinfo_fwd   = take(1) == 1;
+        let mem         = take(1) == 1;
+
+        return Ok(Instruction {
+            valid: opcode != Opcode::NOP,
+            opcode: opcode,
+            lut: lut,
+            operands: operands,
+            sinfo: SwitchInfo {
+                local_set: true,
+                fwd_set: true,
+                idx: sinfo_idx,
+                local: sinfo_local,
+                fwd: sinfo_fwd
+            },
+            mem: mem
+        });
+    }
 }
@@ -1,30 +1,66 @@
 use crate::common::instruction::*;
 use crate::common::hwgraph::*;
-use serde::Serialize;
+use crate::common::primitive::{Bit, SRAMRegion};
+use serde::{Deserialize, Serialize};
 use indexmap::IndexMap;
 
 /// # MappingInfo
 /// - Fields specific to how the design is mapped to a particular emulator processor
-#[derive(Serialize, Debug, Default, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct ProcessorMapping {
     /// Generated instructions for this module
     pub instructions: Vec<Instruction>,
 
     /// Signal mapping info
     pub signal_map: IndexMap<String, NodeMapInfo>,
+
+    /// Initial register state held in the local data memory, indexed by address.
+    /// Addresses that are not listed start at zero
+    pub ldm_init: IndexMap<u32, Bit>,
+
+    /// Initial register state held in the switch data memory, indexed by address
+    pub sdm_init: IndexMap<u32, Bit>,
 }
 
 /// Supported SRAM port types
-#[derive(Serialize, Debug, Default, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub enum SRAMPortType {
     #[default]
     OneRdOneWrPortSRAM = 0,
     SinglePortSRAM
 }
 
+/// # SRAMMemMapping
+/// - A single target SRAM packed into the address space of a sram processor
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SRAMMemMapping {
+    /// Name of the target SRAM blackbox
+    pub name: String,
+
+    /// Type of SRAM
+    pub port_type: SRAMPortType,
+
+    /// Number of bits used in write mask field
+    pub wmask_bits: u32,
+
+    /// Number of bits per target SRAM entry
+    pub width_bits: u32,
+
+    /// Number of address bits of the target SRAM
+    pub addr_bits: u32,
+
+    /// First sram processor entry used by this target SRAM
+    pub base_addr: u32,
+
+    /// Part of the target SRAM blackbox held by this memory
+    pub region: SRAMRegion
+}
+
 /// # MappingInfo
 /// - Fields specific to how the design is mapped to a particular sram processor
-#[derive(Serialize, Debug, Default, Clone)]
+/// - `port_type`, `wmask_bits` and `width_bits` describe the memory in slot 0,
+///   which is what the hardware configuration registers take
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct SRAMMapping {
     /// Type of SRAM
     pub port_type: SRAMPortType,
@@ -33,12 +69,39 @@ pub struct SRAMMapping {
     pub wmask_bits: u32,
 
     /// Number of bits per target SRAM entry
-    pub width_bits: u32
+    pub width_bits: u32,
+
+    /// Target SRAMs packed into this sram processor, indexed by `NodeInfo::sram_slot`
+    pub mems: Vec<SRAMMemMapping>
+}
+
+impl SRAMMapping {
+    /// Returns the target SRAMs mapped to this sram processor.
+    /// Mappings that only have the top level fields set (e.g. the ones
+    /// reconstructed from the configuration registers) are treated as a
+    /// single memory starting at address 0.
+    pub fn slots(self: &Self) -> Vec<SRAMMemMapping> {
+        if self.mems.len() > 0 {
+            return self.mems.clone();
+        }
+        if self.width_bits == 0 {
+            return vec![];
+        }
+        return vec![SRAMMemMapping {
+            name:       String::new(),
+            port_type:  self.port_type.clone(),
+            wmask_bits: self.wmask_bits,
+            width_bits: self.width_bits,
+            addr_bits:  0,
+            base_addr:  0,
+            region:     SRAMRegion::default()
+        }];
+    }
 }
 
 /// # MappingInfo
 /// - Fields specific to how the design is mapped to a particular emulator module
-#[derive(Serialize, Debug, Default, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct ModuleMapping {
     /// Per processor emulation mapping information
     pub proc_mappings: IndexMap<u32, ProcessorMapping>,
@@ -49,7 +112,7 @@ pub struct ModuleMapping {
 
 /// # MappingInfo
 /// - Contains fields specific to the emulator hardware
-#[derive(Serialize, Debug, Default, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct EmulatorMapping {
     /// Number of host steps to emulate a single cycle
     pub host_steps: u32,
@@ -1,8 +1,16 @@
 pub mod circuit;
+pub mod clocks;
+pub mod compiled;
 pub mod config;
+pub mod constraints;
+pub mod error;
 pub mod hwgraph;
+pub mod inst_image;
 pub mod instruction;
 pub mod mapping;
 pub mod network;
 pub mod primitive;
+pub mod sram_image;
+pub mod topology;
 pub mod utils;
+pub mod verify;
@@ -5,7 +5,7 @@ use std::{
     fmt::Debug, collections::LinkedList,
 };
 
-#[derive(Serialize, Debug, Clone, Default, Eq, Hash, PartialEq, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Eq, Hash, PartialEq, Copy)]
 pub struct Coordinate {
     /// module id
     pub module: u32,
@@ -1,7 +1,7 @@
 use crate::common::config::*;
 use crate::common::hwgraph::SignalType;
-use serde::Serialize;
-use strum_macros::EnumCount as EnumCountMacro;
+use serde::{Deserialize, Serialize};
+use strum_macros::{EnumCount as EnumCountMacro, FromRepr};
 use indexmap::IndexMap;
 use std::fmt::Debug;
 use blif_parser::primitives::*;
@@ -11,7 +11,7 @@ pub type Bits = u32;
 pub type Cycle = u32;
 
 /// Opcodes for the emulator instructions
-#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, EnumCountMacro)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, EnumCountMacro, FromRepr)]
 #[repr(u32)]
 pub enum Opcode {
     #[default]
@@ -77,6 +77,32 @@ pub enum Primitive {
     SRAMRdWrAddr,
 }
 
+/// The part of a target SRAM blackbox that is stored in a SRAMNode.
+/// SRAMs that are split into width slices and depth banks (`split_large_srams`)
+/// keep track of which bits & entries of the original SRAM they hold so that
+/// the initial contents of the target SRAM can be distributed among them
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct SRAMRegion {
+    /// Name of the target SRAM blackbox
+    pub target: String,
+
+    /// First data bit of the target SRAM entry
+    pub bit_offset: u32,
+
+    /// First entry of the target SRAM
+    pub entry_offset: u32
+}
+
+impl SRAMRegion {
+    pub fn new(target: &str) -> Self {
+        SRAMRegion {
+            target: target.to_string(),
+            bit_offset: 0,
+            entry_offset: 0
+        }
+    }
+}
+
 /// Represents a node in the gate level netlist
 #[derive(Debug, Clone, Default, PartialEq, Serialize, EnumCountMacro)]
 #[repr(u32)]
@@ -89,7 +115,7 @@ pub enum CircuitPrimitive {
     ConstLut     { val: Bit, output: String },
     Gate         { c: String, d: String, q: String, r: Option<String>, e: Option<String> },
     Latch        { input: String, output: String, control: String, init: LatchInit },
-    SRAMNode     { name: String, conns: IndexMap<String, String> },
+    SRAMNode     { name: String, conns: IndexMap<String, String>, port: String, region: SRAMRegion },
     SRAMRdEn     { name: String },
     SRAMWrEn     { name: String },
     SRAMRdAddr   { name: String, idx: u32 },
@@ -179,7 +205,12 @@ impl From<&ParsedPrimitive> for CircuitPrimitive {
                 }
             }
             ParsedPrimitive::Subckt { name, conns } => {
-                Self::SRAMNode { name: name.clone(), conns: conns.clone() }
+                Self::SRAMNode {
+                    name: name.clone(),
+                    conns: conns.clone(),
+                    port: String::new(),
+                    region: SRAMRegion::new(name)
+                }
             }
         }
     }
@@ -232,8 +263,10 @@ impl CircuitPrimitive {
         }
     }
 
-    pub fn unique_sram_input_idx(self: &Self, pcfg: &PlatformConfig) -> u32 {
-        let offset = self.unique_sram_input_offset(pcfg);
+    /// Unique ID of this SRAM input bit. IDs of each target SRAM packed into
+    /// the same sram processor are placed back to back according to `slot`
+    pub fn unique_sram_input_idx(self: &Self, pcfg: &PlatformConfig, slot: u32) -> u32 {
+        let offset = self.unique_sram_input_offset(pcfg) + slot * pcfg.sram_slot_input_ids();
         match self {
             Self::SRAMRdEn     { name:_ }      => offset,
             Self::SRAMWrEn     { name:_ }      => offset,
@@ -248,10 +281,95 @@ impl CircuitPrimitive {
         }
     }
 
-    pub fn unique_sram_output_idx(self: &Self, pcfg: &PlatformConfig) -> u32 {
+    /// Value of a register at target cycle 0.
+    /// Registers with an unknown or don't care initial value start at zero.
+    /// Returns `None` for nodes that don't hold state
+    pub fn init_value(self: &Self) -> Option<Bit> {
+        match self {
+            Self::Latch { init, .. } => {
+                match init {
+                    LatchInit::ONE => Some(1),
+                    _              => Some(0)
+                }
+            }
+            _ => None
+        }
+    }
+
+    /// Operands of a `Gate` instruction in order: `d`, `e`, `r` and `q`.
+    /// `e` and `r` are only present when the pins are connected, and `q`
+    /// (the current value of the register) is read only when there is an enable
+    pub fn gate_operands(self: &Self) -> Vec<String> {
+        let mut ret = vec![];
+        match self {
+            Self::Gate { c:_, d, q, r, e } => {
+                ret.push(d.clone());
+                match e {
+                    Some(e) => { ret.push(e.clone()); }
+                    None => {}
+                }
+                match r {
+                    Some(r) => { ret.push(r.clone()); }
+                    None => {}
+                }
+                if e.is_some() {
+                    ret.push(q.clone());
+                }
+            }
+            _ => {}
+        }
+        return ret;
+    }
+
+    /// Next value of a `Gate` given its operand values (see `gate_operands`).
+    /// - The enable and the synchronous reset are active high
+    /// - Reset sets the register to zero and takes priority over the enable
+    pub fn gate_next(self: &Self, ops: &Vec<Bit>) -> Bit {
+        match self {
+            Self::Gate { c:_, d:_, q:_, r, e } => {
+                let mut i = 1;
+                let en = match e {
+                    Some(_) => { i += 1; ops[i - 1] }
+                    None => 1
+                };
+                let rst = match r {
+                    Some(_) => { i += 1; ops[i - 1] }
+                    None => 0
+                };
+                if rst == 1 {
+                    return 0;
+                } else if en == 1 {
+                    return ops[0];
+                } else {
+                    return ops[i];
+                }
+            }
+            _ => {
+                assert!(false, "gate_next called on {:?}", self);
+                return 0;
+            }
+        }
+    }
+
+    /// Rows of the truth table of a `Gate` that evaluate to one (the same format
+    /// as `CircuitPrimitive::Lut` tables)
+    pub fn gate_table(self: &Self) -> Vec<Vec<u8>> {
+        let nops = self.gate_operands().len();
+        let mut ret = vec![];
+        for x in 0..(1u32 << nops) {
+            let ops: Vec<Bit> = (0..nops).map(|i| ((x >> i) & 1) as Bit).collect();
+            if self.gate_next(&ops) == 1 {
+                ret.push(ops);
+            }
+        }
+        return ret;
+    }
+
+    /// Unique ID of this SRAM output bit
+    pub fn unique_sram_output_idx(self: &Self, pcfg: &PlatformConfig, slot: u32) -> u32 {
         let w = pcfg.sram_width;
         match self {
-            Self::SRAMRdData { name:_, idx } => *idx,
+            Self::SRAMRdData { name:_, idx } => slot * w + *idx,
             _ => w
         }
     }
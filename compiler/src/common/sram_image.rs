@@ -0,0 +1,247 @@
+use crate::common::primitive::Bit;
+use crate::common::config::CompilerConfig;
+use crate::common::mapping::{EmulatorMapping, SRAMMemMapping};
+use crate::common::error::CompileError;
+use indexmap::{IndexMap, IndexSet};
+use std::fs;
+
+/// Contents of a `$readmemh` or `$readmemb` image.
+/// Each word is stored LSB first and indexed by its address
+#[derive(Debug, Default, Clone)]
+pub struct SRAMImage {
+    pub words: IndexMap<u32, Vec<Bit>>,
+
+    /// Line of the image that each word is on, for error messages
+    pub lines: IndexMap<u32, usize>
+}
+
+/// Remove `//` and `/* */` comments from a memory image. Line breaks are kept
+fn strip_comments(text: &str) -> String {
+    let mut ret = String::new();
+    let mut chars = text.chars().peekable();
+    let mut in_block = false;
+    while let Some(c) = chars.next() {
+        if in_block {
+            if c == '\n' {
+                ret.push('\n');
+            } else if c == '*' && chars.peek() == Some(&'/') {
+                chars.next();
+                in_block = false;
+            }
+            continue;
+        }
+        if c == '/' && chars.peek() == Some(&'/') {
+            while let Some(n) = chars.next() {
+                if n == '\n' {
+                    ret.push('\n');
+                    break;
+                }
+            }
+        } else if c == '/' && chars.peek() == Some(&'*') {
+            chars.next();
+            in_block = true;
+            ret.push(' ');
+        } else {
+            ret.push(c);
+        }
+    }
+    return ret;
+}
+
+impl SRAMImage {
+    /// Parse the image in `path`. Images ending with `.bin` are read like
+    /// `$readmemb`, others like `$readmemh`
+    pub fn from_file(path: &str) -> Result<Self, CompileError> {
+        let text = fs::read_to_string(path)?;
+        let radix_bits = if path.ends_with(".bin") { 1 } else { 4 };
+        return SRAMImage::parse(&text, radix_bits).map_err(|e| {
+            CompileError::Parse { path: path.to_string(), msg: e }
+        });
+    }
+
+    /// Parse the text of a memory image with `radix_bits` bits per digit
+    /// - `//` and `/* */` comments are ignored
+    /// - `@<hex address>` sets the address of the following word
+    /// - `_` separators are ignored and `x`/`z` digits are read as 0
+    /// - Errors name the line of the offending token
+    pub fn parse(text: &str, radix_bits: u32) -> Result<Self, String> {
+        let mut ret = SRAMImage::default();
+        let mut addr: Option<u32> = Some(0);
+        for (lidx, line) in strip_comments(text).lines().enumerate() {
+            let lineno = lidx + 1;
+            for tok in line.split_whitespace() {
+                match tok.strip_prefix('@') {
+                    Some(a) => {
+                        addr = match u32::from_str_radix(a, 16) {
+                            Ok(x) => Some(x),
+                            Err(_) => {
+                                return Err(format!("line {}: invalid address {}", lineno, tok));
+                            }
+                        };
+                        continue;
+                    }
+                    None => {}
+                }
+
+                let cur = match addr {
+                    Some(x) => x,
+                    None => {
+                        return Err(format!("line {}: word {} is past the last address", lineno, tok));
+                    }
+                };
+                let mut bits: Vec<Bit> = vec![];
+                for c in tok.chars().rev() {
+                    let digit = match c {
+                        '_' => { continue; }
+                        'x' | 'X' | 'z' | 'Z' => 0,
+                        _ => match c.to_digit(1 << radix_bits) {
+                            Some(d) => d,
+                            None => {
+                                return Err(format!("line {}: invalid word {} at address 0x{:x}",
+                                    lineno, tok, cur));
+                            }
+                        }
+                    };
+                    for i in 0..radix_bits {
+                        bits.push(((digit >> i) & 1) as Bit);
+                    }
+                }
+                ret.words.insert(cur, bits);
+                ret.lines.insert(cur, lineno);
+                addr = cur.checked_add(1);
+            }
+        }
+        return Ok(ret);
+    }
+
+    /// Returns an error when a word of the image lies outside of the target
+    /// SRAM with `entries` entries of `width_bits` bits. Words may be padded
+    /// to a whole number of digits as long as the padding is zero
+    pub fn check_bounds(self: &Self, entries: u64, width_bits: u32) -> Result<(), String> {
+        for (addr, word) in self.words.iter() {
+            let lineno = self.lines[addr];
+            if *addr as u64 >= entries {
+                return Err(format!("line {}: address 0x{:x} is out of range of {} entries",
+                    lineno, addr, entries));
+            }
+            if word.iter().skip(width_bits as usize).any(|b| *b != 0) {
+                return Err(format!("line {}: word at address 0x{:x} is wider than {} bits",
+                    lineno, addr, width_bits));
+            }
+        }
+        return Ok(());
+    }
+
+    /// Returns the words stored in `mem` indexed by the address within `mem`.
+    /// Only the bits & entries of the target SRAM that `mem` holds are returned
+    /// (see `SRAMRegion`). Bits missing from the image are zero.
+    pub fn entries(self: &Self, mem: &SRAMMemMapping) -> IndexMap<u32, Vec<Bit>> {
+        let region = &mem.region;
+        let entries = 1u64 << mem.addr_bits;
+        let mut ret: IndexMap<u32, Vec<Bit>> = IndexMap::new();
+        for (addr, word) in self.words.iter() {
+            if *addr < region.entry_offset ||
+               (*addr - region.entry_offset) as u64 >= entries {
+                continue;
+            }
+            let mut bits = vec![0; mem.width_bits as usize];
+            for (i, bit) in bits.iter_mut().enumerate() {
+                match word.get(region.bit_offset as usize + i) {
+                    Some(b) => { *bit = *b; }
+                    None => {}
+                }
+            }
+            ret.insert(*addr - region.entry_offset, bits);
+        }
+        ret.sort_keys();
+        return ret;
+    }
+}
+
+/// Parse the `--sram-init <SRAM blackbox name>=<image path>` arguments
+pub fn parse_sram_init_args(sram_init: &Vec<String>) -> Result<IndexMap<String, String>, CompileError> {
+    let mut ret: IndexMap<String, String> = IndexMap::new();
+    for arg in sram_init.iter() {
+        match arg.split_once('=') {
+            Some((name, path)) => {
+                ret.insert(name.to_string(), path.to_string());
+            }
+            None => {
+                return Err(CompileError::invalid_config("sram_init", arg,
+                    "expected <SRAM name>=<image path>"));
+            }
+        }
+    }
+    return Ok(ret);
+}
+
+/// Load the SRAM images in `cfg.sram_init` and return the initial contents of
+/// each module's SRAM processor as (SRAM processor address, data) pairs.
+/// - Images are matched by the SRAM blackbox name, so every instance of a
+///   blackbox starts with the same contents
+/// - Returns an error if an image can't be read, matches no SRAM or holds
+///   words outside of its SRAM
+pub fn sram_init_contents(
+    cfg: &CompilerConfig,
+    emul: &EmulatorMapping
+) -> Result<IndexMap<u32, Vec<(u32, Vec<Bit>)>>, CompileError> {
+    let mut images: IndexMap<String, SRAMImage> = IndexMap::new();
+    for (name, path) in cfg.sram_init.iter() {
+        images.insert(name.clone(), SRAMImage::from_file(path)?);
+    }
+
+    // Size of each target SRAM, from the memories that hold a part of it
+    let mut sizes: IndexMap<String, (u64, u32)> = IndexMap::new();
+    for (_, mmap) in emul.module_mappings.iter() {
+        for mem in mmap.sram_mapping.mems.iter() {
+            let region = &mem.region;
+            let entries = region.entry_offset as u64 + (1u64 << mem.addr_bits);
+            let width = region.bit_offset + mem.width_bits;
+            let size = sizes.entry(region.target.clone()).or_insert((0, 0));
+            *size = (size.0.max(entries), size.1.max(width));
+        }
+    }
+    for (name, image) in images.iter() {
+        match sizes.get(name) {
+            Some((entries, width)) => {
+                match image.check_bounds(*entries, *width) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        return Err(CompileError::Parse { path: cfg.sram_init[name].clone(), msg: e });
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+
+    let mut used: IndexSet<String> = IndexSet::new();
+    let mut ret: IndexMap<u32, Vec<(u32, Vec<Bit>)>> = IndexMap::new();
+    for (m, mmap) in emul.module_mappings.iter() {
+        let mut contents = vec![];
+        let mut bases: IndexSet<u32> = IndexSet::new();
+        for mem in mmap.sram_mapping.mems.iter() {
+            // Ports of a multi-ported SRAM share the same memory
+            if !bases.insert(mem.base_addr) {
+                continue;
+            }
+            match images.get(&mem.region.target) {
+                Some(image) => {
+                    used.insert(mem.region.target.clone());
+                    for (addr, bits) in image.entries(mem) {
+                        contents.push((mem.base_addr + addr, bits));
+                    }
+                }
+                None => {}
+            }
+        }
+        ret.insert(*m, contents);
+    }
+
+    for name in images.keys() {
+        if !used.contains(name) {
+            return Err(CompileError::invalid_config("sram_init", name, "the SRAM is not in the design"));
+        }
+    }
+    return Ok(ret);
+}
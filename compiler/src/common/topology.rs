@@ -0,0 +1,508 @@
+use crate::common::config::{PlatformConfig, TopologyKind};
+use crate::common::error::CompileError;
+use crate::common::network::*;
+use serde::Serialize;
+use std::fmt::Debug;
+use indexmap::{IndexMap, IndexSet};
+use std::collections::VecDeque;
+
+/// Maximum number of multi-hop routes returned for a pair of modules
+const MAX_ROUTES: usize = 1024;
+
+/// Global network links between processors of different modules.
+/// Every processor sends to and receives from at most one remote processor
+#[derive(Clone, Default, Serialize)]
+pub struct TopologyLinks {
+    pub edges: IndexMap<Coordinate, Coordinate>,
+    pub inter_mod_paths: IndexMap<(u32, u32), Vec<NetworkPath>>,
+
+    /// Processors of each module that have a global network link
+    pub gateways: IndexMap<u32, Vec<Coordinate>>
+}
+
+impl TopologyLinks {
+    fn add_path(self: &mut Self, src: Coordinate, dst: Coordinate) {
+        if !self.inter_mod_paths.contains_key(&(src.module, dst.module)) {
+            self.inter_mod_paths.insert((src.module, dst.module), vec![]);
+        }
+        if !self.inter_mod_paths.contains_key(&(dst.module, src.module)) {
+            self.inter_mod_paths.insert((dst.module, src.module), vec![]);
+        }
+        let paths = self.inter_mod_paths.get_mut(&(src.module, dst.module)).unwrap();
+        paths.push(NetworkPath::new(src, dst));
+    }
+
+    /// Add a bidirectional link between `src` and `dst`
+    fn connect(self: &mut Self, src: Coordinate, dst: Coordinate) {
+        self.edges.insert(src, dst);
+        self.edges.insert(dst, src);
+        for c in [src, dst] {
+            if !self.gateways.contains_key(&c.module) {
+                self.gateways.insert(c.module, vec![]);
+            }
+            self.gateways.get_mut(&c.module).unwrap().push(c);
+        }
+        self.add_path(src, dst);
+        self.add_path(dst, src);
+    }
+
+    /// Connect each pair of neighboring modules in `pairs` with as many links
+    /// as possible while giving each neighbor of a module the same number of
+    /// processors
+    fn from_module_pairs(pairs: &Vec<(u32, u32)>, num_mods: u32, num_procs: u32) -> Result<Self, CompileError> {
+        let mut ret = TopologyLinks::default();
+        let mut degree: Vec<u32> = vec![0; num_mods as usize];
+        for (a, b) in pairs.iter() {
+            degree[*a as usize] += 1;
+            degree[*b as usize] += 1;
+        }
+
+        let mut next_proc: Vec<u32> = vec![0; num_mods as usize];
+        for (a, b) in pairs.iter() {
+            let links = (num_procs / degree[*a as usize]).min(num_procs / degree[*b as usize]);
+            if links == 0 {
+                return Err(CompileError::invalid_config("num_procs", num_procs,
+                    &format!("too small to connect modules {} and {} ({} and {} neighbors)",
+                        a, b, degree[*a as usize], degree[*b as usize])));
+            }
+            for _ in 0..links {
+                let src = Coordinate { module: *a, proc: next_proc[*a as usize] };
+                let dst = Coordinate { module: *b, proc: next_proc[*b as usize] };
+                next_proc[*a as usize] += 1;
+                next_proc[*b as usize] += 1;
+                ret.connect(src, dst);
+            }
+        }
+        return Ok(ret);
+    }
+
+    /// Modules directly connected to module `m`
+    fn neighbors(self: &Self, m: u32) -> Vec<u32> {
+        self.inter_mod_paths.iter()
+            .filter(|((m1, _), paths)| *m1 == m && paths.len() > 0)
+            .map(|((_, m2), _)| *m2)
+            .collect()
+    }
+}
+
+/// # Topology
+/// - Wiring of the global network connecting the emulation modules
+/// - Implementations only have to build the `TopologyLinks`. Paths, routes
+///   and latencies are derived from them unless overridden
+pub trait Topology {
+    fn links(self: &Self) -> &TopologyLinks;
+
+    /// Remote processor that each processor sends its global switch output to
+    fn edges(self: &Self) -> &IndexMap<Coordinate, Coordinate> {
+        &self.links().edges
+    }
+
+    /// Processors of `module` that have a global network link
+    fn gateways(self: &Self, module: u32) -> Vec<Coordinate> {
+        match self.links().gateways.get(&module) {
+            Some(g) => g.to_vec(),
+            None => vec![]
+        }
+    }
+
+    /// Returns a Vec<NetworkPath> where the path connects some processor in
+    /// src.module to some processor in dst.module
+    fn inter_mod_paths(self: &Self, src: Coordinate, dst: Coordinate) -> Vec<NetworkPath> {
+        match self.links().inter_mod_paths.get(&(src.module, dst.module)) {
+            Some(paths) => paths.to_vec(),
+            None => vec![]
+        }
+    }
+
+    /// Returns a Vec<NetworkRoute> where the route connects src.module to dst.module
+    /// through intermediate modules. Routes go through the modules on the shortest
+    /// module paths, or through exactly one intermediate module when the modules
+    /// are neighbors
+    fn inter_mod_routes(self: &Self, src: Coordinate, dst: Coordinate) -> Vec<NetworkRoute> {
+        let links = self.links();
+        let mut ret: Vec<NetworkRoute> = vec![];
+        for mods in module_sequences(links, src.module, dst.module).iter() {
+            let mut routes: Vec<NetworkRoute> = vec![NetworkRoute::new()];
+            for hop in mods.windows(2) {
+                let paths = links.inter_mod_paths.get(&(hop[0], hop[1])).unwrap();
+                let mut next = vec![];
+                for route in routes.iter() {
+                    for path in paths.iter() {
+                        let mut r = route.clone();
+                        match r.back() {
+                            Some(last) if last.dst != path.src => {
+                                r.push_back(NetworkPath::new(last.dst, path.src));
+                            }
+                            _ => {}
+                        }
+                        r.push_back(*path);
+                        next.push(r);
+                        if next.len() >= MAX_ROUTES {
+                            break;
+                        }
+                    }
+                    if next.len() >= MAX_ROUTES {
+                        break;
+                    }
+                }
+                routes = next;
+            }
+            ret.append(&mut routes);
+            if ret.len() >= MAX_ROUTES {
+                ret.truncate(MAX_ROUTES);
+                break;
+            }
+        }
+        return ret;
+    }
+
+    /// Latency of a single hop
+    fn path_lat(self: &Self, path: &NetworkPath, pcfg: &PlatformConfig) -> u32 {
+        match path.tpe {
+            PathTypes::ProcessorInternal => 0,
+            PathTypes::InterProcessor    => pcfg.inter_proc_nw_lat,
+            PathTypes::InterModule       => pcfg.inter_mod_nw_lat
+        }
+    }
+}
+
+/// Module sequences from `src` to `dst` (both included) used for multi-hop routes
+fn module_sequences(links: &TopologyLinks, src: u32, dst: u32) -> Vec<Vec<u32>> {
+    let mut ret = vec![];
+    let neighbors = links.neighbors(src);
+    if neighbors.contains(&dst) {
+        for m in neighbors.iter() {
+            if *m != dst && links.neighbors(*m).contains(&dst) {
+                ret.push(vec![src, *m, dst]);
+            }
+        }
+        return ret;
+    }
+
+    // BFS distances from src
+    let mut dist: IndexMap<u32, u32> = IndexMap::new();
+    let mut q: VecDeque<u32> = VecDeque::new();
+    dist.insert(src, 0);
+    q.push_back(src);
+    while let Some(m) = q.pop_front() {
+        for n in links.neighbors(m) {
+            if !dist.contains_key(&n) {
+                dist.insert(n, dist[&m] + 1);
+                q.push_back(n);
+            }
+        }
+    }
+    if !dist.contains_key(&dst) {
+        return ret;
+    }
+
+    // Walk back from dst along the shortest paths
+    let mut partial: Vec<Vec<u32>> = vec![vec![dst]];
+    while let Some(seq) = partial.pop() {
+        let head = *seq.last().unwrap();
+        if head == src {
+            ret.push(seq.into_iter().rev().collect());
+            if ret.len() >= MAX_ROUTES {
+                break;
+            }
+            continue;
+        }
+        let prev: IndexSet<u32> = links.neighbors(head).into_iter()
+            .filter(|n| dist.get(n) == Some(&(dist[&head] - 1)))
+            .collect();
+        for p in prev.iter() {
+            let mut s = seq.clone();
+            s.push(*p);
+            partial.push(s);
+        }
+    }
+    return ret;
+}
+
+/// # HubTopology
+/// - `num_mods - 1` modules are fully connected and each of them is connected
+///   to the last (hub) module
+/// - Requires `num_mods` to be 2^n + 1 (or 1) and `num_procs` to be 2^n
+#[derive(Clone, Default, Serialize)]
+pub struct HubTopology {
+    links: TopologyLinks
+}
+
+impl HubTopology {
+    pub fn new(num_mods: u32, num_procs: u32) -> Result<Self, CompileError> {
+        let mut ret = HubTopology::default();
+        if num_mods == 0 {
+            return Err(CompileError::invalid_config("num_mods", num_mods, "should be at least 1"));
+        } else if num_mods == 1 {
+            return Ok(ret);
+        }
+        let num_mods_1 = num_mods - 1;
+        if num_mods_1 & (num_mods_1 - 1) != 0 {
+            return Err(CompileError::invalid_config("num_mods", num_mods,
+                "should be 2^n + 1 for the hub topology"));
+        }
+        if num_procs & (num_procs - 1) != 0 {
+            return Err(CompileError::invalid_config("num_procs", num_procs,
+                "should be 2^n for the hub topology"));
+        }
+        if num_procs < num_mods_1 {
+            return Err(CompileError::invalid_config("num_procs", num_procs,
+                &format!("should be at least num_mods - 1 ({}) for the hub topology", num_mods_1)));
+        }
+        let grp_sz = num_procs / num_mods_1;
+
+        for m in 0..num_mods_1 {
+            for p in 0..num_procs {
+                let r = p % grp_sz;
+                let q = (p - r) / grp_sz;
+                let src = Coordinate { module: m, proc: p };
+                let dst = if q == m {
+                    let dm = num_mods_1;
+                    let dp = p;
+                    Coordinate { module: dm, proc: dp }
+                } else {
+                    let dm = q;
+                    let dp = m * grp_sz + r;
+                    Coordinate { module: dm, proc: dp }
+                };
+                ret.links.connect(src, dst);
+            }
+        }
+        return Ok(ret);
+    }
+}
+
+impl Topology for HubTopology {
+    fn links(self: &Self) -> &TopologyLinks {
+        &self.links
+    }
+}
+
+/// # MeshTopology
+/// - Modules placed on a `rows` x `cols` grid, connected to their
+///   north/south/east/west neighbors
+/// - With `torus`, the edges of the grid wrap around
+/// - `cols` is the largest divisor of `num_mods` that is at most sqrt(num_mods)
+/// - A prime `num_mods` above 3 is rejected as its grid would degenerate into
+///   a 1 x `num_mods` chain. Use the ring topology for a chain of modules
+#[derive(Clone, Default, Serialize)]
+pub struct MeshTopology {
+    pub rows: u32,
+    pub cols: u32,
+    pub torus: bool,
+    links: TopologyLinks
+}
+
+impl MeshTopology {
+    pub fn new(num_mods: u32, num_procs: u32, torus: bool) -> Result<Self, CompileError> {
+        if num_mods == 0 {
+            return Err(CompileError::invalid_config("num_mods", num_mods, "should be at least 1"));
+        }
+        let mut cols = 1;
+        for c in 1..=num_mods {
+            if c * c > num_mods {
+                break;
+            }
+            if num_mods % c == 0 {
+                cols = c;
+            }
+        }
+        if cols == 1 && num_mods > 3 {
+            return Err(CompileError::invalid_config("num_mods", num_mods,
+                "is prime, so the mesh would be a single chain of modules (use --topology ring)"));
+        }
+        let rows = num_mods / cols;
+        let id = |r: u32, c: u32| r * cols + c;
+
+        let mut pairs: Vec<(u32, u32)> = vec![];
+        for r in 0..rows {
+            for c in 0..cols {
+                if c + 1 < cols {
+                    pairs.push((id(r, c), id(r, c + 1)));
+                } else if torus && cols > 2 {
+                    pairs.push((id(r, c), id(r, 0)));
+                }
+                if r + 1 < rows {
+                    pairs.push((id(r, c), id(r + 1, c)));
+                } else if torus && rows > 2 {
+                    pairs.push((id(r, c), id(0, c)));
+                }
+            }
+        }
+        Ok(MeshTopology {
+            rows: rows,
+            cols: cols,
+            torus: torus,
+            links: TopologyLinks::from_module_pairs(&pairs, num_mods, num_procs)?
+        })
+    }
+}
+
+impl Topology for MeshTopology {
+    fn links(self: &Self) -> &TopologyLinks {
+        &self.links
+    }
+}
+
+/// # RingTopology
+/// - Module `m` is connected to modules `m - 1` and `m + 1` (mod `num_mods`)
+#[derive(Clone, Default, Serialize)]
+pub struct RingTopology {
+    links: TopologyLinks
+}
+
+impl RingTopology {
+    pub fn new(num_mods: u32, num_procs: u32) -> Result<Self, CompileError> {
+        let pairs: Vec<(u32, u32)> = if num_mods <= 2 {
+            (1..num_mods).map(|m| (m - 1, m)).collect()
+        } else {
+            (0..num_mods).map(|m| (m, (m + 1) % num_mods)).collect()
+        };
+        Ok(RingTopology {
+            links: TopologyLinks::from_module_pairs(&pairs, num_mods, num_procs)?
+        })
+    }
+}
+
+impl Topology for RingTopology {
+    fn links(self: &Self) -> &TopologyLinks {
+        &self.links
+    }
+}
+
+/// # CrossbarTopology
+/// - Every pair of modules is directly connected
+/// - Requires `num_procs >= num_mods - 1`
+#[derive(Clone, Default, Serialize)]
+pub struct CrossbarTopology {
+    links: TopologyLinks
+}
+
+impl CrossbarTopology {
+    pub fn new(num_mods: u32, num_procs: u32) -> Result<Self, CompileError> {
+        let mut pairs: Vec<(u32, u32)> = vec![];
+        for a in 0..num_mods {
+            for b in (a + 1)..num_mods {
+                pairs.push((a, b));
+            }
+        }
+        Ok(CrossbarTopology {
+            links: TopologyLinks::from_module_pairs(&pairs, num_mods, num_procs)?
+        })
+    }
+}
+
+impl Topology for CrossbarTopology {
+    fn links(self: &Self) -> &TopologyLinks {
+        &self.links
+    }
+}
+
+/// Global network topology selected by `PlatformConfig`
+#[derive(Clone, Serialize)]
+pub enum GlobalNetworkTopology {
+    Hub(HubTopology),
+    Mesh(MeshTopology),
+    Ring(RingTopology),
+    Crossbar(CrossbarTopology)
+}
+
+impl Default for GlobalNetworkTopology {
+    fn default() -> Self {
+        GlobalNetworkTopology::Hub(HubTopology::default())
+    }
+}
+
+impl GlobalNetworkTopology {
+    pub fn new(kind: TopologyKind, num_mods: u32, num_procs: u32) -> Result<Self, CompileError> {
+        let topology = match kind {
+            TopologyKind::Hub      => GlobalNetworkTopology::Hub(HubTopology::new(num_mods, num_procs)?),
+            TopologyKind::Mesh     => GlobalNetworkTopology::Mesh(MeshTopology::new(num_mods, num_procs, false)?),
+            TopologyKind::Torus    => GlobalNetworkTopology::Mesh(MeshTopology::new(num_mods, num_procs, true)?),
+            TopologyKind::Ring     => GlobalNetworkTopology::Ring(RingTopology::new(num_mods, num_procs)?),
+            TopologyKind::Crossbar => GlobalNetworkTopology::Crossbar(CrossbarTopology::new(num_mods, num_procs)?),
+        };
+        return Ok(topology);
+    }
+
+    /// Inverse of `new`
+    pub fn kind(self: &Self) -> TopologyKind {
+        match self {
+            GlobalNetworkTopology::Hub(_)                => TopologyKind::Hub,
+            GlobalNetworkTopology::Mesh(t) if t.torus    => TopologyKind::Torus,
+            GlobalNetworkTopology::Mesh(_)               => TopologyKind::Mesh,
+            GlobalNetworkTopology::Ring(_)               => TopologyKind::Ring,
+            GlobalNetworkTopology::Crossbar(_)           => TopologyKind::Crossbar,
+        }
+    }
+
+    fn inner(self: &Self) -> &dyn Topology {
+        match self {
+            GlobalNetworkTopology::Hub(t)      => t,
+            GlobalNetworkTopology::Mesh(t)     => t,
+            GlobalNetworkTopology::Ring(t)     => t,
+            GlobalNetworkTopology::Crossbar(t) => t,
+        }
+    }
+}
+
+impl Topology for GlobalNetworkTopology {
+    fn links(self: &Self) -> &TopologyLinks {
+        self.inner().links()
+    }
+
+    fn edges(self: &Self) -> &IndexMap<Coordinate, Coordinate> {
+        self.inner().edges()
+    }
+
+    fn gateways(self: &Self, module: u32) -> Vec<Coordinate> {
+        self.inner().gateways(module)
+    }
+
+    fn inter_mod_paths(self: &Self, src: Coordinate, dst: Coordinate) -> Vec<NetworkPath> {
+        self.inner().inter_mod_paths(src, dst)
+    }
+
+    fn inter_mod_routes(self: &Self, src: Coordinate, dst: Coordinate) -> Vec<NetworkRoute> {
+        self.inner().inter_mod_routes(src, dst)
+    }
+
+    fn path_lat(self: &Self, path: &NetworkPath, pcfg: &PlatformConfig) -> u32 {
+        self.inner().path_lat(path, pcfg)
+    }
+}
+
+impl Debug for GlobalNetworkTopology {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let indent: &str = "    ";
+
+        write!(f, "digraph {{\n")?;
+
+        let mut map: IndexMap<Coordinate, u32> = IndexMap::new();
+
+        for (i, (src, _)) in self.edges().iter().enumerate() {
+            map.insert(*src, i as u32);
+
+            write!(
+                f,
+                "{}{} [ label = \"{:?}\" ]\n",
+                indent,
+                i,
+                src
+            )?;
+        }
+        for (i, (_, dst)) in self.edges().iter().enumerate() {
+            write!(
+                f,
+                "{}{} {} {} ",
+                indent,
+                i,
+                "->",
+                map.get(dst).unwrap()
+            )?;
+            writeln!(f, "[ ]")?;
+        }
+
+        write!(f, "}}")
+    }
+}
@@ -0,0 +1,353 @@
+use crate::common::{
+    config::PlatformConfig,
+    instruction::*,
+    mapping::EmulatorMapping,
+    network::*,
+    primitive::*,
+    topology::Topology,
+};
+use indexmap::IndexMap;
+use std::fmt::Display;
+
+/// Kinds of `ProgramViolation`s
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ViolationKind {
+    /// A processor does not have exactly `host_steps` instructions
+    ProgramLength,
+
+    /// An operand reads a data memory slot past `host_steps`
+    OperandOutOfRange,
+
+    /// A combinational operand reads a slot that is not written in the
+    /// current target cycle by the time it is read
+    ReadBeforeWrite,
+
+    /// A register (Latch, Gate) operand reads a slot that is already
+    /// overwritten in the current target cycle
+    ReadAfterWrite,
+
+    /// A switch receive without a sender driving the port
+    Receive,
+
+    /// `sinfo.fwd` without a received bit to forward or without a receiver
+    Forward,
+
+    /// More than one sender drives the switch port of a receive
+    PortConflict,
+
+    /// SRAM processor input or output index out of range
+    SRAMIndex
+}
+
+/// A rule of the emulator program that is broken at `coord` step `pc`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramViolation {
+    pub kind: ViolationKind,
+    pub coord: Coordinate,
+    pub pc: u32,
+    pub msg: String
+}
+
+impl Display for ProgramViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} at module {} proc {} pc {}: {}",
+            self.kind, self.coord.module, self.coord.proc, self.pc, self.msg)
+    }
+}
+
+struct Program<'a> {
+    emul: &'a EmulatorMapping,
+    pcfg: &'a PlatformConfig,
+
+    /// Processors that send to each processor over the global network
+    glb_senders: IndexMap<Coordinate, Vec<Coordinate>>,
+
+    violations: Vec<ProgramViolation>
+}
+
+impl<'a> Program<'a> {
+    fn inst(self: &Self, coord: Coordinate, pc: u32) -> Option<&'a Instruction> {
+        match self.emul.module_mappings.get(&coord.module) {
+            Some(mmap) => {
+                match mmap.proc_mappings.get(&coord.proc) {
+                    Some(pmap) => pmap.instructions.get(pc as usize),
+                    None => None
+                }
+            }
+            None => None
+        }
+    }
+
+    /// The instruction at `pc` writes the bit it computes to its LDM slot
+    fn writes_ldm(self: &Self, coord: Coordinate, pc: u32) -> bool {
+        match self.inst(coord, pc) {
+            Some(inst) => inst.opcode != Opcode::NOP,
+            None => false
+        }
+    }
+
+    /// The instruction at `pc` receives a bit into its SDM slot.
+    /// Receives are marked with `sinfo.local_set` by `map_instructions`
+    fn writes_sdm(self: &Self, coord: Coordinate, pc: u32) -> bool {
+        match self.inst(coord, pc) {
+            Some(inst) => inst.sinfo.local_set,
+            None => false
+        }
+    }
+
+    /// The processor drives its switch output ports at `pc`
+    fn drives_switch(self: &Self, coord: Coordinate, pc: u32) -> bool {
+        match self.inst(coord, pc) {
+            Some(inst) => inst.sinfo.fwd || inst.opcode != Opcode::NOP,
+            None => false
+        }
+    }
+
+    /// Steps between sending a bit over `src` -> `dst` and receiving it
+    fn path_lat(self: &Self, src: Coordinate, dst: Coordinate) -> u32 {
+        self.pcfg.topology.path_lat(&NetworkPath::new(src, dst), self.pcfg)
+    }
+
+    fn report(self: &mut Self, kind: ViolationKind, coord: Coordinate, pc: u32, msg: String) {
+        self.violations.push(ProgramViolation { kind: kind, coord: coord, pc: pc, msg: msg });
+    }
+
+    fn check_program_length(self: &mut Self) {
+        let emul = self.emul;
+        for (m, mmap) in emul.module_mappings.iter() {
+            for (p, pmap) in mmap.proc_mappings.iter() {
+                if pmap.instructions.len() as u32 != emul.host_steps {
+                    self.report(ViolationKind::ProgramLength, Coordinate { module: *m, proc: *p }, 0,
+                        format!("{} instructions, expect host_steps {}", pmap.instructions.len(), emul.host_steps));
+                }
+            }
+        }
+    }
+
+    /// Operands that read the data memories. The other operands of SRAM
+    /// instructions hold the SRAM processor index
+    fn read_operands(inst: &Instruction) -> Vec<&Operand> {
+        match inst.opcode {
+            Opcode::SRAMOut => vec![],
+            Opcode::SRAMIn  => inst.operands.iter().filter(|op| op.idx == 0).collect(),
+            _ => inst.operands.iter().collect()
+        }
+    }
+
+    fn check_operands(self: &mut Self, coord: Coordinate, pc: u32, inst: &Instruction) {
+        // A bit written to slot `rs` can be read from `rs + visible_lat`
+        let visible_lat = self.pcfg.fetch_decode_lat() + self.pcfg.dmem_wr_lat;
+        let register = inst.opcode == Opcode::Latch || inst.opcode == Opcode::Gate;
+        for op in Program::read_operands(inst).iter() {
+            let dmem = if op.local { "LDM" } else { "SDM" };
+            if op.rs >= self.emul.host_steps {
+                self.report(ViolationKind::OperandOutOfRange, coord, pc,
+                    format!("operand {} reads {} slot {} >= host_steps {}", op.idx, dmem, op.rs, self.emul.host_steps));
+                continue;
+            }
+
+            let written = if op.local {
+                self.writes_ldm(coord, op.rs)
+            } else {
+                self.writes_sdm(coord, op.rs)
+            };
+            if !written {
+                self.report(ViolationKind::ReadBeforeWrite, coord, pc,
+                    format!("operand {} reads {} slot {} which is never written", op.idx, dmem, op.rs));
+            } else if register && op.rs + visible_lat <= pc {
+                self.report(ViolationKind::ReadAfterWrite, coord, pc,
+                    format!("{:?} operand {} reads {} slot {} after it is written at step {}",
+                        inst.opcode, op.idx, dmem, op.rs, op.rs + visible_lat));
+            } else if !register && op.rs + visible_lat > pc {
+                self.report(ViolationKind::ReadBeforeWrite, coord, pc,
+                    format!("{:?} operand {} reads {} slot {} before it is written at step {}",
+                        inst.opcode, op.idx, dmem, op.rs, op.rs + visible_lat));
+            }
+        }
+    }
+
+    fn check_receive(self: &mut Self, coord: Coordinate, pc: u32, inst: &Instruction) {
+        if !inst.sinfo.local_set {
+            return;
+        }
+        let idx = inst.sinfo.idx;
+        if idx >= self.pcfg.num_procs {
+            self.report(ViolationKind::Receive, coord, pc,
+                format!("receives from processor {} >= num_procs {}", idx, self.pcfg.num_procs));
+            return;
+        }
+
+        let senders = if inst.sinfo.local {
+            vec![Coordinate { module: coord.module, proc: idx }]
+        } else {
+            match self.glb_senders.get(&coord) {
+                Some(s) => s.clone(),
+                None => vec![]
+            }
+        };
+        let sender = match senders.iter().find(|s| s.proc == idx) {
+            Some(s) => *s,
+            None => {
+                self.report(ViolationKind::Receive, coord, pc,
+                    format!("no global network link from processor {} (links from {:?})", idx, senders));
+                return;
+            }
+        };
+
+        // Other processors linked to the same global switch input
+        for other in senders.iter() {
+            if *other == sender {
+                continue;
+            }
+            let lat = self.path_lat(*other, coord);
+            if pc >= lat && self.drives_switch(*other, pc - lat) {
+                self.report(ViolationKind::PortConflict, coord, pc,
+                    format!("{:?} and {:?} both drive the global switch input", sender, other));
+            }
+        }
+
+        let lat = self.path_lat(sender, coord);
+        if pc < lat {
+            self.report(ViolationKind::Receive, coord, pc,
+                format!("receives from {:?} which would have to send at step {} - {}", sender, pc, lat));
+            return;
+        }
+        let send_pc = pc - lat;
+        if !self.drives_switch(sender, send_pc) {
+            self.report(ViolationKind::Receive, coord, pc,
+                format!("receives from {:?} which does not send at step {}", sender, send_pc));
+        }
+    }
+
+    /// `sinfo.fwd` sends the bit received by the previous instruction
+    fn check_forward(self: &mut Self, coord: Coordinate, pc: u32, inst: &Instruction) {
+        if !inst.sinfo.fwd {
+            return;
+        }
+        if pc == 0 || !self.writes_sdm(coord, pc - 1) {
+            self.report(ViolationKind::Forward, coord, pc,
+                "forwards a bit that was not received in the previous step".to_string());
+        }
+
+        let mut receivers = vec![];
+        for p in 0..self.pcfg.num_procs {
+            receivers.push((Coordinate { module: coord.module, proc: p }, true));
+        }
+        match self.pcfg.topology.edges().get(&coord) {
+            Some(dst) => { receivers.push((*dst, false)); }
+            None => {}
+        }
+
+        let mut received = false;
+        for (dst, local) in receivers.iter() {
+            if *dst == coord {
+                continue;
+            }
+            let recv_pc = pc + self.path_lat(coord, *dst);
+            match self.inst(*dst, recv_pc) {
+                Some(rinst) => {
+                    if rinst.sinfo.local_set && rinst.sinfo.local == *local && rinst.sinfo.idx == coord.proc {
+                        received = true;
+                    }
+                }
+                None => {}
+            }
+        }
+        if !received {
+            self.report(ViolationKind::Forward, coord, pc,
+                "forwards a bit that no processor receives".to_string());
+        }
+    }
+
+    /// SRAM processor index held in `operands[1..]`. Each operand holds
+    /// `index_bits` of the index, which is what `to_bits` encodes
+    fn sram_index(self: &Self, inst: &Instruction) -> u64 {
+        let op_bits = self.pcfg.index_bits();
+        let mask = (1u64 << op_bits) - 1;
+        let mut uidx: u64 = 0;
+        for op in inst.operands.iter() {
+            if op.idx == 0 {
+                continue;
+            }
+            uidx |= (op.rs as u64 & mask) << ((op.idx - 1) * op_bits);
+        }
+        return uidx;
+    }
+
+    fn check_sram(self: &mut Self, coord: Coordinate, pc: u32, inst: &Instruction) {
+        if inst.opcode != Opcode::SRAMIn && inst.opcode != Opcode::SRAMOut {
+            return;
+        }
+        let uidx = self.sram_index(inst);
+        let slots = self.emul.module_mappings.get(&coord.module).unwrap().sram_mapping.slots();
+
+        if inst.opcode == Opcode::SRAMIn {
+            let ids = self.pcfg.sram_slot_input_ids() as u64;
+            let (slot, id) = (uidx / ids, (uidx % ids) as u32);
+            if slot >= slots.len() as u64 {
+                self.report(ViolationKind::SRAMIndex, coord, pc,
+                    format!("SRAM input {} is in slot {}, module has {} slots", uidx, slot, slots.len()));
+            } else if id >= self.pcfg.sram_other_offset() {
+                self.report(ViolationKind::SRAMIndex, coord, pc,
+                    format!("SRAM input {} has unknown slot local id {}", uidx, id));
+            }
+        } else {
+            let width = self.pcfg.sram_width as u64;
+            let (slot, bit) = (uidx / width, (uidx % width) as u32);
+            if slot >= slots.len() as u64 {
+                self.report(ViolationKind::SRAMIndex, coord, pc,
+                    format!("SRAM output {} is in slot {}, module has {} slots", uidx, slot, slots.len()));
+            } else if bit >= slots[slot as usize].width_bits {
+                self.report(ViolationKind::SRAMIndex, coord, pc,
+                    format!("SRAM output bit {} of slot {} is wider than {} bits",
+                        bit, slot, slots[slot as usize].width_bits));
+            }
+        }
+    }
+}
+
+/// Processors that send to each processor over the global network
+pub fn global_senders(pcfg: &PlatformConfig) -> IndexMap<Coordinate, Vec<Coordinate>> {
+    let mut ret: IndexMap<Coordinate, Vec<Coordinate>> = IndexMap::new();
+    for (src, dst) in pcfg.topology.edges().iter() {
+        if !ret.contains_key(dst) {
+            ret.insert(*dst, vec![]);
+        }
+        ret.get_mut(dst).unwrap().push(*src);
+    }
+    return ret;
+}
+
+/// # verify_program
+/// - Checks an emulator program without the graph it was compiled from
+/// - Every operand reads a slot that is written within `host_steps`:
+///   before the read for combinational instructions, after the read for
+///   registers (Latch, Gate) which read the value of the previous target cycle
+/// - Every receive (`sinfo.local_set`) has exactly one sender driving the
+///   switch port, either with the bit it computes or with a forwarded bit
+/// - Every `sinfo.fwd` forwards a received bit to some receiver, so that
+///   multi-hop routes are chained
+/// - SRAM processor input and output indices are within the SRAM slots
+/// - Returns all violations, which is empty for a valid program
+pub fn verify_program(emul: &EmulatorMapping, pcfg: &PlatformConfig) -> Vec<ProgramViolation> {
+    let mut program = Program {
+        emul: emul,
+        pcfg: pcfg,
+        glb_senders: global_senders(pcfg),
+        violations: vec![]
+    };
+
+    program.check_program_length();
+    for (m, mmap) in emul.module_mappings.iter() {
+        for (p, pmap) in mmap.proc_mappings.iter() {
+            let coord = Coordinate { module: *m, proc: *p };
+            for (pc, inst) in pmap.instructions.iter().enumerate() {
+                program.check_operands(coord, pc as u32, inst);
+                program.check_receive(coord, pc as u32, inst);
+                program.check_forward(coord, pc as u32, inst);
+                program.check_sram(coord, pc as u32, inst);
+            }
+        }
+    }
+    return program.violations;
+}
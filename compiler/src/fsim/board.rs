@@ -1,21 +1,25 @@
 use crate::fsim::module::*;
 use crate::fsim::switch::*;
-use crate::common::{hwgraph::*, config::*, network::*, circuit::Circuit, primitive::*};
+use crate::fsim::lanes::Lanes;
+use crate::common::{hwgraph::*, config::*, network::*, circuit::Circuit, primitive::*, instruction::Instruction, mapping::SRAMMapping};
+use crate::common::sram_image::sram_init_contents;
+use crate::common::topology::Topology;
 use petgraph::graph::NodeIndex;
 use indexmap::IndexMap;
 use std::fmt::Debug;
 use std::iter::Iterator;
 
-/// Represents a group of emulation `Module`s connected together
-pub struct Board {
+/// Represents a group of emulation `Module`s connected together.
+/// `Board<LaneVec>` runs 64 stimuli at once (see `Lanes`)
+pub struct Board<T: Lanes = Bit> {
     /// Global network
-    global_switch: Switch,
+    global_switch: Switch<T>,
 
     /// Describes the connectivity of the processors in the `global_switch`
     global_switch_edges: IndexMap<Coordinate, Coordinate>,
 
     /// Modules
-    modules: Vec<Module>,
+    modules: Vec<Module<T>>,
 
     /// Number of host cycles used to emulate one target cycle
     host_steps: u32,
@@ -27,15 +31,15 @@ pub struct Board {
     pcfg: PlatformConfig
 }
 
-impl Debug for Board {
+impl<T: Lanes> Debug for Board<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Module[\n  {:?}\n{:#?}\n]", self.global_switch, self.modules)
     }
 }
 
-impl Board {
+impl<T: Lanes> Board<T> {
     pub fn from(c: &Circuit) -> Self {
-        let mut modules: IndexMap<u32, Module> = IndexMap::new();
+        let mut modules: IndexMap<u32, Module<T>> = IndexMap::new();
         let mut signal_map: IndexMap<String, NodeMapInfo> = IndexMap::new();
         let pcfg = &c.platform_cfg;
 
@@ -51,6 +55,7 @@ impl Board {
                 for (pc, inst) in pmap.instructions.iter().enumerate() {
                     module.procs[p as usize].set_inst(inst.clone(), pc);
                 }
+                module.procs[p as usize].init_dmem(&pmap.ldm_init, &pmap.sdm_init);
 
                 for (sig, nm) in pmap.signal_map.iter() {
                     signal_map.insert(sig.clone(), nm.clone());
@@ -58,19 +63,84 @@ impl Board {
                 }
             }
             module.signal_map = mod_signal_map;
-            module.sram_proc.set_sram_mapping(&mmap.sram_mapping);
+            module.set_sram_mapping(&mmap.sram_mapping);
             modules.insert(*m, module);
         }
 
         modules.sort_keys();
 
-        Board {
+        let mut board = Board {
             global_switch: Switch::new(pcfg.total_procs(), pcfg.inter_mod_nw_lat),
-            global_switch_edges: pcfg.topology.edges.clone(),
+            global_switch_edges: pcfg.topology.edges().clone(),
             modules: modules.into_values().collect(),
             host_steps: c.emul.host_steps,
             signal_map: signal_map,
             pcfg: pcfg.clone()
+        };
+
+        let contents = sram_init_contents(&c.compiler_cfg, &c.emul).unwrap();
+        board.init_srams(&contents);
+        return board;
+    }
+
+    /// Preload the SRAM processors with the initial contents of the target SRAMs.
+    /// `contents` contains the (address, data) pairs of each module
+    pub fn init_srams(self: &mut Self, contents: &IndexMap<u32, Vec<(u32, Vec<Bit>)>>) {
+        for (m, mcontents) in contents.iter() {
+            self.modules[*m as usize].init_sram(mcontents);
+        }
+    }
+
+    /// Set the initial value of a register state bit held in the data memory
+    /// of processor `proc` in module `module`
+    pub fn init_dmem_bit(self: &mut Self, module: u32, proc: u32, sdm: bool, addr: u32, bit: Bit) {
+        let mut init: IndexMap<u32, Bit> = IndexMap::new();
+        init.insert(addr, bit);
+        let empty: IndexMap<u32, Bit> = IndexMap::new();
+        let proc = &mut self.modules[module as usize].procs[proc as usize];
+        if sdm {
+            proc.init_dmem(&empty, &init);
+        } else {
+            proc.init_dmem(&init, &empty);
+        }
+    }
+
+    /// Build a `Board` directly from per module, per processor instruction
+    /// streams. There is no signal mapping information, so signals can only
+    /// be accessed by their coordinates (`set_io_i`, `get_io_o`).
+    pub fn from_instructions(
+        pcfg: &PlatformConfig,
+        host_steps: u32,
+        insts: &IndexMap<u32, Vec<Vec<Instruction>>>,
+        sram_mappings: &IndexMap<u32, SRAMMapping>
+    ) -> Self {
+        let mut modules: Vec<Module<T>> = vec![];
+        for m in 0..pcfg.num_mods {
+            let mut module = Module::new(m, pcfg, host_steps);
+            match insts.get(&m) {
+                Some(minsts) => {
+                    for (p, pinsts) in minsts.iter().enumerate() {
+                        for (pc, inst) in pinsts.iter().enumerate() {
+                            module.procs[p].set_inst(inst.clone(), pc);
+                        }
+                    }
+                }
+                None => {}
+            }
+            match sram_mappings.get(&m) {
+                Some(smap) => module.set_sram_mapping(smap),
+                None => {}
+            }
+            modules.push(module);
+        }
+
+        Board {
+            global_switch: Switch::new(pcfg.total_procs(), pcfg.inter_mod_nw_lat),
+            global_switch_edges: pcfg.topology.edges().clone(),
+            modules: modules,
+            host_steps: host_steps,
+            signal_map: IndexMap::new(),
+            pcfg: pcfg.clone()
         }
     }
 
@@ -81,18 +151,18 @@ impl Board {
     pub fn print(self: &Self) {
         for (_, module) in self.modules.iter().enumerate() {
             module.print();
-            module.sram_proc.print();
+            module.print_sram();
         }
     }
 
     pub fn nodeindex(self: &Self, signal: &str) -> Option<NodeIndex> {
         match self.signal_map.get(signal) {
-            Some(map) => Some(map.idx),
+            Some(map) => map.idx,
             None => None,
         }
     }
 
-    pub fn peek(self: &Self, signal: &str) -> Option<Bit> {
+    pub fn peek(self: &Self, signal: &str) -> Option<T> {
         match self.signal_map.get(signal) {
             Some(map) => Some(self
                               .modules[map.info.coord.module as usize]
@@ -102,7 +172,7 @@ impl Board {
         }
     }
 
-    pub fn poke(self: &mut Self, signal: &str, val: Bit) -> Option<Bit> {
+    pub fn poke(self: &mut Self, signal: &str, val: T) -> Option<T> {
         match self.signal_map.get(signal) {
             Some(map) => {
                 let inst = self.modules[map.info.coord.module as usize]
@@ -125,6 +195,44 @@ impl Board {
         }
     }
 
+    /// Value of `signal` in lane `lane`
+    pub fn peek_lane(self: &Self, signal: &str, lane: u32) -> Option<Bit> {
+        match self.peek(signal) {
+            Some(val) => Some(val.lane(lane)),
+            None => None
+        }
+    }
+
+    /// Drive the input `signal` of lane `lane`, leaving the other lanes as they are
+    pub fn poke_lane(self: &mut Self, signal: &str, lane: u32, bit: Bit) -> Option<Bit> {
+        match self.signal_map.get(signal) {
+            Some(map) => {
+                let proc = &self.modules[map.info.coord.module as usize]
+                               .procs[map.info.coord.proc as usize];
+                let mut val = proc.get_io_i();
+                val.set_lane(lane, bit);
+                match self.poke(signal, val) {
+                    Some(_) => Some(bit),
+                    None => None
+                }
+            }
+            None => {
+                println!("Cannot find signal {} to poke", signal);
+                None
+            }
+        }
+    }
+
+    /// Set the IO input bit of processor `proc` in module `module`
+    pub fn set_io_i(self: &mut Self, module: u32, proc: u32, val: T) {
+        self.modules[module as usize].procs[proc as usize].set_io_i(val);
+    }
+
+    /// Get the IO output bit of processor `proc` in module `module`
+    pub fn get_io_o(self: &mut Self, module: u32, proc: u32) -> T {
+        self.modules[module as usize].procs[proc as usize].get_io_o()
+    }
+
     fn set_global_switch_out(self: &mut Self) {
         for (m, module) in self.modules.iter_mut().enumerate() {
             for (p, proc) in module.procs.iter_mut().enumerate() {
@@ -185,8 +293,8 @@ impl Board {
     pub fn step_with_input(
         self: &mut Self,
         step: u32,
-        input_stimuli: &IndexMap<u32, Vec<(&str, Bit)>>
-    ) -> Vec<Vec<(Bit, Bit)>> {
+        input_stimuli: &IndexMap<u32, Vec<(&str, T)>>
+    ) -> Vec<Vec<(T, T)>> {
         match input_stimuli.get(&(step as u32)) {
             Some(vec) => {
                 for (sig, bit) in vec.iter() {
@@ -210,7 +318,7 @@ impl Board {
 
     pub fn run_cycle(
         self: &mut Self,
-        input_stimuli: &IndexMap<u32, Vec<(&str, Bit)>>
+        input_stimuli: &IndexMap<u32, Vec<(&str, T)>>
     ) {
         for step in 0..self.host_steps {
             match input_stimuli.get(&(step as u32)) {
@@ -227,7 +335,7 @@ impl Board {
 
     pub fn run_cycle_verbose(
         self: &mut Self,
-        input_stimuli: &IndexMap<u32, Vec<(&str, Bit)>>,
+        input_stimuli: &IndexMap<u32, Vec<(&str, T)>>,
         cycle: &u32
     ) {
         println!("==================== Running Cycle {} ======================", cycle);
@@ -0,0 +1,81 @@
+use crate::common::primitive::Bit;
+use std::fmt::{Debug, Display};
+
+/// Bit vector used to simulate `LaneVec::LANES` stimuli at once
+pub type LaneVec = u64;
+
+/// # Lanes
+/// - Value held by a data memory slot, switch port or IO port of the
+///   functional simulator
+/// - `Bit` simulates a single stimulus. `LaneVec` simulates 64 independent
+///   stimuli in one pass, lane `l` being bit `l` of every value
+pub trait Lanes: Copy + Default + Debug + Display + PartialEq {
+    /// Number of stimuli simulated at once
+    const LANES: u32;
+
+    /// Same `bit` in every lane
+    fn splat(b: Bit) -> Self;
+
+    fn lane(self: &Self, l: u32) -> Bit;
+
+    fn set_lane(self: &mut Self, l: u32, b: Bit);
+
+    /// Looks up `table` in every lane. Operand `i` is bit `i` of the entry
+    fn lut(table: u64, operands: &Vec<Self>) -> Self;
+}
+
+impl Lanes for Bit {
+    const LANES: u32 = 1;
+
+    fn splat(b: Bit) -> Self {
+        b
+    }
+
+    fn lane(self: &Self, _l: u32) -> Bit {
+        *self
+    }
+
+    fn set_lane(self: &mut Self, _l: u32, b: Bit) {
+        *self = b;
+    }
+
+    fn lut(table: u64, operands: &Vec<Self>) -> Self {
+        let mut entry = 0;
+        for (i, bit) in operands.iter().enumerate() {
+            entry = entry + (bit << i);
+        }
+        ((table >> entry) & 1) as u8
+    }
+}
+
+impl Lanes for LaneVec {
+    const LANES: u32 = 64;
+
+    fn splat(b: Bit) -> Self {
+        if b != 0 { u64::MAX } else { 0 }
+    }
+
+    fn lane(self: &Self, l: u32) -> Bit {
+        ((*self >> l) & 1) as Bit
+    }
+
+    fn set_lane(self: &mut Self, l: u32, b: Bit) {
+        *self = (*self & !(1 << l)) | (((b & 1) as u64) << l);
+    }
+
+    /// Sum of products over the entries of `table` that are set
+    fn lut(table: u64, operands: &Vec<Self>) -> Self {
+        let mut ret = 0;
+        for e in 0..(1u64 << operands.len()) {
+            if (table >> e) & 1 == 0 {
+                continue;
+            }
+            let mut lanes = u64::MAX;
+            for (i, op) in operands.iter().enumerate() {
+                lanes &= if (e >> i) & 1 == 1 { *op } else { !*op };
+            }
+            ret |= lanes;
+        }
+        return ret;
+    }
+}
@@ -4,3 +4,4 @@ pub mod switch;
 pub mod memory;
 pub mod board;
 pub mod sram;
+pub mod lanes;
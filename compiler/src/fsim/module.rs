@@ -1,6 +1,7 @@
 use crate::fsim::processor::*;
 use crate::fsim::switch::*;
-use crate::common::{config::PlatformConfig, hwgraph::NodeMapInfo, primitive::*};
+use crate::fsim::lanes::Lanes;
+use crate::common::{config::PlatformConfig, hwgraph::NodeMapInfo, mapping::SRAMMapping, primitive::*};
 use indexmap::IndexMap;
 use petgraph::graph::NodeIndex;
 use std::fmt::Debug;
@@ -11,17 +12,19 @@ use super::sram::SRAMProcessor;
 
 /// Represents a group of emulation `Processor`s connected together using a
 /// all to all communication switch
-pub struct Module {
+pub struct Module<T: Lanes = Bit> {
     /// Unique module id
     pub id: u32,
 
     /// All to all communication switch
-    pub switch: Switch,
+    pub switch: Switch<T>,
 
     /// `Processor`s in this `Module`
-    pub procs: Vec<Processor>,
+    pub procs: Vec<Processor<T>>,
 
-    pub sram_proc: SRAMProcessor,
+    /// SRAM processor of each lane. There is a single one shared by all the
+    /// lanes until an SRAM is mapped to this module
+    pub sram_procs: Vec<SRAMProcessor>,
 
     /// Total number of host machine cycles to emulate one target cycle
     pub host_steps: u32,
@@ -30,19 +33,19 @@ pub struct Module {
     pub signal_map: IndexMap<String, NodeMapInfo>
 }
 
-impl Debug for Module {
+impl<T: Lanes> Debug for Module<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Module[\n  {:?}\n{:#?}\n]", self.switch, self.procs)
     }
 }
 
-impl Module {
+impl<T: Lanes> Module<T> {
     pub fn new(id: u32, cfg: &PlatformConfig, host_steps_: u32) -> Self {
         Module {
             id: id,
             switch: Switch::new(cfg.num_procs, cfg.inter_proc_nw_lat),
             procs: (0..cfg.num_procs).map(|i| Processor::new(i as u32, host_steps_, cfg)).collect_vec(),
-            sram_proc: SRAMProcessor::new(id, host_steps_, cfg),
+            sram_procs: vec![SRAMProcessor::new(id, host_steps_, cfg)],
             host_steps: host_steps_,
             signal_map: IndexMap::new()
         }
@@ -83,13 +86,50 @@ impl Module {
             }
             print!("\n");
         }
-// self.sram_proc.print();
+// self.print_sram();
     }
 
     pub fn print_sigmap(self: &Self) {
         println!("{:#?}", self.signal_map);
     }
 
+    pub fn set_sram_mapping(self: &mut Self, map: &SRAMMapping) {
+        if !map.slots().is_empty() {
+            let pcfg = self.procs[0].cfg.clone();
+            while (self.sram_procs.len() as u32) < T::LANES {
+                self.sram_procs.push(SRAMProcessor::new(self.id, self.host_steps, &pcfg));
+            }
+        }
+        for sram_proc in self.sram_procs.iter_mut() {
+            sram_proc.set_sram_mapping(map);
+        }
+    }
+
+    /// Preload the SRAM of every lane with the (address, data) pairs in `contents`
+    pub fn init_sram(self: &mut Self, contents: &Vec<(u32, Vec<Bit>)>) {
+        for sram_proc in self.sram_procs.iter_mut() {
+            sram_proc.init_sram(contents);
+        }
+    }
+
+    pub fn print_sram(self: &Self) {
+        for sram_proc in self.sram_procs.iter() {
+            sram_proc.print();
+        }
+    }
+
+    /// SRAM output bits of processor `i`
+    fn sram_out(self: &Self, i: usize) -> T {
+        if self.sram_procs.len() == 1 {
+            return T::splat(self.sram_procs[0].ports[i].op);
+        }
+        let mut ret = T::default();
+        for (l, sram_proc) in self.sram_procs.iter().enumerate() {
+            ret.set_lane(l as u32, sram_proc.ports[i].op);
+        }
+        return ret;
+    }
+
     pub fn compute(self: &mut Self) {
         for (_, proc) in self.procs.iter_mut().enumerate() {
             proc.fetch();
@@ -99,16 +139,21 @@ impl Module {
         for (_, proc) in self.procs.iter_mut().enumerate() {
             proc.update_sram_in();
         }
-        for (i, proc) in self.procs.iter().enumerate() {
-            let sp = self.sram_proc.ports.get_mut(i).unwrap();
-            sp.val = proc.get_sram_in_val();
-            sp.idx = proc.get_sram_in_idx();
+        for sram_proc in self.sram_procs.iter_mut() {
+            for (i, proc) in self.procs.iter().enumerate() {
+                let sp = sram_proc.ports.get_mut(i).unwrap();
+                sp.val = proc.get_sram_in_val();
+                sp.idx = proc.get_sram_in_idx();
+            }
         }
 
         // set sram output
-        self.sram_proc.set_sram_out();
-        for (i, proc) in self.procs.iter_mut().enumerate() {
-            proc.set_sram_out(self.sram_proc.ports.get(i).unwrap().op);
+        for sram_proc in self.sram_procs.iter_mut() {
+            sram_proc.set_sram_out();
+        }
+        for i in 0..self.procs.len() {
+            let op = self.sram_out(i);
+            self.procs[i].set_sram_out(op);
         }
 
         // compute fout
@@ -117,13 +162,17 @@ impl Module {
         }
 
         // set sram ip
-        for (i, proc) in self.procs.iter().enumerate() {
-            let sp = self.sram_proc.ports.get_mut(i).unwrap();
-            sp.ip  = proc.get_sram_in_ip();
+        for (l, sram_proc) in self.sram_procs.iter_mut().enumerate() {
+            for (i, proc) in self.procs.iter().enumerate() {
+                let sp = sram_proc.ports.get_mut(i).unwrap();
+                sp.ip  = proc.get_sram_in_ip().lane(l as u32);
+            }
         }
 
         // update sram processor
-        self.sram_proc.run_cycle();
+        for sram_proc in self.sram_procs.iter_mut() {
+            sram_proc.run_cycle();
+        }
     }
 
     pub fn set_local_switch_out(self: &mut Self) {
@@ -148,14 +197,14 @@ impl Module {
         }
     }
 
-    pub fn peek(self: &Self, signal: &str) -> Option<Bit> {
+    pub fn peek(self: &Self, signal: &str) -> Option<T> {
         match self.signal_map.get(signal) {
             Some(map) => Some(self.procs[map.info.coord.proc as usize].ldm[map.info.pc as usize]),
             None => None,
         }
     }
 
-    pub fn poke(self: &mut Self, signal: &str, val: Bit) -> Option<Bit> {
+    pub fn poke(self: &mut Self, signal: &str, val: T) -> Option<T> {
         match self.signal_map.get(signal) {
             Some(map) => {
                 let inst = self.procs[map.info.coord.proc as usize].imem[map.info.pc as usize].clone();
@@ -176,7 +225,7 @@ impl Module {
 
     pub fn nodeindex(self: &Self, signal: &str) -> Option<NodeIndex> {
         match self.signal_map.get(signal) {
-            Some(map) => Some(map.idx),
+            Some(map) => map.idx,
             None => None,
         }
     }
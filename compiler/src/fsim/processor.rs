@@ -1,16 +1,18 @@
 use crate::common::{config::*, primitive::*, instruction::*};
 use crate::fsim::memory::*;
 use crate::fsim::sram::ProcessorSRAMPort;
+use crate::fsim::lanes::Lanes;
+use indexmap::IndexMap;
 use std::fmt::Debug;
 
 #[derive(Default, Clone, Debug)]
-struct ProcessorSwitchPort {
-    ip: Bit,
-    op: Bit,
+struct ProcessorSwitchPort<T: Lanes> {
+    ip: T,
+    op: T,
 }
 
 #[derive(Clone)]
-pub struct Processor {
+pub struct Processor<T: Lanes = Bit> {
     /// Processor id
     pub processor_id: u32,
     pub cfg: PlatformConfig,
@@ -24,44 +26,44 @@ pub struct Processor {
     pub imem: AbstractMemory<Instruction>,
 
     /// Local data memory
-    pub ldm: AbstractMemory<Bit>,
+    pub ldm: AbstractMemory<T>,
 
     /// Switch data memory
-    pub sdm: AbstractMemory<Bit>,
+    pub sdm: AbstractMemory<T>,
 
     /// For pipelining instructions
     pipeline: Vec<Instruction>,
 
     /// IO input port
-    io_i: Bit,
+    io_i: T,
 
     /// IO output port
-    io_o: Bit,
+    io_o: T,
 
     /// Local switch port (within a `Module`)
-    sw_loc: ProcessorSwitchPort,
+    sw_loc: ProcessorSwitchPort<T>,
 
     /// Receive bit from `sin_idx` from Local switch
     sin_idx: u32,
 
     /// Global switch port (within a `Board`)
-    sw_glb: ProcessorSwitchPort,
+    sw_glb: ProcessorSwitchPort<T>,
 
     /// true when receiving from `sw_loc` instead of `sw_glb` & vice versa
     sin_local: bool,
 
     /// Bit to send when we need to forward the bit to the network instead of
     /// the computed bit. Used when inst.sinfo.fwd is true
-    sin_fwd_bit: Bit,
+    sin_fwd_bit: T,
 
-    sram_port: ProcessorSRAMPort,
+    sram_port: ProcessorSRAMPort<T>,
 
 
-    pub dbg_ldm_wbit: Bit,
-    pub dbg_sdm_wbit: Bit
+    pub dbg_ldm_wbit: T,
+    pub dbg_sdm_wbit: T
 }
 
-impl Processor {
+impl<T: Lanes> Processor<T> {
     pub fn new(id_: u32, host_steps_: Bits, cfg: &PlatformConfig) -> Self {
         Processor {
             cfg: cfg.clone(),
@@ -70,19 +72,19 @@ impl Processor {
             ldm:  AbstractMemory::new(cfg.max_steps as u32, cfg.dmem_rd_lat, cfg.lut_inputs, cfg.dmem_wr_lat, 1),
             sdm:  AbstractMemory::new(cfg.max_steps as u32, cfg.dmem_rd_lat, cfg.lut_inputs, cfg.dmem_wr_lat, 1),
             pipeline: vec![Instruction::default(); cfg.dmem_rd_lat as usize],
-            io_i: 0,
-            io_o: 0,
+            io_i: T::default(),
+            io_o: T::default(),
             pc: 0,
             target_cycle: 0,
             sw_loc: ProcessorSwitchPort::default(),
             sw_glb: ProcessorSwitchPort::default(),
             sin_local: false,
-            sin_fwd_bit: 0,
+            sin_fwd_bit: T::default(),
             sin_idx: 0,
             sram_port: ProcessorSRAMPort::default(),
             processor_id: id_,
-            dbg_ldm_wbit: 0,
-            dbg_sdm_wbit: 0
+            dbg_ldm_wbit: T::default(),
+            dbg_sdm_wbit: T::default()
         }
     }
 
@@ -91,6 +93,18 @@ impl Processor {
         self.imem[step] = inst;
     }
 
+    /// Preload the register state held in the data memories (the same in every lane)
+    pub fn init_dmem(self: &mut Self, ldm_init: &IndexMap<u32, Bit>, sdm_init: &IndexMap<u32, Bit>) {
+        for (addr, bit) in ldm_init.iter() {
+            assert!((*addr as usize) < self.ldm.data.len());
+            self.ldm.data[*addr as usize] = T::splat(*bit);
+        }
+        for (addr, bit) in sdm_init.iter() {
+            assert!((*addr as usize) < self.sdm.data.len());
+            self.sdm.data[*addr as usize] = T::splat(*bit);
+        }
+    }
+
     /// Processor pipeline
     /// 1. Fetch
     /// 2. Read LDM & SDM
@@ -135,17 +149,17 @@ impl Processor {
         let de_inst = self.pipeline.remove(0);
 
         // Read the operands from the LDM and SDM
-        let mut operands: Vec<Bit> = Vec::new();
+        let mut operands: Vec<T> = Vec::new();
         self.ldm.update_rd_ports();
         self.sdm.update_rd_ports();
         for i in 0..self.cfg.lut_inputs {
             let ldm_resp = match self.ldm.get_rport(i as u32).cur_resp() {
                 Some(resp) => resp.data,
-                None       => 0
+                None       => T::default()
             };
             let sdm_resp = match self.sdm.get_rport(i as u32).cur_resp() {
                 Some(resp) => resp.data,
-                None       => 0
+                None       => T::default()
             };
             let bit = match de_inst.operands.get(i as usize) {
                 Some(op) => if op.local { ldm_resp } else { sdm_resp },
@@ -160,21 +174,17 @@ impl Processor {
 
         // LUT lookup
         let f_out = match &de_inst.opcode {
-            Opcode::NOP => 0,
+            Opcode::NOP => T::default(),
             Opcode::Input => self.io_i,
-            Opcode::Lut => {
-                let mut entry = 0;
-                for (i, bit) in operands.iter().enumerate() {
-                    entry = entry + (bit << i);
-                }
-                ((de_inst.lut >> entry) & 1) as u8
+            Opcode::Lut | Opcode::Gate => {
+                T::lut(de_inst.lut, &operands)
             }
             Opcode::Output => {
                 let bit = *operands.get(0).unwrap();
                 self.io_o = bit;
                 bit
             }
-            Opcode::Gate | Opcode::Latch => {
+            Opcode::Latch => {
                 *operands.get(0).unwrap()
             }
             Opcode::SRAMOut => {
@@ -183,7 +193,7 @@ impl Processor {
             Opcode::SRAMIn => {
                 *operands.get(0).unwrap()
             }
-            _ => 0,
+            _ => T::default(),
         };
 
         self.sram_port.ip = f_out;
@@ -208,7 +218,7 @@ impl Processor {
         self.ldm.run_cycle();
         self.imem.run_cycle();
 
-        self.dbg_ldm_wbit = if self.pc >= self.cfg.fetch_decode_lat() { f_out } else { 0 };
+        self.dbg_ldm_wbit = if self.pc >= self.cfg.fetch_decode_lat() { f_out } else { T::default() };
     }
 
     pub fn update_sram_in(self: &mut Self) {
@@ -260,38 +270,42 @@ impl Processor {
             self.pc += 1;
         }
 
-        self.dbg_sdm_wbit = if self.pc >= self.cfg.fetch_decode_lat() { sdm_store_bit } else { 0 };
+        self.dbg_sdm_wbit = if self.pc >= self.cfg.fetch_decode_lat() { sdm_store_bit } else { T::default() };
     }
 
     pub fn get_switch_in_id(self: &Self) -> Bits {
         self.sin_idx
     }
 
-    pub fn set_local_switch_in(self: &mut Self, b: Bit) {
+    pub fn set_local_switch_in(self: &mut Self, b: T) {
         self.sw_loc.ip = b;
     }
 
-    pub fn get_local_switch_out(self: &mut Self) -> Bit {
+    pub fn get_local_switch_out(self: &mut Self) -> T {
         self.sw_loc.op
     }
 
-    pub fn set_global_switch_in(self: &mut Self, b: Bit) {
+    pub fn set_global_switch_in(self: &mut Self, b: T) {
         self.sw_glb.ip = b;
     }
 
-    pub fn get_global_switch_out(self: &mut Self) -> Bit {
+    pub fn get_global_switch_out(self: &mut Self) -> T {
         self.sw_glb.op
     }
 
-    pub fn set_io_i(self: &mut Self, x: Bit) {
+    pub fn set_io_i(self: &mut Self, x: T) {
         self.io_i = x
     }
 
-    pub fn get_io_o(self: &mut Self) -> Bit {
+    pub fn get_io_i(self: &Self) -> T {
+        self.io_i
+    }
+
+    pub fn get_io_o(self: &mut Self) -> T {
         self.io_o
     }
 
-    pub fn set_sram_out(self: &mut Self, b: Bit) {
+    pub fn set_sram_out(self: &mut Self, b: T) {
         self.sram_port.op = b;
     }
 
@@ -303,24 +317,27 @@ impl Processor {
         self.sram_port.idx
     }
 
-    pub fn get_sram_in_ip(self: &Self) -> Bit {
+    pub fn get_sram_in_ip(self: &Self) -> T {
         self.sram_port.ip
     }
 
-    fn print_bitvec(self: &Self, bitvec: &Vec<Bit>) {
-        let mut hex_bits = vec![];
-        for chunk in bitvec.chunks(64) {
-            let mut hex: u64 = 0;
-            for (i, b) in chunk.iter().enumerate() {
-                hex |= (*b as u64) << i;
+    /// Prints the bits of each lane as hex
+    fn print_bitvec(self: &Self, bitvec: &Vec<T>) {
+        for l in 0..T::LANES {
+            let mut hex_bits = vec![];
+            for chunk in bitvec.chunks(64) {
+                let mut hex: u64 = 0;
+                for (i, b) in chunk.iter().enumerate() {
+                    hex |= (b.lane(l) as u64) << i;
+                }
+                hex_bits.push(hex);
             }
-            hex_bits.push(hex);
-        }
-        print!("0x");
-        for h in hex_bits.iter().rev() {
-            print!("{:x},", h);
+            print!("0x");
+            for h in hex_bits.iter().rev() {
+                print!("{:x},", h);
+            }
+            print!("\n");
         }
-        print!("\n");
     }
 
     pub fn print_ldm(self: &Self) {
@@ -332,7 +349,7 @@ impl Processor {
     }
 }
 
-impl Debug for Processor {
+impl<T: Lanes> Debug for Processor<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "  ldm: ")?;
         for chunk in self.ldm.data.chunks(16) {
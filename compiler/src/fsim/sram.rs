@@ -1,7 +1,8 @@
 use crate::common::{
-    config::*, mapping::{SRAMMapping, SRAMPortType}, primitive::{Bit, Bits, Primitive}
+    config::*, mapping::{SRAMMapping, SRAMMemMapping, SRAMPortType}, primitive::{Bit, Bits, Primitive}
 };
 use crate::fsim::memory::*;
+use crate::fsim::lanes::Lanes;
 use std::fmt::Debug;
 use std::collections::VecDeque;
 
@@ -107,14 +108,75 @@ impl SRAMInputs {
     }
 }
 
+/// Connection between a processor and the SRAM processor. `val` and `idx`
+/// come from the instruction and are the same in every lane
 #[derive(Default, Debug, Clone)]
-pub struct ProcessorSRAMPort {
+pub struct ProcessorSRAMPort<T: Lanes = Bit> {
     pub val: Bit,
     pub idx: Bits,
-    pub ip: Bit,
-    pub op: Bit
+    pub ip: T,
+    pub op: T
 }
 
+/// Per target SRAM state of the SRAM processor
+#[derive(Default, Debug, Clone)]
+struct SRAMSlot {
+    mapping: SRAMMemMapping,
+    inputs: Vec<SRAMInputs>,
+    prev_input: SRAMInputs,
+    cur_rd_data: SRAMEntry,
+    wr_rd_data: SRAMEntry,
+}
+
+impl SRAMSlot {
+    fn new(mapping: SRAMMemMapping, width: u32) -> Self {
+        SRAMSlot {
+            mapping: mapping,
+            inputs: vec![SRAMInputs::new(width); 2],
+            prev_input: SRAMInputs::new(width),
+            cur_rd_data: SRAMEntry::new(width),
+            wr_rd_data: SRAMEntry::new(width)
+        }
+    }
+
+    /// Returns (wen, waddr) for the current input
+    fn write_req(self: &Self, input: &SRAMInputs) -> (bool, Bits) {
+        let base = self.mapping.base_addr;
+        match self.mapping.port_type {
+            SRAMPortType::OneRdOneWrPortSRAM => {
+                (input.wr_en != 0, input.wr_addr + base)
+            }
+            SRAMPortType::SinglePortSRAM => {
+                (input.wr_en != 0 && input.rd_en != 0, input.rd_addr + base)
+            }
+        }
+    }
+
+    fn read_en(self: &Self, input: &SRAMInputs) -> bool {
+        match self.mapping.port_type {
+            SRAMPortType::OneRdOneWrPortSRAM => {
+                input.rd_en != 0
+            }
+            SRAMPortType::SinglePortSRAM => {
+                input.wr_en == 0 && input.rd_en != 0
+            }
+        }
+    }
+}
+
+/// Read requests in flight: (slot, true if the read is for a masked write)
+type SRAMRdTag = (usize, bool);
+
+/// # SRAMProcessor
+/// - Multiple target SRAMs can be packed into the SRAM processor. Each of
+///   them is assigned a slot and a base address within the SRAM.
+/// - The slots are accessed in two phases within a target cycle so that
+///   every read observes all the writes of the previous target cycle:
+///   - slot `k` performs the read for a masked write at
+///     `pcfg.sram_slot_wr_step(k)` and its write `sram_rd_lat` steps afterwards
+///   - after that, slot `k` sends out its read at `pcfg.sram_slot_rd_req_step(n, k)`
+/// - Slots sharing the same base address are ports of the same target SRAM.
+///   Writes are applied in slot order.
 pub struct SRAMProcessor {
     pub id: u32,
     pub pc: u32,
@@ -124,9 +186,8 @@ pub struct SRAMProcessor {
     pub ports_pipeline_regs: VecDeque<Vec<ProcessorSRAMPort>>,
     mapping: SRAMMapping,
     cur: u32,
-    inputs: Vec<SRAMInputs>,
-    prev_input: SRAMInputs,
-    cur_rd_data: SRAMEntry,
+    slots: Vec<SRAMSlot>,
+    rd_req_slots: VecDeque<SRAMRdTag>,
     sram: AbstractMemory<SRAMEntry>
 }
 
@@ -150,9 +211,8 @@ impl SRAMProcessor {
             ports: vec![ProcessorSRAMPort::default(); cfg.num_procs as usize],
             ports_pipeline_regs: pipeline_regs,
             mapping: SRAMMapping::default(),
-            inputs: vec![SRAMInputs::new(cfg.sram_width); 2],
-            prev_input: SRAMInputs::new(cfg.sram_width),
-            cur_rd_data: SRAMEntry::new(cfg.sram_width),
+            slots: vec![SRAMSlot::new(SRAMMemMapping::default(), cfg.sram_width)],
+            rd_req_slots: VecDeque::new(),
             sram: AbstractMemory::new(
                 cfg.sram_entries,
                 cfg.sram_rd_lat, cfg.sram_rd_ports,
@@ -171,10 +231,37 @@ impl SRAMProcessor {
     }
 
     pub fn set_sram_mapping(self: &mut Self, map: &SRAMMapping) {
-        println!("Emulating SRAM {:?} with {} bits per entry",
-            map.port_type, map.width_bits);
+        let mems = map.slots();
+        assert!(self.pcfg.sram_slot_steps(mems.len() as u32) <= self.host_steps,
+            "host_steps {} is too short to access {} SRAMs",
+            self.host_steps, mems.len());
 
         self.mapping = map.clone();
+        self.slots.clear();
+        for mem in mems.iter() {
+            println!("Emulating SRAM {} {:?} with {} bits per entry at base 0x{:x}",
+                mem.name, mem.port_type, mem.width_bits, mem.base_addr);
+            self.slots.push(SRAMSlot::new(mem.clone(), self.pcfg.sram_width));
+        }
+        if self.slots.len() == 0 {
+            self.slots.push(SRAMSlot::new(SRAMMemMapping::default(), self.pcfg.sram_width));
+        }
+    }
+
+    /// Write the initial contents of the target SRAMs given as (address, data) pairs
+    pub fn init_sram(self: &mut Self, contents: &Vec<(u32, Vec<Bit>)>) {
+        for (addr, bits) in contents.iter() {
+            assert!(*addr < self.pcfg.sram_entries,
+                "SRAM init address 0x{:x} out of range for module {}", addr, self.id);
+            assert!(bits.len() <= self.pcfg.sram_width as usize,
+                "SRAM init data has {} bits, SRAM processor has {}",
+                bits.len(), self.pcfg.sram_width);
+
+            let entry = &mut self.sram[*addr as usize];
+            for (i, b) in bits.iter().enumerate() {
+                entry.bits[i] = *b;
+            }
+        }
     }
 
     fn recv_input_idx(self: &Self) -> u32 {
@@ -185,7 +272,8 @@ impl SRAMProcessor {
         self.cur as u32
     }
 
-    fn masked_write_data(self: &Self, sram_input: &SRAMInputs, rd_data: &SRAMEntry) -> Vec<Bit> {
+    fn masked_write_data(self: &Self, slot: &SRAMSlot, sram_input: &SRAMInputs) -> Vec<Bit> {
+        let rd_data = &slot.wr_rd_data;
         assert!(sram_input.wr_data.len() == self.pcfg.sram_width as usize,
             "Number of wr_data bits {} != sram width {}",
             sram_input.wr_data.len(), self.pcfg.sram_width);
@@ -198,7 +286,7 @@ impl SRAMProcessor {
             "Number of wr_mask bits {} != rd_data.bits bits {}",
             sram_input.wr_mask.len(), rd_data.bits.len());
 
-        assert!(self.mapping.wmask_bits > 0,
+        assert!(slot.mapping.wmask_bits > 0,
             "masked_write_data should only be called for SRAMs w/ wmask bits");
 
         let mut ret = vec![];
@@ -224,18 +312,28 @@ impl SRAMProcessor {
     pub fn set_sram_out(self: &mut Self) {
         // Get value from the current read data port
         self.sram.update_rd_ports();
-        let rd_resp = match self.sram.get_rport(0).cur_resp() {
-            Some(resp) => resp.data,
-            None => SRAMEntry::new(self.pcfg.sram_width),
+        match self.sram.get_rport(0).cur_resp() {
+            Some(resp) => {
+                let (slot, for_write) = self.rd_req_slots.pop_front().unwrap();
+                if for_write {
+                    self.slots[slot].wr_rd_data = resp.data;
+                } else {
+                    self.slots[slot].cur_rd_data = resp.data;
+                }
+            }
+            None => {}
         };
 
-        self.cur_rd_data = rd_resp.clone();
-
         // Set the output port
         for p in self.ports.iter_mut() {
-            let bit_pos = p.idx as usize;
-            match rd_resp.bits.get(bit_pos) {
-                Some(bit) => { p.op = *bit; }
+            let (slot, bit_pos) = self.pcfg.index_to_sram_output_slot(p.idx);
+            match self.slots.get(slot as usize) {
+                Some(s) => {
+                    match s.cur_rd_data.bits.get(bit_pos as usize) {
+                        Some(bit) => { p.op = *bit; }
+                        None => { }
+                    }
+                }
                 None => { }
             }
         }
@@ -256,11 +354,13 @@ impl SRAMProcessor {
 
 
         // Receive inputs and update input regs
+        let ridx = self.recv_input_idx() as usize;
         for p in port_inputs_piplined.iter() {
             if p.val != 0 {
-                let (prim, bit_pos) = self.pcfg.index_to_sram_input_type(p.idx);
-                let ridx = self.recv_input_idx() as usize;
-                let input = self.inputs.get_mut(ridx).unwrap();
+                let (slot, sidx) = self.pcfg.index_to_sram_input_slot(p.idx);
+                let (prim, bit_pos) = self.pcfg.index_to_sram_input_type(sidx);
+                let input = self.slots.get_mut(slot as usize).unwrap()
+                    .inputs.get_mut(ridx).unwrap();
                 match prim {
                     Primitive::SRAMRdEn     => { input.set_rd_en(p.ip); }
                     Primitive::SRAMWrEn     => { input.set_wr_en(p.ip); }
@@ -276,73 +376,61 @@ impl SRAMProcessor {
             }
         }
 
+        let nslots = self.slots.len() as u32;
         let uidx = self.use_input_idx() as usize;
-        let cur_input = self.inputs.get(uidx).unwrap();
 
-        // Check if this request should be a read or a write
-        let (wen, waddr) = match self.mapping.port_type {
-            SRAMPortType::OneRdOneWrPortSRAM => {
-                (cur_input.wr_en != 0, cur_input.wr_addr)
-            }
-            SRAMPortType::SinglePortSRAM => {
-                (cur_input.wr_en != 0 && cur_input.rd_en != 0, cur_input.rd_addr)
+        // Write phase
+        let sidx = (self.pc / self.pcfg.sram_rd_en_step()) as usize;
+        if (sidx as u32) < nslots {
+            let slot_pc = self.pc - self.pcfg.sram_slot_wr_step(sidx as u32);
+            let slot = self.slots.get(sidx).unwrap();
+            let cur_input = slot.inputs.get(uidx).unwrap();
+            let (wen, waddr) = slot.write_req(cur_input);
+
+            if wen && slot_pc == 0 && slot.mapping.wmask_bits > 0 {
+                // Write request, need to read the current value in the write address
+                // to emulate write mask behavior
+                self.sram.get_rport(0).submit_req(ReadReq {
+                    addr: waddr
+                });
+                self.rd_req_slots.push_back((sidx, true));
             }
-        };
 
-        let ren = match self.mapping.port_type {
-            SRAMPortType::OneRdOneWrPortSRAM => {
-                cur_input.rd_en != 0
-            }
-            SRAMPortType::SinglePortSRAM => {
-                cur_input.wr_en == 0 && cur_input.rd_en != 0
+            // Send out SRAM write request
+            if wen && slot_pc == self.pcfg.sram_rd_lat {
+                let wdata = if slot.mapping.wmask_bits == 0 {
+                    // No write mask for this SRAM
+                    cur_input.wr_data.clone()
+                } else {
+                    // Write mask for this SRAM
+                    self.masked_write_data(slot, cur_input)
+                };
+                self.sram.get_wport(0).submit_req(WriteReq {
+                    addr: waddr,
+                    data: SRAMEntry { bits: wdata }
+                });
             }
-        };
-
-        // If read enable is high, read from the current input
-        // otherwise, use the address from the previous input
-        let raddr = if ren {
-            cur_input.rd_addr
-        } else {
-            self.prev_input.rd_addr
-        };
-
-        if wen && self.pc == 0 {
-            // Write request, need to read the current value in the write address
-            // to emulate write mask behavior
-            self.sram.get_rport(0).submit_req(ReadReq {
-                addr: waddr
-            });
-        } else {
-            // Send out SRAM read request
-            self.sram.get_rport(0).submit_req(ReadReq {
-                addr: raddr
-            });
         }
 
-        // Send out SRAM write request
-        if wen && self.pc == self.pcfg.sram_rd_lat {
-            let wdata = if self.mapping.wmask_bits == 0 {
-                // No write mask for this SRAM
-                cur_input.wr_data.clone()
+        // Read phase
+        let rd_start = self.pcfg.sram_slot_rd_req_step(nslots, 0);
+        if self.pc >= rd_start {
+            let rslot = std::cmp::min(self.pc - rd_start, nslots - 1) as usize;
+            let slot = self.slots.get(rslot).unwrap();
+            let cur_input = slot.inputs.get(uidx).unwrap();
+
+            // If read enable is high, read from the current input
+            // otherwise, use the address from the previous input
+            let raddr = if slot.read_en(cur_input) {
+                cur_input.rd_addr
             } else {
-                // Write mask for this SRAM
-                self.masked_write_data(cur_input, &self.cur_rd_data)
+                slot.prev_input.rd_addr
             };
-            self.sram.get_wport(0).submit_req(WriteReq {
-                addr: waddr,
-                data: SRAMEntry { bits: wdata }
-            });
 
-// println!("FSIM: SRAM single_port: {:?} width: {} wmask: {} ren: {} wen: {} wr_addr: 0x{:x} wr_data: 0x{:X?} wr_mask: 0x{:X?} rd_addr: 0x{:x}",
-// self.mapping.port_type,
-// self.mapping.width_bits,
-// self.mapping.wmask_bits,
-// cur_input.rd_en,
-// cur_input.wr_en,
-// cur_input.wr_addr,
-// cur_input.wr_data,
-// cur_input.wr_mask,
-// cur_input.rd_addr);
+            self.sram.get_rport(0).submit_req(ReadReq {
+                addr: raddr + slot.mapping.base_addr
+            });
+            self.rd_req_slots.push_back((rslot, false));
         }
 
         // Update SRAM state
@@ -351,10 +439,14 @@ impl SRAMProcessor {
         // Update PC
         if self.pc == self.host_steps - 1 {
             self.pc = 0;
-            if ren {
-                self.prev_input = cur_input.clone();
+            let cur = self.cur as usize;
+            for slot in self.slots.iter_mut() {
+                let cur_input = slot.inputs.get(cur).unwrap();
+                if slot.read_en(cur_input) {
+                    slot.prev_input = cur_input.clone();
+                }
+                slot.inputs.get_mut(cur).unwrap().init();
             }
-            self.inputs.get_mut(self.cur as usize).unwrap().init();
             self.cur = (self.cur + 1) % 2;
         } else {
             self.pc += 1;
@@ -1,14 +1,15 @@
 use crate::common::primitive::{Bit, Cycle};
 use crate::fsim::memory::Token;
+use crate::fsim::lanes::Lanes;
 
 #[derive(Default, Clone, Debug)]
-struct SwitchPort {
-    msgs: Vec<Token<Bit>>,
+struct SwitchPort<T: Lanes> {
+    msgs: Vec<Token<T>>,
     cycle: Cycle,
     lat: Cycle
 }
 
-impl SwitchPort {
+impl<T: Lanes> SwitchPort<T> {
     pub fn new(lat: Cycle) -> Self {
         SwitchPort {
             msgs: vec![],
@@ -29,7 +30,7 @@ impl SwitchPort {
         self.cycle += 1;
     }
 
-    pub fn cur_req(self: &Self) -> Option<Bit> {
+    pub fn cur_req(self: &Self) -> Option<T> {
         match self.msgs.first() {
             Some(token) => {
                 if token.cycle <= self.cycle {
@@ -44,7 +45,7 @@ impl SwitchPort {
         }
     }
 
-    pub fn submit_req(self: &mut Self, data: Bit) {
+    pub fn submit_req(self: &mut Self, data: T) {
         self.msgs.push(Token {
             value: data,
             cycle: self.cycle + self.lat
@@ -53,25 +54,25 @@ impl SwitchPort {
 }
 
 #[derive(Default, Debug)]
-pub struct Switch {
-    ports: Vec<SwitchPort>,
+pub struct Switch<T: Lanes = Bit> {
+    ports: Vec<SwitchPort<T>>,
 }
 
-impl Switch {
+impl<T: Lanes> Switch<T> {
     pub fn new(nprocs: u32, lat: Cycle) -> Self {
         Switch {
             ports: vec![SwitchPort::new(lat); nprocs as usize],
         }
     }
 
-    pub fn get_port_val(self: &Self, pid: usize) -> Bit {
+    pub fn get_port_val(self: &Self, pid: usize) -> T {
         match self.ports[pid].cur_req() {
             Some(x) => x,
-            None    => 0 // TODO: should return proper X instead of zero when a value is unknown
+            None    => T::default() // TODO: should return proper X instead of zero when a value is unknown
         }
     }
 
-    pub fn set_port_val(self: &mut Self, pid: usize, val: Bit) {
+    pub fn set_port_val(self: &mut Self, pid: usize, val: T) {
         self.ports[pid].submit_req(val);
     }
 
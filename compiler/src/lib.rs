@@ -22,6 +22,30 @@ pub mod blif_sim_test {
         imem_lat: u32,
         dmem_rd_lat: u32,
         dmem_wr_lat: u32,
+    ) -> bool {
+        return test_blif_sim_sram_cfg(
+            sv_file_path, top_mod, input_stimuli_path, blif_file_path,
+            num_mods, num_procs,
+            inter_proc_nw_lat, inter_mod_nw_lat, imem_lat, dmem_rd_lat, dmem_wr_lat,
+            128, 1024, 1, vec![]);
+    }
+
+    fn test_blif_sim_sram_cfg(
+        sv_file_path: &str,
+        top_mod: &str,
+        input_stimuli_path: &str,
+        blif_file_path: &str,
+        num_mods: u32,
+        num_procs: u32,
+        inter_proc_nw_lat: u32,
+        inter_mod_nw_lat: u32,
+        imem_lat: u32,
+        dmem_rd_lat: u32,
+        dmem_wr_lat: u32,
+        sram_width: u32,
+        sram_entries: u32,
+        sram_slots: u32,
+        sram_init: Vec<String>,
     ) -> bool {
         let args = Args {
             verbose:            false,
@@ -31,6 +55,7 @@ pub mod blif_sim_test {
             input_stimuli_path: input_stimuli_path.to_string(),
             blif_file_path:     blif_file_path.to_string(),
             vcd:                None,
+            ref_sim:            RefSimulator::default(),
             instance_path:      "testharness.top".to_string(),
             clock_start_low:    false,
             timesteps_per_cycle: 2,
@@ -39,6 +64,7 @@ pub mod blif_sim_test {
             check_cycle_period: 1,
             num_mods:           num_mods,
             num_procs:          num_procs,
+            topology:           TopologyKind::Hub,
             max_steps:          65536,
             lut_inputs:         3,
             inter_proc_nw_lat:  inter_proc_nw_lat,
@@ -46,13 +72,24 @@ pub mod blif_sim_test {
             imem_lat:           imem_lat,
             dmem_rd_lat:        dmem_rd_lat,
             dmem_wr_lat:        dmem_wr_lat,
-            sram_width:         128,
-            sram_entries:       1024,
+            sram_width:         sram_width,
+            sram_entries:       sram_entries,
             sram_rd_ports:      1,
             sram_wr_ports:      1,
             sram_rd_lat:        1,
             sram_wr_lat:        1,
             sram_ip_pl:         1,
+            sram_slots:         sram_slots,
+            sram_init:          sram_init,
+            clocks:             vec![],
+            lut_map:            false,
+            partition_constraints: None,
+            partitioner:        PartitionerKind::default(),
+            repartition_iters:  0,
+            repartition_time_limit: None,
+            compiled:           None,
+            kaminpar_cfg:       KaMinParConfig::default(),
+            pass_cfg:           PassManagerConfig::default(),
             dbg_tail_length:    u32::MAX, // don't print debug graph when testing
             dbg_tail_threshold: u32::MAX  // don't print debug graph when testing
         };
@@ -214,6 +251,76 @@ pub mod blif_sim_test {
         );
     }
 
+    #[test_case(5, 8, 0, 0, 1, 0, 0; "mod 5 procs 8 imem 0 dmem rd 0 wr 1 nw proc 0 nw mod 0")]
+    #[test_case(5, 8, 1, 1, 1, 0, 1; "mod 5 procs 8 imem 1 dmem rd 1 wr 1 nw proc 0 nw mod 1")]
+    pub fn test_1r1w_sram_split(num_mods: u32, num_procs: u32, imem_lat: u32, dmem_rd_lat: u32, dmem_wr_lat: u32, inter_proc_nw_lat: u32, inter_mod_nw_lat: u32) {
+        assert_eq!(
+            test_blif_sim_sram_cfg(
+                "../examples/OneReadOneWritePortSRAM.sv",
+                "OneReadOneWritePortSRAM",
+                "../examples/OneReadOneWritePortSRAM.input",
+                "../examples/OneReadOneWritePortSRAM.lut.blif",
+                num_mods, num_procs,
+                inter_proc_nw_lat, inter_mod_nw_lat, imem_lat, dmem_rd_lat, dmem_wr_lat,
+                4, 4, 1, vec![]
+            ),
+            true
+        );
+    }
+
+    #[test_case(5, 8, 0, 0, 1, 0, 0, 128, 1024; "mod 5 procs 8 imem 0 dmem rd 0 wr 1 nw proc 0 nw mod 0")]
+    #[test_case(5, 8, 0, 0, 1, 0, 0, 4, 4; "mod 5 procs 8 imem 0 dmem rd 0 wr 1 nw proc 0 nw mod 0 split")]
+    pub fn test_1r1w_sram_init(num_mods: u32, num_procs: u32, imem_lat: u32, dmem_rd_lat: u32, dmem_wr_lat: u32, inter_proc_nw_lat: u32, inter_mod_nw_lat: u32, sram_width: u32, sram_entries: u32) {
+        assert_eq!(
+            test_blif_sim_sram_cfg(
+                "../examples/OneReadOneWritePortSRAM.sv",
+                "OneReadOneWritePortSRAM",
+                "../examples/OneReadOneWritePortSRAM.input",
+                "../examples/OneReadOneWritePortSRAM.lut.blif",
+                num_mods, num_procs,
+                inter_proc_nw_lat, inter_mod_nw_lat, imem_lat, dmem_rd_lat, dmem_wr_lat,
+                sram_width, sram_entries, 1,
+                vec!["mem_8x8=../examples/mem_8x8.hex".to_string()]
+            ),
+            true
+        );
+    }
+
+    #[test_case(5, 8, 0, 0, 1, 0, 0, 3; "mod 5 procs 8 imem 0 dmem rd 0 wr 1 nw proc 0 nw mod 0 slots 3")]
+    #[test_case(5, 8, 1, 1, 1, 0, 1, 3; "mod 5 procs 8 imem 1 dmem rd 1 wr 1 nw proc 0 nw mod 1 slots 3")]
+    #[test_case(2, 8, 0, 0, 1, 0, 0, 4; "mod 2 procs 8 imem 0 dmem rd 0 wr 1 nw proc 0 nw mod 0 slots 4")]
+    pub fn test_2r1w_sram(num_mods: u32, num_procs: u32, imem_lat: u32, dmem_rd_lat: u32, dmem_wr_lat: u32, inter_proc_nw_lat: u32, inter_mod_nw_lat: u32, sram_slots: u32) {
+        assert_eq!(
+            test_blif_sim_sram_cfg(
+                "../examples/TwoReadOneWritePortSRAM.sv",
+                "TwoReadOneWritePortSRAM",
+                "../examples/TwoReadOneWritePortSRAM.input",
+                "../examples/TwoReadOneWritePortSRAM.lut.blif",
+                num_mods, num_procs,
+                inter_proc_nw_lat, inter_mod_nw_lat, imem_lat, dmem_rd_lat, dmem_wr_lat,
+                128, 1024, sram_slots, vec![]
+            ),
+            true
+        );
+    }
+
+    #[test_case(5, 8, 0, 0, 1, 0, 0; "mod 5 procs 8 imem 0 dmem rd 0 wr 1 nw proc 0 nw mod 0")]
+    #[test_case(5, 8, 1, 1, 1, 0, 1; "mod 5 procs 8 imem 1 dmem rd 1 wr 1 nw proc 0 nw mod 1")]
+    pub fn test_1rw_sram_split(num_mods: u32, num_procs: u32, imem_lat: u32, dmem_rd_lat: u32, dmem_wr_lat: u32, inter_proc_nw_lat: u32, inter_mod_nw_lat: u32) {
+        assert_eq!(
+            test_blif_sim_sram_cfg(
+                "../examples/SinglePortSRAM.sv",
+                "SinglePortSRAM",
+                "../examples/SinglePortSRAM.input",
+                "../examples/SinglePortSRAM.lut.blif",
+                num_mods, num_procs,
+                inter_proc_nw_lat, inter_mod_nw_lat, imem_lat, dmem_rd_lat, dmem_wr_lat,
+                4, 4, 1, vec![]
+            ),
+            true
+        );
+    }
+
     #[test_case(5, 8, 0, 0, 1, 0, 0; "mod 5 procs 8 imem 0 dmem rd 0 wr 1 nw proc 0 nw mod 0")]
     #[test_case(5, 8, 1, 0, 1, 0, 1; "mod 5 procs 8 imem 1 dmem rd 0 wr 1 nw proc 0 nw mod 1")]
     #[test_case(5, 8, 1, 1, 1, 0, 1; "mod 5 procs 8 imem 1 dmem rd 1 wr 1 nw proc 0 nw mod 1")]
@@ -230,6 +337,26 @@ pub mod blif_sim_test {
             true
         );
     }
+
+    #[test_case(5, 8, 0, 0, 1, 0, 0; "mod 5 procs 8 imem 0 dmem rd 0 wr 1 nw proc 0 nw mod 0")]
+    #[test_case(5, 8, 1, 1, 1, 0, 1; "mod 5 procs 8 imem 1 dmem rd 1 wr 1 nw proc 0 nw mod 1")]
+    #[test_case(1, 8, 0, 0, 1, 0, 0; "mod 1 procs 8 imem 0 dmem rd 0 wr 1 nw proc 0 nw mod 0")]
+    #[test_case(1, 8, 1, 1, 1, 0, 1; "mod 1 procs 8 imem 1 dmem rd 1 wr 1 nw proc 0 nw mod 1")]
+    pub fn test_cache(num_mods: u32, num_procs: u32, imem_lat: u32, dmem_rd_lat: u32, dmem_wr_lat: u32, inter_proc_nw_lat: u32, inter_mod_nw_lat: u32) {
+        // With a single module, both SRAMs of the cache are packed into it
+        assert_eq!(
+            test_blif_sim_sram_cfg(
+                "../examples/Cache.sv",
+                "Cache",
+                "../examples/Cache.input",
+                "../examples/Cache.lut.blif",
+                num_mods, num_procs,
+                inter_proc_nw_lat, inter_mod_nw_lat, imem_lat, dmem_rd_lat, dmem_wr_lat,
+                128, 1024, 2, vec![]
+            ),
+            true
+        );
+    }
 }
 
 #[cfg(test)]
@@ -259,6 +386,7 @@ pub mod emulation_tester {
             input_stimuli_path: input_stimuli_path.to_string(),
             blif_file_path:     blif_file_path.to_string(),
             vcd:                None,
+            ref_sim:            RefSimulator::default(),
             instance_path:      "testharness.top".to_string(),
             clock_start_low:    false,
             timesteps_per_cycle: 2,
@@ -267,6 +395,7 @@ pub mod emulation_tester {
             check_cycle_period: 1,
             num_mods:           num_mods,
             num_procs:          num_procs,
+            topology:           TopologyKind::Hub,
             max_steps:          65536,
             lut_inputs:         3,
             inter_proc_nw_lat:  inter_proc_nw_lat,
@@ -281,6 +410,17 @@ pub mod emulation_tester {
             sram_rd_lat:        1,
             sram_wr_lat:        1,
             sram_ip_pl:         1,
+            sram_slots:         1,
+            sram_init:          vec![],
+            clocks:             vec![],
+            lut_map:            false,
+            partition_constraints: None,
+            partitioner:        PartitionerKind::default(),
+            repartition_iters:  0,
+            repartition_time_limit: None,
+            compiled:           None,
+            kaminpar_cfg:       KaMinParConfig::default(),
+            pass_cfg:           PassManagerConfig::default(),
             dbg_tail_length:    u32::MAX, // don't print debug graph when testing
             dbg_tail_threshold: u32::MAX  // don't print debug graph when testing
         });
@@ -506,3 +646,1374 @@ pub mod emulation_tester {
         );
     }
 }
+
+#[cfg(test)]
+pub mod compile_error_test {
+    use std::fs;
+    use crate::common::error::CompileError;
+    use crate::common::sram_image::SRAMImage;
+    use crate::testing::fixtures::*;
+    use crate::testing::try_new_circuit;
+    use test_case::test_case;
+
+    #[test]
+    pub fn test_schedule_exceeds_max_steps() {
+        let mut args = example_args("Adder", "compile-error-dir-Adder", &[
+            "--num-mods",  "1",
+            "--num-procs", "4",
+        ]);
+        args.max_steps = 2;
+        match try_new_circuit(&args) {
+            Err(e) => {
+                assert!(e.is_capacity(), "{}", e);
+                assert!(matches!(e, CompileError::ScheduleExceedsMaxSteps { max_steps: 2, .. }), "{}", e);
+            }
+            Ok(_) => {
+                assert!(false, "Adder should not fit in 2 steps");
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_split_sram_lut_inputs() {
+        let args = example_args("OneReadOneWritePortSRAM", "compile-error-dir-OneReadOneWritePortSRAM", &[
+            "--num-mods",     "5",
+            "--num-procs",    "8",
+            "--lut-inputs",   "2",
+            "--sram-width",   "4",
+            "--sram-entries", "4",
+        ]);
+        match try_new_circuit(&args) {
+            Err(e) => {
+                assert!(matches!(e, CompileError::InvalidConfig { ref param, .. } if param == "lut_inputs"), "{}", e);
+            }
+            Ok(_) => {
+                assert!(false, "splitting SRAMs should need 3 input LUTs");
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_not_enough_sram_slots() {
+        let args = example_args("Cache", "compile-error-dir-Cache", &[
+            "--num-mods",  "1",
+            "--num-procs", "8",
+        ]);
+        match try_new_circuit(&args) {
+            Err(e) => {
+                assert!(e.is_capacity(), "{}", e);
+                assert!(matches!(e, CompileError::NotEnoughSRAMSlots { sram_slots: 1, .. }), "{}", e);
+            }
+            Ok(_) => {
+                assert!(false, "Cache has two SRAMs which should not fit in a single slot");
+            }
+        }
+    }
+
+    #[test_case(&["--num-mods", "4", "--num-procs", "8"],  "num_mods";  "hub num mods")]
+    #[test_case(&["--num-mods", "3", "--num-procs", "6"],  "num_procs"; "hub num procs")]
+    #[test_case(&["--num-mods", "6", "--num-procs", "4", "--topology", "crossbar"], "num_procs"; "crossbar links")]
+    #[test_case(&["--num-mods", "0", "--num-procs", "8"],  "num_mods";  "hub no modules")]
+    #[test_case(&["--num-mods", "7", "--num-procs", "8", "--topology", "mesh"], "num_mods"; "prime mesh")]
+    #[test_case(&["--num-mods", "2", "--num-procs", "8", "--clocks", "slow_clock"],   "clocks"; "clock without period")]
+    #[test_case(&["--num-mods", "2", "--num-procs", "8", "--clocks", "slow_clock=0"], "clocks"; "clock period 0")]
+    pub fn test_invalid_config(flags: &[&str], expected: &str) {
+        let args = example_args("GCD", "compile-error-dir-GCD", flags);
+        match try_new_circuit(&args) {
+            Err(e) => {
+                assert!(matches!(e, CompileError::InvalidConfig { ref param, .. } if param == expected), "{}", e);
+            }
+            Ok(_) => {
+                assert!(false, "{:?} should be rejected", flags);
+            }
+        }
+    }
+
+    #[test_case("00 11\n/* a\n b */ 2g", "line 3: invalid word 2g"; "bad digit after a block comment")]
+    #[test_case("00\n@zz 11", "line 2: invalid address @zz"; "bad address")]
+    #[test_case("@ffffffff 00 11", "line 1: word 11 is past the last address"; "address overflow")]
+    pub fn test_sram_image_parse_error(text: &str, expected: &str) {
+        match SRAMImage::parse(text, 4) {
+            Err(e) => { assert_eq!(e, expected); }
+            Ok(image) => { assert!(false, "parsed {:?}", image); }
+        }
+    }
+
+    #[test_case("address",   "@8 ff",      "line 1: address 0x8 is out of range"; "address")]
+    #[test_case("width",     "00\n1ff",    "line 2: word at address 0x1 is wider than 8 bits"; "width")]
+    #[test_case("malformed", "a5\nbroken", "line 2: invalid word broken"; "malformed")]
+    pub fn test_sram_image_out_of_range(name: &str, image: &str, expected: &str) {
+        let dir = format!("compile-error-dir-sram-image-{}", name);
+        fs::create_dir_all(&dir).unwrap();
+        let path = format!("{}/mem_8x8.hex", dir);
+        fs::write(&path, image).unwrap();
+
+        let sram_init = format!("mem_8x8={}", path);
+        let args = example_args("OneReadOneWritePortSRAM", &dir, &[
+            "--num-mods",  "5",
+            "--num-procs", "8",
+            "--sram-init", &sram_init,
+        ]);
+        match try_new_circuit(&args) {
+            Err(CompileError::Parse { path: p, msg }) => {
+                assert_eq!(p, path);
+                assert!(msg.starts_with(expected), "{}", msg);
+            }
+            Err(e) => {
+                assert!(false, "{}", e);
+            }
+            Ok(_) => {
+                assert!(false, "{} should not load into mem_8x8", path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod pass_manager_test {
+    use std::fs;
+    use std::path::Path;
+    use crate::common::config::*;
+    use crate::common::error::CompileError;
+    use crate::passes::runner::catch_internal;
+    use crate::testing::fixtures::*;
+    use crate::testing::try_new_circuit;
+
+    /// Compiles GCD with `pass_cfg` into a fresh `sim_dir` and returns the
+    /// output directory
+    fn compile_gcd(sim_dir: &str, pass_cfg: PassManagerConfig) -> String {
+        let mut args = example_args("GCD", sim_dir, &[
+            "--num-mods",  "2",
+            "--num-procs", "8",
+        ]);
+        args.pass_cfg = pass_cfg;
+        let _ = fs::remove_dir_all(sim_dir);
+        let circuit = try_new_circuit(&args).unwrap_or_else(|e| panic!("{}", e));
+        return circuit.compiler_cfg.output_dir;
+    }
+
+    #[test]
+    pub fn test_pass_selection_and_dumps() {
+        let skipped = [CompilerPass::ConstProp, CompilerPass::Strash, CompilerPass::CompactSchedule];
+        let passes: Vec<CompilerPass> = CompilerPass::default_pipeline().into_iter()
+            .filter(|p| !skipped.contains(p))
+            .collect();
+        let mut pass_cfg = PassManagerConfig::default();
+        pass_cfg.passes = passes.clone();
+        pass_cfg.dump_after = vec![CompilerPass::Partition];
+        pass_cfg.dump_format = GraphDumpFormat::Json;
+        pass_cfg.save_pass_report = true;
+        let out = compile_gcd("pass-manager-dir-selection", pass_cfg);
+
+        // Only the selected passes ran, in order
+        let text = fs::read_to_string(format!("{}/GCD.passes.json", out)).unwrap();
+        let report: serde_json::Value = serde_json::from_str(&text).unwrap();
+        let ran: Vec<String> = report["passes"].as_array().unwrap().iter()
+            .map(|s| s["pass"].as_str().unwrap().to_string())
+            .collect();
+        let expected: Vec<String> = passes.iter().map(|p| p.name()).collect();
+        assert_eq!(ran, expected);
+
+        // Only the requested dump is written, named after the pipeline position
+        let pidx = passes.iter().position(|p| *p == CompilerPass::Partition).unwrap();
+        let dump = format!("{}/GCD.{}-after-partition.json", out, pidx);
+        assert!(Path::new(&dump).exists(), "{} is missing", dump);
+        let dumps: Vec<String> = fs::read_dir(&out).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|f| f.contains("-after-") || f.contains("-before-"))
+            .collect();
+        assert_eq!(dumps.len(), 1, "{:?}", dumps);
+    }
+
+    #[test]
+    pub fn test_no_pass_report_by_default() {
+        let out = compile_gcd("pass-manager-dir-default", PassManagerConfig::default());
+        assert!(!Path::new(&format!("{}/GCD.passes.json", out)).exists());
+    }
+
+    #[test]
+    pub fn test_internal_error() {
+        let res = catch_internal("test-pass", || {
+            assert!(false, "broken invariant");
+            Ok(())
+        });
+        match res {
+            Err(CompileError::Internal { pass, msg }) => {
+                assert_eq!(pass, "test-pass");
+                assert!(msg.contains("broken invariant"), "{}", msg);
+            }
+            other => {
+                assert!(false, "{:?}", other);
+            }
+        }
+        assert!(catch_internal("test-pass", || Ok(())).is_ok());
+    }
+}
+
+#[cfg(test)]
+pub mod sram_packing_test {
+    use crate::common::error::CompileError;
+    use crate::testing::fixtures::*;
+    use crate::testing::{try_new_circuit, try_new_hw_circuit};
+
+    #[test]
+    pub fn test_pack_cache() {
+        let args = example_args("Cache", "sram-packing-dir-Cache", &[
+            "--num-mods",   "1",
+            "--num-procs",  "8",
+            "--sram-slots", "2",
+        ]);
+        let circuit = try_new_circuit(&args).unwrap_or_else(|e| panic!("{}", e));
+
+        // Both SRAMs share the SRAM processor of module 0 without overlapping
+        let mems = &circuit.emul.module_mappings.get(&0).unwrap().sram_mapping.mems;
+        assert_eq!(mems.len(), 2);
+        let mut names: Vec<&str> = mems.iter().map(|m| m.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["mem_8x8", "tagMem_8x3"]);
+        assert_eq!(mems[0].base_addr, 0);
+        assert_eq!(mems[1].base_addr, 1 << mems[0].addr_bits);
+
+        assert_blif_sim_matches(args);
+    }
+
+    /// The SRAM processor of the emulator RTL holds a single target SRAM
+    #[test]
+    pub fn test_hw_rejects_packing() {
+        let args = example_args("Cache", "sram-packing-hw-dir-Cache", &[
+            "--num-mods",   "1",
+            "--num-procs",  "8",
+            "--sram-slots", "2",
+        ]);
+        match try_new_hw_circuit(&args) {
+            Err(e) => {
+                assert!(matches!(e, CompileError::InvalidConfig { ref param, .. } if param == "sram_slots"), "{}", e);
+            }
+            Ok(_) => {
+                assert!(false, "packed SRAMs should be rejected for the emulator hardware");
+            }
+        }
+    }
+
+    /// The SRAM processor of the emulator RTL can't serialize target SRAM ports
+    #[test]
+    pub fn test_hw_rejects_multi_port_sram() {
+        let args = example_args("TwoReadOneWritePortSRAM", "sram-ports-hw-dir-TwoReadOneWritePortSRAM", &[
+            "--num-mods",     "2",
+            "--num-procs",    "8",
+            "--sram-width",   "128",
+            "--sram-entries", "1024",
+        ]);
+        match try_new_hw_circuit(&args) {
+            Err(e) => {
+                assert!(matches!(e, CompileError::InvalidConfig { ref param, .. } if param == "blif_file_path"), "{}", e);
+            }
+            Ok(_) => {
+                assert!(false, "multi-ported SRAMs should be rejected for the emulator hardware");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod reg_init_test {
+    use crate::testing::fixtures::*;
+    use crate::testing::try_new_circuit;
+
+    /// reg_0[0] starts at one and has no reset, so a wrong initial value
+    /// shows up on io_c from the first cycle on
+    #[test]
+    pub fn test_reg_init_one() {
+        let args = compile_args("reg-init-dir", "RegInitOne", 2);
+        let circuit = try_new_circuit(&args).unwrap_or_else(|e| panic!("{}", e));
+        let mut ones = 0;
+        for (_, mmap) in circuit.emul.module_mappings.iter() {
+            for (_, pmap) in mmap.proc_mappings.iter() {
+                ones += pmap.ldm_init.values().chain(pmap.sdm_init.values())
+                    .filter(|b| **b == 1)
+                    .count();
+            }
+        }
+        assert!(ones > 0, "no initial register value is set");
+
+        assert_blif_sim_matches(args);
+    }
+
+    /// reg_0 and reg_1 latch the same net but start from opposite values
+    #[test]
+    pub fn test_reg_init_shared_input() {
+        let args = compile_args("reg-init-dir", "RegInitShared", 1);
+        let circuit = try_new_circuit(&args).unwrap_or_else(|e| panic!("{}", e));
+        let mut inits = vec![];
+        for (_, mmap) in circuit.emul.module_mappings.iter() {
+            for (_, pmap) in mmap.proc_mappings.iter() {
+                inits.extend(pmap.ldm_init.values().chain(pmap.sdm_init.values()).cloned());
+            }
+        }
+        inits.sort();
+        assert_eq!(inits, vec![0, 1], "the registers should start from their own slots");
+
+        assert_blif_sim_matches(args);
+    }
+}
+
+#[cfg(test)]
+pub mod gate_test {
+    use test_case::test_case;
+    use crate::common::primitive::*;
+    use crate::testing::fixtures::*;
+    use crate::testing::try_new_circuit;
+
+    // With 3 LUT inputs, the Gates with both an enable and a reset need 4
+    // operands (d, e, r, q) so their enables are split out into mux LUTs
+    #[test_case(3, 2; "lut inputs 3")]
+    #[test_case(4, 0; "lut inputs 4")]
+    pub fn test_gate_regs(lut_inputs: u32, split: u32) {
+        let lut_inputs_str = lut_inputs.to_string();
+        let args = example_args("GateRegs", &format!("gate-dir-GateRegs-{}", lut_inputs), &[
+            "--lut-inputs", &lut_inputs_str,
+            "--num-mods",   "2",
+            "--num-procs",  "8",
+        ]);
+        let circuit = try_new_circuit(&args).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(circuit.opt_stats.gate_enables_split, split);
+
+        let muxes = circuit.graph.node_weights()
+            .filter(|n| n.is() == Primitive::Lut && n.name().starts_with("GATE-EN-"))
+            .count();
+        assert_eq!(muxes as u32, split);
+
+        assert_blif_sim_matches(args);
+    }
+}
+
+#[cfg(test)]
+pub mod clock_domain_test {
+    use test_case::test_case;
+    use crate::common::primitive::*;
+    use crate::testing::fixtures::*;
+    use crate::testing::try_new_circuit;
+
+    #[test_case(2; "period 2")]
+    #[test_case(3; "period 3")]
+    pub fn test_two_clocks(period: u32) {
+        let clocks = format!("slow_clock={}", period);
+        let args = example_args("TwoClocks", &format!("clock-domain-dir-TwoClocks-{}", period), &[
+            "--clocks",    &clocks,
+            "--num-mods",  "2",
+            "--num-procs", "8",
+        ]);
+        let circuit = try_new_circuit(&args).unwrap_or_else(|e| panic!("{}", e));
+
+        // The SRAM in the slow domain is only accessed on its clock edges
+        for prim in [Primitive::SRAMRdEn, Primitive::SRAMWrEn] {
+            let gated = circuit.graph.node_weights()
+                .filter(|n| n.is() == prim)
+                .all(|n| n.name().starts_with("CLK-EN-"));
+            assert!(gated, "{:?} is not gated by the clock enable", prim);
+        }
+
+        assert_blif_sim_matches(args);
+    }
+}
+
+#[cfg(test)]
+pub mod lut_map_test {
+    use test_case::test_case;
+    use indexmap::IndexMap;
+    use crate::common::primitive::*;
+    use crate::passes::blif_to_circuit::blif_to_circuit;
+    use crate::passes::lut_map::lut_map;
+    use crate::testing::fixtures::*;
+
+    #[test_case("DupInput", 3; "dup input 3")]
+    #[test_case("GCD",      3; "gcd 3")]
+    #[test_case("GCD",      4; "gcd 4")]
+    #[test_case("Fir",      4; "fir 4")]
+    pub fn test_blif_sim_lut_map(top_mod: &str, lut_inputs: u32) {
+        let lut_inputs_str = lut_inputs.to_string();
+        let args = example_args(top_mod, &format!("lut-map-dir-{}-{}", top_mod, lut_inputs), &[
+            "--lut-map",
+            "--lut-inputs", &lut_inputs_str,
+            "--num-mods",   "2",
+            "--num-procs",  "8",
+        ]);
+        assert_blif_sim_matches(args);
+    }
+
+    #[test]
+    pub fn test_duplicated_input() {
+        let mut circuit = blif_to_circuit("../examples/DupInput.lut.blif").unwrap();
+        circuit.platform_cfg.lut_inputs = 3;
+        circuit.compiler_cfg.lut_map = true;
+        lut_map(&mut circuit).unwrap();
+
+        // y = (a & a & b) & b & b & d = a & b & d
+        let node = circuit.graph.node_weights()
+            .find(|n| n.is() == Primitive::Lut && n.name() == "y")
+            .unwrap();
+        let (inputs, table) = match &node.prim {
+            CircuitPrimitive::Lut { inputs, table, .. } => (inputs, table),
+            _ => unreachable!()
+        };
+        let mut sorted = inputs.clone();
+        sorted.sort();
+        assert_eq!(sorted, vec!["a", "b", "d"]);
+
+        for row in 0..8u32 {
+            let vals: IndexMap<&str, u8> = ["a", "b", "d"].iter().enumerate()
+                .map(|(i, n)| (*n, ((row >> i) & 1) as u8))
+                .collect();
+            let ivals: Vec<u8> = inputs.iter().map(|i| vals[i.as_str()]).collect();
+            let y = if table.contains(&ivals) { 1 } else { 0 };
+            assert_eq!(y, vals["a"] & vals["b"] & vals["d"], "{:?}", vals);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod topology_test {
+    use crate::common::config::*;
+    use crate::common::network::PathTypes;
+    use crate::testing::fixtures::*;
+    use crate::testing::try_new_circuit;
+    use test_case::test_case;
+
+    fn topology_args(top_mod: &str, topology: &str, num_mods: u32, extra: &[&str]) -> Args {
+        let num_mods = num_mods.to_string();
+        let mut flags = vec![
+            "--topology",         topology,
+            "--num-mods",         &num_mods,
+            "--num-procs",        "8",
+            "--inter-mod-nw-lat", "1",
+        ];
+        flags.extend_from_slice(extra);
+        return example_args(top_mod, &format!("topology-sim-dir-{}-{}", top_mod, topology), &flags);
+    }
+
+    #[test_case("ring",     3; "ring 3")]
+    #[test_case("ring",     6; "ring 6")]
+    #[test_case("mesh",     6; "mesh 6")]
+    #[test_case("torus",    9; "torus 9")]
+    #[test_case("crossbar", 4; "crossbar 4")]
+    pub fn test_gcd(topology: &str, num_mods: u32) {
+        assert_blif_sim_matches(topology_args("GCD", topology, num_mods, &[]));
+    }
+
+    // Sparse topologies where most module pairs are several hops apart
+    #[test_case("ring",     12; "ring 12")]
+    #[test_case("mesh",     16; "mesh 16")]
+    pub fn test_fir_multi_hop(topology: &str, num_mods: u32) {
+        assert_blif_sim_matches(topology_args("Fir", topology, num_mods, &[]));
+    }
+
+    /// The constraints keep the GCD registers on module 3 of a 6 module ring
+    /// and the IO on module 0, three hops away. Every edge between modules
+    /// that aren't neighbors has to be routed through other modules
+    #[test]
+    pub fn test_forced_multi_hop_route() {
+        let num_mods = 6;
+        let args = topology_args("GCD", "ring", num_mods,
+            &["--partition-constraints", "../examples/GCDRing.constraints"]);
+        let circuit = try_new_circuit(&args).unwrap_or_else(|e| panic!("{}", e));
+
+        let ring_dist = |a: u32, b: u32| -> u32 {
+            let d = (a + num_mods - b) % num_mods;
+            return std::cmp::min(d, num_mods - d);
+        };
+        let mut far_edges = 0;
+        for eidx in circuit.graph.edge_indices() {
+            let (src, dst) = circuit.graph.edge_endpoints(eidx).unwrap();
+            let src_mod = circuit.graph.node_weight(src).unwrap().info().coord.module;
+            let dst_mod = circuit.graph.node_weight(dst).unwrap().info().coord.module;
+            if ring_dist(src_mod, dst_mod) < 2 {
+                continue;
+            }
+            far_edges += 1;
+            let edge = circuit.graph.edge_weight(eidx).unwrap();
+            let route = edge.route.as_ref().expect("edge between modules without a route");
+            let hops = route.iter().filter(|p| p.tpe == PathTypes::InterModule).count();
+            assert!(hops > 1, "module {} to {} in {} hops: {:?}", src_mod, dst_mod, hops, route);
+        }
+        assert!(far_edges > 0);
+
+        assert_blif_sim_matches(args);
+    }
+}
+
+#[cfg(test)]
+pub mod partition_constraints_test {
+    use std::fs;
+    use indexmap::IndexSet;
+    use crate::passes::blif_to_circuit::blif_to_circuit;
+    use crate::testing::fixtures::*;
+    use crate::testing::try_new_circuit;
+
+    #[test]
+    pub fn test_gcd_constraints() {
+        let args = example_args("GCD", "partition-constraints-dir-GCD", &[
+            "--partition-constraints", "../examples/GCD.constraints",
+            "--kaminpar-seed",         "7",
+            "--num-mods",              "5",
+            "--num-procs",             "8",
+        ]);
+        let circuit = try_new_circuit(&args).unwrap_or_else(|e| panic!("{}", e));
+        for nidx in circuit.graph.node_indices() {
+            let node = circuit.graph.node_weight(nidx).unwrap();
+            let coord = node.info().coord;
+            if node.name().starts_with("io_") {
+                assert_eq!(coord.module, 0, "{} at {:?}", node.name(), coord);
+                assert!(coord.proc <= 5, "{} at {:?}", node.name(), coord);
+            } else if node.name().starts_with("x[") || node.name().starts_with("y[") {
+                assert_eq!(coord.module, 1, "{} at {:?}", node.name(), coord);
+            }
+        }
+    }
+
+    /// Pin 40% of the Fir nodes to module 0. The unconstrained nodes should
+    /// fill up module 1 instead of being split evenly between both modules
+    #[test]
+    pub fn test_pinned_module_capacity() {
+        let dir = "partition-constraints-dir-Fir-capacity";
+        fs::create_dir_all(dir).unwrap();
+        let netlist = blif_to_circuit("../examples/Fir.lut.blif").unwrap_or_else(|e| panic!("{}", e));
+        let names: Vec<String> = netlist.graph.node_weights()
+            .map(|n| n.name().to_string())
+            .collect();
+        let pinned: IndexSet<String> = names.iter().take(names.len() * 2 / 5).cloned().collect();
+        let rules: Vec<String> = pinned.iter()
+            .map(|n| format!("regex ^{}$ module=0", regex::escape(n)))
+            .collect();
+        let path = format!("{}/Fir.constraints", dir);
+        fs::write(&path, rules.join("\n")).unwrap();
+
+        let args = example_args("Fir", dir, &[
+            "--partition-constraints", &path,
+            "--num-mods",              "2",
+            "--num-procs",             "8",
+        ]);
+        let circuit = try_new_circuit(&args).unwrap_or_else(|e| panic!("{}", e));
+        let mut load = [0usize; 2];
+        let mut total = 0;
+        for node in circuit.graph.node_weights() {
+            if !names.contains(&node.name().to_string()) {
+                continue;
+            }
+            if pinned.contains(node.name()) {
+                assert_eq!(node.info().coord.module, 0, "{} is pinned to module 0", node.name());
+            }
+            load[node.info().coord.module as usize] += 1;
+            total += 1;
+        }
+        assert!(load[0] <= total / 2 + total / 8,
+            "module 0 holds {} of {} nodes: {:?}", load[0], total, load);
+
+        assert_blif_sim_matches(args);
+    }
+}
+
+#[cfg(test)]
+pub mod partitioner_test {
+    use petgraph::{graph::NodeIndex, visit::EdgeRef};
+    use crate::common::hwgraph::*;
+    use crate::common::primitive::*;
+    use crate::passes::partitioner::*;
+    use crate::testing::fixtures::*;
+    use test_case::test_case;
+
+    #[test_case("GCD",  1, 8; "gcd 1 module")]
+    #[test_case("GCD",  9, 8; "gcd 9 modules")]
+    #[test_case("Fir",  5, 8; "fir 5 modules")]
+    pub fn test_multilevel(top_mod: &str, num_mods: u32, num_procs: u32) {
+        let num_mods_str = num_mods.to_string();
+        let num_procs = num_procs.to_string();
+        let args = example_args(top_mod, &format!("multilevel-sim-dir-{}-{}", top_mod, num_mods), &[
+            "--partitioner", "multilevel",
+            "--num-mods",    &num_mods_str,
+            "--num-procs",   &num_procs,
+        ]);
+        assert_blif_sim_matches(args);
+    }
+
+    /// `ncliques` cliques of `size` nodes (edge weight 10) chained into a path
+    /// by single edges of weight 1
+    fn clique_path(ncliques: usize, size: usize) -> PartitionGraph {
+        let mut g = PartitionGraph::default();
+        let nodes: Vec<NodeIndex> = (0..ncliques * size)
+            .map(|i| g.add_node(HWNode::new(CircuitPrimitive::Input { name: format!("n{}", i) })))
+            .collect();
+        let add_edge = |g: &mut PartitionGraph, a: usize, b: usize, w: i32| {
+            let mut edge = HWEdge::new(SignalType::Wire { name: format!("n{}", a) });
+            edge.weight = Some(w);
+            g.add_edge(nodes[a], nodes[b], edge);
+        };
+        for c in 0..ncliques {
+            for i in 0..size {
+                for j in (i + 1)..size {
+                    add_edge(&mut g, c * size + i, c * size + j, 10);
+                }
+            }
+            if c + 1 < ncliques {
+                add_edge(&mut g, c * size + size - 1, (c + 1) * size, 1);
+            }
+        }
+        return g;
+    }
+
+    #[test_case(1; "seed 1")]
+    #[test_case(7; "seed 7")]
+    #[test_case(42; "seed 42")]
+    pub fn test_multilevel_cut_and_balance(seed: u64) {
+        let (ncliques, size, k) = (8, 32, 4);
+        let g = clique_path(ncliques, size);
+        let partitioner = MultilevelPartitioner { seed: seed, epsilon: 0.03 };
+        let part = partitioner.partition(&g, k as u32).unwrap();
+        assert_eq!(part.len(), g.node_count());
+
+        // Only the edges between the cliques are cut. Two cliques per part
+        // are a perfect balance with 3 cut edges
+        let cut: i32 = g.edge_references()
+            .filter(|e| part[e.source().index()] != part[e.target().index()])
+            .map(|e| e.weight().weight.unwrap())
+            .sum();
+        assert!(cut < 10, "cut {} splits a clique", cut);
+        assert!(cut >= 3, "cut {} is below the optimum", cut);
+
+        let mut weights = vec![0; k];
+        for p in part.iter() {
+            weights[*p as usize] += 1;
+        }
+        let avg = (ncliques * size / k) as f64;
+        for w in weights.iter() {
+            assert!(*w > 0 && (*w as f64) <= (avg * 1.03).ceil() + 1.0,
+                "unbalanced part weights {:?}", weights);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod repartition_test {
+    use crate::common::config::*;
+    use crate::testing::fixtures::*;
+    use crate::testing::try_new_circuit;
+
+    fn fir_args(sim_dir: &str, repartition_iters: u32) -> Args {
+        let repartition_iters = repartition_iters.to_string();
+        return example_args("Fir", sim_dir, &[
+            "--num-mods",          "9",
+            "--num-procs",         "8",
+            "--inter-mod-nw-lat",  "2",
+            "--repartition-iters", &repartition_iters,
+        ]);
+    }
+
+    #[test]
+    pub fn test_fir_repartition_keeps_best() {
+        let host_steps = |args: &Args| {
+            try_new_circuit(args).unwrap_or_else(|e| panic!("{}", e)).emul.host_steps
+        };
+        // With a slow inter-module network, the critical inter-module edges of
+        // the first schedule dominate and weighting them up has to pay off
+        let baseline = host_steps(&fir_args("repartition-dir-Fir-0", 0));
+        let repartitioned = host_steps(&fir_args("repartition-dir-Fir-4", 4));
+        assert!(repartitioned < baseline, "{} >= {}", repartitioned, baseline);
+    }
+
+    #[test]
+    pub fn test_fir_repartition_sim() {
+        assert_blif_sim_matches(fir_args("repartition-sim-dir-Fir", 4));
+    }
+}
+
+#[cfg(test)]
+pub mod compact_schedule_test {
+    use crate::common::config::*;
+    use crate::testing::fixtures::*;
+    use crate::testing::try_new_circuit;
+    use test_case::test_case;
+
+    fn compact_args(top_mod: &str, num_mods: u32, compact: bool) -> Args {
+        let num_mods_str = num_mods.to_string();
+        let mut args = example_args(top_mod, &format!("compact-dir-{}-{}-{}", top_mod, num_mods, compact), &[
+            "--num-mods",          &num_mods_str,
+            "--num-procs",         "8",
+            "--inter-mod-nw-lat",  "1",
+            "--inter-proc-nw-lat", "1",
+        ]);
+        args.pass_cfg.passes = CompilerPass::default_pipeline()
+            .into_iter()
+            .filter(|p| compact || *p != CompilerPass::CompactSchedule)
+            .collect();
+        return args;
+    }
+
+    fn host_steps(top_mod: &str, num_mods: u32, compact: bool) -> u32 {
+        let circuit = try_new_circuit(&compact_args(top_mod, num_mods, compact))
+            .unwrap_or_else(|e| panic!("{}", e));
+        return circuit.emul.host_steps;
+    }
+
+    #[test_case("GCD", 1; "gcd 1 module")]
+    pub fn test_compact_not_longer(top_mod: &str, num_mods: u32) {
+        let list_sched = host_steps(top_mod, num_mods, false);
+        let compacted = host_steps(top_mod, num_mods, true);
+        assert!(compacted <= list_sched, "{} > {}", compacted, list_sched);
+    }
+
+    // The list schedule of Fir on several modules leaves a long tail of
+    // nearly empty PCs behind the inter-module transfers
+    #[test_case("Fir", 5; "fir 5 modules")]
+    pub fn test_compact_shorter(top_mod: &str, num_mods: u32) {
+        let list_sched = host_steps(top_mod, num_mods, false);
+        let compacted = host_steps(top_mod, num_mods, true);
+        assert!(compacted < list_sched, "{} >= {}", compacted, list_sched);
+    }
+
+    #[test_case("GCD", 1; "gcd 1 module")]
+    #[test_case("Fir", 5; "fir 5 modules")]
+    pub fn test_compact_sim(top_mod: &str, num_mods: u32) {
+        let mut args = compact_args(top_mod, num_mods, true);
+        args.sim_dir = format!("compact-sim-dir-{}-{}", top_mod, num_mods);
+        assert_blif_sim_matches(args);
+    }
+}
+
+#[cfg(test)]
+pub mod compiled_test {
+    use crate::common::config::*;
+    use crate::common::error::CompileError;
+    use crate::common::primitive::*;
+    use crate::testing::fixtures::*;
+    use crate::testing::try_new_circuit;
+
+    fn gcd_args(sim_dir: &str, blif_file_path: &str, compiled: Option<&str>) -> Args {
+        let mut args = example_args("GCD", sim_dir, &[
+            "--num-mods",  "2",
+            "--num-procs", "8",
+        ]);
+        args.blif_file_path = blif_file_path.to_string();
+        args.compiled = compiled.map(|c| c.to_string());
+        return args;
+    }
+
+    #[test]
+    pub fn test_reload_compiled() {
+        let compiled = try_new_circuit(&gcd_args("compiled-dir-GCD", "../examples/GCD.lut.blif", None)).unwrap();
+        let path = format!("{}/GCD.compiled.json", compiled.compiler_cfg.output_dir);
+        let loaded = try_new_circuit(&gcd_args("compiled-dir-GCD", "../examples/GCD.lut.blif", Some(&path))).unwrap();
+
+        assert_eq!(loaded.emul.host_steps, compiled.emul.host_steps);
+        assert_eq!(loaded.platform_cfg.num_mods, compiled.platform_cfg.num_mods);
+        assert_eq!(
+            serde_json::to_string(&loaded.emul.module_mappings.values().map(|m| &m.sram_mapping).collect::<Vec<_>>()).unwrap(),
+            serde_json::to_string(&compiled.emul.module_mappings.values().map(|m| &m.sram_mapping).collect::<Vec<_>>()).unwrap());
+        for (m, mmap) in compiled.emul.module_mappings.iter() {
+            for (p, pmap) in mmap.proc_mappings.iter() {
+                let lmap = loaded.emul.module_mappings.get(m).unwrap().proc_mappings.get(p).unwrap();
+                assert_eq!(
+                    serde_json::to_string(&lmap.instructions).unwrap(),
+                    serde_json::to_string(&pmap.instructions).unwrap());
+                assert_eq!(lmap.signal_map.len(), pmap.signal_map.len());
+
+                // Only the IOs point to a node of the rebuilt graph
+                for (sig, nmi) in lmap.signal_map.iter() {
+                    let opcode = lmap.instructions[nmi.info.pc as usize].opcode;
+                    match nmi.idx {
+                        Some(nidx) => {
+                            assert_eq!(loaded.graph.node_weight(nidx).unwrap().name(), sig);
+                        }
+                        None => {
+                            assert!(opcode != Opcode::Input && opcode != Opcode::Output,
+                                "IO {} has no node", sig);
+                        }
+                    }
+                }
+            }
+        }
+
+        // The IO nodes are rebuilt with their schedule
+        for nidx in compiled.graph.node_indices() {
+            let node = compiled.graph.node_weight(nidx).unwrap();
+            if node.is() != Primitive::Input && node.is() != Primitive::Output {
+                continue;
+            }
+            let lnode = loaded.graph.node_indices()
+                .map(|l| loaded.graph.node_weight(l).unwrap())
+                .find(|l| l.name() == node.name())
+                .unwrap();
+            assert_eq!(lnode.is(), node.is());
+            assert_eq!(lnode.info().coord, node.info().coord);
+            assert_eq!(lnode.info().pc, node.info().pc);
+        }
+    }
+
+    #[test]
+    pub fn test_stale_compiled() {
+        let compiled = try_new_circuit(&gcd_args("stale-compiled-dir-GCD", "../examples/GCD.lut.blif", None)).unwrap();
+        let path = format!("{}/GCD.compiled.json", compiled.compiler_cfg.output_dir);
+        match try_new_circuit(&gcd_args("stale-compiled-dir-GCD", "../examples/Adder.lut.blif", Some(&path))) {
+            Err(e) => {
+                assert!(matches!(e, CompileError::InvalidConfig { .. }), "{}", e);
+            }
+            Ok(_) => {
+                assert!(false, "GCD.compiled.json should not load for Adder.lut.blif");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod inst_image_test {
+    use std::fs;
+    use crate::common::config::*;
+    use crate::common::error::CompileError;
+    use crate::common::instruction::Instruction;
+    use crate::common::inst_image::*;
+    use crate::testing::fixtures::*;
+    use crate::testing::try_new_circuit;
+
+    #[test]
+    pub fn test_image_roundtrip() {
+        for (top_mod, num_mods) in EXAMPLE_DESIGNS.iter() {
+            let circuit = try_new_circuit(&compile_args("inst-image-dir", top_mod, *num_mods)).unwrap();
+            let pcfg = &circuit.platform_cfg;
+            let host_steps = circuit.emul.host_steps;
+
+            let paths = save_instruction_images(&circuit).unwrap();
+            let module_insts = module_instructions(&circuit.emul);
+            assert_eq!(paths.len(), module_insts.len());
+
+            for (path, (m, insts)) in paths.iter().zip(module_insts.iter()) {
+                let image = fs::read(path).unwrap();
+                assert_eq!(image.len(), insts.len() * INST_BEAT_BYTES);
+
+                let dinsts = disassemble_image(&image, pcfg).unwrap();
+                assert_eq!(dinsts.len(), insts.len());
+                for (i, (dinst, inst)) in dinsts.iter().zip(insts.iter()).enumerate() {
+                    assert_eq!(dinst.module, *m);
+                    assert_eq!(dinst.proc, i as u32 / host_steps);
+                    assert_eq!(dinst.pc, i as u32 % host_steps);
+                    assert_eq!(dinst.inst.opcode, inst.opcode, "{}", dinst.disasm());
+                    assert_eq!(dinst.inst.to_bits(pcfg), inst.to_bits(pcfg), "{}", dinst.disasm());
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_unknown_opcode() {
+        let pcfg = PlatformConfig::default();
+        let mut image = encode_inst_beat(&Instruction::new(pcfg.lut_inputs), &pcfg, 0, 0);
+
+        // The opcode is in the most significant bits of the beat
+        let beat_bits = inst_beat_bits(&pcfg) as usize;
+        for i in 0..pcfg.opcode_bits() as usize {
+            let b = beat_bits - 1 - i;
+            image[b / 8] |= 1 << (b % 8);
+        }
+        match disassemble_image(&image, &pcfg) {
+            Err(e) => {
+                assert!(matches!(e, CompileError::InvalidConfig { .. }), "{}", e);
+            }
+            Ok(dinsts) => {
+                assert!(false, "decoded an unknown opcode as {}", dinsts[0].disasm());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod verify_program_test {
+    use crate::common::circuit::Circuit;
+    use crate::common::instruction::*;
+    use crate::common::mapping::EmulatorMapping;
+    use crate::common::network::*;
+    use crate::common::primitive::*;
+    use crate::common::topology::Topology;
+    use crate::common::verify::*;
+    use crate::testing::fixtures::*;
+    use crate::testing::try_new_circuit;
+
+    fn compile(top_mod: &str, num_mods: u32) -> Circuit {
+        return try_new_circuit(&compile_args("verify-program-dir", top_mod, num_mods)).unwrap();
+    }
+
+    /// First instruction for which `f` holds
+    fn find_inst<F>(emul: &EmulatorMapping, f: F) -> Option<(Coordinate, u32)>
+    where F: Fn(&Instruction) -> bool
+    {
+        for (m, mmap) in emul.module_mappings.iter() {
+            for (p, pmap) in mmap.proc_mappings.iter() {
+                for (pc, inst) in pmap.instructions.iter().enumerate() {
+                    if f(inst) {
+                        return Some((Coordinate { module: *m, proc: *p }, pc as u32));
+                    }
+                }
+            }
+        }
+        return None;
+    }
+
+    fn inst_mut(emul: &mut EmulatorMapping, coord: Coordinate, pc: u32) -> &mut Instruction {
+        let pmap = emul.module_mappings.get_mut(&coord.module).unwrap()
+            .proc_mappings.get_mut(&coord.proc).unwrap();
+        return pmap.instructions.get_mut(pc as usize).unwrap();
+    }
+
+    fn has_violation(violations: &[ProgramViolation], kind: ViolationKind, coord: Coordinate, pc: u32) -> bool {
+        violations.iter().any(|v| v.kind == kind && v.coord == coord && v.pc == pc)
+    }
+
+    #[test]
+    pub fn test_compiled_program_is_valid() {
+        let srams = [("OneReadOneWritePortSRAM", 1)];
+        for (top_mod, num_mods) in EXAMPLE_DESIGNS.iter().chain(srams.iter()) {
+            let circuit = compile(top_mod, *num_mods);
+            let violations = verify_program(&circuit.emul, &circuit.platform_cfg);
+            assert!(violations.is_empty(), "{}: {:#?}", top_mod, violations);
+        }
+    }
+
+    #[test]
+    pub fn test_operand_violations() {
+        let circuit = compile("GCD", 3);
+        let (coord, pc) = find_inst(&circuit.emul, |inst| {
+            inst.opcode == Opcode::Lut && inst.operands.iter().any(|op| op.local)
+        }).unwrap();
+
+        // Read its own output before it is written
+        let mut emul = circuit.emul.clone();
+        for op in inst_mut(&mut emul, coord, pc).operands.iter_mut() {
+            if op.local {
+                op.rs = pc;
+            }
+        }
+        let violations = verify_program(&emul, &circuit.platform_cfg);
+        assert!(has_violation(&violations, ViolationKind::ReadBeforeWrite, coord, pc), "{:#?}", violations);
+
+        let mut emul = circuit.emul.clone();
+        let host_steps = emul.host_steps;
+        for op in inst_mut(&mut emul, coord, pc).operands.iter_mut() {
+            op.rs = host_steps;
+        }
+        let violations = verify_program(&emul, &circuit.platform_cfg);
+        assert!(has_violation(&violations, ViolationKind::OperandOutOfRange, coord, pc), "{:#?}", violations);
+    }
+
+    #[test]
+    pub fn test_receive_without_sender() {
+        let circuit = compile("GCD", 3);
+        let pcfg = &circuit.platform_cfg;
+        let (coord, pc) = find_inst(&circuit.emul, |inst| {
+            inst.sinfo.local_set && inst.sinfo.local
+        }).unwrap();
+
+        let idx = circuit.emul.module_mappings.get(&coord.module).unwrap()
+            .proc_mappings.get(&coord.proc).unwrap()
+            .instructions[pc as usize].sinfo.idx;
+        let sender = Coordinate { module: coord.module, proc: idx };
+        let send_pc = pc - pcfg.topology.path_lat(&NetworkPath::new(sender, coord), pcfg);
+
+        let mut emul = circuit.emul.clone();
+        let sinst = inst_mut(&mut emul, sender, send_pc);
+        sinst.opcode = Opcode::NOP;
+        sinst.sinfo.fwd = false;
+        let violations = verify_program(&emul, pcfg);
+        assert!(has_violation(&violations, ViolationKind::Receive, coord, pc), "{:#?}", violations);
+    }
+
+    #[test]
+    pub fn test_broken_forward_chain() {
+        let circuit = compile("GCD", 3);
+        let (coord, pc) = find_inst(&circuit.emul, |inst| inst.sinfo.fwd)
+            .expect("GCD on 3 modules is expected to forward bits");
+
+        let mut emul = circuit.emul.clone();
+        inst_mut(&mut emul, coord, pc - 1).sinfo.local_set = false;
+        let violations = verify_program(&emul, &circuit.platform_cfg);
+        assert!(has_violation(&violations, ViolationKind::Forward, coord, pc), "{:#?}", violations);
+    }
+
+    #[test]
+    pub fn test_sram_index_out_of_range() {
+        let circuit = compile("OneReadOneWritePortSRAM", 1);
+        let pcfg = &circuit.platform_cfg;
+        let (coord, pc) = find_inst(&circuit.emul, |inst| inst.opcode == Opcode::SRAMOut).unwrap();
+
+        let mut emul = circuit.emul.clone();
+        for op in inst_mut(&mut emul, coord, pc).operands.iter_mut() {
+            if op.idx != 0 {
+                op.rs = (1 << pcfg.index_bits()) - 1;
+            }
+        }
+        let violations = verify_program(&emul, pcfg);
+        assert!(has_violation(&violations, ViolationKind::SRAMIndex, coord, pc), "{:#?}", violations);
+    }
+}
+
+#[cfg(test)]
+pub mod equiv_test {
+    use crate::common::circuit::Circuit;
+    use crate::common::primitive::*;
+    use crate::passes::blif_to_circuit::blif_to_circuit;
+    use crate::testing::equiv::check_equivalence;
+    use crate::testing::fixtures::*;
+    use crate::testing::try_new_circuit;
+
+    fn compile(top_mod: &str, num_mods: u32) -> (Circuit, Circuit) {
+        let args = compile_args("equiv-dir", top_mod, num_mods);
+        let compiled = try_new_circuit(&args).unwrap();
+        let netlist = blif_to_circuit(&args.blif_file_path).unwrap();
+        return (netlist, compiled);
+    }
+
+    #[test]
+    pub fn test_compiled_program_is_equivalent() {
+        let gates = [("GateRegs", 2)];
+        for (top_mod, num_mods) in EXAMPLE_DESIGNS.iter().chain(gates.iter()) {
+            let (netlist, compiled) = compile(top_mod, *num_mods);
+            let report = check_equivalence(&netlist, &compiled).unwrap();
+            assert!(report.equivalent(), "{}: {:#?}", top_mod, report);
+            assert!(report.outputs > 0);
+            if *top_mod != "Adder" {
+                assert!(report.registers > 0, "{}", top_mod);
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_gates_are_compared() {
+        let (netlist, compiled) = compile("GateRegs", 2);
+        let report = check_equivalence(&netlist, &compiled).unwrap();
+        assert!(report.equivalent(), "{:#?}", report);
+
+        // Every Gate of the netlist is compared against its compiled register
+        assert!(report.unmatched_registers.is_empty(), "{:?}", report.unmatched_registers);
+        let gates = netlist.graph.node_weights()
+            .filter(|n| n.is() == Primitive::Gate)
+            .count();
+        assert!(gates > 0);
+        assert!(report.registers >= gates);
+    }
+
+    #[test]
+    pub fn test_unmatched_output() {
+        let (netlist, mut compiled) = compile("Adder", 1);
+
+        // Drop the signal of one compiled output
+        let mut dropped = None;
+        for (_, mmap) in compiled.emul.module_mappings.iter_mut() {
+            for (_, pmap) in mmap.proc_mappings.iter_mut() {
+                let name = pmap.signal_map.iter()
+                    .find(|(_, nmi)| pmap.instructions[nmi.info.pc as usize].opcode == Opcode::Output)
+                    .map(|(name, _)| name.clone());
+                match name {
+                    Some(name) if dropped.is_none() => {
+                        pmap.signal_map.shift_remove(&name);
+                        dropped = Some(name);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let dropped = dropped.expect("Adder is expected to have an output");
+
+        let report = check_equivalence(&netlist, &compiled).unwrap();
+        assert!(report.counterexample.is_none());
+        assert_eq!(report.unmatched_outputs, vec![dropped]);
+        assert!(!report.equivalent());
+    }
+
+    #[test]
+    pub fn test_counterexample() {
+        let (netlist, mut compiled) = compile("GCD", 3);
+        let lut_inputs = compiled.platform_cfg.lut_inputs;
+
+        // Invert a LUT that drives a register
+        let mut target = None;
+        for (m, mmap) in compiled.emul.module_mappings.iter() {
+            for (p, pmap) in mmap.proc_mappings.iter() {
+                for inst in pmap.instructions.iter() {
+                    if inst.opcode != Opcode::Latch && inst.opcode != Opcode::Gate {
+                        continue;
+                    }
+                    let op = match inst.operands.iter().find(|op| op.idx == 0) {
+                        Some(op) => op,
+                        None => continue
+                    };
+                    if op.local && pmap.instructions[op.rs as usize].opcode == Opcode::Lut {
+                        target = Some((*m, *p, op.rs as usize));
+                    }
+                }
+            }
+        }
+        let (m, p, pc) = target.expect("GCD is expected to have a register driven by a LUT");
+        let inst = compiled.emul.module_mappings.get_mut(&m).unwrap()
+            .proc_mappings.get_mut(&p).unwrap()
+            .instructions.get_mut(pc).unwrap();
+        inst.lut = !inst.lut & (u64::MAX >> (64 - (1 << lut_inputs)));
+
+        let report = check_equivalence(&netlist, &compiled).unwrap();
+        match &report.counterexample {
+            Some(cex) => {
+                assert!(!cex.mismatches.is_empty());
+                assert!(!cex.inputs.is_empty());
+            }
+            None => {
+                assert!(false, "inverted LUT at {} {} {} was not detected", m, p, pc);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod optimize_test {
+    use crate::common::config::*;
+    use crate::passes::blif_to_circuit::blif_to_circuit;
+    use crate::testing::equiv::check_equivalence;
+    use crate::testing::fixtures::*;
+    use crate::testing::try_new_circuit;
+    use test_case::test_case;
+
+    /// Compile OptNetlist running `pass` as the only netlist optimization pass.
+    /// `expected` are the `OptStats` counts (const_inputs_folded, const_luts,
+    /// buffers_absorbed, inverters_absorbed, luts_merged)
+    #[test_case(CompilerPass::ConstProp,     [5, 1, 0, 0, 0]; "const prop")]
+    #[test_case(CompilerPass::AbsorbBuffers, [0, 0, 1, 1, 0]; "absorb buffers")]
+    #[test_case(CompilerPass::Strash,        [0, 0, 0, 0, 1]; "strash")]
+    pub fn test_opt_pass(pass: CompilerPass, expected: [u32; 5]) {
+        let mut args = example_args("OptNetlist", &format!("optimize-dir-{}", pass.name()), &[
+            "--num-mods",  "1",
+            "--num-procs", "8",
+        ]);
+        let opt_passes = [CompilerPass::ConstProp, CompilerPass::AbsorbBuffers, CompilerPass::Strash];
+        args.pass_cfg.passes = CompilerPass::default_pipeline().into_iter()
+            .filter(|p| *p == pass || !opt_passes.contains(p))
+            .collect();
+
+        let compiled = try_new_circuit(&args).unwrap_or_else(|e| panic!("{}", e));
+        let opt = &compiled.opt_stats;
+        assert_eq!(
+            [opt.const_inputs_folded, opt.const_luts, opt.buffers_absorbed,
+             opt.inverters_absorbed, opt.luts_merged],
+            expected, "{:#?}", opt);
+
+        let netlist = blif_to_circuit(&args.blif_file_path).unwrap();
+        let report = check_equivalence(&netlist, &compiled).unwrap();
+        assert!(report.equivalent(), "{:#?}", report.counterexample);
+    }
+}
+
+#[cfg(test)]
+pub mod lanes_test {
+    use indexmap::IndexMap;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use crate::common::primitive::*;
+    use crate::fsim::board::Board;
+    use crate::fsim::lanes::*;
+    use crate::testing::blifsim::compare_blif_sim_to_fsim_lanes;
+    use crate::testing::fixtures::*;
+    use crate::testing::try_new_circuit;
+
+    #[test]
+    pub fn test_lanes_match_blif_sim() {
+        let srams = [("OneReadOneWritePortSRAM", 1)];
+        for (top_mod, num_mods) in EXAMPLE_DESIGNS.iter().chain(srams.iter()) {
+            compare_blif_sim_to_fsim_lanes(compile_args("lanes-dir", top_mod, *num_mods), 0)
+                .unwrap_or_else(|e| panic!("{}: {}", top_mod, e));
+        }
+    }
+
+    #[test]
+    pub fn test_lanes_match_scalar_board() {
+        let circuit = try_new_circuit(&compile_args("lanes-dir", "GCD", 2)).unwrap();
+        let fetch_decode_lat = circuit.platform_cfg.fetch_decode_lat();
+        let checked_lanes = [0, 17, LaneVec::LANES - 1];
+
+        let mut board: Board<LaneVec> = Board::from(&circuit);
+        let mut scalar: Vec<Board> = checked_lanes.iter().map(|_| Board::from(&circuit)).collect();
+
+        let mut inputs: Vec<(String, u32)> = vec![];
+        let mut signals: Vec<String> = vec![];
+        for nidx in circuit.graph.node_indices() {
+            let node = circuit.graph.node_weight(nidx).unwrap();
+            if node.is() == Primitive::Input && board.nodeindex(node.name()).is_some() {
+                inputs.push((node.name().to_string(), node.info().pc + fetch_decode_lat));
+            }
+            signals.push(node.name().to_string());
+        }
+
+        let no_stimuli_lanes: IndexMap<u32, Vec<(&str, LaneVec)>> = IndexMap::new();
+        let no_stimuli: IndexMap<u32, Vec<(&str, Bit)>> = IndexMap::new();
+        let mut rng = StdRng::seed_from_u64(0);
+        for cycle in 0..32 {
+            for step in 0..circuit.emul.host_steps {
+                for (name, _) in inputs.iter().filter(|(_, s)| *s == step) {
+                    let val: LaneVec = rng.gen();
+                    for l in 0..LaneVec::LANES {
+                        assert!(board.poke_lane(name, l, val.lane(l)).is_some());
+                    }
+                    for (i, l) in checked_lanes.iter().enumerate() {
+                        scalar[i].poke(name, val.lane(*l));
+                    }
+                }
+                board.step_with_input(step, &no_stimuli_lanes);
+                for s in scalar.iter_mut() {
+                    s.step_with_input(step, &no_stimuli);
+                }
+            }
+
+            for sig in signals.iter() {
+                for (i, l) in checked_lanes.iter().enumerate() {
+                    assert_eq!(board.peek_lane(sig, *l), scalar[i].peek(sig),
+                        "cycle {} lane {} signal {}", cycle, l, sig);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod ref_rtlsim_test {
+    use indexmap::IndexMap;
+    use std::ffi::OsStr;
+    use std::io::ErrorKind;
+    use std::path::Path;
+    use std::process::Command;
+    use crate::common::config::RefSimulator;
+    use crate::rtlsim::ref_rtlsim::*;
+    use crate::rtlsim::ref_rtlsim_testharness::*;
+    use crate::rtlsim::rtlsim_utils::get_input_stimuli;
+    use test_case::test_case;
+
+    fn adder_testbench(timing: &TestbenchTiming) -> Result<String, String> {
+        return generate_testbench(
+            "../examples/Adder.sv",
+            "Adder",
+            &get_input_stimuli("../examples/Adder.input"),
+            timing,
+            &IndexMap::new());
+    }
+
+    #[test]
+    pub fn test_first_sample_time() {
+        let timing = TestbenchTiming {
+            first_sample_time: 140,
+            ..TestbenchTiming::default()
+        };
+        let tb = adder_testbench(&timing).unwrap();
+        assert!(tb.contains("localparam FIRST_SAMPLE_TIME=140;"));
+        assert!(tb.contains("#(FIRST_SAMPLE_TIME - $time);"));
+
+        let line = format!(
+            "0 {} time_unit_ps 1000 clock_period 20 reset_cycles 4 first_sample_time 140",
+            TESTBENCH_TIMING_TAG);
+        assert_eq!(TestbenchTiming::from_line(&line), Some(timing.clone()));
+        assert_eq!(timing.cycle(139), None);
+        assert_eq!(timing.cycle(timing.sample_time(0)), Some(0));
+        assert_eq!(timing.cycle(timing.sample_time(3)), Some(3));
+    }
+
+    #[test_case(40; "during reset")]
+    #[test_case(90; "not a rising edge")]
+    pub fn test_bad_first_sample_time(first_sample_time: u64) {
+        let timing = TestbenchTiming {
+            first_sample_time: first_sample_time,
+            ..TestbenchTiming::default()
+        };
+        assert!(adder_testbench(&timing).is_err());
+    }
+
+    #[test]
+    pub fn test_parse_rtl_simulation_output() {
+        let timing_line = format!(
+            "0 {} time_unit_ps 1000 clock_period 20 reset_cycles 4 first_sample_time 80",
+            TESTBENCH_TIMING_TAG);
+        let output = format!("{}\n80 {}\n80 io_out 1\n100 io_out 0\n120 {}=\n",
+            timing_line, START_SIMULATION_TAG, END_SIMULATION_TAG);
+        let (timing, parsed) = parse_rtl_simulation_output(&output).unwrap();
+        assert_eq!(timing, TestbenchTiming::default());
+        assert!(parsed.contains("\n0 io_out 1\n1 io_out 0\n"), "{}", parsed);
+    }
+
+    #[test_case("stray line"; "stray line")]
+    #[test_case("20 io_out 1"; "sample before the first cycle")]
+    #[test_case(""; "empty line")]
+    pub fn test_corrupted_rtl_simulation_output(line: &str) {
+        let timing_line = format!(
+            "0 {} time_unit_ps 1000 clock_period 20 reset_cycles 4 first_sample_time 80",
+            TESTBENCH_TIMING_TAG);
+        let output = format!("{}\n80 {}\n{}\n120 {}=\n",
+            timing_line, START_SIMULATION_TAG, line, END_SIMULATION_TAG);
+        assert!(parse_rtl_simulation_output(&output).is_err());
+    }
+
+    #[test]
+    pub fn test_missing_testbench_timing() {
+        let output = format!("80 {}\n80 io_out 1\n120 {}=\n",
+            START_SIMULATION_TAG, END_SIMULATION_TAG);
+        assert!(parse_rtl_simulation_output(&output).is_err());
+    }
+
+    #[test_case(RefSimulator::VCS,       "vcs",       "./rtlsim_binary"; "vcs")]
+    #[test_case(RefSimulator::Verilator, "verilator", "./rtlsim_binary"; "verilator")]
+    #[test_case(RefSimulator::Icarus,    "iverilog",  "vvp"; "icarus")]
+    pub fn test_ref_sim_commands(sim: RefSimulator, compile_prog: &str, run_prog: &str) {
+        let simulator = ref_rtl_simulator(&sim);
+        let sim_dir = Path::new("ref-sim-dir");
+        let build_dir = sim_dir.join("build");
+
+        let compile = simulator.compile_cmd(sim_dir, "Adder-testbench.sv", "Adder.sv");
+        let compile_args: Vec<&OsStr> = compile.get_args().collect();
+        assert_eq!(compile.get_program(), OsStr::new(compile_prog));
+        assert_eq!(compile.get_current_dir(), Some(sim_dir));
+        assert!(compile_args.contains(&OsStr::new("Adder-testbench.sv")));
+        assert!(compile_args.contains(&OsStr::new("Adder.sv")));
+        assert!(compile_args.iter().any(|a| a.to_string_lossy().ends_with(REF_RTLSIM_BINARY)));
+
+        let run = simulator.run_cmd(&build_dir);
+        assert_eq!(run.get_program(), OsStr::new(run_prog));
+        assert_eq!(run.get_current_dir(), Some(build_dir.as_path()));
+        assert!(run.get_program().to_string_lossy().ends_with(REF_RTLSIM_BINARY) ||
+                run.get_args().any(|a| a == OsStr::new(REF_RTLSIM_BINARY)));
+    }
+
+    #[test]
+    pub fn test_default_ref_sim_is_free() {
+        assert_eq!(RefSimulator::default(), RefSimulator::Icarus);
+    }
+
+    struct MissingSimulator;
+
+    impl RefRTLSimulator for MissingSimulator {
+        fn name(self: &Self) -> &'static str {
+            "missing"
+        }
+
+        fn compile_cmd(self: &Self, sim_dir: &Path, _tb_file: &str, _design_file: &str) -> Command {
+            let mut cmd = Command::new("bee-missing-ref-simulator");
+            cmd.current_dir(sim_dir);
+            return cmd;
+        }
+
+        fn run_cmd(self: &Self, build_dir: &Path) -> Command {
+            let mut cmd = Command::new("bee-missing-ref-simulator");
+            cmd.current_dir(build_dir);
+            return cmd;
+        }
+    }
+
+    #[test]
+    pub fn test_missing_ref_sim() {
+        match MissingSimulator.compile(Path::new("."), "tb.sv", "design.sv") {
+            Ok(_) => {
+                assert!(false, "compiling with a missing simulator should fail");
+            }
+            Err(e) => {
+                assert_eq!(e.kind(), ErrorKind::NotFound);
+                assert!(e.to_string().contains("bee-missing-ref-simulator"), "{}", e);
+                assert!(e.to_string().contains("--ref-sim"), "{}", e);
+            }
+        }
+    }
+}
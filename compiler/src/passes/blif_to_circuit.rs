@@ -21,7 +21,7 @@ fn extract_index(input: &str) -> Option<u32> {
 /// Checks if the parent node is a SRAM and sets the SignalType accordingly
 fn signal_type(src: &NodeIndex, circuit: &Circuit, wire: &str) -> SignalType {
     let node = circuit.graph.node_weight(*src).unwrap();
-    if let CircuitPrimitive::SRAMNode { name:_, conns } = &node.prim {
+    if let CircuitPrimitive::SRAMNode { name:_, conns, .. } = &node.prim {
         // Parent node is a SRAM
         // Signal type should be a SRAMRdData as it is the only output port from SRAM nodes
         let wire_to_port: IndexMap<String, String> =
@@ -52,6 +52,103 @@ fn hash_subckt(name: &String, conns: &IndexMap<String, String>) -> String {
     return ret;
 }
 
+/// Splits a SRAM blackbox port name such as `R1_addr[3]` into its target
+/// SRAM port (`R1`) and the field (`addr`)
+fn split_sram_port(port: &str) -> Option<(&str, &str)> {
+    let (prefix, field) = port.split_once('_')?;
+    let digits = prefix.trim_start_matches(|c| c == 'R' || c == 'W');
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let field = field.split('[').next().unwrap();
+    return Some((prefix, field));
+}
+
+/// Returns true if `port` is a read data port of a SRAM blackbox
+fn is_sram_rd_data_port(port: &str) -> bool {
+    match split_sram_port(port) {
+        Some((prefix, field)) => {
+            if prefix.starts_with("RW") {
+                field == "rdata"
+            } else if prefix.starts_with('R') {
+                field == "data"
+            } else {
+                false
+            }
+        }
+        None => false
+    }
+}
+
+/// Returns the `SignalType` of a SRAM blackbox input port
+fn sram_port_signal(port: &str, wire: &str) -> Option<SignalType> {
+    let (prefix, field) = split_sram_port(port)?;
+    let name = wire.to_string();
+    let idx = extract_index(port);
+    if prefix.starts_with("RW") {
+        match field {
+            "addr"  => Some(SignalType::SRAMRdWrAddr { name: name, idx: idx.unwrap() }),
+            "en"    => Some(SignalType::SRAMRdWrEn   { name: name }),
+            "wmode" => Some(SignalType::SRAMRdWrMode { name: name }),
+            "wmask" => Some(SignalType::SRAMWrMask   { name: name, idx: idx.unwrap() }),
+            "wdata" => Some(SignalType::SRAMWrData   { name: name, idx: idx.unwrap() }),
+            _ => None
+        }
+    } else if prefix.starts_with('R') {
+        match field {
+            "addr" => Some(SignalType::SRAMRdAddr { name: name, idx: idx.unwrap() }),
+            "en"   => Some(SignalType::SRAMRdEn   { name: name }),
+            _ => None
+        }
+    } else {
+        match field {
+            "addr" => Some(SignalType::SRAMWrAddr { name: name, idx: idx.unwrap() }),
+            "en"   => Some(SignalType::SRAMWrEn   { name: name }),
+            "mask" => Some(SignalType::SRAMWrMask { name: name, idx: idx.unwrap() }),
+            "data" => Some(SignalType::SRAMWrData { name: name, idx: idx.unwrap() }),
+            _ => None
+        }
+    }
+}
+
+/// Groups the connections of a SRAM blackbox by target SRAM port.
+/// 1R1W (`R0` & `W0`) and single port (`RW0`) SRAMs map directly to a
+/// `SRAMProcessor` and are kept as a single group with an empty port name.
+/// Other multi-ported SRAMs get a group per port.
+fn sram_port_groups(conns: &IndexMap<String, String>) -> IndexMap<String, IndexMap<String, String>> {
+    let mut groups: IndexMap<String, IndexMap<String, String>> = IndexMap::new();
+    for (port, wire) in conns.iter() {
+        let prefix = match split_sram_port(port) {
+            Some((prefix, _)) => prefix.to_string(),
+            None => String::new()
+        };
+        if !groups.contains_key(&prefix) {
+            groups.insert(prefix.clone(), IndexMap::new());
+        }
+        groups.get_mut(&prefix).unwrap().insert(port.clone(), wire.clone());
+    }
+
+    let single = groups.keys().all(|p| p == "R0" || p == "W0" || p.is_empty()) ||
+                 groups.keys().all(|p| p == "RW0" || p.is_empty());
+    if single {
+        let mut ret = IndexMap::new();
+        ret.insert(String::new(), conns.clone());
+        return ret;
+    }
+
+    // Connections that don't belong to a port (e.g. shared clocks) go to every port
+    match groups.swap_remove("") {
+        Some(shared) => {
+            for (_, g) in groups.iter_mut() {
+                g.extend(shared.clone());
+            }
+        }
+        None => { }
+    }
+    groups.sort_keys();
+    return groups;
+}
+
 // nidx  wire
 //   o  ------> o ----> o ---->
 fn module_to_circuit(module: &ParsedPrimitive, circuit: &mut Circuit) {
@@ -76,6 +173,36 @@ fn module_to_circuit(module: &ParsedPrimitive, circuit: &mut Circuit) {
 
         // Add nodes to graph
         for e in elems.iter() {
+            // SRAM blackboxes can map to multiple nodes (see `sram_port_groups`)
+            if let ParsedPrimitive::Subckt { name, conns } = e {
+                let uname = hash_subckt(name, conns);
+                for (port, pconns) in sram_port_groups(conns).iter() {
+                    let sram = if port.is_empty() {
+                        CircuitPrimitive::SRAMNode {
+                            name: name.clone(),
+                            conns: pconns.clone(),
+                            port: String::new(),
+                            region: SRAMRegion::new(name)
+                        }
+                    } else {
+                        CircuitPrimitive::SRAMNode {
+                            name: uname.clone(),
+                            conns: pconns.clone(),
+                            port: port.clone(),
+                            region: SRAMRegion::new(name)
+                        }
+                    };
+                    let nidx = circuit.graph.add_node(HWNode::new(sram));
+                    sram_to_nodeidx.insert(format!("{}-{}", uname, port), nidx);
+                    for (p, wire) in pconns.iter() {
+                        if is_sram_rd_data_port(p) {
+                            net_to_nodeidx.insert(wire.to_string(), nidx);
+                        }
+                    }
+                }
+                continue;
+            }
+
             let nidx = circuit.graph.add_node(HWNode::new(CircuitPrimitive::from(e)));
             match e {
                 ParsedPrimitive::Lut { inputs:_, output, .. } => {
@@ -87,15 +214,6 @@ fn module_to_circuit(module: &ParsedPrimitive, circuit: &mut Circuit) {
                 ParsedPrimitive::Latch { input:_, output, .. } => {
                     net_to_nodeidx.insert(output.to_string(), nidx);
                 }
-                ParsedPrimitive::Subckt { name, conns } => {
-                    sram_to_nodeidx.insert(hash_subckt(name, conns), nidx);
-                    for (port, wire) in conns.iter() {
-                        if port.contains("R0_data") ||
-                           port.contains("RW0_rdata") {
-                            net_to_nodeidx.insert(wire.to_string(), nidx);
-                        }
-                    }
-                }
                 _ => {
                     assert!(false, "Unrecoginzed primitive: {:?}", e);
                 }
@@ -119,7 +237,7 @@ fn module_to_circuit(module: &ParsedPrimitive, circuit: &mut Circuit) {
                         }
                     }
                 }
-                ParsedPrimitive::Gate { c:_, d, q, r:_, e } => {
+                ParsedPrimitive::Gate { c:_, d, q, r, e } => {
                     let d_idx = net_to_nodeidx.get(d).unwrap();
                     let q_idx = net_to_nodeidx.get(q).unwrap();
                     let sig = signal_type(d_idx, circuit, d);
@@ -133,6 +251,15 @@ fn module_to_circuit(module: &ParsedPrimitive, circuit: &mut Circuit) {
                         }
                         None => (),
                     };
+
+                    match &r {
+                        Some(r) => {
+                            let r_idx = net_to_nodeidx.get(r).unwrap();
+                            let r_sig = signal_type(r_idx, circuit, r);
+                            circuit.graph.add_edge(*r_idx, *q_idx, HWEdge::new(r_sig));
+                        }
+                        None => (),
+                    };
                 }
                 ParsedPrimitive::Latch { input, output, .. } => {
                     let d_idx = net_to_nodeidx.get(input).unwrap();
@@ -144,63 +271,17 @@ fn module_to_circuit(module: &ParsedPrimitive, circuit: &mut Circuit) {
                 }
                 ParsedPrimitive::Subckt { name, conns } => {
                     let uname = hash_subckt(name, conns);
-                    let sram_idx = sram_to_nodeidx.get(&uname).unwrap();
-                    for (port, wire) in conns.iter() {
-                        let bidx = extract_index(port);
-                        let p_idx = net_to_nodeidx.get(wire).unwrap();
-
-                        let sig = if port.contains("RW0_addr") {
-                            Some(SignalType::SRAMRdWrAddr {
-                                name: wire.to_string(), idx: bidx.unwrap()
-                            })
-                        } else if port.contains("RW0_en") {
-                            Some(SignalType::SRAMRdWrEn {
-                                name: wire.to_string()
-                            })
-                        } else if port.contains("RW0_wmode") {
-                            Some(SignalType::SRAMRdWrMode {
-                                name: wire.to_string()
-                            })
-                        } else if port.contains("RW0_wmask") {
-                            Some(SignalType::SRAMWrMask {
-                                name: wire.to_string(), idx: bidx.unwrap()
-                            })
-                        } else if port.contains("RW0_wdata") {
-                            Some(SignalType::SRAMWrData {
-                                name: wire.to_string(), idx: bidx.unwrap()
-                            })
-                        } else if port.contains("R0_addr") {
-                            Some(SignalType::SRAMRdAddr {
-                                name: wire.to_string(), idx: bidx.unwrap()
-                            })
-                        } else if port.contains("R0_en") {
-                            Some(SignalType::SRAMRdEn {
-                                name: wire.to_string()
-                            })
-                        } else if port.contains("W0_en") {
-                            Some(SignalType::SRAMWrEn {
-                                name: wire.to_string()
-                            })
-                        } else if port.contains("W0_mask") {
-                            Some(SignalType::SRAMWrMask {
-                                name: wire.to_string(), idx: bidx.unwrap()
-                            })
-                        } else if port.contains("W0_addr") {
-                            Some(SignalType::SRAMWrAddr {
-                                name: wire.to_string(), idx: bidx.unwrap()
-                            })
-                        } else if port.contains("W0_data") {
-                            Some(SignalType::SRAMWrData {
-                                name: wire.to_string(), idx: bidx.unwrap()
-                            })
-                        } else {
-                            None
-                        };
-                        match sig {
-                            Some(s) => {
-                                circuit.graph.add_edge(*p_idx, *sram_idx, HWEdge::new(s));
+                    for (sram_port, pconns) in sram_port_groups(conns).iter() {
+                        let sram_idx = sram_to_nodeidx
+                            .get(&format!("{}-{}", uname, sram_port)).unwrap();
+                        for (port, wire) in pconns.iter() {
+                            match sram_port_signal(port, wire) {
+                                Some(s) => {
+                                    let p_idx = net_to_nodeidx.get(wire).unwrap();
+                                    circuit.graph.add_edge(*p_idx, *sram_idx, HWEdge::new(s));
+                                }
+                                None => {}
                             }
-                            None => {}
                         }
                     }
                 }
@@ -225,14 +306,17 @@ pub fn blif_to_circuit(input_file_path: &str) -> Result<Circuit, String> {
     let blif_file = parse_blif_file(input_file_path);
     match blif_file {
         Ok(modules) => {
-            assert!(modules.len() == 1, "Can only ingest flattened design for now");
+            if modules.len() != 1 {
+                return Err(format!("Can only ingest flattened design for now, got {} modules",
+                    modules.len()));
+            }
             for module in modules.iter() {
                 match module {
                     ParsedPrimitive::Module { .. } => {
                         module_to_circuit(module, &mut circuit);
                     }
                     _ => {
-                        assert!(false, "None module found");
+                        return Err("None module found".to_string());
                     }
                 }
             }
@@ -44,10 +44,18 @@ pub fn check_connectivity(circuit: &Circuit) {
                     }
                 }
             }
-            Primitive::Latch |
+            Primitive::Latch => {
+                assert!(pcnt == 1, "Latch should have 1 input, got {}", pcnt);
+                assert!(ccnt > 0, "Latch with no outputs: {:?}", node);
+            }
             Primitive::Gate => {
-                assert!(pcnt == 1, "Latch/Gate should have 1 input, got {}", pcnt);
-                assert!(ccnt > 0, "Latch/Gate with no outputs: {:?}", node);
+                // The current value of a Gate with an enable is not an edge
+                let mut npins = node.prim.gate_operands().len();
+                if let CircuitPrimitive::Gate { e: Some(_), .. } = &node.prim {
+                    npins -= 1;
+                }
+                assert!(pcnt == npins, "Gate should have {} inputs, got {}", npins, pcnt);
+                assert!(ccnt > 0, "Gate with no outputs: {:?}", node);
             }
             Primitive::SRAMRdEn         |
                 Primitive::SRAMWrEn     |
@@ -0,0 +1,16 @@
+use crate::common::{
+    circuit::Circuit,
+    error::CompileError,
+    verify::verify_program
+};
+
+/// # check_program
+/// - Runs `verify_program` on the output of `map_instructions`
+/// - Fails with `CompileError::InvalidProgram` listing every violation
+pub fn check_program(circuit: &Circuit) -> Result<(), CompileError> {
+    let violations = verify_program(&circuit.emul, &circuit.platform_cfg);
+    if violations.is_empty() {
+        return Ok(());
+    }
+    return Err(CompileError::InvalidProgram { violations: violations });
+}
@@ -0,0 +1,255 @@
+use crate::common::{
+    circuit::Circuit,
+    clocks::clock_enable_name,
+    primitive::*,
+    hwgraph::*,
+};
+use indexmap::IndexMap;
+use petgraph::{
+    graph::{EdgeIndex, NodeIndex},
+    visit::EdgeRef,
+    Direction::{Incoming, Outgoing}
+};
+
+/// Incoming edge of `nidx` that carries `net`
+fn find_input_edge(circuit: &Circuit, nidx: NodeIndex, net: &str) -> EdgeIndex {
+    for pedge in circuit.graph.edges_directed(nidx, Incoming) {
+        if pedge.weight().signal.name() == net {
+            return pedge.id();
+        }
+    }
+    assert!(false, "Cannot find the edge driving {} of {:?}", net,
+        circuit.graph.node_weight(nidx).unwrap());
+    return EdgeIndex::end();
+}
+
+/// Insert `lut` in between the `net` input of `nidx` and its driver.
+/// `lut` reads `net`, the clock enable and `extra` (in that order)
+fn insert_enable_lut(
+    circuit: &mut Circuit,
+    nidx: NodeIndex,
+    net: &str,
+    en_idx: NodeIndex,
+    extra: Vec<NodeIndex>,
+    lut: CircuitPrimitive
+) {
+    let eidx = find_input_edge(circuit, nidx, net);
+    let lut_node = HWNode::new(lut);
+    let lut_name = lut_node.name().to_string();
+    let lut_idx = circuit.graph.add_node(lut_node);
+
+    let (src, _) = circuit.graph.edge_endpoints(eidx).unwrap();
+    let edge = circuit.graph.edge_weight(eidx).unwrap().clone();
+    circuit.graph.add_edge(src, lut_idx, edge);
+    for pidx in [en_idx].iter().chain(extra.iter()) {
+        let pname = circuit.graph.node_weight(*pidx).unwrap().name().to_string();
+        circuit.graph.add_edge(*pidx, lut_idx, HWEdge::new(SignalType::Wire { name: pname }));
+    }
+    circuit.graph.add_edge(lut_idx, nidx, HWEdge::new(SignalType::Wire { name: lut_name }));
+    circuit.graph.remove_edge(eidx);
+}
+
+/// Gate `net` of register `q` with the clock enable `en`
+fn and_with_enable(
+    circuit: &mut Circuit,
+    nidx: NodeIndex,
+    net: &str,
+    q: &str,
+    en: &str,
+    en_idx: NodeIndex
+) -> String {
+    let and_name = format!("CLK-EN-{}-{}", net, q);
+    let and = CircuitPrimitive::Lut {
+        inputs: vec![ net.to_string(), en.to_string() ],
+        output: and_name.clone(),
+        table: vec![ vec![1, 1] ]
+    };
+    insert_enable_lut(circuit, nidx, net, en_idx, vec![], and);
+    return and_name;
+}
+
+/// Gate the enables of SRAM `nidx` (write, read & single port enables)
+/// with the clock enable `en` so that the SRAM is only accessed on the
+/// edges of its clock
+fn gate_sram_enables(circuit: &mut Circuit, nidx: NodeIndex, en: &str, en_idx: NodeIndex) {
+    let mut enables: Vec<(EdgeIndex, SignalType)> = vec![];
+    for pedge in circuit.graph.edges_directed(nidx, Incoming) {
+        match &pedge.weight().signal {
+            SignalType::SRAMWrEn   { .. } |
+            SignalType::SRAMRdEn   { .. } |
+            SignalType::SRAMRdWrEn { .. } => {
+                enables.push((pedge.id(), pedge.weight().signal.clone()));
+            }
+            _ => { }
+        }
+    }
+    let sram = circuit.graph.node_weight(nidx).unwrap().name().to_string();
+    for (eidx, signal) in enables.iter() {
+        let net = signal.name().to_string();
+        let and_name = format!("CLK-EN-{}-{}", net, sram);
+        let and_idx = circuit.graph.add_node(HWNode::new(CircuitPrimitive::Lut {
+            inputs: vec![ net.clone(), en.to_string() ],
+            output: and_name.clone(),
+            table: vec![ vec![1, 1] ]
+        }));
+        // The enable can come straight out of another SRAM
+        let (src, _) = circuit.graph.edge_endpoints(*eidx).unwrap();
+        let mut src_signal = SignalType::Wire { name: net.clone() };
+        for cedge in circuit.graph.edges_directed(src, Outgoing) {
+            match &cedge.weight().signal {
+                SignalType::SRAMRdData { name, .. } if *name == net => {
+                    src_signal = cedge.weight().signal.clone();
+                }
+                _ => { }
+            }
+        }
+        circuit.graph.add_edge(src, and_idx, HWEdge::new(src_signal));
+        circuit.graph.add_edge(en_idx, and_idx,
+            HWEdge::new(SignalType::Wire { name: en.to_string() }));
+        let gated = match signal {
+            SignalType::SRAMWrEn { .. } => SignalType::SRAMWrEn   { name: and_name },
+            SignalType::SRAMRdEn { .. } => SignalType::SRAMRdEn   { name: and_name },
+            _                           => SignalType::SRAMRdWrEn { name: and_name }
+        };
+        circuit.graph.add_edge(and_idx, nidx, HWEdge::new(gated));
+        circuit.graph.remove_edge(*eidx);
+    }
+}
+
+/// Clock net of a SRAM blackbox port
+fn sram_clock(conns: &IndexMap<String, String>) -> Option<&String> {
+    for (port, wire) in conns.iter() {
+        if port.ends_with("_clk") {
+            return Some(wire);
+        }
+    }
+    return None;
+}
+
+/// # `clock_domains`
+/// - Registers are clocked by the target base clock, i.e. every register is
+///   updated once per emulated cycle
+/// - Registers of a domain declared slower than the base clock (`--clocks`)
+///   are only updated in the base cycles that end with an edge of their clock.
+///   This is done by adding a clock enable input per slow domain and feeding
+///   it to the enable (and synchronous reset) of the domain's registers
+/// - SRAMs of a slow domain have their read & write enables gated the same way
+/// - Registers of undeclared clocks are in the base domain
+pub fn clock_domains(circuit: &mut Circuit) {
+    let clocks = circuit.compiler_cfg.clocks.clone();
+    let mut domain_regs: IndexMap<String, Vec<NodeIndex>> = IndexMap::new();
+    let mut domain_srams: IndexMap<String, Vec<NodeIndex>> = IndexMap::new();
+    let mut base_regs = 0;
+    let mut base_srams = 0;
+
+    for nidx in circuit.graph.node_indices() {
+        let node = circuit.graph.node_weight(nidx).unwrap();
+        let (clock, is_sram) = match &node.prim {
+            CircuitPrimitive::Latch { control, .. } => (control, false),
+            CircuitPrimitive::Gate  { c, .. }       => (c, false),
+            CircuitPrimitive::SRAMNode { conns, .. } => {
+                match sram_clock(conns) {
+                    Some(clock) => (clock, true),
+                    None => { continue; }
+                }
+            }
+            _ => { continue; }
+        };
+        match clocks.get(clock) {
+            Some(period) if *period > 1 => {
+                if !domain_regs.contains_key(clock) {
+                    domain_regs.insert(clock.clone(), vec![]);
+                    domain_srams.insert(clock.clone(), vec![]);
+                }
+                if is_sram {
+                    domain_srams.get_mut(clock).unwrap().push(nidx);
+                } else {
+                    domain_regs.get_mut(clock).unwrap().push(nidx);
+                }
+            }
+            _ => {
+                if is_sram {
+                    base_srams += 1;
+                } else {
+                    base_regs += 1;
+                }
+            }
+        }
+    }
+
+    println!("Base clock domain: {} registers, {} SRAMs", base_regs, base_srams);
+    for (clock, regs) in domain_regs.iter() {
+        let srams = &domain_srams[clock];
+        println!("Clock domain {} (period {}): {} registers, {} SRAMs",
+            clock, clocks[clock], regs.len(), srams.len());
+
+        let en = clock_enable_name(clock);
+        let en_idx = circuit.graph.add_node(
+            HWNode::new(CircuitPrimitive::Input { name: en.clone() }));
+
+        for nidx in srams.iter() {
+            gate_sram_enables(circuit, *nidx, &en, en_idx);
+        }
+
+        for nidx in regs.iter() {
+            let prim = circuit.graph.node_weight(*nidx).unwrap().prim.clone();
+            let init_one = prim.init_value() == Some(1);
+            match prim {
+                CircuitPrimitive::Latch { input, output, control, init } => {
+                    if init_one {
+                        // Keep the Latch so that it starts at one: hold the
+                        // current value unless the clock fires
+                        let mux_name = format!("CLK-EN-{}", output);
+                        let mux = CircuitPrimitive::Lut {
+                            inputs: vec![ input.clone(), en.clone(), output.clone() ],
+                            output: mux_name.clone(),
+                            table: vec![
+                                vec![1, 1, 0],
+                                vec![1, 1, 1],
+                                vec![0, 0, 1],
+                                vec![1, 0, 1]
+                            ]
+                        };
+                        insert_enable_lut(circuit, *nidx, &input, en_idx, vec![*nidx], mux);
+                        let node = circuit.graph.node_weight_mut(*nidx).unwrap();
+                        node.prim = CircuitPrimitive::Latch {
+                            input: mux_name,
+                            output: output,
+                            control: control,
+                            init: init
+                        };
+                    } else {
+                        circuit.graph.add_edge(en_idx, *nidx,
+                            HWEdge::new(SignalType::Wire { name: en.clone() }));
+                        let node = circuit.graph.node_weight_mut(*nidx).unwrap();
+                        node.prim = CircuitPrimitive::Gate {
+                            c: control,
+                            d: input,
+                            q: output,
+                            r: None,
+                            e: Some(en.clone())
+                        };
+                    }
+                }
+                CircuitPrimitive::Gate { c, d, q, r, e } => {
+                    // The synchronous reset should also wait for the clock edge
+                    let r = match r {
+                        Some(r) => Some(and_with_enable(circuit, *nidx, &r, &q, &en, en_idx)),
+                        None => None
+                    };
+                    let e = match e {
+                        Some(e) => and_with_enable(circuit, *nidx, &e, &q, &en, en_idx),
+                        None => {
+                            circuit.graph.add_edge(en_idx, *nidx,
+                                HWEdge::new(SignalType::Wire { name: en.clone() }));
+                            en.clone()
+                        }
+                    };
+                    let node = circuit.graph.node_weight_mut(*nidx).unwrap();
+                    node.prim = CircuitPrimitive::Gate { c: c, d: d, q: q, r: r, e: Some(e) };
+                }
+                _ => {}
+            }
+        }
+    }
+}
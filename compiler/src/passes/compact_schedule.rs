@@ -0,0 +1,180 @@
+use crate::common::{
+    circuit::Circuit,
+    primitive::*,
+    network::*,
+};
+use crate::passes::inst_schedule::{
+    check_schedule, host_steps, is_timing_source, route_ports, PortSlot
+};
+use indexmap::IndexMap;
+use petgraph::{
+    graph::NodeIndex,
+    visit::EdgeRef,
+    Direction::{Incoming, Outgoing}
+};
+use std::cmp::max;
+
+/// Maximum number of sweeps over the schedule
+const COMPACT_MAX_SWEEPS: u32 = 8;
+
+/// Instruction slots & network ports reserved by each node
+#[derive(Debug, Default)]
+struct Reservations {
+    slots: IndexMap<(Coordinate, u32), NodeIndex>,
+    ports: IndexMap<PortSlot, NodeIndex>
+}
+
+impl Reservations {
+    fn node_ports(circuit: &Circuit, nidx: NodeIndex, pc: u32) -> Vec<PortSlot> {
+        let mut ret = vec![];
+        for cedge in circuit.graph.edges_directed(nidx, Outgoing) {
+            match &cedge.weight().route {
+                Some(route) => {
+                    ret.append(&mut route_ports(route, pc, &circuit.platform_cfg));
+                }
+                None => {}
+            }
+        }
+        return ret;
+    }
+
+    fn new(circuit: &Circuit) -> Self {
+        let mut ret = Reservations::default();
+        for nidx in circuit.graph.node_indices() {
+            ret.reserve(circuit, nidx);
+        }
+        return ret;
+    }
+
+    fn reserve(self: &mut Self, circuit: &Circuit, nidx: NodeIndex) {
+        let info = circuit.graph.node_weight(nidx).unwrap().info();
+        self.slots.insert((info.coord, info.pc), nidx);
+        for p in Reservations::node_ports(circuit, nidx, info.pc) {
+            self.ports.insert(p, nidx);
+        }
+    }
+
+    fn release(self: &mut Self, circuit: &Circuit, nidx: NodeIndex) {
+        let info = circuit.graph.node_weight(nidx).unwrap().info();
+        self.slots.swap_remove(&(info.coord, info.pc));
+        for p in Reservations::node_ports(circuit, nidx, info.pc) {
+            match self.ports.get(&p) {
+                Some(owner) if *owner == nidx => { self.ports.swap_remove(&p); }
+                _ => {}
+            }
+        }
+    }
+
+    /// `nidx` can be executed at `pc` without contending with other nodes
+    fn available(self: &Self, circuit: &Circuit, nidx: NodeIndex, pc: u32) -> bool {
+        let coord = circuit.graph.node_weight(nidx).unwrap().info().coord;
+        if self.slots.contains_key(&(coord, pc)) {
+            return false;
+        }
+        for p in Reservations::node_ports(circuit, nidx, pc) {
+            match self.ports.get(&p) {
+                Some(owner) if *owner != nidx => { return false; }
+                _ => {}
+            }
+        }
+        return true;
+    }
+}
+
+/// Earliest pc at which `nidx` can be executed without violating the
+/// scheduling constraints of `inst_schedule::schedule_candidates_at_pc`
+fn earliest_pc(circuit: &Circuit, nidx: NodeIndex) -> u32 {
+    let pcfg = &circuit.platform_cfg;
+    let node = circuit.graph.node_weight(nidx).unwrap();
+    let mut lb = 0;
+
+    // Inputs have to arrive
+    if !is_timing_source(node.is()) {
+        for pedge in circuit.graph.edges_directed(nidx, Incoming) {
+            let parent = circuit.graph.node_weight(pedge.source()).unwrap();
+            match &pedge.weight().route {
+                Some(route) => {
+                    lb = max(lb, parent.info().pc + pcfg.nw_route_dep_lat(route));
+                }
+                None => {}
+            }
+        }
+    }
+
+    // SRAM read data has to be valid
+    if node.is() == Primitive::SRAMRdData {
+        let nslots = circuit.emul.module_mappings
+            .get(&node.info().coord.module).unwrap()
+            .sram_mapping.mems.len() as u32;
+        lb = max(lb, pcfg.sram_slot_rd_step(nslots, node.info().sram_slot));
+    }
+
+    // The produced bit cannot override a FF input before it is read
+    // (`overrides_ff_input` & `child_ff_scheduled`)
+    for cidx in circuit.graph.neighbors_directed(nidx, Outgoing) {
+        let cnode = circuit.graph.node_weight(cidx).unwrap();
+        if cnode.is() == Primitive::Gate     ||
+           cnode.is() == Primitive::Latch    ||
+           cnode.is() == Primitive::Input    ||
+           cnode.is() == Primitive::ConstLut ||
+           cnode.is() == Primitive::SRAMRdData {
+            lb = max(lb, cnode.info().pc + 1);
+        }
+    }
+    return lb;
+}
+
+/// # compact_schedule
+/// - Post pass of `schedule_instructions` that fills in the idle slots of the
+///   list schedule, which tends to leave a long tail of nearly empty PCs
+/// - Visits the nodes in the order of their pc and moves each of them to the
+///   earliest pc where
+///     - its inputs arrived and its outputs don't override FF inputs
+///     - its processor is idle
+///     - the network ports along its routes (kept as is) are not reserved
+///       by other nodes
+/// - Repeats until nothing moves (or `COMPACT_MAX_SWEEPS`), then recomputes
+///   `host_steps`
+pub fn compact_schedule(circuit: &mut Circuit) {
+    let before = circuit.emul.host_steps;
+    let mut rsv = Reservations::new(circuit);
+    let mut moved = 0;
+
+    for sweep in 0..COMPACT_MAX_SWEEPS {
+        let mut order: Vec<NodeIndex> = circuit.graph.node_indices().collect();
+        order.sort_by_key(|nidx| (circuit.graph.node_weight(*nidx).unwrap().info().pc, *nidx));
+
+        let mut sweep_moved = 0;
+        for nidx in order.iter() {
+            let pc = circuit.graph.node_weight(*nidx).unwrap().info().pc;
+            let lb = earliest_pc(circuit, *nidx);
+            if lb >= pc {
+                continue;
+            }
+            match (lb..pc).find(|try_pc| rsv.available(circuit, *nidx, *try_pc)) {
+                Some(new_pc) => {
+                    rsv.release(circuit, *nidx);
+                    let node = circuit.graph.node_weight_mut(*nidx).unwrap();
+                    node.info_mut().pc = new_pc;
+                    rsv.reserve(circuit, *nidx);
+                    sweep_moved += 1;
+                }
+                None => {}
+            }
+        }
+        println!("compact_schedule sweep {}: moved {} nodes", sweep, sweep_moved);
+        moved += sweep_moved;
+        if sweep_moved == 0 {
+            break;
+        }
+    }
+
+    let mut end_pc = 0;
+    for nidx in circuit.graph.node_indices() {
+        end_pc = max(end_pc, circuit.graph.node_weight(nidx).unwrap().info().pc + 1);
+    }
+    circuit.emul.host_steps = host_steps(circuit, end_pc);
+    println!("compact_schedule: moved {} nodes, host_steps {} -> {}",
+        moved, before, circuit.emul.host_steps);
+    check_schedule(circuit);
+}
@@ -1,19 +1,29 @@
 use indexmap::{IndexMap, IndexSet};
 use crate::common::{
     circuit::Circuit,
+    constraints::PartitionConstraints,
+    error::CompileError,
     primitive::*,
     network::Coordinate
 };
 use petgraph::graph::NodeIndex;
 
 /// Distribute the IO nodes so that they only one input and one output
-/// IO node is assigned to each processor
-pub fn distribute_io(circuit: &mut Circuit) {
-    distribute_io_with_dir(circuit, Primitive::Input);
-    distribute_io_with_dir(circuit, Primitive::Output);
+/// IO node is assigned to each processor.
+/// IO nodes pinned by the partition constraints are only moved to the
+/// modules and processors of their rule
+pub fn distribute_io(circuit: &mut Circuit) -> Result<(), CompileError> {
+    let constraints = PartitionConstraints::from_config(&circuit.compiler_cfg)?;
+    distribute_io_with_dir(circuit, Primitive::Input,  &constraints)?;
+    distribute_io_with_dir(circuit, Primitive::Output, &constraints)?;
+    return Ok(());
 }
 
-fn distribute_io_with_dir(circuit: &mut Circuit, direction: Primitive) {
+fn distribute_io_with_dir(
+    circuit: &mut Circuit,
+    direction: Primitive,
+    constraints: &PartitionConstraints
+) -> Result<(), CompileError> {
     let mut free_procs: IndexSet<Coordinate> = IndexSet::new();
     let pcfg = &circuit.platform_cfg;
     for m in 0..pcfg.num_mods {
@@ -39,21 +49,50 @@ fn distribute_io_with_dir(circuit: &mut Circuit, direction: Primitive) {
     }
 
     // Try reassigning
-    for (_, nodes) in io_mapping.iter() {
+    for (coord, nodes) in io_mapping.iter() {
         if nodes.len() == 1 {
             continue;
         }
-        assert!(nodes.len() - 1 <= free_procs.len(),
-            "Not enough free processor for IO {:?}", direction);
+        let names: Vec<String> = nodes.iter()
+            .map(|nidx| circuit.graph.node_weight(*nidx).unwrap().name().to_string())
+            .collect();
+        let not_enough_procs = CompileError::NotEnoughProcsForIO {
+            direction: direction,
+            coord: *coord,
+            nodes: names.clone(),
+            free_procs: free_procs.len()
+        };
+        if nodes.len() - 1 > free_procs.len() {
+            return Err(not_enough_procs);
+        }
+
+        // Pinned nodes stay in place before the ones that can go anywhere
+        let mut order: Vec<usize> = (0..nodes.len()).collect();
+        order.sort_by_key(|i| constraints.rule_of(&names[*i]).is_none());
 
-        for (i, nidx) in nodes.iter().enumerate() {
+        for (i, ni) in order.iter().enumerate() {
             // Skip the first node
             if i == 0 {
                 continue;
             }
-            let free = free_procs.pop().unwrap();
-            let info = circuit.graph.node_weight_mut(*nidx).unwrap().info_mut();
+            // Stay in the same module if possible so that the IO node is
+            // kept close to the logic it was partitioned with
+            let name = &names[*ni];
+            let same_module = free_procs.iter()
+                .position(|c| c.module == coord.module && constraints.allows(name, c));
+            let pos = match same_module {
+                Some(pos) => pos,
+                None => {
+                    match free_procs.iter().position(|c| constraints.allows(name, c)) {
+                        Some(pos) => pos,
+                        None => { return Err(not_enough_procs); }
+                    }
+                }
+            };
+            let free = free_procs.swap_remove_index(pos).unwrap();
+            let info = circuit.graph.node_weight_mut(nodes[*ni]).unwrap().info_mut();
             info.coord = free;
         }
     }
+    return Ok(());
 }
@@ -1,7 +1,9 @@
 use crate::common::{
     circuit::Circuit,
     primitive::*,
-    hwgraph::NodeMapInfo,
+    hwgraph::{HWNode, NodeMapInfo},
+    config::PlatformConfig,
+    error::CompileError,
     mapping::*,
     instruction::*,
     network::*
@@ -13,21 +15,50 @@ use petgraph::{
     Direction::{Incoming, Outgoing}
 };
 
+/// Encode the rows of a LUT `table` that evaluate to one into the LUT bits
+/// of an instruction. Unused operands are don't cares.
+fn lut_table_bits(pcfg: &PlatformConfig, node: &HWNode, table_vec: &Vec<Vec<u8>>) -> u64 {
+    let mut table: u64 = 0;
+    let mut ops: u32 = 0;
+    for entry in table_vec.iter() {
+        let mut x = 0;
+        ops = entry.len() as u32;
+        for (i, e) in entry.iter().enumerate() {
+            x = x + (e << i);
+        }
+        table = table | (1 << x);
+        assert!(x < 64,
+            "Can support up to 6 operands with u64, node {} {:?} {:?}",
+            node.name(), node.is(), node.info());
+    }
+    let mut table_repeated: u64 = table;
+    let nops = pcfg.lut_inputs - ops;
+    for i in 0..(1 << nops) {
+        table_repeated |= table << ((1 << ops) * i);
+    }
+    return table_repeated;
+}
+
 /// # `map_instructions`
 /// - After the instructions are scheduled, set the appropriate registers and
 /// network input values
-pub fn map_instructions(circuit: &mut Circuit) {
+pub fn map_instructions(circuit: &mut Circuit) -> Result<(), CompileError> {
     let pcfg = &circuit.platform_cfg;
 
     for (_, mmap) in circuit.emul.module_mappings.iter_mut() {
         for pi in 0..pcfg.num_procs {
             mmap.proc_mappings.insert(pi, ProcessorMapping {
                 instructions: vec![Instruction::default(); circuit.emul.host_steps as usize],
-                signal_map: IndexMap::new()
+                signal_map: IndexMap::new(),
+                ldm_init: IndexMap::new(),
+                sdm_init: IndexMap::new()
             });
         }
     }
 
+    // (module, processor, operand of the register, initial value)
+    let mut reg_inits: Vec<(u32, u32, Operand, Bit)> = vec![];
+
     for nidx in circuit.graph.node_indices() {
         let node = circuit.graph.node_weight(nidx).unwrap();
         let coord = node.info().coord;
@@ -44,27 +75,31 @@ pub fn map_instructions(circuit: &mut Circuit) {
         // build LUT table
         match &node.prim {
             // Normal LUT
-            CircuitPrimitive::Lut { inputs:_, output:_, table } => {
-                let table_vec = table.to_vec();
-                let mut table: u64 = 0;
-                let mut ops: u32 = 0;
-                for entry in table_vec.iter() {
-                    let mut x = 0;
-                    ops = entry.len() as u32;
-                    for (i, e) in entry.iter().enumerate() {
-                        x = x + (e << i);
-                    }
-                    table = table | (1 << x);
-                    assert!(x < 64,
-                        "Can support up to 6 operands with u64, node {} {:?} {:?}",
-                        node.name(), node.is(), node.info());
+            CircuitPrimitive::Lut { inputs, output:_, table } => {
+                if inputs.len() as u32 > pcfg.lut_inputs {
+                    return Err(CompileError::TooManyOperands {
+                        node: node.name().to_string(),
+                        coord: coord,
+                        operands: inputs.len() as u32,
+                        lut_inputs: pcfg.lut_inputs
+                    });
                 }
-                let mut table_repeated: u64 = table;
-                let nops = pcfg.lut_inputs - ops;
-                for i in 0..(1 << nops) {
-                    table_repeated |= table << ((1 << ops) * i);
+                node_inst.lut = lut_table_bits(pcfg, node, table);
+            }
+            // Register with an enable and/or a synchronous reset.
+            // The next value is computed by a LUT over the `gate_operands`
+            CircuitPrimitive::Gate { .. } => {
+                // `lower_gates` splits out the enable of Gates with too many operands
+                let nops = node.prim.gate_operands().len() as u32;
+                if nops > pcfg.lut_inputs {
+                    return Err(CompileError::TooManyOperands {
+                        node: node.name().to_string(),
+                        coord: coord,
+                        operands: nops,
+                        lut_inputs: pcfg.lut_inputs
+                    });
                 }
-                node_inst.lut = table_repeated;
+                node_inst.lut = lut_table_bits(pcfg, node, &node.prim.gate_table());
             }
             // Constant LUT
             CircuitPrimitive::ConstLut { val, .. } => {
@@ -79,15 +114,34 @@ pub fn map_instructions(circuit: &mut Circuit) {
         }
 
         // assign operands
+        let gate_ops = node.prim.gate_operands();
+        let mut gate_ops_used: Vec<bool> = vec![false; gate_ops.len()];
         let pedges = circuit.graph.edges_directed(nidx, Incoming);
         for pedge in pedges {
             let pnode = circuit.graph.node_weight(pedge.source()).unwrap();
 
+            let unknown_operand = CompileError::UnknownOperand {
+                node: node.name().to_string(),
+                coord: coord,
+                operand: pnode.name().to_string()
+            };
             let mut op_idx = 0;
             match &node.prim {
                 CircuitPrimitive::Lut { inputs, .. } => {
-                    let lut_inputs = inputs.to_vec();
-                    op_idx = lut_inputs.iter().position(|n| n == pnode.name()).unwrap();
+                    op_idx = match inputs.iter().position(|n| n == pnode.name()) {
+                        Some(pos) => pos,
+                        None => { return Err(unknown_operand); }
+                    };
+                }
+                CircuitPrimitive::Gate { .. } => {
+                    // The same net can drive multiple pins of a Gate
+                    let pos = (0..gate_ops.len())
+                        .position(|i| !gate_ops_used[i] && gate_ops[i] == pnode.name());
+                    op_idx = match pos {
+                        Some(pos) => pos,
+                        None => { return Err(unknown_operand); }
+                    };
+                    gate_ops_used[op_idx] = true;
                 }
                 _ => { }
             }
@@ -104,7 +158,28 @@ pub fn map_instructions(circuit: &mut Circuit) {
                 rs: pnode.info().pc + pc_offset,
                 local: use_ldm,
                 idx: op_idx as u32
-            })
+            });
+
+            // A latch outputs the bit it reads from its input's data memory
+            // slot. This slot holds the register state across target cycles
+            match node.prim.init_value() {
+                Some(init) => {
+                    reg_inits.push((coord.module, coord.proc, node_inst.operands.last().unwrap().clone(), init));
+                }
+                None => {}
+            }
+        }
+
+        // A Gate with an enable reads its current value from its own LDM slot
+        match &node.prim {
+            CircuitPrimitive::Gate { e: Some(_), .. } => {
+                node_inst.operands.push(Operand {
+                    rs: node.info().pc,
+                    local: true,
+                    idx: (gate_ops.len() - 1) as u32
+                });
+            }
+            _ => { }
         }
 
         // Node is input to the SRAM processor
@@ -128,9 +203,16 @@ pub fn map_instructions(circuit: &mut Circuit) {
             let op_bits = pcfg.index_bits();
             let max_op_bits = (op_bits * (pcfg.lut_inputs - 1)) as u64;
             let max_op_num = (1u64 << max_op_bits) - 1u64;
-            let uidx = node.prim.unique_sram_input_idx(pcfg);
-            assert!(uidx as u64 <= max_op_num,
-                "SRAM unique input idx {} > max_op_num {}", uidx, max_op_num);
+            let slot = node.info().sram_slot;
+            let uidx = node.prim.unique_sram_input_idx(pcfg, slot);
+            if uidx as u64 > max_op_num {
+                return Err(CompileError::SRAMIndexOutOfRange {
+                    node: node.name().to_string(),
+                    coord: coord,
+                    idx: uidx,
+                    max_idx: max_op_num
+                });
+            }
 
             for i in 1..pcfg.lut_inputs {
                 let sl = (i - 1) * op_bits;
@@ -143,18 +225,29 @@ pub fn map_instructions(circuit: &mut Circuit) {
             }
 
             // Set SRAM mapping
-            if node.is() == Primitive::SRAMRdWrAddr ||
-               node.is() == Primitive::SRAMRdWrMode ||
-               node.is() == Primitive::SRAMRdWrEn {
-                module_mapping.sram_mapping.port_type = SRAMPortType::SinglePortSRAM;
+            let port_type = if node.is() == Primitive::SRAMRdWrAddr ||
+                               node.is() == Primitive::SRAMRdWrMode ||
+                               node.is() == Primitive::SRAMRdWrEn {
+                SRAMPortType::SinglePortSRAM
             } else {
-                module_mapping.sram_mapping.port_type = SRAMPortType::OneRdOneWrPortSRAM;
-            }
+                SRAMPortType::OneRdOneWrPortSRAM
+            };
 
+            let sram_mapping = &mut module_mapping.sram_mapping;
+            let mem = sram_mapping.mems.get_mut(slot as usize).unwrap();
+            mem.port_type = port_type.clone();
             if node.is() == Primitive::SRAMWrMask {
-                module_mapping.sram_mapping.wmask_bits += 1;
-            } else if node.is() == Primitive::SRAMWrData {
-                module_mapping.sram_mapping.width_bits += 1;
+                mem.wmask_bits += 1;
+            }
+
+            // The hardware configuration registers describe slot 0
+            if slot == 0 {
+                sram_mapping.port_type = port_type;
+                if node.is() == Primitive::SRAMWrMask {
+                    sram_mapping.wmask_bits += 1;
+                } else if node.is() == Primitive::SRAMWrData {
+                    sram_mapping.width_bits += 1;
+                }
             }
         }
 
@@ -174,9 +267,15 @@ pub fn map_instructions(circuit: &mut Circuit) {
             let op_bits = pcfg.index_bits();
             let max_op_bits = (op_bits * (pcfg.lut_inputs - 1)) as u64;
             let max_op_num = (1u64 << max_op_bits) - 1u64;
-            let uidx = node.prim.unique_sram_output_idx(pcfg);
-            assert!(uidx as u64 <= max_op_num,
-                "SRAM unique input idx {} > max_op_num {}", uidx, max_op_num);
+            let uidx = node.prim.unique_sram_output_idx(pcfg, node.info().sram_slot);
+            if uidx as u64 > max_op_num {
+                return Err(CompileError::SRAMIndexOutOfRange {
+                    node: node.name().to_string(),
+                    coord: coord,
+                    idx: uidx,
+                    max_idx: max_op_num
+                });
+            }
 
             for i in 1..pcfg.lut_inputs {
                 let sl = (i - 1) * op_bits;
@@ -273,7 +372,7 @@ pub fn map_instructions(circuit: &mut Circuit) {
            node.is() != Primitive::SRAMRdData {
             let nodemap = NodeMapInfo {
                 info: node.info().clone(),
-                idx: nidx,
+                idx: Some(nidx),
             };
             circuit.emul
                 .module_mappings.get_mut(&coord.module).unwrap()
@@ -286,7 +385,7 @@ pub fn map_instructions(circuit: &mut Circuit) {
             if node.name().split(".").collect_vec().len() > 1 {
                 let nodemap = NodeMapInfo {
                     info: node.info().clone(),
-                    idx: nidx,
+                    idx: Some(nidx),
                 };
                 circuit.emul
                     .module_mappings.get_mut(&coord.module).unwrap()
@@ -295,4 +394,26 @@ pub fn map_instructions(circuit: &mut Circuit) {
                 }
         }
     }
+
+    for (m, p, op, init) in reg_inits.iter() {
+        let pmap = circuit.emul
+            .module_mappings.get_mut(m).unwrap()
+            .proc_mappings.get_mut(p).unwrap();
+        let dmem_init = if op.local { &mut pmap.ldm_init } else { &mut pmap.sdm_init };
+        // `split_reg_nodes` gives Latches with different initial values their own input slot
+        match dmem_init.get(&op.rs) {
+            Some(prev) if *prev != *init => {
+                return Err(CompileError::Internal {
+                    pass: "map_instructions".to_string(),
+                    msg: format!("Registers sharing the input at module {} proc {} rs {} have different initial values",
+                        m, p, op.rs)
+                });
+            }
+            Some(_) => { }
+            None => {
+                dmem_init.insert(op.rs, *init);
+            }
+        }
+    }
+    return Ok(());
 }
@@ -4,6 +4,8 @@ use crate::common::{
     network::*,
     hwgraph::*,
     config::*,
+    topology::Topology,
+    error::CompileError,
     utils::save_graph_pdf
 };
 use full_palette::{ORANGE, RED};
@@ -16,7 +18,8 @@ use petgraph::{
 };
 use fixedbitset::FixedBitSet;
 use plotters::prelude::*;
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, BinaryHeap};
+use std::cmp::Reverse;
 use std::cmp::Ordering;
 use std::cmp::max;
 use std::fmt::Debug;
@@ -383,15 +386,20 @@ fn input_arrived(
    return !unresolved_dep;
 }
 
+/// Nodes whose inputs are not dependencies within a target cycle
+pub fn is_timing_source(prim: Primitive) -> bool {
+    prim == Primitive::Input    ||
+    prim == Primitive::ConstLut ||
+    prim == Primitive::Latch    ||
+    prim == Primitive::Gate
+}
+
 /// All input bits arrived & usable from parent nodes
 fn all_inputs_arrived(circuit: &Circuit, nidx: &NodeIndex, pc: &u32, stats: &mut ScheduleStats) -> bool {
     let mut arrived = true;
     let node = circuit.graph.node_weight(*nidx).unwrap();
     let parent_edges = circuit.graph.edges_directed(*nidx, Incoming);
-    if node.is() != Primitive::Input    &&
-       node.is() != Primitive::ConstLut &&
-       node.is() != Primitive::Latch    &&
-       node.is() != Primitive::Gate {
+    if !is_timing_source(node.is()) {
         for pedge in parent_edges {
             if !input_arrived(circuit, pedge, pc, stats) {
                 arrived = false;
@@ -417,6 +425,33 @@ fn child_ff_scheduled(circuit: &Circuit, nidx: &NodeIndex) -> bool {
     return can_schedule;
 }
 
+/// A network port at a step. `out` is true for output ports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PortSlot {
+    pub out: bool,
+    pub port: u32,
+    pub pc: u32
+}
+
+/// Ports occupied when shipping a bit over `route` starting at `pc`.
+/// For each path, the output port of its source and the input port of
+/// its destination
+pub fn route_ports(route: &NetworkRoute, pc: u32, pcfg: &PlatformConfig) -> Vec<PortSlot> {
+    let mut ret = vec![];
+    let mut cur_route = NetworkRoute::new();
+    for (i, path) in route.iter().enumerate() {
+        let send_pc = if i == 0 {
+            pc + pcfg.nw_route_lat(&cur_route)
+        } else {
+            pc + pcfg.nw_route_dep_lat(&cur_route)
+        };
+        cur_route.push_back(*path);
+        ret.push(PortSlot { out: true,  port: path.src.id(pcfg), pc: send_pc });
+        ret.push(PortSlot { out: false, port: path.dst.id(pcfg), pc: pc + pcfg.nw_route_lat(&cur_route) });
+    }
+    return ret;
+}
+
 /// When shipping a bit starting at `pc`, the `route` doesn't have any
 /// contention
 fn route_usable(
@@ -426,23 +461,13 @@ fn route_usable(
     pcfg: &PlatformConfig
 )-> bool {
     let mut usable = true;
-    let mut cur_route = NetworkRoute::new();
-    for (i, path) in route.iter().enumerate() {
-        if i == 0 {
-            if nw.oports.is_busy(path.src.id(pcfg), *pc + pcfg.nw_route_lat(&cur_route)) {
-                usable = false;
-                break;
-            }
+    for p in route_ports(route, *pc, pcfg) {
+        let busy = if p.out {
+            nw.oports.is_busy(p.port, p.pc)
         } else {
-            if nw.oports.is_busy(path.src.id(pcfg), *pc + pcfg.nw_route_dep_lat(&cur_route)) {
-                usable = false;
-                break;
-            }
-        }
-
-        cur_route.push_back(*path);
-
-        if nw.iports.is_busy(path.dst.id(pcfg), pc + pcfg.nw_route_lat(&cur_route)) {
+            nw.iports.is_busy(p.port, p.pc)
+        };
+        if busy {
             usable = false;
             break;
         }
@@ -474,6 +499,94 @@ fn route_add_back(dst: &Coordinate, route: &NetworkRoute) -> NetworkRoute {
     return new_route;
 }
 
+/// Number of routes that `find_route` lets pass through each processor
+const ROUTE_SEARCH_K: u32 = 4;
+
+/// Maximum number of hops of the routes found by `find_route`
+const ROUTE_SEARCH_MAX_HOPS: usize = 32;
+
+/// # `find_route`
+/// - k-shortest-path search from `src` to `dst` over the processor level
+///   network: the global links of the topology and the local network hops
+///   to the processors that have a global link (or to `dst`)
+/// - Each hop is only taken when the output port of its source and the input
+///   port of its destination are free at the PC the bit passes through them
+/// - Routes are expanded in the order of their latency and each processor is
+///   expanded by at most `ROUTE_SEARCH_K` routes
+fn find_route(
+    nw: &mut NetworkAvailability,
+    src: &Coordinate,
+    dst: &Coordinate,
+    pc: &u32,
+    pcfg: &PlatformConfig
+) -> Option<NetworkRoute> {
+    let mut routes: Vec<NetworkRoute> = vec![NetworkRoute::new()];
+    let mut expanded: IndexMap<Coordinate, u32> = IndexMap::new();
+    let mut heap: BinaryHeap<Reverse<(u32, usize, usize)>> = BinaryHeap::new();
+    heap.push(Reverse((pcfg.nw_route_lat(&routes[0]), 0, 0)));
+
+    while let Some(Reverse((_, hops, ridx))) = heap.pop() {
+        let route = routes[ridx].clone();
+        let at = match route.back() {
+            Some(p) => p.dst,
+            None => *src
+        };
+        if at == *dst {
+            return Some(route);
+        }
+
+        let cnt = expanded.entry(at).or_insert(0);
+        if *cnt >= ROUTE_SEARCH_K || hops >= ROUTE_SEARCH_MAX_HOPS {
+            continue;
+        }
+        *cnt += 1;
+
+        // Two consecutive local network hops can always be merged into one
+        let last_local = match route.back() {
+            Some(p) => p.tpe == PathTypes::InterProcessor,
+            None => false
+        };
+        let mut next: Vec<NetworkPath> = vec![];
+        match pcfg.topology.edges().get(&at) {
+            Some(remote) => next.push(NetworkPath::new(at, *remote)),
+            None => {}
+        }
+        if !last_local {
+            if at.module == dst.module {
+                next.push(NetworkPath::new(at, *dst));
+            }
+            for g in pcfg.topology.gateways(at.module).iter() {
+                if *g != at && *g != *dst {
+                    next.push(NetworkPath::new(at, *g));
+                }
+            }
+        }
+
+        let send_pc = if route.is_empty() {
+            *pc + pcfg.nw_route_lat(&route)
+        } else {
+            *pc + pcfg.nw_route_dep_lat(&route)
+        };
+        if nw.oports.is_busy(at.id(pcfg), send_pc) {
+            continue;
+        }
+        for path in next.iter() {
+            if path.dst == *src || route.iter().any(|p| p.dst == path.dst) {
+                continue;
+            }
+            let mut new_route = route.clone();
+            new_route.push_back(*path);
+            let lat = pcfg.nw_route_lat(&new_route);
+            if nw.iports.is_busy(path.dst.id(pcfg), *pc + lat) {
+                continue;
+            }
+            routes.push(new_route);
+            heap.push(Reverse((lat, hops + 1, routes.len() - 1)));
+        }
+    }
+    return None;
+}
+
 /// There is some path from node `nidx` to `cidx` that is not busy
 /// - `nidx` and `cidx` are placed in the same processor
 /// - `nidx` and `cidx` are placed in the same module
@@ -481,7 +594,8 @@ fn route_add_back(dst: &Coordinate, route: &NetworkRoute) -> NetworkRoute {
 ///     - Check for routes that are resuable from shipping bits to other child nodes
 ///       (`inter_mod_routes`)
 ///     - Search for direct inter-module path
-///     - Search for one-hop inter-module path
+///     - Search for multi-hop inter-module path (`Topology::inter_mod_routes`)
+///     - Search for a free route of any length (`find_route`)
 fn child_reachable(
     circuit: &Circuit,
     nidx: &NodeIndex,
@@ -524,7 +638,6 @@ fn child_reachable(
 
         // no path exists yet, search for a new path
         let paths = pcfg.topology.inter_mod_paths(src, dst);
-        assert!(paths.len() > 0, "No inter module path from {:?} to {:?}", src, dst);
         for p in paths.iter() {
             let route = NetworkRoute::from([*p]);
             let new_route = route_add_back(&dst, &route_add_front(&src, &route));
@@ -550,6 +663,18 @@ fn child_reachable(
             }
         }
 
+        // all the topology routes are busy, search the processor level network
+        match find_route(nw, &src, &dst, pc, &pcfg) {
+            Some(route) => {
+                if !inter_mod_routes.contains_key(&dst.module) {
+                    inter_mod_routes.insert(dst.module, vec![]);
+                }
+                inter_mod_routes.get_mut(&dst.module).unwrap().push(route.clone());
+                return Some(route);
+            }
+            None => {}
+        }
+
         // no paths
         return None;
     }
@@ -663,15 +788,12 @@ fn mark_nw_busy(
     route: &NetworkRoute,
     pcfg: &PlatformConfig
 ) {
-    let mut cur_route = NetworkRoute::new();
-    for (i, path) in route.iter().enumerate() {
-        if i == 0 {
-            nw.oports.set_busy(path.src.id(pcfg), *pc + pcfg.nw_route_lat(&cur_route));
+    for p in route_ports(route, *pc, pcfg) {
+        if p.out {
+            nw.oports.set_busy(p.port, p.pc);
         } else {
-            nw.oports.set_busy(path.src.id(pcfg), pc + pcfg.nw_route_dep_lat(&cur_route));
+            nw.iports.set_busy(p.port, p.pc);
         }
-        cur_route.push_back(*path);
-        nw.iports.set_busy(path.dst.id(pcfg), pc + pcfg.nw_route_lat(&cur_route));
     }
 }
 
@@ -688,9 +810,14 @@ fn schedule_candidates_at_pc(
     for cand in candidates.iter() {
         let node = circuit.graph.node_weight(cand.index).unwrap();
 
-        // Cannot schedule a SRAM read until pc >= pcfg.sram_rd_en_step
-        if node.is() == Primitive::SRAMRdData && *pc < pcfg.sram_rd_en_step() {
-            continue;
+        // Cannot schedule a SRAM read until the read data of this slot is valid
+        if node.is() == Primitive::SRAMRdData {
+            let nslots = circuit.emul.module_mappings
+                .get(&node.info().coord.module).unwrap()
+                .sram_mapping.mems.len() as u32;
+            if *pc < pcfg.sram_slot_rd_step(nslots, node.info().sram_slot) {
+                continue;
+            }
         }
 
         // Cannot schedule a `Input` or a `SRAMRdData` if it is directly
@@ -756,9 +883,46 @@ fn schedule_candidates_at_pc(
     return remove_nodes;
 }
 
-pub fn schedule_instructions(circuit: &mut Circuit) {
-    schedule_instructions_internal(circuit);
+/// Number of host steps of the schedule when the last node is scheduled
+/// before `end_pc`
+pub fn host_steps(circuit: &Circuit, end_pc: u32) -> u32 {
+    let mut max_nw_route_dep_lat = 0;
+    for eidx in circuit.graph.edge_indices() {
+        match &circuit.graph.edge_weight(eidx) {
+            Some(e) => {
+                match &e.route {
+                    Some(r) => {
+                        max_nw_route_dep_lat = max(max_nw_route_dep_lat,
+                                                   circuit.platform_cfg.nw_route_dep_lat(&r));
+                    }
+                    None => {
+                        assert!(false, "Edge with unassigned NetworkRoute");
+                    }
+                }
+            }
+            None => {
+                assert!(false, "Edge with unassigned NetworkRoute");
+            }
+        }
+    }
+
+    let steps = end_pc + 1 +                                // <base>
+                max(max_nw_route_dep_lat,                   // NW
+                    circuit.platform_cfg.sram_ip_pl);       // SRAM
+
+    // Every target SRAM packed into a SRAM processor has to be accessed
+    // within a single target cycle
+    let mut max_sram_slots = 0;
+    for (_, mmap) in circuit.emul.module_mappings.iter() {
+        max_sram_slots = max(max_sram_slots, mmap.sram_mapping.mems.len() as u32);
+    }
+    return max(steps, circuit.platform_cfg.sram_slot_steps(max_sram_slots));
+}
+
+pub fn schedule_instructions(circuit: &mut Circuit) -> Result<(), CompileError> {
+    schedule_instructions_internal(circuit)?;
     check_schedule(circuit);
+    return Ok(());
 }
 
 /// Implements the modified list scheduling algorithm.
@@ -768,7 +932,7 @@ pub fn schedule_instructions(circuit: &mut Circuit) {
 /// mobility (rank - ASAP) == 0 first. We can increment the PC while doing so.
 /// Then, for the PC range, slot in the nodes with mobility != 0 as much as
 /// possible.
-fn schedule_instructions_internal(circuit: &mut Circuit) {
+fn schedule_instructions_internal(circuit: &mut Circuit) -> Result<(), CompileError> {
     let mut cpn: IndexSet<NodeIndex> = IndexSet::new();
     for nidx in circuit.graph.node_indices() {
         let rank = &circuit.graph.node_weight(nidx).unwrap().info().rank;
@@ -901,37 +1065,18 @@ fn schedule_instructions_internal(circuit: &mut Circuit) {
 
         print_tail_graph(circuit, &per_pc_scheduled, &debug_scheduled_nodes, pc_min, cur_rank);
 
-        assert!(pc < circuit.platform_cfg.max_steps,
-                "Schedule failed {} nodes out of {} nodes scheduled, pc {} max_steps {}",
-                scheduled_map.count_ones(..),
-                scheduled_map.len(),
-                pc,
-                circuit.platform_cfg.max_steps);
-    }
-
-    let mut max_nw_route_dep_lat = 0;
-    for eidx in circuit.graph.edge_indices() {
-        match &circuit.graph.edge_weight(eidx) {
-            Some(e) => {
-                match &e.route {
-                    Some(r) => {
-                        max_nw_route_dep_lat = max(max_nw_route_dep_lat,
-                                                   circuit.platform_cfg.nw_route_dep_lat(&r));
-                    }
-                    None => {
-                        assert!(false, "Edge with unassigned NetworkRoute");
-                    }
-                }
-            }
-            None => {
-                assert!(false, "Edge with unassigned NetworkRoute");
-            }
+        if pc >= circuit.platform_cfg.max_steps {
+            return Err(CompileError::ScheduleExceedsMaxSteps {
+                pc: pc,
+                max_steps: circuit.platform_cfg.max_steps,
+                rank: cur_rank,
+                scheduled: scheduled_map.count_ones(..),
+                total: scheduled_map.len()
+            });
         }
     }
 
-    circuit.emul.host_steps = pc + 1 +                               // <base>
-                              max(max_nw_route_dep_lat,              // NW
-                                  circuit.platform_cfg.sram_ip_pl);  // SRAM
+    circuit.emul.host_steps = host_steps(circuit, pc);
 
     let total_steps = circuit.emul.host_steps * circuit.platform_cfg.total_procs();
     println!("Machine ({} / {}) = {:.2} %, host_steps = {}",
@@ -953,6 +1098,7 @@ fn schedule_instructions_internal(circuit: &mut Circuit) {
     println!("Must schedule failed reasons: {:?}",         must_schedule_stats);
     println!("Best effort schedule failed reasons: {:?}",  be_schedule_stats);
     println!("Extra effort schedule failed reasons: {:?}", ex_schedule_stats);
+    return Ok(());
 }
 
 fn check_route(route: &NetworkRoute, msg: &str) {
@@ -961,7 +1107,8 @@ fn check_route(route: &NetworkRoute, msg: &str) {
     }
 }
 
-fn check_schedule(circuit: &Circuit) {
+/// Sanity checks of the final schedule. Also run after `compact_schedule`
+pub fn check_schedule(circuit: &Circuit) {
     for eidx in circuit.graph.edge_indices() {
         let edge = circuit.graph.edge_weight(eidx).unwrap();
         match &edge.route {
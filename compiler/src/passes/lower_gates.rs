@@ -0,0 +1,101 @@
+use crate::common::{
+    circuit::Circuit,
+    error::CompileError,
+    primitive::*,
+    hwgraph::*,
+};
+use petgraph::{
+    graph::{EdgeIndex, NodeIndex},
+    visit::EdgeRef,
+    Direction::Incoming
+};
+
+/// # `lower_gates`
+/// - `Gate`s with an enable and/or a synchronous reset are executed as a single
+///   fused instruction that reads `d`, `e`, `r` and its current value (see
+///   `CircuitPrimitive::gate_operands`)
+/// - When a `Gate` needs more operands than `lut_inputs`, the enable is split
+///   out into a mux LUT (`e ? d : q`) that drives `d` of the `Gate`.
+///   The number of split enables is reported by `print_stats`
+pub fn lower_gates(circuit: &mut Circuit) -> Result<(), CompileError> {
+    let lut_inputs = circuit.platform_cfg.lut_inputs as usize;
+    let mut split_gates: Vec<NodeIndex> = vec![];
+
+    for nidx in circuit.graph.node_indices() {
+        let node = circuit.graph.node_weight(nidx).unwrap();
+        if node.is() != Primitive::Gate {
+            continue;
+        }
+        if node.prim.gate_operands().len() > lut_inputs {
+            split_gates.push(nidx);
+        }
+    }
+
+    if split_gates.len() > 0 && lut_inputs < 3 {
+        return Err(CompileError::invalid_config(
+            "lut_inputs", lut_inputs, "Gate enables need at least 3 LUT inputs"));
+    }
+
+    for nidx in split_gates.iter() {
+        let node = circuit.graph.node_weight(*nidx).unwrap();
+        let (d, q, e) = match &node.prim {
+            CircuitPrimitive::Gate { c:_, d, q, r:_, e: Some(e) } => (d.clone(), q.clone(), e.clone()),
+            _ => {
+                return Err(CompileError::invalid_config("lut_inputs", lut_inputs,
+                    &format!("Gate {} needs {} operands without an enable to split out",
+                        node.name(), node.prim.gate_operands().len())));
+            }
+        };
+
+        // Edges driving the `d` and `e` pins. Edge signals carry the net names
+        // (the parent node can be a SRAM which is named after the blackbox)
+        let mut d_edge: Option<EdgeIndex> = None;
+        let mut e_edge: Option<EdgeIndex> = None;
+        for pedge in circuit.graph.edges_directed(*nidx, Incoming) {
+            let net = pedge.weight().signal.name();
+            if d_edge.is_none() && net == d {
+                d_edge = Some(pedge.id());
+            } else if e_edge.is_none() && net == e {
+                e_edge = Some(pedge.id());
+            }
+        }
+        assert!(d_edge.is_some() && e_edge.is_some(),
+            "Cannot find the d or e edge of Gate {}", q);
+
+        let mux_name = format!("GATE-EN-{}", q);
+        let mux = CircuitPrimitive::Lut {
+            inputs: vec![ d.clone(), e.clone(), q.clone() ],
+            output: mux_name.clone(),
+            table: vec![
+                vec![1, 1, 0],
+                vec![1, 1, 1],
+                vec![0, 0, 1],
+                vec![1, 0, 1]
+            ]
+        };
+        let mux_idx = circuit.graph.add_node(HWNode::new(mux));
+
+        for eidx in [d_edge.unwrap(), e_edge.unwrap()] {
+            let (src, _) = circuit.graph.edge_endpoints(eidx).unwrap();
+            let edge = circuit.graph.edge_weight(eidx).unwrap().clone();
+            circuit.graph.add_edge(src, mux_idx, edge);
+        }
+        circuit.graph.add_edge(*nidx, mux_idx, HWEdge::new(SignalType::Wire { name: q.clone() }));
+        circuit.graph.add_edge(mux_idx, *nidx, HWEdge::new(SignalType::Wire { name: mux_name.clone() }));
+
+        // Remove the larger index first so that the other index stays valid
+        let mut old_edges = vec![d_edge.unwrap(), e_edge.unwrap()];
+        old_edges.sort();
+        for eidx in old_edges.iter().rev() {
+            circuit.graph.remove_edge(*eidx);
+        }
+
+        let node = circuit.graph.node_weight_mut(*nidx).unwrap();
+        if let CircuitPrimitive::Gate { c:_, d, q:_, r:_, e } = &mut node.prim {
+            *d = mux_name;
+            *e = None;
+        }
+    }
+    circuit.opt_stats.gate_enables_split += split_gates.len() as u32;
+    return Ok(());
+}
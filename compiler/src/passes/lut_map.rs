@@ -0,0 +1,374 @@
+use crate::common::{
+    circuit::Circuit,
+    error::CompileError,
+    primitive::*,
+    hwgraph::*,
+};
+use indexmap::{IndexMap, IndexSet};
+use petgraph::{
+    graph::{EdgeIndex, NodeIndex},
+    visit::EdgeRef,
+    Direction::{Incoming, Outgoing}
+};
+use std::collections::VecDeque;
+
+/// Number of cuts kept per node (besides the trivial cut)
+const MAX_CUTS: usize = 10;
+
+/// Signal in the combinational network: the node driving the net and the net
+type Signal = (NodeIndex, String);
+
+/// Cut of a LUT node. `leaves` are sorted signal ids
+#[derive(Debug, Clone, PartialEq)]
+struct Cut {
+    leaves: Vec<usize>,
+
+    /// Rank of the LUT implementing this cut (same as the `asap` rank of
+    /// `set_rank`: registers, inputs and constants have rank 0)
+    depth: u32,
+
+    /// Estimated number of LUTs needed to implement the cone of the cut
+    area_flow: f32,
+}
+
+struct MapState {
+    /// All the signals. Signals of LUT nodes are their outputs
+    signals: IndexSet<Signal>,
+
+    /// Edge to reuse when connecting a signal to a mapped LUT
+    signal_edges: IndexMap<usize, HWEdge>,
+
+    /// LUT nodes in topological order
+    luts: Vec<NodeIndex>,
+
+    /// Input signals of each LUT node in the order of its `inputs`.
+    /// `None` for inputs without a driver
+    fanins: IndexMap<NodeIndex, Vec<Option<usize>>>,
+
+    /// Priority cuts of each LUT node, best first
+    cuts: IndexMap<NodeIndex, Vec<Cut>>,
+}
+
+impl MapState {
+    fn lut_of(self: &Self, sig: usize) -> Option<NodeIndex> {
+        let (nidx, _) = self.signals.get_index(sig).unwrap();
+        if self.cuts.contains_key(nidx) { Some(*nidx) } else { None }
+    }
+
+    fn depth(self: &Self, sig: usize) -> u32 {
+        match self.lut_of(sig) {
+            Some(nidx) => self.cuts[&nidx][0].depth,
+            None => 0
+        }
+    }
+
+    fn area_flow(self: &Self, sig: usize, fanouts: &IndexMap<usize, u32>) -> f32 {
+        match self.lut_of(sig) {
+            Some(nidx) => self.cuts[&nidx][0].area_flow / fanouts[&sig].max(1) as f32,
+            None => 0.0
+        }
+    }
+}
+
+/// Union of two sorted leaf sets, `None` when it has more than `k` leaves
+fn merge_leaves(a: &Vec<usize>, b: &Vec<usize>, k: usize) -> Option<Vec<usize>> {
+    let mut ret = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if j == b.len() || (i < a.len() && a[i] < b[j]) {
+            ret.push(a[i]);
+            i += 1;
+        } else if i == a.len() || b[j] < a[i] {
+            ret.push(b[j]);
+            j += 1;
+        } else {
+            ret.push(a[i]);
+            i += 1;
+            j += 1;
+        }
+        if ret.len() > k {
+            return None;
+        }
+    }
+    return Some(ret);
+}
+
+/// Collect the signals, fanins and the topological order of the LUT nodes
+fn init_map_state(circuit: &Circuit) -> MapState {
+    let mut st = MapState {
+        signals: IndexSet::new(),
+        signal_edges: IndexMap::new(),
+        luts: vec![],
+        fanins: IndexMap::new(),
+        cuts: IndexMap::new(),
+    };
+
+    let mut indeg: IndexMap<NodeIndex, u32> = IndexMap::new();
+    for nidx in circuit.graph.node_indices() {
+        let node = circuit.graph.node_weight(nidx).unwrap();
+        let inputs = match &node.prim {
+            CircuitPrimitive::Lut { inputs, .. } => inputs,
+            _ => { continue; }
+        };
+        let mut fanin: Vec<Option<usize>> = vec![None; inputs.len()];
+        let mut lut_parents = 0;
+        for pedge in circuit.graph.edges_directed(nidx, Incoming) {
+            let net = pedge.weight().signal.name();
+            let (sig, _) = st.signals.insert_full((pedge.source(), net.to_string()));
+            if !st.signal_edges.contains_key(&sig) {
+                st.signal_edges.insert(sig, pedge.weight().clone());
+            }
+            // The same net can drive several pins of the LUT
+            for (pos, i) in inputs.iter().enumerate() {
+                if i == net {
+                    fanin[pos] = Some(sig);
+                }
+            }
+            if circuit.graph.node_weight(pedge.source()).unwrap().is() == Primitive::Lut {
+                lut_parents += 1;
+            }
+        }
+        st.signals.insert((nidx, node.name().to_string()));
+        st.fanins.insert(nidx, fanin);
+        indeg.insert(nidx, lut_parents);
+    }
+
+    let mut q: VecDeque<NodeIndex> = indeg.iter()
+        .filter(|(_, d)| **d == 0)
+        .map(|(n, _)| *n)
+        .collect();
+    while let Some(nidx) = q.pop_front() {
+        st.luts.push(nidx);
+        for cidx in circuit.graph.neighbors_directed(nidx, Outgoing) {
+            match indeg.get_mut(&cidx) {
+                Some(d) => {
+                    *d -= 1;
+                    if *d == 0 {
+                        q.push_back(cidx);
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+    return st;
+}
+
+/// Enumerate the priority cuts of every LUT node in topological order
+fn enumerate_cuts(
+    circuit: &Circuit,
+    st: &mut MapState,
+    fanouts: &IndexMap<usize, u32>,
+    k: usize
+) -> Result<(), CompileError> {
+    for nidx in st.luts.clone().iter() {
+        let fanin: Vec<usize> = st.fanins[nidx].iter().filter_map(|x| *x).collect();
+        let mut fanin_set = fanin.clone();
+        fanin_set.sort();
+        fanin_set.dedup();
+        if fanin_set.len() > k {
+            let node = circuit.graph.node_weight(*nidx).unwrap();
+            return Err(CompileError::TooManyOperands {
+                node: node.name().to_string(),
+                coord: node.info().coord,
+                operands: fanin_set.len() as u32,
+                lut_inputs: k as u32
+            });
+        }
+
+        // Cartesian product of the fanin cuts (including the trivial cuts)
+        let mut merged: Vec<Vec<usize>> = vec![vec![]];
+        for sig in fanin_set.iter() {
+            let mut fcuts: Vec<Vec<usize>> = vec![vec![*sig]];
+            match st.lut_of(*sig) {
+                Some(pidx) => {
+                    fcuts.extend(st.cuts[&pidx].iter().map(|c| c.leaves.clone()));
+                }
+                None => {}
+            }
+            let mut next = vec![];
+            for m in merged.iter() {
+                for f in fcuts.iter() {
+                    match merge_leaves(m, f, k) {
+                        Some(leaves) => {
+                            if !next.contains(&leaves) {
+                                next.push(leaves);
+                            }
+                        }
+                        None => {}
+                    }
+                }
+            }
+            merged = next;
+        }
+
+        let mut cuts: Vec<Cut> = merged.into_iter().map(|leaves| {
+            let depth = leaves.iter().map(|l| st.depth(*l)).max().unwrap_or(0) + 1;
+            let area_flow = 1.0 + leaves.iter().map(|l| st.area_flow(*l, fanouts)).sum::<f32>();
+            Cut { leaves: leaves, depth: depth, area_flow: area_flow }
+        }).collect();
+        cuts.sort_by(|a, b| {
+            a.depth.cmp(&b.depth)
+                .then(a.area_flow.partial_cmp(&b.area_flow).unwrap())
+                .then(a.leaves.len().cmp(&b.leaves.len()))
+        });
+        cuts.truncate(MAX_CUTS);
+        st.cuts.insert(*nidx, cuts);
+    }
+    return Ok(());
+}
+
+/// Value of `sig` given the values of the cut `leaves`
+fn eval_signal(
+    circuit: &Circuit,
+    st: &MapState,
+    sig: usize,
+    leaves: &IndexMap<usize, Bit>,
+    memo: &mut IndexMap<usize, Bit>
+) -> Bit {
+    match leaves.get(&sig) {
+        Some(v) => { return *v; }
+        None => {}
+    }
+    match memo.get(&sig) {
+        Some(v) => { return *v; }
+        None => {}
+    }
+    let nidx = st.lut_of(sig).unwrap();
+    let table = match &circuit.graph.node_weight(nidx).unwrap().prim {
+        CircuitPrimitive::Lut { table, .. } => table,
+        _ => unreachable!()
+    };
+    let vals: Vec<u8> = st.fanins[&nidx].iter().map(|f| match f {
+        Some(f) => eval_signal(circuit, st, *f, leaves, memo),
+        None => 0
+    }).collect();
+    let v = if table.contains(&vals) { 1 } else { 0 };
+    memo.insert(sig, v);
+    return v;
+}
+
+/// # `lut_map`
+/// - Maps the combinational `Lut` nodes of a gate level netlist (e.g. a AIG
+///   written by `abc -g aig`) to `lut_inputs`-input LUTs
+/// - Cuts are enumerated in topological order keeping the `MAX_CUTS` best cuts
+///   per node. Cuts are ranked by depth (the `asap` rank of `set_rank`) and then
+///   by area flow so that the mapping is depth optimal among the kept cuts
+/// - The cover is selected from the LUTs that drive a non-LUT node backwards,
+///   and the LUTs that are not used by the cover are removed
+/// - Skipped when every LUT already fits unless `compiler_cfg.lut_map` is set
+pub fn lut_map(circuit: &mut Circuit) -> Result<(), CompileError> {
+    let k = circuit.platform_cfg.lut_inputs as usize;
+    let mut st = init_map_state(circuit);
+    let lut_cnt = st.fanins.len();
+
+    let max_fanin = st.fanins.values().map(|f| f.len()).max().unwrap_or(0);
+    if !circuit.compiler_cfg.lut_map && max_fanin <= k {
+        println!("LUT mapping skipped: {} LUTs with at most {} inputs", lut_cnt, max_fanin);
+        return Ok(());
+    }
+    if st.luts.len() != lut_cnt {
+        return Err(CompileError::Internal {
+            pass: "lut_map".to_string(),
+            msg: format!("Combinational loop through {} LUTs", lut_cnt - st.luts.len())
+        });
+    }
+
+    let mut fanouts: IndexMap<usize, u32> = IndexMap::new();
+    for fanin in st.fanins.values() {
+        for f in fanin.iter().filter_map(|x| *x) {
+            *fanouts.entry(f).or_insert(0) += 1;
+        }
+    }
+    enumerate_cuts(circuit, &mut st, &fanouts, k)?;
+
+    // Select the cover starting from the LUTs that drive a non-LUT node
+    let mut required: IndexSet<NodeIndex> = IndexSet::new();
+    for nidx in st.luts.iter() {
+        let drives_non_lut = circuit.graph.neighbors_directed(*nidx, Outgoing).any(|c| {
+            circuit.graph.node_weight(c).unwrap().is() != Primitive::Lut
+        });
+        if drives_non_lut {
+            required.insert(*nidx);
+        }
+    }
+    let mut cover: IndexMap<NodeIndex, Vec<usize>> = IndexMap::new();
+    for nidx in st.luts.iter().rev() {
+        if !required.contains(nidx) {
+            continue;
+        }
+        let leaves = st.cuts[nidx][0].leaves.clone();
+        for l in leaves.iter() {
+            match st.lut_of(*l) {
+                Some(lidx) => { required.insert(lidx); }
+                None => {}
+            }
+        }
+        cover.insert(*nidx, leaves);
+    }
+
+    // Compute the truth tables of the mapped LUTs before touching the graph
+    let mut tables: IndexMap<NodeIndex, Vec<Vec<u8>>> = IndexMap::new();
+    for (nidx, leaves) in cover.iter() {
+        let sig = st.signals.get_index_of(&(*nidx, circuit.graph.node_weight(*nidx).unwrap().name().to_string())).unwrap();
+        let mut table = vec![];
+        for row in 0..(1u32 << leaves.len()) {
+            let vals: IndexMap<usize, Bit> = leaves.iter().enumerate()
+                .map(|(i, l)| (*l, ((row >> i) & 1) as Bit))
+                .collect();
+            let mut memo: IndexMap<usize, Bit> = IndexMap::new();
+            if eval_signal(circuit, &st, sig, &vals, &mut memo) == 1 {
+                table.push(vals.values().cloned().collect());
+            }
+        }
+        tables.insert(*nidx, table);
+    }
+
+    // Rewire the mapped LUTs to their cut leaves
+    let mut max_depth = 0;
+    for (nidx, leaves) in cover.iter() {
+        max_depth = max_depth.max(st.cuts[nidx][0].depth);
+
+        let mut old_edges: Vec<EdgeIndex> = circuit.graph
+            .edges_directed(*nidx, Incoming)
+            .map(|e| e.id())
+            .collect();
+        old_edges.sort();
+        for eidx in old_edges.iter().rev() {
+            circuit.graph.remove_edge(*eidx);
+        }
+
+        let mut inputs = vec![];
+        for l in leaves.iter() {
+            let (src, net) = st.signals.get_index(*l).unwrap();
+            let edge = match st.signal_edges.get(l) {
+                Some(e) => e.clone(),
+                None => HWEdge::new(SignalType::Wire { name: net.clone() })
+            };
+            circuit.graph.add_edge(*src, *nidx, edge);
+            inputs.push(net.clone());
+        }
+
+        let node = circuit.graph.node_weight_mut(*nidx).unwrap();
+        let output = node.name().to_string();
+        node.prim = CircuitPrimitive::Lut {
+            inputs: inputs,
+            output: output,
+            table: tables.swap_remove(nidx).unwrap()
+        };
+    }
+
+    // Remove the LUTs that were absorbed into the mapped LUTs
+    let mut removed: Vec<NodeIndex> = st.luts.iter()
+        .filter(|n| !cover.contains_key(*n))
+        .cloned()
+        .collect();
+    removed.sort();
+    for nidx in removed.iter().rev() {
+        circuit.graph.remove_node(*nidx);
+    }
+
+    println!("LUT mapping: {} LUTs -> {} {}-input LUTs, depth {}",
+        lut_cnt, cover.len(), k, max_depth);
+    return Ok(());
+}
@@ -1,15 +1,24 @@
 pub mod dce;
+pub mod lut_map;
+pub mod optimize;
 pub mod replicate_consts;
 pub mod inst_map;
 pub mod inst_schedule;
+pub mod compact_schedule;
 pub mod blif_to_circuit;
 pub mod prepartition_set_rank;
 pub mod partition;
+pub mod partitioner;
+pub mod repartition;
 pub mod split_sram_nodes;
+pub mod split_large_srams;
 pub mod split_reg_nodes;
+pub mod lower_gates;
+pub mod clock_domains;
 pub mod set_rank;
 pub mod check_rank;
 pub mod check_connectivity;
+pub mod check_program;
 pub mod print_stats;
 pub mod distribute_io;
 pub mod runner;
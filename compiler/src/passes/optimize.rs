@@ -0,0 +1,364 @@
+use crate::common::{
+    circuit::Circuit,
+    error::CompileError,
+    primitive::*,
+    hwgraph::*,
+};
+use indexmap::{IndexMap, IndexSet};
+use petgraph::{
+    graph::{EdgeIndex, NodeIndex},
+    visit::EdgeRef,
+    Direction::{Incoming, Outgoing}
+};
+use std::collections::VecDeque;
+
+/// LUT nodes in topological order of the combinational LUT network
+fn lut_topo_order(circuit: &Circuit) -> Vec<NodeIndex> {
+    let mut indeg: IndexMap<NodeIndex, u32> = IndexMap::new();
+    for nidx in circuit.graph.node_indices() {
+        if circuit.graph.node_weight(nidx).unwrap().is() != Primitive::Lut {
+            continue;
+        }
+        let lut_parents = circuit.graph.neighbors_directed(nidx, Incoming)
+            .filter(|p| circuit.graph.node_weight(*p).unwrap().is() == Primitive::Lut)
+            .count();
+        indeg.insert(nidx, lut_parents as u32);
+    }
+
+    let mut ret = vec![];
+    let mut q: VecDeque<NodeIndex> = indeg.iter()
+        .filter(|(_, d)| **d == 0)
+        .map(|(n, _)| *n)
+        .collect();
+    while let Some(nidx) = q.pop_front() {
+        ret.push(nidx);
+        for cidx in circuit.graph.neighbors_directed(nidx, Outgoing) {
+            match indeg.get_mut(&cidx) {
+                Some(d) => {
+                    *d -= 1;
+                    if *d == 0 {
+                        q.push_back(cidx);
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+    return ret;
+}
+
+fn lut_inputs(circuit: &Circuit, nidx: NodeIndex) -> Vec<String> {
+    match &circuit.graph.node_weight(nidx).unwrap().prim {
+        CircuitPrimitive::Lut { inputs, .. } => inputs.clone(),
+        _ => vec![]
+    }
+}
+
+fn lut_table(circuit: &Circuit, nidx: NodeIndex) -> Vec<Vec<u8>> {
+    match &circuit.graph.node_weight(nidx).unwrap().prim {
+        CircuitPrimitive::Lut { table, .. } => table.clone(),
+        _ => vec![]
+    }
+}
+
+fn set_lut(circuit: &mut Circuit, nidx: NodeIndex, inputs: Vec<String>, mut table: Vec<Vec<u8>>) {
+    table.sort();
+    table.dedup();
+    let node = circuit.graph.node_weight_mut(nidx).unwrap();
+    let output = node.name().to_string();
+    node.prim = CircuitPrimitive::Lut { inputs: inputs, output: output, table: table };
+}
+
+/// LUT consumers of `nidx`
+fn lut_children(circuit: &Circuit, nidx: NodeIndex) -> IndexSet<NodeIndex> {
+    circuit.graph.neighbors_directed(nidx, Outgoing)
+        .filter(|c| circuit.graph.node_weight(*c).unwrap().is() == Primitive::Lut)
+        .collect()
+}
+
+/// Remove the edges carrying `net` from `src` to `dst`
+fn remove_net_edges(circuit: &mut Circuit, src: NodeIndex, dst: NodeIndex, net: &str) {
+    let mut eidxs: Vec<EdgeIndex> = circuit.graph.edges_directed(dst, Incoming)
+        .filter(|e| e.source() == src && e.weight().signal.name() == net)
+        .map(|e| e.id())
+        .collect();
+    eidxs.sort();
+    for eidx in eidxs.iter().rev() {
+        circuit.graph.remove_edge(*eidx);
+    }
+}
+
+/// Pins of LUT `cidx` that read `net`. A net can drive several pins
+fn input_positions(
+    circuit: &Circuit,
+    pass: &str,
+    cidx: NodeIndex,
+    net: &str
+) -> Result<Vec<usize>, CompileError> {
+    let pos: Vec<usize> = lut_inputs(circuit, cidx).iter()
+        .enumerate()
+        .filter(|(_, i)| *i == net)
+        .map(|(p, _)| p)
+        .collect();
+    if pos.is_empty() {
+        return Err(CompileError::Internal {
+            pass: pass.to_string(),
+            msg: format!("LUT {} does not read {}",
+                circuit.graph.node_weight(cidx).unwrap().name(), net)
+        });
+    }
+    return Ok(pos);
+}
+
+/// Remove the pins `pos` of a LUT, keeping the rows in which they are `val`
+fn remove_pins(
+    inputs: &mut Vec<String>,
+    table: Vec<Vec<u8>>,
+    pos: &Vec<usize>,
+    val: Bit
+) -> Vec<Vec<u8>> {
+    let mut table = table;
+    for p in pos.iter().rev() {
+        inputs.remove(*p);
+        table = table.into_iter()
+            .filter(|row| row[*p] == val)
+            .map(|mut row| { row.remove(*p); row })
+            .collect();
+    }
+    return table;
+}
+
+/// Merge the pins of a LUT that read the same net into the first one
+fn merge_duplicate_pins(inputs: &mut Vec<String>, table: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+    let mut table = table;
+    let mut p = inputs.len();
+    while p > 0 {
+        p -= 1;
+        match inputs[..p].iter().position(|i| *i == inputs[p]) {
+            Some(first) => {
+                inputs.remove(p);
+                table = table.into_iter()
+                    .filter(|row| row[p] == row[first])
+                    .map(|mut row| { row.remove(p); row })
+                    .collect();
+            }
+            None => {}
+        }
+    }
+    return table;
+}
+
+/// Value of a LUT whose output does not depend on its inputs
+fn constant_value(inputs: &Vec<String>, table: &Vec<Vec<u8>>) -> Option<Bit> {
+    if table.is_empty() {
+        Some(0)
+    } else if table.len() == 1 << inputs.len() {
+        Some(1)
+    } else {
+        None
+    }
+}
+
+/// Replace every `old_net` input of LUT `cidx` driven by `old_src` with `new_net`
+/// driven by `new_src`, inverting it when `inv` is set.
+/// The pins that end up reading `new_net` are merged into a single pin
+fn replace_lut_input(
+    circuit: &mut Circuit,
+    pass: &str,
+    cidx: NodeIndex,
+    old_src: NodeIndex,
+    old_net: &str,
+    new_src: NodeIndex,
+    new_net: &str,
+    inv: bool,
+    edge: HWEdge
+) -> Result<(), CompileError> {
+    let pos_old = input_positions(circuit, pass, cidx, old_net)?;
+    let mut inputs = lut_inputs(circuit, cidx);
+    let mut table = lut_table(circuit, cidx);
+    for p in pos_old.iter() {
+        inputs[*p] = new_net.to_string();
+        for row in table.iter_mut() {
+            row[*p] ^= inv as u8;
+        }
+    }
+    let table = merge_duplicate_pins(&mut inputs, table);
+
+    // Keep a single edge per pin
+    remove_net_edges(circuit, old_src, cidx, old_net);
+    remove_net_edges(circuit, new_src, cidx, new_net);
+    circuit.graph.add_edge(new_src, cidx, edge);
+    set_lut(circuit, cidx, inputs, table);
+    return Ok(());
+}
+
+fn remove_nodes(circuit: &mut Circuit, nodes: IndexSet<NodeIndex>) {
+    let mut nodes: Vec<NodeIndex> = nodes.into_iter().collect();
+    nodes.sort();
+    for nidx in nodes.iter().rev() {
+        circuit.graph.remove_node(*nidx);
+    }
+}
+
+/// # `const_prop`
+/// - Folds the `ConstLut` inputs of LUTs into their tables
+/// - LUTs whose output no longer depends on their inputs become `ConstLut`s
+///   (keeping their output net) and are folded into their consumers in turn
+/// - Constants that are only read by LUTs are removed
+pub fn const_prop(circuit: &mut Circuit) -> Result<(), CompileError> {
+    let mut q: VecDeque<NodeIndex> = VecDeque::new();
+    for nidx in circuit.graph.node_indices() {
+        match circuit.graph.node_weight(nidx).unwrap().is() {
+            Primitive::ConstLut | Primitive::Lut => q.push_back(nidx),
+            _ => {}
+        }
+    }
+
+    let mut removed: IndexSet<NodeIndex> = IndexSet::new();
+    while let Some(nidx) = q.pop_front() {
+        let node = circuit.graph.node_weight(nidx).unwrap();
+        match node.prim.clone() {
+            CircuitPrimitive::ConstLut { val, output } => {
+                for cidx in lut_children(circuit, nidx) {
+                    let pos = input_positions(circuit, "const_prop", cidx, &output)?;
+                    let mut inputs = lut_inputs(circuit, cidx);
+                    let table = remove_pins(&mut inputs, lut_table(circuit, cidx), &pos, val);
+                    remove_net_edges(circuit, nidx, cidx, &output);
+                    set_lut(circuit, cidx, inputs, table);
+                    circuit.opt_stats.const_inputs_folded += pos.len() as u32;
+                    q.push_back(cidx);
+                }
+                if circuit.graph.neighbors_directed(nidx, Outgoing).count() == 0 {
+                    removed.insert(nidx);
+                }
+            }
+            CircuitPrimitive::Lut { inputs, output, table } => {
+                match constant_value(&inputs, &table) {
+                    Some(val) => {
+                        let mut pedges: Vec<EdgeIndex> = circuit.graph
+                            .edges_directed(nidx, Incoming)
+                            .map(|e| e.id())
+                            .collect();
+                        pedges.sort();
+                        for eidx in pedges.iter().rev() {
+                            circuit.graph.remove_edge(*eidx);
+                        }
+                        let node = circuit.graph.node_weight_mut(nidx).unwrap();
+                        node.prim = CircuitPrimitive::ConstLut { val: val, output: output };
+                        circuit.opt_stats.const_luts += 1;
+                        q.push_back(nidx);
+                    }
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    circuit.opt_stats.nodes_removed += removed.len() as u32;
+    remove_nodes(circuit, removed);
+    return Ok(());
+}
+
+/// # `absorb_buffers`
+/// - Single input LUTs that pass through (buffers) or invert their input
+///   are absorbed into the tables of their LUT consumers
+/// - The buffer is removed when all its consumers are LUTs
+pub fn absorb_buffers(circuit: &mut Circuit) -> Result<(), CompileError> {
+    let mut removed: IndexSet<NodeIndex> = IndexSet::new();
+    for nidx in lut_topo_order(circuit) {
+        let inputs = lut_inputs(circuit, nidx);
+        let table = lut_table(circuit, nidx);
+        if inputs.len() != 1 {
+            continue;
+        }
+        let inv = if table == vec![vec![1]] {
+            false
+        } else if table == vec![vec![0]] {
+            true
+        } else {
+            continue;
+        };
+
+        // Only absorb nets named after their driver so that the consumers
+        // can still find their operands
+        let pedge = circuit.graph.edges_directed(nidx, Incoming)
+            .find(|e| e.weight().signal.name() == inputs[0])
+            .map(|e| (e.source(), e.weight().clone()));
+        let (src, edge) = match pedge {
+            Some(x) => x,
+            None => { continue; }
+        };
+        if circuit.graph.node_weight(src).unwrap().name() != inputs[0] {
+            continue;
+        }
+
+        let output = circuit.graph.node_weight(nidx).unwrap().name().to_string();
+        for cidx in lut_children(circuit, nidx) {
+            replace_lut_input(circuit, "absorb_buffers",
+                cidx, nidx, &output, src, &inputs[0], inv, edge.clone())?;
+        }
+        if circuit.graph.neighbors_directed(nidx, Outgoing).count() == 0 {
+            removed.insert(nidx);
+            if inv {
+                circuit.opt_stats.inverters_absorbed += 1;
+            } else {
+                circuit.opt_stats.buffers_absorbed += 1;
+            }
+        }
+    }
+    circuit.opt_stats.nodes_removed += removed.len() as u32;
+    remove_nodes(circuit, removed);
+    return Ok(());
+}
+
+/// Function of a LUT independent of the order of its inputs
+/// and of nets driving several pins
+fn canonical_lut(inputs: &Vec<String>, table: &Vec<Vec<u8>>) -> (Vec<String>, Vec<Vec<u8>>) {
+    let mut inputs = inputs.clone();
+    let table = merge_duplicate_pins(&mut inputs, table.clone());
+    let mut order: Vec<usize> = (0..inputs.len()).collect();
+    order.sort_by(|a, b| inputs[*a].cmp(&inputs[*b]));
+    let sorted_inputs = order.iter().map(|i| inputs[*i].clone()).collect();
+    let mut sorted_table: Vec<Vec<u8>> = table.iter()
+        .map(|row| order.iter().map(|i| row[*i]).collect())
+        .collect();
+    sorted_table.sort();
+    sorted_table.dedup();
+    return (sorted_inputs, sorted_table);
+}
+
+/// # `strash`
+/// - Structural hashing: LUTs computing the same table over the same nets
+///   are merged into the first one in topological order
+/// - A LUT is only merged when all its consumers are LUTs
+pub fn strash(circuit: &mut Circuit) -> Result<(), CompileError> {
+    let mut hashed: IndexMap<(Vec<String>, Vec<Vec<u8>>), NodeIndex> = IndexMap::new();
+    let mut removed: IndexSet<NodeIndex> = IndexSet::new();
+    for nidx in lut_topo_order(circuit) {
+        let key = canonical_lut(&lut_inputs(circuit, nidx), &lut_table(circuit, nidx));
+        let only_luts = circuit.graph.neighbors_directed(nidx, Outgoing)
+            .all(|c| circuit.graph.node_weight(c).unwrap().is() == Primitive::Lut);
+
+        match hashed.get(&key) {
+            Some(aidx) if only_luts => {
+                let aidx = *aidx;
+                let a_net = circuit.graph.node_weight(aidx).unwrap().name().to_string();
+                let b_net = circuit.graph.node_weight(nidx).unwrap().name().to_string();
+                let edge = HWEdge::new(SignalType::Wire { name: a_net.clone() });
+                for cidx in lut_children(circuit, nidx) {
+                    replace_lut_input(circuit, "strash",
+                        cidx, nidx, &b_net, aidx, &a_net, false, edge.clone())?;
+                }
+                removed.insert(nidx);
+                circuit.opt_stats.luts_merged += 1;
+            }
+            Some(_) => {}
+            None => {
+                hashed.insert(key, nidx);
+            }
+        }
+    }
+    circuit.opt_stats.nodes_removed += removed.len() as u32;
+    remove_nodes(circuit, removed);
+    return Ok(());
+}
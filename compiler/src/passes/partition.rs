@@ -1,16 +1,18 @@
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
 use crate::common::{
     circuit::Circuit,
     hwgraph::*,
     mapping::*,
     config::*,
+    constraints::PartitionConstraints,
+    error::CompileError,
     network::*
 };
+use crate::passes::partitioner::*;
 use petgraph::{
-    graph::{Graph, NodeIndex}, Direction::Outgoing, Undirected
+    graph::NodeIndex, Direction::Outgoing
 };
 use histo::Histogram;
-use kaminpar::KaminParError;
 
 fn edge_weight(circuit: &Circuit, src_idx: &NodeIndex, dst_idx: &NodeIndex) -> f32 {
     let dst = circuit.graph.node_weight(*dst_idx).unwrap().info();
@@ -22,10 +24,20 @@ fn edge_weight(circuit: &Circuit, src_idx: &NodeIndex, dst_idx: &NodeIndex) -> f
     }
 }
 
+/// Edges that were on the critical path of previous schedules
+/// (`circuit.partition_feedback`) cost `1 + hits` times more to cut
 pub fn set_edge_weights(circuit: &mut Circuit, communication: u32) {
     for eidx in circuit.graph.edge_indices() {
         let e = circuit.graph.edge_endpoints(eidx).unwrap();
-        let cost_f32 = 1000.0 * (communication as f32  - edge_weight(circuit, &e.0, &e.1));
+        let mut cost_f32 = 1000.0 * (communication as f32  - edge_weight(circuit, &e.0, &e.1));
+        if !circuit.partition_feedback.is_empty() {
+            let src = circuit.graph.node_weight(e.0).unwrap().name().to_string();
+            let dst = circuit.graph.node_weight(e.1).unwrap().name().to_string();
+            match circuit.partition_feedback.get(&(src, dst)) {
+                Some(hits) => { cost_f32 *= (1 + *hits) as f32; }
+                None => {}
+            }
+        }
         circuit.graph.edge_weight_mut(eidx).unwrap().weight = Some(cost_f32 as i32);
     }
 }
@@ -50,21 +62,8 @@ fn set_module(
     info.coord = Coordinate { module: module, ..info.coord };
 }
 
-/// Call the KaMinPar partitioner
-fn kaminpar_partition(
-    g: &Graph<HWNode, HWEdge, Undirected>,
-    kaminpar: &KaMinParConfig,
-    npartitions: u32
-) -> Result<Vec<u32>, KaminParError> {
-    let result = kaminpar::PartitionerBuilder::with_epsilon(kaminpar.epsilon)
-        .seed(kaminpar.seed)
-        .threads(std::num::NonZeroUsize::new(kaminpar.nthreads as usize).unwrap())
-        .partition_edge_weighted(&g, npartitions);
-    return result;
-}
-
 /// Return the histogram where `partition` is the output from the
-/// kaminpar partitioner
+/// partitioner
 fn get_partition_histogram(partition: Vec<u32>) -> Histogram {
     let mut pid_to_cnt_map: IndexMap<u32, u32> = IndexMap::new();
     for pid in partition.iter() {
@@ -84,28 +83,207 @@ fn get_partition_histogram(partition: Vec<u32>) -> Histogram {
     return histogram;
 }
 
+/// Number of clusters per module (or processor) that the unconstrained nodes
+/// are split into before filling up the capacity left by the pinned nodes
+const CLUSTERS_PER_PART: u32 = 4;
+
 /// Partition the design onto multiple modules and processors within a module
-pub fn partition(circuit: &mut Circuit) {
+/// - Nodes matching a rule of `compiler_cfg.partition_constraints` are
+///   partitioned first onto the modules (and processors) of the rule
+/// - The remaining nodes then fill up the capacity that is left on each
+///   module (and processor)
+pub fn partition(circuit: &mut Circuit) -> Result<(), CompileError> {
     let pcfg = circuit.platform_cfg.clone();
+    let constraints = PartitionConstraints::from_config(&circuit.compiler_cfg)?;
+    constraints.check(&pcfg)?;
+    let partitioner = get_partitioner(circuit.compiler_cfg.partitioner, &circuit.kaminpar_cfg)?;
+    println!("Partitioning with {}", partitioner.name());
+
+    // Nodes of each constraint rule
+    let mut rule_nodes: IndexMap<usize, Vec<NodeIndex>> = IndexMap::new();
+    for nidx in circuit.graph.node_indices() {
+        let node = circuit.graph.node_weight(nidx).unwrap();
+        match constraints.rule_of(node.name()) {
+            Some(r) => {
+                if !rule_nodes.contains_key(&r) {
+                    rule_nodes.insert(r, vec![]);
+                }
+                rule_nodes.get_mut(&r).unwrap().push(nidx);
+            }
+            None => {}
+        }
+    }
 
     // Module partition
     set_edge_weights(circuit, pcfg.inter_mod_nw_lat * 2 + pcfg.dmem_wr_lat);
-    kaminpar_partition_module(circuit);
+    if rule_nodes.is_empty() {
+        partition_module(circuit, partitioner.as_ref())?;
+    } else {
+        let mut load = vec![0u32; pcfg.num_mods as usize];
+        let mut pinned: IndexSet<NodeIndex> = IndexSet::new();
+        for (r, nodes) in rule_nodes.iter() {
+            let rule = &constraints.rules[*r];
+            println!("Partition constraint line {}: {} nodes on modules {:?}",
+                rule.line, nodes.len(), rule.modules);
+            let parts = sub_partition(circuit, partitioner.as_ref(), nodes, rule.modules.len() as u32)?;
+            for (nidx, pid) in nodes.iter().zip(parts.iter()) {
+                let module = rule.modules[*pid as usize];
+                set_module(&mut circuit.graph, *nidx, module);
+                load[module as usize] += 1;
+                pinned.insert(*nidx);
+            }
+        }
+
+        let free: Vec<NodeIndex> = circuit.graph.node_indices()
+            .filter(|nidx| !pinned.contains(nidx))
+            .collect();
+        let parts = fill_partition(circuit, partitioner.as_ref(), &free, &mut load)?;
+        for (nidx, module) in free.iter().zip(parts.iter()) {
+            set_module(&mut circuit.graph, *nidx, *module);
+        }
+        println!("Module loads with partition constraints: {:?}", load);
+    }
 
     // Processor partition
     set_edge_weights(circuit, pcfg.inter_proc_nw_lat + pcfg.dmem_wr_lat);
-    kaminpar_partition_processor(circuit);
+    partition_processor(circuit, partitioner.as_ref())?;
+
+    // Nodes pinned to processors of each module
+    let mut module_pinned: IndexMap<u32, IndexMap<usize, Vec<NodeIndex>>> = IndexMap::new();
+    for (r, nodes) in rule_nodes.iter() {
+        if constraints.rules[*r].procs.is_none() {
+            continue;
+        }
+        for nidx in nodes.iter() {
+            let module = circuit.graph.node_weight(*nidx).unwrap().info().coord.module;
+            if !module_pinned.contains_key(&module) {
+                module_pinned.insert(module, IndexMap::new());
+            }
+            let rules = module_pinned.get_mut(&module).unwrap();
+            if !rules.contains_key(r) {
+                rules.insert(*r, vec![]);
+            }
+            rules.get_mut(r).unwrap().push(*nidx);
+        }
+    }
+
+    // Repartition the modules with pinned nodes onto their processors
+    for (module, rules) in module_pinned.iter() {
+        let mut load = vec![0u32; pcfg.num_procs as usize];
+        let mut pinned: IndexSet<NodeIndex> = IndexSet::new();
+        for (r, mnodes) in rules.iter() {
+            let procs = constraints.rules[*r].procs.as_ref().unwrap();
+            let parts = sub_partition(circuit, partitioner.as_ref(), mnodes, procs.len() as u32)?;
+            for (nidx, pid) in mnodes.iter().zip(parts.iter()) {
+                let proc = procs[*pid as usize];
+                set_proc(&mut circuit.graph, *nidx, proc);
+                load[proc as usize] += 1;
+                pinned.insert(*nidx);
+            }
+        }
+
+        let free: Vec<NodeIndex> = circuit.graph.node_indices()
+            .filter(|nidx| !pinned.contains(nidx))
+            .filter(|nidx| circuit.graph.node_weight(*nidx).unwrap().info().coord.module == *module)
+            .collect();
+        let parts = fill_partition(circuit, partitioner.as_ref(), &free, &mut load)?;
+        for (nidx, proc) in free.iter().zip(parts.iter()) {
+            set_proc(&mut circuit.graph, *nidx, *proc);
+        }
+        println!("Module {} processor loads with partition constraints: {:?}", module, load);
+    }
+    return Ok(());
+}
+
+/// Assign `nodes` to the `load.len()` parts (modules or processors) that
+/// already hold `load` nodes, and add the assigned nodes to `load`.
+/// - `nodes` are partitioned into `CLUSTERS_PER_PART` clusters per part
+/// - Starting from the largest cluster, each cluster goes to the part with
+///   the lowest load so that the parts with pinned nodes don't get more
+///   nodes than the others
+fn fill_partition(
+    circuit: &Circuit,
+    partitioner: &dyn Partitioner,
+    nodes: &Vec<NodeIndex>,
+    load: &mut Vec<u32>
+) -> Result<Vec<u32>, CompileError> {
+    if nodes.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let nclusters = (load.len() as u32 * CLUSTERS_PER_PART).min(nodes.len() as u32);
+    let clusters = sub_partition(circuit, partitioner, nodes, nclusters)?;
+    let mut cluster_sizes = vec![0u32; nclusters as usize];
+    for c in clusters.iter() {
+        cluster_sizes[*c as usize] += 1;
+    }
+
+    let mut order: Vec<usize> = (0..nclusters as usize).collect();
+    order.sort_by(|a, b| cluster_sizes[*b].cmp(&cluster_sizes[*a]));
+
+    let mut cluster_part = vec![0u32; nclusters as usize];
+    for c in order.iter() {
+        let part = (0..load.len()).min_by_key(|p| load[*p]).unwrap();
+        cluster_part[*c] = part as u32;
+        load[part] += cluster_sizes[*c];
+    }
+    return Ok(clusters.iter().map(|c| cluster_part[*c as usize]).collect());
+}
+
+/// Partition the subgraph induced by `nodes` into `npartitions`
+fn sub_partition(
+    circuit: &Circuit,
+    partitioner: &dyn Partitioner,
+    nodes: &Vec<NodeIndex>,
+    npartitions: u32
+) -> Result<Vec<u32>, CompileError> {
+    if npartitions == 1 {
+        return Ok(vec![0; nodes.len()]);
+    }
+
+    let mut sg = SubGraph::default();
+    for nidx in nodes.iter() {
+        let sg_nidx = sg.subgraph.add_node(circuit.graph.node_weight(*nidx).unwrap().clone());
+        sg.to_global.insert(sg_nidx, *nidx);
+        sg.to_local.insert(*nidx, sg_nidx);
+    }
+    for eidx in circuit.graph.edge_indices() {
+        let (src, dst) = circuit.graph.edge_endpoints(eidx).unwrap();
+        match (sg.to_local.get(&src), sg.to_local.get(&dst)) {
+            (Some(s), Some(d)) => {
+                sg.subgraph.add_edge(*s, *d, circuit.graph.edge_weight(eidx).unwrap().clone());
+            }
+            _ => {}
+        }
+    }
+
+    let undir_graph = sg.subgraph.into_edge_type();
+    match partitioner.partition(&undir_graph, npartitions) {
+        Ok(partition) => {
+            assert!(partition.len() == nodes.len(),
+                "partition assignment doesn't match node cnt");
+            return Ok(partition);
+        }
+        Err(e) => {
+            return Err(CompileError::Internal {
+                pass: "partition".to_string(),
+                msg: format!("constrained {} partitioning of {} nodes failed: {}",
+                    partitioner.name(), nodes.len(), e)
+            });
+        }
+    }
 }
 
-/// Partition the circuit using the KaMinPar partitioning algorithm
-/// and assign a module ID to each node
-pub fn kaminpar_partition_module(circuit: &mut Circuit) {
-    let kaminpar = &circuit.kaminpar_cfg;
+/// Partition the circuit using `partitioner` and assign a module ID to each node
+pub fn partition_module(
+    circuit: &mut Circuit,
+    partitioner: &dyn Partitioner
+) -> Result<(), CompileError> {
     let pcfg = &circuit.platform_cfg;
     let undir_graph = circuit.graph.clone().into_edge_type();
 
     if pcfg.num_mods != 1 {
-        let result = kaminpar_partition(&undir_graph, &kaminpar, pcfg.num_mods);
+        let result = partitioner.partition(&undir_graph, pcfg.num_mods);
         match result {
             Ok(partition) => {
                 assert!(partition.len() == circuit.graph.node_count(),
@@ -119,11 +297,15 @@ pub fn kaminpar_partition_module(circuit: &mut Circuit) {
                 println!("===================================================");
 
             }
-            Err(_) => {
-                println!("Global Kaminpar partitioning failed");
+            Err(e) => {
+                return Err(CompileError::Internal {
+                    pass: "partition".to_string(),
+                    msg: format!("global {} partitioning failed: {}", partitioner.name(), e)
+                });
             }
         }
     }
+    return Ok(());
 }
 
 #[derive(Default, Debug)]
@@ -176,14 +358,16 @@ fn get_subgraphs(circuit: &Circuit) -> IndexMap<u32, SubGraph> {
 
 /// For each subgraph assigned to each module, parttion & assign a it
 /// to a processor
-pub fn kaminpar_partition_processor(circuit: &mut Circuit) {
-    let kaminpar = &circuit.kaminpar_cfg;
+pub fn partition_processor(
+    circuit: &mut Circuit,
+    partitioner: &dyn Partitioner
+) -> Result<(), CompileError> {
     let pcfg = &circuit.platform_cfg;
 
     let subgraphs = get_subgraphs(circuit);
     for (module, sg) in subgraphs.iter() {
         let undir_graph = sg.subgraph.clone().into_edge_type();
-        let result = kaminpar_partition(&undir_graph, &kaminpar, pcfg.num_procs);
+        let result = partitioner.partition(&undir_graph, pcfg.num_procs);
         match result {
             Ok(partition) => {
                 for (local_nidx, pidx) in sg.subgraph.node_indices().zip(&partition) {
@@ -194,8 +378,12 @@ pub fn kaminpar_partition_processor(circuit: &mut Circuit) {
                 println!("{}", get_partition_histogram(partition));
                 println!("===================================================");
             }
-            Err(_) => {
-                println!("Local Kaminpar partitioning failed {}", module);
+            Err(e) => {
+                return Err(CompileError::Internal {
+                    pass: "partition".to_string(),
+                    msg: format!("local {} partitioning of module {} failed: {}",
+                        partitioner.name(), module, e)
+                });
             }
         }
     }
@@ -203,4 +391,5 @@ pub fn kaminpar_partition_processor(circuit: &mut Circuit) {
     for i in 0..circuit.platform_cfg.num_mods {
         circuit.emul.module_mappings.insert(i, ModuleMapping::default());
     }
+    return Ok(());
 }
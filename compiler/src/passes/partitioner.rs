@@ -0,0 +1,439 @@
+use crate::common::{
+    config::*,
+    error::CompileError,
+    hwgraph::*
+};
+use petgraph::{graph::Graph, Undirected, visit::EdgeRef};
+use rand::{rngs::StdRng, SeedableRng, seq::SliceRandom};
+use std::collections::{BinaryHeap, VecDeque};
+
+pub type PartitionGraph = Graph<HWNode, HWEdge, Undirected>;
+
+/// # Partitioner
+/// - Splits a graph into `npartitions` parts of (roughly) equal node count
+///   while minimizing the weight of the edges crossing the parts
+/// - Edge weights are `HWEdge::weight` (set by `partition::set_edge_weights`)
+pub trait Partitioner {
+    fn name(self: &Self) -> &'static str;
+
+    /// Partition id of each node in the order of `g.node_indices()`
+    fn partition(self: &Self, g: &PartitionGraph, npartitions: u32) -> Result<Vec<u32>, String>;
+}
+
+/// Partitioner selected by `CompilerConfig::partitioner`
+pub fn get_partitioner(
+    kind: PartitionerKind,
+    cfg: &KaMinParConfig
+) -> Result<Box<dyn Partitioner>, CompileError> {
+    match kind {
+        PartitionerKind::Multilevel => {
+            Ok(Box::new(MultilevelPartitioner { seed: cfg.seed, epsilon: cfg.epsilon }))
+        }
+        #[cfg(feature = "kaminpar")]
+        PartitionerKind::KaMinPar => {
+            Ok(Box::new(KaMinParPartitioner { cfg: cfg.clone() }))
+        }
+        #[cfg(not(feature = "kaminpar"))]
+        PartitionerKind::KaMinPar => {
+            Err(CompileError::invalid_config("partitioner", kind,
+                "the compiler was built without the kaminpar feature"))
+        }
+    }
+}
+
+/// KaMinPar (C++ library) partitioner
+#[cfg(feature = "kaminpar")]
+pub struct KaMinParPartitioner {
+    cfg: KaMinParConfig
+}
+
+#[cfg(feature = "kaminpar")]
+impl Partitioner for KaMinParPartitioner {
+    fn name(self: &Self) -> &'static str {
+        "kaminpar"
+    }
+
+    fn partition(self: &Self, g: &PartitionGraph, npartitions: u32) -> Result<Vec<u32>, String> {
+        let result = kaminpar::PartitionerBuilder::with_epsilon(self.cfg.epsilon)
+            .seed(self.cfg.seed)
+            .threads(std::num::NonZeroUsize::new(self.cfg.nthreads as usize).unwrap())
+            .partition_edge_weighted(&g, npartitions);
+        match result {
+            Ok(p) => Ok(p),
+            Err(e) => Err(format!("{:?}", e))
+        }
+    }
+}
+
+/// Stop coarsening once the graph has at most this many nodes per partition
+const COARSEST_NODES_PER_PART: usize = 16;
+
+/// Number of FM passes per level
+const FM_PASSES: u32 = 8;
+
+/// Number of moves without improvement after which a FM pass gives up
+const FM_MAX_BAD_MOVES: usize = 128;
+
+/// Graph of one level of the multilevel hierarchy
+struct Level {
+    /// Neighbors and the total weight of the edges to them
+    adj: Vec<Vec<(usize, i64)>>,
+
+    /// Number of original nodes in each node
+    vwgt: Vec<i64>,
+}
+
+impl Level {
+    fn from(g: &PartitionGraph) -> Self {
+        let n = g.node_count();
+        let mut adj: Vec<Vec<(usize, i64)>> = vec![vec![]; n];
+        for e in g.edge_references() {
+            let (a, b) = (e.source().index(), e.target().index());
+            if a == b {
+                continue;
+            }
+            let w = e.weight().weight.unwrap_or(0).max(0) as i64;
+            adj[a].push((b, w));
+            adj[b].push((a, w));
+        }
+        for nbrs in adj.iter_mut() {
+            merge_parallel_edges(nbrs);
+        }
+        Level { adj: adj, vwgt: vec![1; n] }
+    }
+
+    fn len(self: &Self) -> usize {
+        self.vwgt.len()
+    }
+
+    fn total_weight(self: &Self) -> i64 {
+        self.vwgt.iter().sum()
+    }
+}
+
+fn merge_parallel_edges(nbrs: &mut Vec<(usize, i64)>) {
+    nbrs.sort();
+    let mut merged: Vec<(usize, i64)> = Vec::with_capacity(nbrs.len());
+    for (v, w) in nbrs.iter() {
+        match merged.last_mut() {
+            Some((lv, lw)) if *lv == *v => { *lw += *w; }
+            _ => { merged.push((*v, *w)); }
+        }
+    }
+    *nbrs = merged;
+}
+
+/// # MultilevelPartitioner
+/// - Pure Rust multilevel k-way partitioner
+/// - Coarsening: heavy edge matching in random order until the graph has
+///   `COARSEST_NODES_PER_PART` nodes per partition or stops shrinking
+/// - Initial partition: the coarsest graph is cut into `npartitions` chunks
+///   of equal weight in BFS order
+/// - Uncoarsening: the partition is projected back level by level and
+///   refined with k-way Fiduccia-Mattheyses passes
+/// - Parts are at most `(1 + epsilon)` times the average part weight (or the
+///   average plus the heaviest coarse node)
+pub struct MultilevelPartitioner {
+    pub seed: u64,
+    pub epsilon: f64
+}
+
+impl Partitioner for MultilevelPartitioner {
+    fn name(self: &Self) -> &'static str {
+        "multilevel"
+    }
+
+    fn partition(self: &Self, g: &PartitionGraph, npartitions: u32) -> Result<Vec<u32>, String> {
+        if npartitions == 0 {
+            return Err("Cannot partition into 0 parts".to_string());
+        }
+        let k = npartitions as usize;
+        if g.node_count() == 0 || k == 1 {
+            return Ok(vec![0; g.node_count()]);
+        }
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let finest = Level::from(g);
+        let total = finest.total_weight();
+        let avg = (total + k as i64 - 1) / k as i64;
+        let max_node_wgt = (avg / 2).max(1);
+
+        // Coarsening
+        let mut levels: Vec<Level> = vec![finest];
+        let mut cmaps: Vec<Vec<usize>> = vec![];
+        loop {
+            let cur = levels.last().unwrap();
+            if cur.len() <= COARSEST_NODES_PER_PART * k {
+                break;
+            }
+            let (coarse, cmap) = coarsen(cur, max_node_wgt, &mut rng);
+            if coarse.len() as f64 > 0.95 * cur.len() as f64 {
+                break;
+            }
+            levels.push(coarse);
+            cmaps.push(cmap);
+        }
+
+        // Initial partition of the coarsest graph
+        let coarsest = levels.last().unwrap();
+        let mut part = initial_partition(coarsest, k);
+
+        // Uncoarsening
+        for lvl in (0..levels.len()).rev() {
+            if lvl + 1 < levels.len() {
+                let cmap = &cmaps[lvl];
+                part = (0..levels[lvl].len()).map(|u| part[cmap[u]]).collect();
+            }
+            let level = &levels[lvl];
+            let heaviest = level.vwgt.iter().cloned().max().unwrap_or(1);
+            let max_pw = ((avg as f64 * (1.0 + self.epsilon)).ceil() as i64).max(avg + heaviest);
+            rebalance(level, &mut part, k, max_pw);
+            for _ in 0..FM_PASSES {
+                if fm_refine(level, &mut part, k, max_pw) <= 0 {
+                    break;
+                }
+            }
+        }
+        return Ok(part.into_iter().map(|p| p as u32).collect());
+    }
+}
+
+/// Heavy edge matching. Returns the coarse graph and the coarse node of each node
+fn coarsen(level: &Level, max_node_wgt: i64, rng: &mut StdRng) -> (Level, Vec<usize>) {
+    let n = level.len();
+    let mut order: Vec<usize> = (0..n).collect();
+    order.shuffle(rng);
+
+    let mut mate: Vec<usize> = vec![usize::MAX; n];
+    for u in order.iter() {
+        if mate[*u] != usize::MAX {
+            continue;
+        }
+        let mut best: Option<(i64, usize)> = None;
+        for (v, w) in level.adj[*u].iter() {
+            if mate[*v] != usize::MAX || level.vwgt[*u] + level.vwgt[*v] > max_node_wgt {
+                continue;
+            }
+            match best {
+                Some((bw, _)) if bw >= *w => {}
+                _ => { best = Some((*w, *v)); }
+            }
+        }
+        match best {
+            Some((_, v)) => {
+                mate[*u] = v;
+                mate[v] = *u;
+            }
+            None => {
+                mate[*u] = *u;
+            }
+        }
+    }
+
+    let mut cmap: Vec<usize> = vec![usize::MAX; n];
+    let mut cn = 0;
+    for u in 0..n {
+        if cmap[u] == usize::MAX {
+            cmap[u] = cn;
+            cmap[mate[u]] = cn;
+            cn += 1;
+        }
+    }
+
+    let mut vwgt: Vec<i64> = vec![0; cn];
+    let mut adj: Vec<Vec<(usize, i64)>> = vec![vec![]; cn];
+    for u in 0..n {
+        let cu = cmap[u];
+        vwgt[cu] += level.vwgt[u];
+        for (v, w) in level.adj[u].iter() {
+            let cv = cmap[*v];
+            if cu != cv {
+                adj[cu].push((cv, *w));
+            }
+        }
+    }
+    for nbrs in adj.iter_mut() {
+        merge_parallel_edges(nbrs);
+    }
+    return (Level { adj: adj, vwgt: vwgt }, cmap);
+}
+
+/// Cut the nodes in BFS order into `k` chunks of equal weight
+fn initial_partition(level: &Level, k: usize) -> Vec<usize> {
+    let n = level.len();
+    let total = level.total_weight();
+    let mut part: Vec<usize> = vec![0; n];
+    let mut visited: Vec<bool> = vec![false; n];
+    let mut acc = 0;
+    for root in 0..n {
+        if visited[root] {
+            continue;
+        }
+        let mut q: VecDeque<usize> = VecDeque::new();
+        visited[root] = true;
+        q.push_back(root);
+        while let Some(u) = q.pop_front() {
+            part[u] = ((acc * k as i64) / total.max(1)).min(k as i64 - 1) as usize;
+            acc += level.vwgt[u];
+            for (v, _) in level.adj[u].iter() {
+                if !visited[*v] {
+                    visited[*v] = true;
+                    q.push_back(*v);
+                }
+            }
+        }
+    }
+    return part;
+}
+
+fn part_weights(level: &Level, part: &Vec<usize>, k: usize) -> Vec<i64> {
+    let mut pw: Vec<i64> = vec![0; k];
+    for u in 0..level.len() {
+        pw[part[u]] += level.vwgt[u];
+    }
+    return pw;
+}
+
+/// Edge weight from `u` to each part
+fn connectivity(level: &Level, part: &Vec<usize>, u: usize, k: usize) -> Vec<i64> {
+    let mut conn: Vec<i64> = vec![0; k];
+    for (v, w) in level.adj[u].iter() {
+        conn[part[*v]] += *w;
+    }
+    return conn;
+}
+
+/// Best part to move `u` to without exceeding `max_pw` and the cut reduction
+fn best_move(
+    level: &Level,
+    part: &Vec<usize>,
+    pw: &Vec<i64>,
+    u: usize,
+    k: usize,
+    max_pw: i64
+) -> Option<(i64, usize)> {
+    let conn = connectivity(level, part, u, k);
+    let from = part[u];
+    let mut best: Option<(i64, usize)> = None;
+    for q in 0..k {
+        if q == from || conn[q] == 0 || pw[q] + level.vwgt[u] > max_pw {
+            continue;
+        }
+        let gain = conn[q] - conn[from];
+        match best {
+            Some((bg, bq)) if bg > gain || (bg == gain && pw[bq] <= pw[q]) => {}
+            _ => { best = Some((gain, q)); }
+        }
+    }
+    return best;
+}
+
+/// Move nodes out of the parts heavier than `max_pw`, losing as little as possible
+fn rebalance(level: &Level, part: &mut Vec<usize>, k: usize, max_pw: i64) {
+    let mut pw = part_weights(level, part, k);
+    for p in 0..k {
+        if pw[p] <= max_pw {
+            continue;
+        }
+        let mut cands: Vec<(i64, usize)> = (0..level.len())
+            .filter(|u| part[*u] == p)
+            .map(|u| {
+                let conn = connectivity(level, part, u, k);
+                (conn[p] - conn.iter().enumerate()
+                    .filter(|(q, _)| *q != p)
+                    .map(|(_, c)| *c)
+                    .max()
+                    .unwrap_or(0), u)
+            })
+            .collect();
+        cands.sort();
+        for (_, u) in cands.iter() {
+            if pw[p] <= max_pw {
+                break;
+            }
+            let conn = connectivity(level, part, *u, k);
+            let target = (0..k)
+                .filter(|q| *q != p && pw[*q] + level.vwgt[*u] <= max_pw)
+                .max_by_key(|q| (conn[*q], -pw[*q]));
+            match target {
+                Some(q) => {
+                    pw[p] -= level.vwgt[*u];
+                    pw[q] += level.vwgt[*u];
+                    part[*u] = q;
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+/// One k-way Fiduccia-Mattheyses pass. Each node moves at most once, moves are
+/// taken in the order of their gain (which may be negative) and the pass is
+/// rolled back to the prefix with the best total gain. Returns that gain
+fn fm_refine(level: &Level, part: &mut Vec<usize>, k: usize, max_pw: i64) -> i64 {
+    let n = level.len();
+    let mut pw = part_weights(level, part, k);
+    let mut heap: BinaryHeap<(i64, usize, usize)> = BinaryHeap::new();
+    for u in 0..n {
+        let boundary = level.adj[u].iter().any(|(v, _)| part[*v] != part[u]);
+        if !boundary {
+            continue;
+        }
+        match best_move(level, part, &pw, u, k, max_pw) {
+            Some((gain, q)) => heap.push((gain, u, q)),
+            None => {}
+        }
+    }
+
+    let mut moved: Vec<bool> = vec![false; n];
+    let mut moves: Vec<(usize, usize)> = vec![];
+    let mut cum = 0;
+    let mut best = 0;
+    let mut best_len = 0;
+    while let Some((gain, u, q)) = heap.pop() {
+        if moved[u] {
+            continue;
+        }
+        match best_move(level, part, &pw, u, k, max_pw) {
+            Some((g, t)) if g == gain && t == q => {}
+            Some((g, t)) => {
+                heap.push((g, u, t));
+                continue;
+            }
+            None => {
+                continue;
+            }
+        }
+
+        let from = part[u];
+        pw[from] -= level.vwgt[u];
+        pw[q] += level.vwgt[u];
+        part[u] = q;
+        moved[u] = true;
+        moves.push((u, from));
+        cum += gain;
+        if cum > best {
+            best = cum;
+            best_len = moves.len();
+        } else if moves.len() - best_len > FM_MAX_BAD_MOVES {
+            break;
+        }
+
+        for (v, _) in level.adj[u].iter() {
+            if moved[*v] {
+                continue;
+            }
+            match best_move(level, part, &pw, *v, k, max_pw) {
+                Some((g, t)) => heap.push((g, *v, t)),
+                None => {}
+            }
+        }
+    }
+
+    // Roll back the moves after the best prefix
+    while moves.len() > best_len {
+        let (u, from) = moves.pop().unwrap();
+        part[u] = from;
+    }
+    return best;
+}
@@ -6,4 +6,11 @@ pub fn print_stats(circuit: &Circuit) {
     let nodes = circuit.graph.node_count();
     let edges = circuit.graph.edge_count();
     println!("Connected components: {} V: {} E: {}", cc, nodes, edges);
+
+    let opt = &circuit.opt_stats;
+    println!("Netlist optimizations: {} nodes removed", opt.nodes_removed);
+    println!("- constant inputs folded: {} constant LUTs: {}", opt.const_inputs_folded, opt.const_luts);
+    println!("- buffers absorbed: {} inverters absorbed: {}", opt.buffers_absorbed, opt.inverters_absorbed);
+    println!("- duplicate LUTs merged: {}", opt.luts_merged);
+    println!("Gate enables split into LUTs: {}", opt.gate_enables_split);
 }
@@ -0,0 +1,83 @@
+use crate::common::{
+    circuit::Circuit,
+    network::*,
+};
+use crate::passes::inst_schedule::is_timing_source;
+use indexmap::IndexSet;
+use petgraph::{
+    graph::NodeIndex,
+    visit::EdgeRef,
+    Direction::Incoming
+};
+
+/// Nodes scheduled in the last `1 / CRITICAL_TAIL_FRACTION` of the schedule
+/// are the endpoints of the critical paths
+const CRITICAL_TAIL_FRACTION: u32 = 20;
+
+/// An edge is on a critical path when its destination was scheduled at most
+/// this many steps after the bit arrived
+const CRITICAL_SLACK: u32 = 1;
+
+/// # critical_inter_mod_edges
+/// - Must run after `schedule_instructions`
+/// - Walks back from the nodes at the end of the schedule through the edges
+///   whose bit arrived just in time for the child to be scheduled
+/// - Returns the (source, destination) names of the edges on these paths that
+///   went over the global network, i.e. the ones that lengthened `host_steps`
+pub fn critical_inter_mod_edges(circuit: &Circuit) -> IndexSet<(String, String)> {
+    let pcfg = &circuit.platform_cfg;
+    let mut last_pc = 0;
+    for nidx in circuit.graph.node_indices() {
+        last_pc = last_pc.max(circuit.graph.node_weight(nidx).unwrap().info().pc);
+    }
+    let tail_start = last_pc.saturating_sub((last_pc / CRITICAL_TAIL_FRACTION).max(1));
+
+    let mut stack: Vec<NodeIndex> = vec![];
+    let mut visited: IndexSet<NodeIndex> = IndexSet::new();
+    for nidx in circuit.graph.node_indices() {
+        if circuit.graph.node_weight(nidx).unwrap().info().pc >= tail_start {
+            stack.push(nidx);
+            visited.insert(nidx);
+        }
+    }
+
+    let mut ret: IndexSet<(String, String)> = IndexSet::new();
+    while let Some(nidx) = stack.pop() {
+        let node = circuit.graph.node_weight(nidx).unwrap();
+        if is_timing_source(node.is()) {
+            continue;
+        }
+        for pedge in circuit.graph.edges_directed(nidx, Incoming) {
+            let route = match &pedge.weight().route {
+                Some(r) => r,
+                None => { continue; }
+            };
+            let parent = circuit.graph.node_weight(pedge.source()).unwrap();
+            let arrival = parent.info().pc + pcfg.nw_route_dep_lat(route);
+            if arrival + CRITICAL_SLACK < node.info().pc {
+                continue;
+            }
+            if pcfg.nw_route_type(route) == PathTypes::InterModule {
+                ret.insert((parent.name().to_string(), node.name().to_string()));
+            }
+            if !visited.contains(&pedge.source()) {
+                visited.insert(pedge.source());
+                stack.push(pedge.source());
+            }
+        }
+    }
+    return ret;
+}
+
+/// Record the critical inter-module edges of the current schedule in
+/// `circuit.partition_feedback`. Returns the number of such edges
+pub fn add_partition_feedback(circuit: &mut Circuit) -> usize {
+    let edges = critical_inter_mod_edges(circuit);
+    for e in edges.iter() {
+        match circuit.partition_feedback.get_mut(e) {
+            Some(hits) => { *hits += 1; }
+            None => { circuit.partition_feedback.insert(e.clone(), 1); }
+        }
+    }
+    return edges.len();
+}
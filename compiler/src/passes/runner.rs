@@ -1,75 +1,325 @@
 use crate::passes::*;
 use crate::common::circuit::Circuit;
+use crate::common::config::{CompilerPass, GraphDumpFormat, PassCheck, PassManagerConfig};
+use crate::common::error::CompileError;
+use crate::common::utils::write_string_to_file;
 use partition::partition;
 use prepartition_set_rank::{init_rank_order, prepartition_find_rank_order};
 use split_sram_nodes::split_sram_nodes;
+use split_large_srams::split_large_srams;
 use split_reg_nodes::split_reg_nodes;
+use lower_gates::lower_gates;
+use clock_domains::clock_domains;
 use replicate_consts::replicate_consts;
 use dce::dead_code_elimination;
+use lut_map::lut_map;
+use optimize::{const_prop, absorb_buffers, strash};
 use inst_map::map_instructions;
 use inst_schedule::schedule_instructions;
+use compact_schedule::compact_schedule;
 use set_rank::find_rank_order;
 use check_rank::check_rank_order;
+use check_connectivity::check_connectivity;
+use check_program::check_program;
 use distribute_io::distribute_io;
 use print_stats::print_stats;
+use repartition::add_partition_feedback;
+use serde::Serialize;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::time::Instant;
-// use check_connectivity::check_connectivity;
-
-pub fn run_compiler_passes(c: &mut Circuit) {
-    let dce_start = Instant::now();
-    dead_code_elimination(c);
-    let dce_time = dce_start.elapsed().as_millis();
-    println!("DCE done");
-
-    let partition_start = Instant::now();
-    prepartition_find_rank_order(c);
-    partition(c);
-    init_rank_order(c);
-    let partition_time = partition_start.elapsed().as_millis();
-    println!("Partition done");
-
-    let sram_const_start = Instant::now();
-    split_reg_nodes(c);
-// check_connectivity(c);
-
-    split_sram_nodes(c);
-// check_connectivity(c);
-
-    replicate_consts(c);
-// check_connectivity(c);
-
-    distribute_io(c);
-
-    let sram_const_time = sram_const_start.elapsed().as_millis();
-    println!("SRAM and constant replication done");
-
-    let rank_start = Instant::now();
-    find_rank_order(c);
-    check_rank_order(c);
-    let rank_time = rank_start.elapsed().as_millis();
-    println!("Set rank order done");
-
-    let schedule_start = Instant::now();
-    schedule_instructions(c);
-    let schedule_time = schedule_start.elapsed().as_millis();
-    println!("Scheduling done");
-
-    let map_start = Instant::now();
-    map_instructions(c);
-    let map_time = map_start.elapsed().as_millis();
-    println!("Mapping done");
-
-    print_stats(c);
-
-    let compiler_time = dce_time + rank_time + partition_time + sram_const_time + schedule_time + map_time;
-    println!("===============================");
-    println!("Compiler Execution Time");
-    println!("===============================");
-    println!("DCE       : {} % {} ms", dce_time         as f32 / compiler_time as f32 * 100f32, dce_time);
-    println!("rank      : {} % {} ms", rank_time        as f32 / compiler_time as f32 * 100f32, rank_time);
-    println!("partition : {} % {} ms", partition_time   as f32 / compiler_time as f32 * 100f32, partition_time);
-    println!("sram_const: {} % {} ms", sram_const_time  as f32 / compiler_time as f32 * 100f32, sram_const_time);
-    println!("schedule  : {} % {} ms", schedule_time    as f32 / compiler_time as f32 * 100f32, schedule_time);
-    println!("map       : {} % {} ms", map_time         as f32 / compiler_time as f32 * 100f32, map_time);
-    println!("===============================");
+
+/// Wall time and graph size change of a single pass
+#[derive(Serialize, Debug, Clone)]
+pub struct PassStats {
+    pub pass: CompilerPass,
+
+    /// Wall time of the pass in milliseconds (checks and dumps excluded)
+    pub time_ms: u128,
+
+    pub nodes_before: usize,
+    pub nodes_after: usize,
+    pub edges_before: usize,
+    pub edges_after: usize,
+}
+
+impl PassStats {
+    pub fn node_delta(self: &Self) -> i64 {
+        self.nodes_after as i64 - self.nodes_before as i64
+    }
+
+    pub fn edge_delta(self: &Self) -> i64 {
+        self.edges_after as i64 - self.edges_before as i64
+    }
+}
+
+/// Per pass statistics in the order that the passes ran
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct PassReport {
+    pub passes: Vec<PassStats>,
+    pub total_ms: u128,
+
+    /// Host steps of the initial schedule and of each repartitioning iteration
+    pub repartition_host_steps: Vec<u32>,
+}
+
+impl PassReport {
+    pub fn print(self: &Self) {
+        println!("===============================");
+        println!("Compiler Execution Time");
+        println!("===============================");
+        for s in self.passes.iter() {
+            println!("{:<24}: {:>6.2} % {} ms V: {:+} E: {:+}",
+                s.pass.name(),
+                s.time_ms as f32 / self.total_ms.max(1) as f32 * 100f32,
+                s.time_ms,
+                s.node_delta(),
+                s.edge_delta());
+        }
+        if !self.repartition_host_steps.is_empty() {
+            println!("Repartition host_steps: {:?}", self.repartition_host_steps);
+        }
+        println!("===============================");
+    }
+
+    /// Save the report as `<output_dir>/<top>.passes.json`
+    pub fn save(self: &Self, circuit: &Circuit) -> Result<(), CompileError> {
+        let ccfg = &circuit.compiler_cfg;
+        let file_path = format!("{}/{}.passes.json", ccfg.output_dir, ccfg.top_module);
+        write_string_to_file(serde_json::to_string_pretty(self)?, &file_path)?;
+        Ok(())
+    }
+}
+
+fn run_pass(c: &mut Circuit, pass: &CompilerPass) -> Result<(), CompileError> {
+    match pass {
+        CompilerPass::Dce                   => dead_code_elimination(c),
+        CompilerPass::LutMap                => lut_map(c)?,
+        CompilerPass::ConstProp             => const_prop(c)?,
+        CompilerPass::AbsorbBuffers         => absorb_buffers(c)?,
+        CompilerPass::Strash                => strash(c)?,
+        CompilerPass::ClockDomains          => clock_domains(c),
+        CompilerPass::LowerGates            => lower_gates(c)?,
+        CompilerPass::SplitLargeSrams       => split_large_srams(c)?,
+        CompilerPass::PrepartitionRankOrder => prepartition_find_rank_order(c),
+        CompilerPass::Partition             => partition(c)?,
+        CompilerPass::InitRankOrder         => init_rank_order(c),
+        CompilerPass::SplitRegNodes         => split_reg_nodes(c),
+        CompilerPass::SplitSramNodes        => split_sram_nodes(c)?,
+        CompilerPass::ReplicateConsts       => replicate_consts(c),
+        CompilerPass::DistributeIo          => distribute_io(c)?,
+        CompilerPass::FindRankOrder         => find_rank_order(c),
+        CompilerPass::CheckRankOrder        => check_rank_order(c),
+        CompilerPass::CheckConnectivity     => check_connectivity(c),
+        CompilerPass::ScheduleInstructions  => schedule_instructions(c)?,
+        CompilerPass::CompactSchedule       => compact_schedule(c),
+        CompilerPass::MapInstructions       => map_instructions(c)?,
+        CompilerPass::VerifyProgram         => check_program(c)?,
+        CompilerPass::PrintStats            => print_stats(c),
+    }
+    return Ok(());
+}
+
+fn run_checks(c: &Circuit, checks: &Vec<PassCheck>) {
+    for check in checks.iter() {
+        match check {
+            PassCheck::Connectivity => check_connectivity(c),
+            PassCheck::RankOrder    => check_rank_order(c),
+        }
+    }
+}
+
+/// Run `f` and turn the failed `assert!`s within it into `CompileError::Internal`
+pub fn catch_internal<F>(pass: &str, f: F) -> Result<(), CompileError>
+where
+    F: FnOnce() -> Result<(), CompileError>
+{
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(res) => res,
+        Err(payload) => {
+            let msg = if let Some(s) = payload.downcast_ref::<String>() {
+                s.clone()
+            } else if let Some(s) = payload.downcast_ref::<&str>() {
+                s.to_string()
+            } else {
+                "unknown panic".to_string()
+            };
+            Err(CompileError::Internal { pass: pass.to_string(), msg: msg })
+        }
+    }
+}
+
+fn dump_graph(c: &Circuit, pfx: &str, fmt: &GraphDumpFormat) -> std::io::Result<()> {
+    match fmt {
+        GraphDumpFormat::Dot  => c.save_graph_dot(pfx),
+        GraphDumpFormat::Json => c.save_graph_json(pfx),
+    }
+}
+
+/// Run `pass` (the `i`th pass of the pipeline) with the requested checks and
+/// dumps around it and record its statistics in `report`
+fn run_managed_pass(
+    c: &mut Circuit,
+    i: usize,
+    pass: &CompilerPass,
+    pass_cfg: &PassManagerConfig,
+    report: &mut PassReport,
+    dump: bool
+) -> Result<(), CompileError> {
+    if dump && pass_cfg.dump_before.contains(pass) {
+        dump_graph(c, &format!("{}-before-{}", i, pass.name()), &pass_cfg.dump_format)?;
+    }
+
+    let nodes_before = c.graph.node_count();
+    let edges_before = c.graph.edge_count();
+    let start = Instant::now();
+    catch_internal(&pass.name(), || run_pass(c, pass))?;
+    let time_ms = start.elapsed().as_millis();
+    println!("{} done", pass.name());
+
+    report.total_ms += time_ms;
+    report.passes.push(PassStats {
+        pass: *pass,
+        time_ms: time_ms,
+        nodes_before: nodes_before,
+        nodes_after: c.graph.node_count(),
+        edges_before: edges_before,
+        edges_after: c.graph.edge_count(),
+    });
+
+    if pass_cfg.should_check_after(pass) {
+        catch_internal(&format!("checks after {}", pass.name()), || {
+            run_checks(c, &pass_cfg.checks);
+            Ok(())
+        })?;
+    }
+
+    if dump && pass_cfg.dump_after.contains(pass) {
+        dump_graph(c, &format!("{}-after-{}", i, pass.name()), &pass_cfg.dump_format)?;
+    }
+    return Ok(());
+}
+
+/// # `repartition`
+/// - `c` is scheduled and `snapshot` is the circuit right before the pass at
+///   `pipeline[part_idx]` (`Partition`)
+/// - Reruns `pipeline[part_idx..=sched_idx]` (up to the final schedule) on copies of `snapshot` while
+///   weighting up the inter-module edges that were critical in the previous
+///   schedules (`passes::repartition`)
+/// - Stops after `compiler_cfg.repartition_iters` iterations or when
+///   `compiler_cfg.repartition_time_limit` runs out, and keeps the schedule
+///   with the fewest host steps in `c`
+/// - Iterations that do not fit on the platform (e.g. exceed `max_steps`)
+///   are discarded
+fn repartition(
+    c: &mut Circuit,
+    snapshot: &Circuit,
+    pipeline: &Vec<CompilerPass>,
+    part_idx: usize,
+    sched_idx: usize,
+    report: &mut PassReport
+) -> Result<(), CompileError> {
+    let ccfg = c.compiler_cfg.clone();
+    let pass_cfg = ccfg.pass_cfg.clone();
+    let start = Instant::now();
+    let mut last = c.clone();
+    let mut iter = 0;
+
+    report.repartition_host_steps.push(c.emul.host_steps);
+    loop {
+        if ccfg.repartition_iters != 0 && iter >= ccfg.repartition_iters {
+            break;
+        }
+        match ccfg.repartition_time_limit {
+            Some(secs) if start.elapsed().as_secs() >= secs => { break; }
+            _ => {}
+        }
+        iter += 1;
+
+        let critical = add_partition_feedback(&mut last);
+        if critical == 0 {
+            println!("Repartition {}: no critical inter-module edges", iter);
+            break;
+        }
+
+        let mut trial = snapshot.clone();
+        trial.partition_feedback = last.partition_feedback.clone();
+        let mut result = Ok(());
+        for i in part_idx..=sched_idx {
+            result = run_managed_pass(&mut trial, i, &pipeline[i], &pass_cfg, report, false);
+            if result.is_err() {
+                break;
+            }
+        }
+        match result {
+            Ok(_) => {
+                println!("Repartition {}: {} critical edges, host_steps {} (best {})",
+                    iter, critical, trial.emul.host_steps, c.emul.host_steps);
+                report.repartition_host_steps.push(trial.emul.host_steps);
+                if trial.emul.host_steps < c.emul.host_steps {
+                    *c = trial.clone();
+                }
+                last = trial;
+            }
+            Err(e) if e.is_capacity() => {
+                println!("Repartition {}: discarded ({})", iter, e);
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+    }
+    c.partition_feedback = last.partition_feedback;
+    return Ok(());
+}
+
+/// # `run_compiler_passes`
+/// - Runs the passes in `compiler_cfg.pass_cfg` (the full pipeline by default)
+/// - Dumps `<top>.<i>-{before,after}-<pass>.{dot,json}` for the requested passes
+///   where `i` is the position of the pass in the pipeline
+/// - Runs the requested checks after each pass
+/// - Repartitions after `ScheduleInstructions` (and `CompactSchedule`) when
+///   `compiler_cfg.repartition_iters` or `compiler_cfg.repartition_time_limit`
+///   is set (see `repartition`)
+/// - Returns the per pass statistics which are also saved in `<top>.passes.json`
+///   when `pass_cfg.save_pass_report` is set
+/// - Returns the `CompileError` of the first pass that fails. Failed internal
+///   assertions are reported as `CompileError::Internal`
+pub fn run_compiler_passes(c: &mut Circuit) -> Result<PassReport, CompileError> {
+    let pass_cfg = c.compiler_cfg.pass_cfg.clone();
+    let pipeline = pass_cfg.pipeline()?;
+    let mut report = PassReport::default();
+
+    let repartition_enabled = c.compiler_cfg.repartition_iters != 0 ||
+                              c.compiler_cfg.repartition_time_limit.is_some();
+    let part_idx = pipeline.iter().position(|p| *p == CompilerPass::Partition);
+    let mut snapshot: Option<Circuit> = None;
+
+    for (i, pass) in pipeline.iter().enumerate() {
+        if repartition_enabled && Some(i) == part_idx {
+            snapshot = Some(c.clone());
+        }
+
+        run_managed_pass(c, i, pass, &pass_cfg, &mut report, true)?;
+
+        // The schedule is final after `CompactSchedule` when it directly
+        // follows `ScheduleInstructions`
+        let schedule_done = *pass == CompilerPass::CompactSchedule ||
+            (*pass == CompilerPass::ScheduleInstructions &&
+             pipeline.get(i + 1) != Some(&CompilerPass::CompactSchedule));
+        if schedule_done {
+            match (&snapshot, part_idx) {
+                (Some(snap), Some(pidx)) => {
+                    repartition(c, snap, &pipeline, pidx, i, &mut report)?;
+                    snapshot = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    report.print();
+    if pass_cfg.save_pass_report {
+        report.save(c)?;
+    }
+    return Ok(report);
 }
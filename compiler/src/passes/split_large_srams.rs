@@ -0,0 +1,517 @@
+use indexmap::IndexMap;
+use crate::common::{
+    circuit::Circuit,
+    config::PlatformConfig,
+    error::CompileError,
+    primitive::*,
+    hwgraph::*
+};
+use petgraph::{
+    graph::NodeIndex, visit::EdgeRef, Direction::{Incoming, Outgoing}
+};
+use blif_parser::primitives::LatchInit;
+
+type PortConn = (NodeIndex, HWEdge);
+
+/// Ports of a target SRAM blackbox
+#[derive(Debug, Default)]
+struct SRAMPorts {
+    /// Blackbox name
+    name: String,
+
+    /// Target SRAM port of a multi-ported SRAM (empty otherwise)
+    port: String,
+
+    /// Part of the target SRAM stored in this blackbox
+    region: SRAMRegion,
+
+    /// Net driving the clock port
+    clock: String,
+
+    /// True for `SinglePortSRAM`s (RW0 port)
+    single_port: bool,
+
+    /// SRAMRdEn or SRAMRdWrEn
+    rd_en: Option<PortConn>,
+
+    /// SRAMWrEn or SRAMRdWrMode
+    wr_en: Option<PortConn>,
+
+    /// SRAMRdAddr or SRAMRdWrAddr indexed by the address bit
+    rd_addr: IndexMap<u32, PortConn>,
+
+    /// SRAMWrAddr indexed by the address bit
+    wr_addr: IndexMap<u32, PortConn>,
+
+    /// SRAMWrData indexed by the data bit
+    wr_data: IndexMap<u32, PortConn>,
+
+    /// SRAMWrMask indexed by the mask bit
+    wr_mask: IndexMap<u32, PortConn>,
+
+    /// Consumers of each read data bit (child node, SRAMRdData edge)
+    rd_data: IndexMap<u32, Vec<PortConn>>,
+}
+
+impl SRAMPorts {
+    fn addr_bits(self: &Self) -> u32 {
+        self.rd_addr.len() as u32
+    }
+
+    fn width_bits(self: &Self) -> u32 {
+        let mut w = self.wr_data.len() as u32;
+        for (idx, _) in self.rd_data.iter() {
+            w = std::cmp::max(w, idx + 1);
+        }
+        return w;
+    }
+}
+
+fn collect_sram_ports(circuit: &Circuit, nidx: NodeIndex) -> SRAMPorts {
+    let mut ports = SRAMPorts::default();
+    let node = circuit.graph.node_weight(nidx).unwrap();
+    match &node.prim {
+        CircuitPrimitive::SRAMNode { name, conns, port, region } => {
+            ports.name = name.to_string();
+            ports.port = port.to_string();
+            ports.region = region.clone();
+            for (port, wire) in conns.iter() {
+                if port.ends_with("_clk") {
+                    ports.clock = wire.to_string();
+                }
+            }
+        }
+        _ => {
+            assert!(false, "Node {:?} is not a SRAMNode", nidx);
+        }
+    }
+
+    for pedge in circuit.graph.edges_directed(nidx, Incoming) {
+        let conn = (pedge.source(), pedge.weight().clone());
+        match &pedge.weight().signal {
+            SignalType::SRAMRdEn     { .. }     => { ports.rd_en = Some(conn); }
+            SignalType::SRAMWrEn     { .. }     => { ports.wr_en = Some(conn); }
+            SignalType::SRAMRdAddr   { idx, .. } => { ports.rd_addr.insert(*idx, conn); }
+            SignalType::SRAMWrAddr   { idx, .. } => { ports.wr_addr.insert(*idx, conn); }
+            SignalType::SRAMWrData   { idx, .. } => { ports.wr_data.insert(*idx, conn); }
+            SignalType::SRAMWrMask   { idx, .. } => { ports.wr_mask.insert(*idx, conn); }
+            SignalType::SRAMRdWrEn   { .. }     => {
+                ports.single_port = true;
+                ports.rd_en = Some(conn);
+            }
+            SignalType::SRAMRdWrMode { .. }     => {
+                ports.single_port = true;
+                ports.wr_en = Some(conn);
+            }
+            SignalType::SRAMRdWrAddr { idx, .. } => {
+                ports.single_port = true;
+                ports.rd_addr.insert(*idx, conn);
+            }
+            _ => { }
+        }
+    }
+
+    for cedge in circuit.graph.edges_directed(nidx, Outgoing) {
+        match &cedge.weight().signal {
+            SignalType::SRAMRdData { idx, .. } => {
+                if !ports.rd_data.contains_key(idx) {
+                    ports.rd_data.insert(*idx, vec![]);
+                }
+                ports.rd_data.get_mut(idx).unwrap()
+                    .push((cedge.target(), cedge.weight().clone()));
+            }
+            _ => { }
+        }
+    }
+
+    ports.rd_addr.sort_keys();
+    ports.wr_addr.sort_keys();
+    ports.wr_data.sort_keys();
+    ports.wr_mask.sort_keys();
+    ports.rd_data.sort_keys();
+    return ports;
+}
+
+/// Name of the net carried by `sig`
+fn signal_name(sig: &SignalType) -> String {
+    sig.name().to_string()
+}
+
+fn wire(sig: &SignalType) -> SignalType {
+    SignalType::Wire { name: signal_name(sig) }
+}
+
+/// Returns `sig` with its bit index replaced by `new_idx`
+fn reindex_signal(sig: &SignalType, new_idx: u32) -> SignalType {
+    match sig {
+        SignalType::SRAMRdAddr   { name, .. } => SignalType::SRAMRdAddr   { name: name.clone(), idx: new_idx },
+        SignalType::SRAMRdData   { name, .. } => SignalType::SRAMRdData   { name: name.clone(), idx: new_idx },
+        SignalType::SRAMWrAddr   { name, .. } => SignalType::SRAMWrAddr   { name: name.clone(), idx: new_idx },
+        SignalType::SRAMWrMask   { name, .. } => SignalType::SRAMWrMask   { name: name.clone(), idx: new_idx },
+        SignalType::SRAMWrData   { name, .. } => SignalType::SRAMWrData   { name: name.clone(), idx: new_idx },
+        SignalType::SRAMRdWrAddr { name, .. } => SignalType::SRAMRdWrAddr { name: name.clone(), idx: new_idx },
+        _ => sig.clone()
+    }
+}
+
+/// Returns a enable signal of the same port type as `sig` driven by `name`
+fn rename_enable_signal(sig: &SignalType, name: &str) -> SignalType {
+    match sig {
+        SignalType::SRAMRdEn     { .. } => SignalType::SRAMRdEn     { name: name.to_string() },
+        SignalType::SRAMWrEn     { .. } => SignalType::SRAMWrEn     { name: name.to_string() },
+        SignalType::SRAMRdWrEn   { .. } => SignalType::SRAMRdWrEn   { name: name.to_string() },
+        SignalType::SRAMRdWrMode { .. } => SignalType::SRAMRdWrMode { name: name.to_string() },
+        _ => SignalType::Wire { name: name.to_string() }
+    }
+}
+
+/// Adds a LUT named `output` that computes `f` over `inputs`.
+/// Each input is given as (parent node, signal from the parent).
+fn add_lut<F>(
+    circuit: &mut Circuit,
+    output: &str,
+    inputs: &Vec<(NodeIndex, SignalType)>,
+    f: F
+) -> NodeIndex
+where
+    F: Fn(&Vec<u8>) -> bool
+{
+    let n = inputs.len();
+    assert!(n as u32 <= circuit.platform_cfg.lut_inputs,
+        "LUT {} has {} inputs, platform supports {}",
+        output, n, circuit.platform_cfg.lut_inputs);
+
+    let mut table = vec![];
+    for row in 0..(1u32 << n) {
+        let vals: Vec<u8> = (0..n).map(|i| ((row >> i) & 1) as u8).collect();
+        if f(&vals) {
+            table.push(vals);
+        }
+    }
+
+    let lut = CircuitPrimitive::Lut {
+        inputs: inputs.iter().map(|(_, sig)| signal_name(sig)).collect(),
+        output: output.to_string(),
+        table: table
+    };
+    let lidx = circuit.graph.add_node(HWNode::new(lut));
+    for (pidx, sig) in inputs.iter() {
+        circuit.graph.add_edge(*pidx, lidx, HWEdge::new(sig.clone()));
+    }
+    return lidx;
+}
+
+/// Returns `en & (sel == bank)` where `sel` are the upper address bits
+fn add_bank_decoder(
+    circuit: &mut Circuit,
+    prefix: &str,
+    en: &PortConn,
+    sel: &Vec<PortConn>,
+    bank: u32
+) -> (NodeIndex, String) {
+    let mut acc = (en.0, wire(&en.1.signal));
+    for (j, (sidx, sedge)) in sel.iter().enumerate() {
+        let bit = ((bank >> j) & 1) as u8;
+        let name = format!("{}.dec{}", prefix, j);
+        let inputs = vec![acc.clone(), (*sidx, wire(&sedge.signal))];
+        let lidx = add_lut(circuit, &name, &inputs, |v| v[0] == 1 && v[1] == bit);
+        acc = (lidx, SignalType::Wire { name: name });
+    }
+    return (acc.0, signal_name(&acc.1));
+}
+
+/// Registers the bank select bits of the last enabled read so that the read
+/// data of the next cycle can be steered to the correct bank
+fn add_read_select_regs(
+    circuit: &mut Circuit,
+    ports: &SRAMPorts,
+    sel: &Vec<PortConn>
+) -> Vec<(NodeIndex, SignalType)> {
+    let prefix = format!("{}.rdsel", ports.name);
+    let en = ports.rd_en.as_ref().unwrap();
+    let mut ren = (en.0, wire(&en.1.signal));
+
+    // Single port SRAMs only read when the write mode is low
+    if ports.single_port {
+        let wmode = ports.wr_en.as_ref().unwrap();
+        let name = format!("{}.ren", prefix);
+        let inputs = vec![ren.clone(), (wmode.0, wire(&wmode.1.signal))];
+        let lidx = add_lut(circuit, &name, &inputs, |v| v[0] == 1 && v[1] == 0);
+        ren = (lidx, SignalType::Wire { name: name });
+    }
+
+    let mut regs = vec![];
+    for (j, (aidx, aedge)) in sel.iter().enumerate() {
+        let d = format!("{}.d{}", prefix, j);
+        let q = format!("{}.q{}", prefix, j);
+        let latch = CircuitPrimitive::Latch {
+            input: d.clone(),
+            output: q.clone(),
+            control: ports.clock.clone(),
+            init: LatchInit::ZERO
+        };
+        let qidx = circuit.graph.add_node(HWNode::new(latch));
+
+        // q <= ren ? addr : q
+        let inputs = vec![
+            ren.clone(),
+            (*aidx, wire(&aedge.signal)),
+            (qidx, SignalType::Wire { name: q.clone() })
+        ];
+        let didx = add_lut(circuit, &d, &inputs, |v| if v[0] == 1 { v[1] == 1 } else { v[2] == 1 });
+        circuit.graph.add_edge(didx, qidx, HWEdge::new(SignalType::Wire { name: d.clone() }));
+        regs.push((qidx, SignalType::Wire { name: q }));
+    }
+    return regs;
+}
+
+/// - split_large_srams
+/// Target SRAMs that are wider than `sram_width` or deeper than
+/// `sram_entries` can't be mapped to a single SRAM processor.
+/// Split them into width slices and depth banks that each fit in a SRAM
+/// processor. The bank decode logic and the read data mux are added as LUTs
+/// so that the SRAM processors themselves need no changes.
+pub fn split_large_srams(circuit: &mut Circuit) -> Result<(), CompileError> {
+    let pcfg = circuit.platform_cfg.clone();
+    if !pcfg.sram_entries.is_power_of_two() {
+        return Err(CompileError::invalid_config(
+            "sram_entries", pcfg.sram_entries, "should be a power of two"));
+    }
+
+    let mut large_srams: Vec<NodeIndex> = vec![];
+    for nidx in circuit.graph.node_indices() {
+        let node = circuit.graph.node_weight(nidx).unwrap();
+        if node.is() != Primitive::SRAMNode {
+            continue;
+        }
+        let ports = collect_sram_ports(circuit, nidx);
+        if ports.width_bits() > pcfg.sram_width ||
+           (1u64 << ports.addr_bits()) > pcfg.sram_entries as u64 {
+            // Splitting multi-ported SRAMs is not supported
+            if !ports.port.is_empty() {
+                return Err(CompileError::invalid_config(
+                    "sram", &ports.name,
+                    &format!("port {} of a multi-ported SRAM is larger than the SRAM processor, and multi-ported SRAMs can't be split",
+                        ports.port)));
+            }
+
+            // The bits covered by a write mask bit can't be split across slices
+            if slice_width(&pcfg, &ports) == 0 {
+                return Err(CompileError::SRAMDoesNotFit {
+                    sram: ports.name.clone(),
+                    width: ports.width_bits(),
+                    entries: 1u64 << ports.addr_bits(),
+                    sram_width: pcfg.sram_width,
+                    sram_entries: pcfg.sram_entries
+                });
+            }
+            large_srams.push(nidx);
+        }
+    }
+
+    // The read select registers and the read data mux are built from
+    // 3 input LUTs
+    if !large_srams.is_empty() && pcfg.lut_inputs < 3 {
+        return Err(CompileError::invalid_config(
+            "lut_inputs", pcfg.lut_inputs, "should be at least 3 to split large SRAMs"));
+    }
+
+    for nidx in large_srams.iter() {
+        let ports = collect_sram_ports(circuit, *nidx);
+        split_large_sram(circuit, &ports);
+    }
+
+    // Remove the original SRAM nodes
+    large_srams.sort();
+    for nidx in large_srams.iter().rev() {
+        circuit.graph.remove_node(*nidx);
+    }
+    return Ok(());
+}
+
+/// Number of data bits covered by each write mask bit of `ports`
+fn mask_gran(ports: &SRAMPorts) -> u32 {
+    if ports.wr_mask.len() > 0 {
+        ports.width_bits() / ports.wr_mask.len() as u32
+    } else {
+        1
+    }
+}
+
+/// Width of the slices of `ports`. The data bits covered by a single mask
+/// bit are kept within the same slice
+fn slice_width(pcfg: &PlatformConfig, ports: &SRAMPorts) -> u32 {
+    let mask_gran = mask_gran(ports);
+    return (pcfg.sram_width / mask_gran) * mask_gran;
+}
+
+fn split_large_sram(circuit: &mut Circuit, ports: &SRAMPorts) {
+    let pcfg = circuit.platform_cfg.clone();
+    let width = ports.width_bits();
+    let addr_bits = ports.addr_bits();
+    let bank_addr_bits = std::cmp::min(addr_bits, pcfg.sram_entries.trailing_zeros());
+    let nbanks = 1u32 << (addr_bits - bank_addr_bits);
+
+    let slice_width = slice_width(&pcfg, ports);
+    let mask_gran = mask_gran(ports);
+    let nslices = (width + slice_width - 1) / slice_width;
+
+    println!("Splitting SRAM {} ({} x {}) into {} slices x {} banks",
+        ports.name, 1u64 << addr_bits, width, nslices, nbanks);
+
+    let rd_sel: Vec<PortConn> = ports.rd_addr.iter()
+        .filter(|(idx, _)| **idx >= bank_addr_bits)
+        .map(|(_, conn)| conn.clone())
+        .collect();
+    let wr_sel: Vec<PortConn> = ports.wr_addr.iter()
+        .filter(|(idx, _)| **idx >= bank_addr_bits)
+        .map(|(_, conn)| conn.clone())
+        .collect();
+
+    let rd_en = ports.rd_en.as_ref().unwrap();
+    let rd_sel_regs = if nbanks > 1 {
+        add_read_select_regs(circuit, ports, &rd_sel)
+    } else {
+        vec![]
+    };
+
+    // Per bank enable signals shared by all the slices
+    let mut bank_rd_en: Vec<PortConn> = vec![];
+    let mut bank_wr_en: Vec<Option<PortConn>> = vec![];
+    for b in 0..nbanks {
+        let prefix = format!("{}.bank{}", ports.name, b);
+        if nbanks == 1 {
+            bank_rd_en.push(rd_en.clone());
+            bank_wr_en.push(ports.wr_en.clone());
+            continue;
+        }
+
+        let (ridx, rname) = add_bank_decoder(circuit, &format!("{}.ren", prefix), rd_en, &rd_sel, b);
+        bank_rd_en.push((ridx, HWEdge::new(rename_enable_signal(&rd_en.1.signal, &rname))));
+
+        if ports.single_port {
+            // The enable is already decoded, the write mode is shared
+            bank_wr_en.push(ports.wr_en.clone());
+        } else {
+            match &ports.wr_en {
+                Some(wr_en) => {
+                    let (widx, wname) = add_bank_decoder(circuit, &format!("{}.wen", prefix), wr_en, &wr_sel, b);
+                    bank_wr_en.push(Some((widx, HWEdge::new(rename_enable_signal(&wr_en.1.signal, &wname)))));
+                }
+                None => {
+                    bank_wr_en.push(None);
+                }
+            }
+        }
+    }
+
+    for s in 0..nslices {
+        let lo = s * slice_width;
+        let hi = std::cmp::min(width, lo + slice_width);
+
+        // rd_data bit -> per bank read data ports
+        let mut bank_rd_data: IndexMap<u32, Vec<(NodeIndex, SignalType)>> = IndexMap::new();
+
+        for b in 0..nbanks {
+            let name = format!("{}.s{}.b{}", ports.name, s, b);
+            let sram = CircuitPrimitive::SRAMNode {
+                name: name.clone(),
+                conns: IndexMap::new(),
+                port: String::new(),
+                region: SRAMRegion {
+                    target: ports.region.target.clone(),
+                    bit_offset: ports.region.bit_offset + lo,
+                    entry_offset: ports.region.entry_offset + (b << bank_addr_bits)
+                }
+            };
+            let sidx = circuit.graph.add_node(HWNode::new(sram));
+
+            // Enables
+            let (pidx, edge) = &bank_rd_en[b as usize];
+            circuit.graph.add_edge(*pidx, sidx, edge.clone());
+            match &bank_wr_en[b as usize] {
+                Some((pidx, edge)) => { circuit.graph.add_edge(*pidx, sidx, edge.clone()); }
+                None => { }
+            }
+
+            // Lower address bits are shared by all banks
+            for (idx, (pidx, edge)) in ports.rd_addr.iter() {
+                if *idx < bank_addr_bits {
+                    circuit.graph.add_edge(*pidx, sidx, edge.clone());
+                }
+            }
+            for (idx, (pidx, edge)) in ports.wr_addr.iter() {
+                if *idx < bank_addr_bits {
+                    circuit.graph.add_edge(*pidx, sidx, edge.clone());
+                }
+            }
+
+            // Data and mask bits of this slice
+            for (idx, (pidx, edge)) in ports.wr_data.iter() {
+                if *idx >= lo && *idx < hi {
+                    let e = HWEdge::new(reindex_signal(&edge.signal, idx - lo));
+                    circuit.graph.add_edge(*pidx, sidx, e);
+                }
+            }
+            for (idx, (pidx, edge)) in ports.wr_mask.iter() {
+                let bit = idx * mask_gran;
+                if bit >= lo && bit < hi {
+                    let e = HWEdge::new(reindex_signal(&edge.signal, (bit - lo) / mask_gran));
+                    circuit.graph.add_edge(*pidx, sidx, e);
+                }
+            }
+
+            // Read data
+            for (idx, consumers) in ports.rd_data.iter() {
+                if *idx < lo || *idx >= hi {
+                    continue;
+                }
+                if nbanks == 1 {
+                    for (cidx, edge) in consumers.iter() {
+                        let e = HWEdge::new(reindex_signal(&edge.signal, idx - lo));
+                        circuit.graph.add_edge(sidx, *cidx, e);
+                    }
+                } else {
+                    if !bank_rd_data.contains_key(idx) {
+                        bank_rd_data.insert(*idx, vec![]);
+                    }
+                    let rdata = SignalType::SRAMRdData {
+                        name: format!("{}.rdata[{}]", name, idx - lo),
+                        idx: idx - lo
+                    };
+                    bank_rd_data.get_mut(idx).unwrap().push((sidx, rdata));
+                }
+            }
+        }
+
+        // Read data mux
+        for (idx, banks) in bank_rd_data.iter() {
+            let consumers = ports.rd_data.get(idx).unwrap();
+            let out = signal_name(&consumers.first().unwrap().1.signal);
+            let mut level = banks.clone();
+
+            // Mux tree where level `j` is steered by the registered select bit `j`
+            for (j, q) in rd_sel_regs.iter().enumerate() {
+                let last = j == rd_sel_regs.len() - 1;
+                let mut next = vec![];
+                for (k, pair) in level.chunks(2).enumerate() {
+                    let name = if last {
+                        out.clone()
+                    } else {
+                        format!("{}.rdmux{}.{}.{}", ports.name, idx, j, k)
+                    };
+                    let inputs = vec![q.clone(), pair[0].clone(), pair[1].clone()];
+                    let lidx = add_lut(circuit, &name, &inputs,
+                        |v| if v[0] == 0 { v[1] == 1 } else { v[2] == 1 });
+                    next.push((lidx, SignalType::Wire { name: name }));
+                }
+                level = next;
+            }
+
+            let (midx, _) = level.first().unwrap();
+            for (cidx, _) in consumers.iter() {
+                circuit.graph.add_edge(*midx, *cidx, HWEdge::new(SignalType::Wire { name: out.clone() }));
+            }
+        }
+    }
+}
@@ -16,46 +16,69 @@ type NewNodeInfo = (HWNode, HWEdge, HWEdge);
 
 /// If a register node (Latch/Gate) has a child that is also a register node,
 /// insert a dummy LUT with table [[1]] (i.e. a passthrough) to remove the
-/// ordering constraints in between the nodes
+/// ordering constraints in between the nodes.
+///
+/// A Latch holds its state in the data memory slot of its input, so Latches
+/// that share an input but start from different values also get a passthrough
+/// each (except for the first one) to give every initial value its own slot
 pub fn split_reg_nodes(circuit: &mut Circuit) {
     let mut new_nodes: IndexMap<SplitRegInfo, NewNodeInfo> = IndexMap::new();
 
     for nidx in circuit.graph.node_indices() {
         let node = circuit.graph.node_weight(nidx).unwrap();
-        if node.is() != Primitive::Latch && node.is() != Primitive::Gate {
-            continue;
-        }
+        let is_reg = node.is() == Primitive::Latch || node.is() == Primitive::Gate;
+
+        // Initial value of the first Latch that keeps reading the input slot
+        let mut slot_init: Option<Bit> = None;
 
         let cedges = circuit.graph.edges_directed(nidx, Outgoing);
         for cedge in cedges {
             let eidx = cedge.id();
             let cidx = cedge.target();
             let cnode = circuit.graph.node_weight(cidx).unwrap();
-            if cnode.is() == Primitive::Latch || cnode.is() == Primitive::Gate {
-                // Try generating a unique name for this new node
-                let mut hasher = DefaultHasher::new();
-                format!("{}-{}", node.name(), cnode.name()).hash(&mut hasher);
-                let new_node_name = format!("SPLIT-{}-{}", node.name(), hasher.finish());
+            if cnode.is() != Primitive::Latch && cnode.is() != Primitive::Gate {
+                continue;
+            }
 
-                // Passthrough LUT node
-                let lut = CircuitPrimitive::Lut {
-                    inputs: vec![ node.name().to_string() ],
-                    output: new_node_name.clone(),
-                    table: vec![ vec![1] ]
+            if !is_reg {
+                let init = match cnode.prim.init_value() {
+                    Some(init) => init,
+                    None => { continue; }
                 };
-                let mut lut_node = HWNode::new(lut);
-                lut_node.info_mut().coord = node.info().coord;
+                match slot_init {
+                    None => {
+                        slot_init = Some(init);
+                        continue;
+                    }
+                    Some(prev) if prev == init => {
+                        continue;
+                    }
+                    _ => { }
+                }
+            }
 
-                assert!(!new_nodes.contains_key(&(nidx, cidx, eidx)),
-                    "{:?} -> {:?} dummy lut already inserted",
-                    nidx, cidx);
+            // Try generating a unique name for this new node
+            let mut hasher = DefaultHasher::new();
+            format!("{}-{}-{}", node.name(), cnode.name(), eidx.index()).hash(&mut hasher);
+            let new_node_name = format!("SPLIT-{}-{}", node.name(), hasher.finish());
 
-                // Parent & child both are Latch/Gate. SignalType must be a `Wire` type
-                let pedge = HWEdge::new(SignalType::Wire { name: node.name().to_string() });
-                let nedge = HWEdge::new(SignalType::Wire { name: new_node_name });
+            // Passthrough LUT node
+            let lut = CircuitPrimitive::Lut {
+                inputs: vec![ node.name().to_string() ],
+                output: new_node_name.clone(),
+                table: vec![ vec![1] ]
+            };
+            let mut lut_node = HWNode::new(lut);
+            lut_node.info_mut().coord = node.info().coord;
 
-                new_nodes.insert((nidx, cidx, eidx), (lut_node, pedge, nedge));
-            }
+            assert!(!new_nodes.contains_key(&(nidx, cidx, eidx)),
+                "{:?} -> {:?} dummy lut already inserted",
+                nidx, cidx);
+
+            let pedge = HWEdge::new(cedge.weight().signal.clone());
+            let nedge = HWEdge::new(SignalType::Wire { name: new_node_name });
+
+            new_nodes.insert((nidx, cidx, eidx), (lut_node, pedge, nedge));
         }
     }
 
@@ -64,6 +87,20 @@ pub fn split_reg_nodes(circuit: &mut Circuit) {
         let lut_idx = circuit.graph.add_node(lut_node.clone());
         circuit.graph.add_edge(*pidx, lut_idx, pedge.clone());
         circuit.graph.add_edge(lut_idx, *cidx, nedge.clone());
+
+        // Gate operands are matched by name, so point the pin to the new node
+        let pname = circuit.graph.node_weight(*pidx).unwrap().name().to_string();
+        let lname = lut_node.name().to_string();
+        let cnode = circuit.graph.node_weight_mut(*cidx).unwrap();
+        if let CircuitPrimitive::Gate { c:_, d, q:_, r, e } = &mut cnode.prim {
+            if *d == pname {
+                *d = lname;
+            } else if e.as_ref() == Some(&pname) {
+                *e = Some(lname);
+            } else if r.as_ref() == Some(&pname) {
+                *r = Some(lname);
+            }
+        }
     }
 
     // Remove the original edges
@@ -4,7 +4,9 @@ use crate::common::{
     primitive::*,
     hwgraph::*,
     config::*,
-    network::*
+    error::CompileError,
+    network::*,
+    mapping::SRAMMemMapping
 };
 use petgraph::{
     graph::NodeIndex, visit::EdgeRef, Direction::{Incoming, Outgoing}
@@ -13,98 +15,335 @@ use petgraph::{
 /// - split_sram_nodes
 /// Given a SRAMNode which represents a SRAM blackbox, split up its
 /// IO port bits into separate nodes.
-pub fn split_sram_nodes(circuit: &mut Circuit) {
-    spread_sram_nodes(circuit);
-    check_sram_node_assignment(circuit);
+pub fn split_sram_nodes(circuit: &mut Circuit) -> Result<(), CompileError> {
+    spread_sram_nodes(circuit)?;
+    check_sram_node_assignment(circuit)?;
     split_sram_node_by_io(circuit);
+    return Ok(());
 }
 
-/// Currently, we assume that each SRAM is mapped to one module.
-/// Try reassigning SRAM nodes if this is not the case.
-fn spread_sram_nodes(circuit: &mut Circuit) {
-    let mut free_modules: IndexSet<u32> = IndexSet::new();
-    let mut sram_mapping: IndexMap<u32, Vec<NodeIndex>> = IndexMap::new();
+/// Returns the (blackbox name, port) of a SRAM node.
+/// For a port of a multi-ported SRAM, the name is the name of the instance
+/// that is shared by all of its ports
+fn sram_node_instance(circuit: &Circuit, nidx: NodeIndex) -> (String, String) {
+    match &circuit.graph.node_weight(nidx).unwrap().prim {
+        CircuitPrimitive::SRAMNode { name, port, .. } => (name.to_string(), port.to_string()),
+        _ => (String::new(), String::new())
+    }
+}
 
-    let pcfg = &circuit.platform_cfg;
-    for p in 0..pcfg.num_mods {
-        free_modules.insert(p);
+/// Returns the part of the target SRAM stored in a SRAM node
+fn sram_node_region(circuit: &Circuit, nidx: NodeIndex) -> SRAMRegion {
+    match &circuit.graph.node_weight(nidx).unwrap().prim {
+        CircuitPrimitive::SRAMNode { region, .. } => region.clone(),
+        _ => SRAMRegion::default()
     }
+}
 
-    // Obtain current mappings from SRAM nodes to modules
+/// Returns the name of a SRAM node
+fn sram_node_name(circuit: &Circuit, nidx: NodeIndex) -> String {
+    let (name, port) = sram_node_instance(circuit, nidx);
+    if port.is_empty() {
+        return name;
+    } else {
+        return format!("{}.{}", name, port);
+    }
+}
+
+/// Returns the (address bits, data bits) of a SRAM node
+fn sram_node_size(circuit: &Circuit, nidx: NodeIndex) -> (u32, u32) {
+    let mut rd_addr_bits = 0;
+    let mut wr_addr_bits = 0;
+    let mut wr_data_bits = 0;
+    let mut rd_data_bits = 0;
+    let pedges = circuit.graph.edges_directed(nidx, Incoming);
+    for pedge in pedges {
+        let edge = circuit.graph.edge_weight(pedge.id()).unwrap();
+        match edge.signal {
+            SignalType::SRAMRdAddr   { .. } => { rd_addr_bits += 1; }
+            SignalType::SRAMRdWrAddr { .. } => { rd_addr_bits += 1; }
+            SignalType::SRAMWrAddr   { .. } => { wr_addr_bits += 1; }
+            SignalType::SRAMWrData   { .. } => { wr_data_bits += 1; }
+            _ => {}
+        }
+    }
+    let cedges = circuit.graph.edges_directed(nidx, Outgoing);
+    for cedge in cedges {
+        let edge = circuit.graph.edge_weight(cedge.id()).unwrap();
+        match edge.signal {
+            SignalType::SRAMRdData { name: _, idx } => {
+                rd_data_bits = rd_data_bits.max(idx + 1);
+            }
+            _ => {}
+        }
+    }
+    return (rd_addr_bits.max(wr_addr_bits), wr_data_bits.max(rd_data_bits));
+}
+
+/// Group the SRAM nodes into target SRAMs.
+/// - The ports of a multi-ported SRAM are separate SRAM nodes that must
+///   share the same SRAM processor and address range. They are grouped by
+///   their instance name and ordered by port name
+/// - Every other SRAM node forms a group by itself
+fn sram_node_groups(circuit: &Circuit) -> Vec<Vec<NodeIndex>> {
+    let mut groups: Vec<Vec<NodeIndex>> = vec![];
+    let mut ported: IndexMap<String, usize> = IndexMap::new();
     for nidx in circuit.graph.node_indices() {
         let node = circuit.graph.node_weight(nidx).unwrap();
         if node.is() != Primitive::SRAMNode {
             continue;
         }
 
+        let (name, port) = sram_node_instance(circuit, nidx);
+        if port.is_empty() {
+            groups.push(vec![nidx]);
+        } else if ported.contains_key(&name) {
+            groups[ported[&name]].push(nidx);
+        } else {
+            ported.insert(name, groups.len());
+            groups.push(vec![nidx]);
+        }
+    }
+
+    for group in groups.iter_mut() {
+        group.sort_by_key(|nidx| sram_node_instance(circuit, *nidx).1);
+    }
+    return groups;
+}
+
+/// Returns the (address bits, data bits) of a group of SRAM nodes
+fn sram_group_size(circuit: &Circuit, group: &Vec<NodeIndex>) -> (u32, u32) {
+    let mut addr_bits = 0;
+    let mut data_bits = 0;
+    for nidx in group.iter() {
+        let (abits, dbits) = sram_node_size(circuit, *nidx);
+        addr_bits = addr_bits.max(abits);
+        data_bits = data_bits.max(dbits);
+    }
+    return (addr_bits, data_bits);
+}
+
+/// Assign SRAM nodes to the modules' SRAM processors.
+/// - Modules without a SRAM node are used first so that the target SRAMs
+///   can be accessed in parallel
+/// - When we run out of free modules, up to `sram_slots` target SRAMs are
+///   packed into the address space of a single SRAM processor. Each of them
+///   gets a `sram_slot` and a base address which is recorded in the `SRAMMapping`
+/// - The ports of a multi-ported SRAM are placed on consecutive slots of
+///   the same SRAM processor and share a base address
+fn spread_sram_nodes(circuit: &mut Circuit) -> Result<(), CompileError> {
+    let groups = sram_node_groups(circuit);
+    let mut free_modules: IndexSet<u32> = IndexSet::new();
+    let mut sram_mapping: IndexMap<u32, Vec<usize>> = IndexMap::new();
+
+    let pcfg = &circuit.platform_cfg;
+    for p in 0..pcfg.num_mods {
+        free_modules.insert(p);
+    }
+
+    // Obtain current mappings from SRAM nodes to modules
+    for (gidx, group) in groups.iter().enumerate() {
+        let node = circuit.graph.node_weight(group[0]).unwrap();
         let module = node.info().coord.module;
         if !sram_mapping.contains_key(&module) {
             sram_mapping.insert(module, vec![]);
         }
-        sram_mapping.get_mut(&module).unwrap().push(nidx);
+        sram_mapping.get_mut(&module).unwrap().push(gidx);
         free_modules.swap_remove(&module);
     }
 
-    // Try reassigning
-    for (_, nodes) in sram_mapping.iter() {
-        // Only one SRAM node in the current module, don't need to do anything
-        if nodes.len() == 1 {
-            continue;
+    // Try reassigning to free modules
+    let mut packed: IndexMap<u32, Vec<usize>> = IndexMap::new();
+    for (module, gidxs) in sram_mapping.iter() {
+        for (i, gidx) in gidxs.iter().enumerate() {
+            // Keep the first group in the current module
+            let target = if i == 0 {
+                *module
+            } else {
+                match free_modules.pop() {
+                    Some(free) => free,
+                    None => *module
+                }
+            };
+            if !packed.contains_key(&target) {
+                packed.insert(target, vec![]);
+            }
+            packed.get_mut(&target).unwrap().push(*gidx);
         }
-        assert!(nodes.len() - 1 <= free_modules.len(), "Not enough free modules for SRAM");
+    }
+
+    // Pack the remaining SRAMs. If the module that the SRAM is currently
+    // assigned to is out of entries or slots, move it to the least occupied module
+    let sram_entries = circuit.platform_cfg.sram_entries;
+    let sram_slots   = circuit.platform_cfg.sram_slots;
+    let mut used_entries: IndexMap<u32, u32> = IndexMap::new();
+    let mut slots: IndexMap<u32, Vec<(NodeIndex, u32, u32, u32)>> = IndexMap::new();
+    for m in 0..circuit.platform_cfg.num_mods {
+        used_entries.insert(m, 0);
+        slots.insert(m, vec![]);
+    }
 
-        for (i, nidx) in nodes.iter().enumerate() {
-            // Skip the first node
-            if i == 0 {
-                continue;
+    for (module, gidxs) in packed.iter() {
+        for gidx in gidxs.iter() {
+            let group = &groups[*gidx];
+            let (addr_bits, width_bits) = sram_group_size(circuit, group);
+            let max_addr_bits = match sram_entries.checked_ilog2() {
+                Some(b) => b,
+                None => 0
+            };
+            if addr_bits > max_addr_bits {
+                return Err(CompileError::SRAMDoesNotFit {
+                    sram: sram_node_name(circuit, group[0]),
+                    width: width_bits,
+                    entries: 1u64.checked_shl(addr_bits).unwrap_or(u64::MAX),
+                    sram_width: circuit.platform_cfg.sram_width,
+                    sram_entries: sram_entries
+                });
             }
-            let free = free_modules.pop().unwrap();
-            let info = circuit.graph.node_weight_mut(*nidx).unwrap().info_mut();
-            assert!(info.coord.module != free, "Assigning to a already assigned node");
-            info.coord = Coordinate { module: free, proc: info.coord.proc };
+            let entries = 1u32 << addr_bits;
+            let nslots = group.len() as u32;
+
+            let target = if used_entries[module] + entries <= sram_entries &&
+                            slots[module].len() as u32 + nslots <= sram_slots {
+                *module
+            } else {
+                let mut best: Option<u32> = None;
+                let mut entries_fit = false;
+                for (m, used) in used_entries.iter() {
+                    if used + entries > sram_entries {
+                        continue;
+                    }
+                    entries_fit = true;
+                    if slots[m].len() as u32 + nslots > sram_slots {
+                        continue;
+                    }
+                    match best {
+                        Some(b) if used_entries[&b] <= *used => {}
+                        _ => { best = Some(*m); }
+                    }
+                }
+                match best {
+                    Some(b) => b,
+                    None if entries_fit => {
+                        return Err(CompileError::NotEnoughSRAMSlots {
+                            sram: sram_node_name(circuit, group[0]),
+                            slots: nslots,
+                            sram_slots: sram_slots
+                        });
+                    }
+                    None => {
+                        return Err(CompileError::SRAMDoesNotFit {
+                            sram: sram_node_name(circuit, group[0]),
+                            width: width_bits,
+                            entries: entries as u64,
+                            sram_width: circuit.platform_cfg.sram_width,
+                            sram_entries: sram_entries
+                        });
+                    }
+                }
+            };
+
+            let base_addr = used_entries[&target];
+            *used_entries.get_mut(&target).unwrap() += entries;
+
+            for nidx in group.iter() {
+                slots.get_mut(&target).unwrap().push((*nidx, base_addr, addr_bits, width_bits));
+
+                let slot = slots[&target].len() as u32 - 1;
+                let info = circuit.graph.node_weight_mut(*nidx).unwrap().info_mut();
+                info.coord = Coordinate { module: target, proc: info.coord.proc };
+                info.sram_slot = slot;
+            }
+        }
+    }
+
+    // Record the packed SRAMs in the sram processor mappings
+    for (m, mems) in slots.iter() {
+        let mut sram_mems = vec![];
+        for (nidx, base_addr, addr_bits, width_bits) in mems.iter() {
+            sram_mems.push(SRAMMemMapping {
+                name: sram_node_name(circuit, *nidx),
+                addr_bits:  *addr_bits,
+                width_bits: *width_bits,
+                base_addr:  *base_addr,
+                region:     sram_node_region(circuit, *nidx),
+                ..SRAMMemMapping::default()
+            });
         }
+        circuit.emul.module_mappings.get_mut(m).unwrap().sram_mapping.mems = sram_mems;
     }
+    return Ok(());
 }
 
-/// Check if the assigned SRAM block fits in the platform's SRAM processor
-fn check_sram_node_assignment(circuit: &Circuit) {
-    let mut allocated_sram_procs: IndexSet<u32> = IndexSet::new();
+/// Check if the assigned SRAM blocks fit in the platform's SRAM processor
+fn check_sram_node_assignment(circuit: &Circuit) -> Result<(), CompileError> {
+    let mut allocated_slots: IndexSet<(u32, u32)> = IndexSet::new();
+    let mut allocated_entries: IndexMap<u32, u32> = IndexMap::new();
+    let mut allocated_ported: IndexMap<String, u32> = IndexMap::new();
+    let groups = sram_node_groups(circuit);
     for nidx in circuit.graph.node_indices() {
         let node = circuit.graph.node_weight(nidx).unwrap();
         if node.is() != Primitive::SRAMNode {
             continue;
         }
 
-        if allocated_sram_procs.contains(&node.info().coord.module) {
-            assert!(false,
-                "Module {} already contains SRAM node",
-                node.info().coord.module);
+        let module = node.info().coord.module;
+        let slot = node.info().sram_slot;
+        assert!(!allocated_slots.contains(&(module, slot)),
+            "Module {} slot {} already contains SRAM node", module, slot);
+        if slot >= circuit.platform_cfg.sram_slots {
+            return Err(CompileError::NotEnoughSRAMSlots {
+                sram: sram_node_name(circuit, nidx),
+                slots: slot + 1,
+                sram_slots: circuit.platform_cfg.sram_slots
+            });
+        }
+        allocated_slots.insert((module, slot));
+
+        let (addr_bits, data_bits) = sram_node_size(circuit, nidx);
+        if !allocated_entries.contains_key(&module) {
+            allocated_entries.insert(module, 0);
+        }
+
+        // Ports of a multi-ported SRAM share their entries
+        let (name, port) = sram_node_instance(circuit, nidx);
+        if port.is_empty() {
+            *allocated_entries.get_mut(&module).unwrap() += 1 << addr_bits;
+        } else if !allocated_ported.contains_key(&name) {
+            let group = groups.iter().find(|g| g.contains(&nidx)).unwrap();
+            *allocated_entries.get_mut(&module).unwrap() +=
+                1 << sram_group_size(circuit, group).0;
+            allocated_ported.insert(name, module);
         } else {
-            let pedges = circuit.graph.edges_directed(nidx, Incoming);
-            let mut addr_bits = 0;
-            let mut data_bits = 0;
-            for pedge in pedges {
-                let edge = circuit.graph.edge_weight(pedge.id()).unwrap().clone();
-                match edge.signal {
-                    SignalType::SRAMRdAddr   { .. } => { addr_bits += 1; }
-                    SignalType::SRAMRdWrAddr { .. } => { addr_bits += 1; }
-                    SignalType::SRAMWrData   { .. } => { data_bits += 1; }
-                    _ => {}
-                }
-            }
+            assert!(allocated_ported[&name] == module,
+                "Ports of SRAM {} are mapped to different modules", name);
+        }
 
-            allocated_sram_procs.insert(node.info().coord.module);
+        let w = circuit.platform_cfg.sram_width;
+        if data_bits > w {
+            return Err(CompileError::SRAMDoesNotFit {
+                sram: sram_node_name(circuit, nidx),
+                width: data_bits,
+                entries: 1u64 << addr_bits,
+                sram_width: w,
+                sram_entries: circuit.platform_cfg.sram_entries
+            });
+        }
+    }
 
-            let w = circuit.platform_cfg.sram_width;
-            let e = circuit.platform_cfg.sram_entries;
-            assert!(w >= data_bits,
-                "SRAM processor has {} bits per entry, got {}",
-                w, data_bits);
-            assert!(e >= 1 << addr_bits,
-                "SRAM processor has {} entries, got {}",
-                e, 1 << addr_bits);
+    let e = circuit.platform_cfg.sram_entries;
+    for (module, entries) in allocated_entries.iter() {
+        if *entries > e {
+            return Err(CompileError::SRAMDoesNotFit {
+                sram: format!("SRAMs packed into module {}", module),
+                width: circuit.platform_cfg.sram_width,
+                entries: *entries as u64,
+                sram_width: circuit.platform_cfg.sram_width,
+                sram_entries: e
+            });
         }
     }
+    return Ok(());
 }
 
 #[derive(Debug)]
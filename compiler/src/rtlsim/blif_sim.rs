@@ -1,6 +1,7 @@
 use crate::common::circuit::*;
-use crate::common::mapping::{SRAMMapping, SRAMPortType};
+use crate::common::mapping::{SRAMMemMapping, SRAMPortType};
 use crate::common::primitive::*;
+use crate::common::sram_image::sram_init_contents;
 use crate::rtlsim::rtlsim_utils::InputStimuliMap;
 use crate::fsim::sram::{SRAMEntry, SRAMInputs};
 use indexmap::IndexMap;
@@ -14,10 +15,12 @@ use petgraph::{
     Direction::{Incoming, Outgoing},
 };
 
+/// State of a target SRAM port.
+/// Ports of the same multi-ported SRAM share their memory contents which are
+/// stored in `BlifSimulator` keyed by (module, base address)
 #[derive(Debug, Default)]
 pub struct SRAMState {
-    pub cfg: SRAMMapping,
-    pub mem: Vec<SRAMEntry>,
+    pub cfg: SRAMMemMapping,
     pub input: SRAMInputs,
     pub rddata: SRAMEntry,
     pub cur_rdaddr: Bits,
@@ -25,18 +28,17 @@ pub struct SRAMState {
 }
 
 impl SRAMState {
-    pub fn new(cfg: &SRAMMapping) -> Self {
+    pub fn new(cfg: &SRAMMemMapping) -> Self {
         SRAMState {
             cfg: cfg.clone(),
-            mem: vec![SRAMEntry::new(cfg.width_bits); 1024], // FIXME
             input: SRAMInputs::new(cfg.width_bits),
             rddata: SRAMEntry::new(cfg.width_bits),
             cur_rdaddr: 0
         }
     }
 
-    pub fn update_cycle(self: &mut Self) {
-        let (ren, wen, waddr) = match self.cfg.port_type {
+    fn enables(self: &Self) -> (bool, bool, Bits) {
+        match self.cfg.port_type {
             SRAMPortType::OneRdOneWrPortSRAM => {
                 (self.input.rd_en != 0,
                  self.input.wr_en != 0,
@@ -47,10 +49,12 @@ impl SRAMState {
                  self.input.wr_en != 0 && self.input.rd_en != 0,
                  self.input.rd_addr)
             }
-        };
+        }
+    }
 
-// println!("ren: {} wen: {} waddr: {} rdaddr: {}",
-// ren, wen, waddr, self.input.rd_addr);
+    /// Apply the write of this cycle to `mem`
+    pub fn write_cycle(self: &Self, mem: &mut Vec<SRAMEntry>) {
+        let (_, wen, waddr) = self.enables();
 
         // Write to SRAM
         if wen {
@@ -59,7 +63,7 @@ impl SRAMState {
                 self.input.wr_data.clone()
             } else {
                 // Read the current data
-                let cur_data = self.mem.get(waddr as usize).unwrap();
+                let cur_data = mem.get(waddr as usize).unwrap();
 
                 // Compute masked written value
                 let mut ret = vec![];
@@ -77,14 +81,19 @@ impl SRAMState {
             };
 
             // Perform the write
-            self.mem[waddr as usize] = SRAMEntry { bits:  wdata };
+            mem[waddr as usize] = SRAMEntry { bits:  wdata };
         }
+    }
+
+    /// Read from `mem` after the writes of all the ports are applied
+    pub fn read_cycle(self: &mut Self, mem: &Vec<SRAMEntry>) {
+        let (ren, _, _) = self.enables();
 
         // Read to SRAM
         if ren {
             self.cur_rdaddr = self.input.rd_addr;
         }
-        self.rddata = self.mem.get(self.cur_rdaddr as usize).unwrap().clone();
+        self.rddata = mem.get(self.cur_rdaddr as usize).unwrap().clone();
 
         self.input.init();
     }
@@ -94,13 +103,16 @@ impl SRAMState {
 pub struct BlifSimulator {
     pub circuit: Circuit,
     pub input_stimulti_blasted: InputStimuliMap,
-    srams: IndexMap<u32, SRAMState>,
+    /// Target SRAM port states indexed by (module, sram_slot)
+    srams: IndexMap<(u32, u32), SRAMState>,
+    /// Target SRAM contents indexed by (module, base address)
+    mems: IndexMap<(u32, u32), Vec<SRAMEntry>>,
     topo_sort_order: Vec<NodeIndex>,
     cur_cycle: u32
 }
 
 impl BlifSimulator {
-    pub fn new(circuit: Circuit, input_stimulti_blasted: InputStimuliMap) -> Self {
+    pub fn new(mut circuit: Circuit, input_stimulti_blasted: InputStimuliMap) -> Self {
         let mut topo_sort_order = vec![];
 
         // compute indeg for the entire graph
@@ -194,15 +206,48 @@ impl BlifSimulator {
             }
         }
 
-        // Assume that we have one SRAM per module for now
-        let mut srams: IndexMap<u32, SRAMState> = IndexMap::new();
-        for (m, mmap) in circuit.emul.module_mappings.iter() {
-            let smap = &mmap.sram_mapping;
-            if smap.width_bits == 0 {
-                continue;
+        // Registers read the value of their input from the previous cycle.
+        // Seed the inputs with the initial register values
+        let mut reg_inits: Vec<(NodeIndex, Bit)> = vec![];
+        for nidx in circuit.graph.node_indices() {
+            let node = circuit.graph.node_weight(nidx).unwrap();
+            match node.prim.init_value() {
+                Some(init) => {
+                    for pidx in circuit.graph.neighbors_directed(nidx, Incoming) {
+                        reg_inits.push((pidx, init));
+                    }
+                }
+                None => {}
             }
-            srams.insert(*m, SRAMState::new(&smap));
+        }
+        for (pidx, init) in reg_inits.iter() {
+            circuit.graph.node_weight_mut(*pidx).unwrap().info_mut().debug.val = *init;
+        }
 
+        // Each module can contain multiple target SRAMs.
+        // Slots with the same base address are ports of the same target SRAM
+        let contents = sram_init_contents(&circuit.compiler_cfg, &circuit.emul).unwrap();
+        let mut srams: IndexMap<(u32, u32), SRAMState> = IndexMap::new();
+        let mut mems: IndexMap<(u32, u32), Vec<SRAMEntry>> = IndexMap::new();
+        for (m, mmap) in circuit.emul.module_mappings.iter() {
+            for (slot, mem) in mmap.sram_mapping.slots().iter().enumerate() {
+                if mem.width_bits == 0 {
+                    continue;
+                }
+                srams.insert((*m, slot as u32), SRAMState::new(mem));
+                if mems.contains_key(&(*m, mem.base_addr)) {
+                    continue;
+                }
+
+                let entries = 1u32 << mem.addr_bits;
+                let mut entry_vec = vec![SRAMEntry::new(mem.width_bits); entries as usize];
+                for (addr, bits) in contents.get(m).unwrap().iter() {
+                    if *addr >= mem.base_addr && *addr - mem.base_addr < entries {
+                        entry_vec[(*addr - mem.base_addr) as usize] = SRAMEntry { bits: bits.clone() };
+                    }
+                }
+                mems.insert((*m, mem.base_addr), entry_vec);
+            }
         }
 
         return BlifSimulator {
@@ -210,6 +255,7 @@ impl BlifSimulator {
             input_stimulti_blasted: input_stimulti_blasted,
             topo_sort_order: topo_sort_order,
             srams: srams,
+            mems: mems,
             cur_cycle: 0
         };
     }
@@ -219,12 +265,30 @@ impl BlifSimulator {
             let parents = self.circuit.graph.neighbors_directed(*nidx, Incoming);
             let node = self.circuit.graph.node_weight(*nidx).unwrap();
             let module = node.info().coord.module;
+            let slot = node.info().sram_slot;
             let prim = node.prim.clone();
 
             let mut node_value = 0;
             match prim {
                 CircuitPrimitive::Gate { .. } => {
-                    assert!(false, "Should find no gates here");
+                    // Parents still hold their values from the previous cycle
+                    let gate_ops = prim.gate_operands();
+                    let mut ops: Vec<Bit> = vec![0; gate_ops.len()];
+                    let mut used: Vec<bool> = vec![false; gate_ops.len()];
+                    for pidx in parents {
+                        let pnode = self.circuit.graph.node_weight(pidx).unwrap();
+                        for (i, op) in gate_ops.iter().enumerate() {
+                            if !used[i] && op == pnode.name() {
+                                ops[i] = pnode.info().debug.val;
+                                used[i] = true;
+                                break;
+                            }
+                        }
+                    }
+                    if let CircuitPrimitive::Gate { e: Some(_), .. } = &prim {
+                        ops[gate_ops.len() - 1] = node.info().debug.val;
+                    }
+                    node_value = prim.gate_next(&ops);
                 }
                 CircuitPrimitive::ConstLut { val, .. } => {
                     node_value = val;
@@ -262,7 +326,7 @@ impl BlifSimulator {
                         .node_weight(parents.last().unwrap()).unwrap()
                         .info().debug.val;
 
-                    self.srams.get_mut(&module).unwrap()
+                    self.srams.get_mut(&(module, slot)).unwrap()
                         .input.set_rd_en(node_value);
                 }
                 CircuitPrimitive::SRAMWrEn { name:_ } => {
@@ -270,7 +334,7 @@ impl BlifSimulator {
                         .node_weight(parents.last().unwrap()).unwrap()
                         .info().debug.val;
 
-                    self.srams.get_mut(&module).unwrap()
+                    self.srams.get_mut(&(module, slot)).unwrap()
                         .input.set_wr_en(node_value);
                 }
                 CircuitPrimitive::SRAMRdAddr { name:_, idx } => {
@@ -278,7 +342,7 @@ impl BlifSimulator {
                         .node_weight(parents.last().unwrap()).unwrap()
                         .info().debug.val;
 
-                    self.srams.get_mut(&module).unwrap()
+                    self.srams.get_mut(&(module, slot)).unwrap()
                         .input.set_rd_addr(node_value, idx);
                 }
                 CircuitPrimitive::SRAMWrAddr { name:_, idx } => {
@@ -286,7 +350,7 @@ impl BlifSimulator {
                         .node_weight(parents.last().unwrap()).unwrap()
                         .info().debug.val;
 
-                    self.srams.get_mut(&module).unwrap()
+                    self.srams.get_mut(&(module, slot)).unwrap()
                         .input.set_wr_addr(node_value, idx);
                 }
                 CircuitPrimitive::SRAMWrMask { name:_, idx } => {
@@ -294,7 +358,7 @@ impl BlifSimulator {
                         .node_weight(parents.last().unwrap()).unwrap()
                         .info().debug.val;
 
-                    self.srams.get_mut(&module).unwrap()
+                    self.srams.get_mut(&(module, slot)).unwrap()
                         .input.set_wr_mask(node_value, idx);
                 }
                 CircuitPrimitive::SRAMWrData { name:_, idx } => {
@@ -302,7 +366,7 @@ impl BlifSimulator {
                         .node_weight(parents.last().unwrap()).unwrap()
                         .info().debug.val;
 
-                    self.srams.get_mut(&module).unwrap()
+                    self.srams.get_mut(&(module, slot)).unwrap()
                         .input.set_wr_data(node_value, idx);
                 }
                 CircuitPrimitive::SRAMRdWrEn { name:_ } => {
@@ -310,7 +374,7 @@ impl BlifSimulator {
                         .node_weight(parents.last().unwrap()).unwrap()
                         .info().debug.val;
 
-                    self.srams.get_mut(&module).unwrap()
+                    self.srams.get_mut(&(module, slot)).unwrap()
                         .input.set_rd_en(node_value);
                 }
                 CircuitPrimitive::SRAMRdWrMode { name:_ } => {
@@ -318,7 +382,7 @@ impl BlifSimulator {
                         .node_weight(parents.last().unwrap()).unwrap()
                         .info().debug.val;
 
-                    self.srams.get_mut(&module).unwrap()
+                    self.srams.get_mut(&(module, slot)).unwrap()
                         .input.set_wr_en(node_value);
                 }
                 CircuitPrimitive::SRAMRdWrAddr { name:_, idx } => {
@@ -326,11 +390,11 @@ impl BlifSimulator {
                         .node_weight(parents.last().unwrap()).unwrap()
                         .info().debug.val;
 
-                    self.srams.get_mut(&module).unwrap()
+                    self.srams.get_mut(&(module, slot)).unwrap()
                         .input.set_rd_addr(node_value, idx);
                 }
                 CircuitPrimitive::SRAMRdData { name:_, idx } => {
-                    node_value = self.srams.get(&module).unwrap().rddata.bit(idx);
+                    node_value = self.srams.get(&(module, slot)).unwrap().rddata.bit(idx);
                 }
                 _ => {
                 }
@@ -340,8 +404,14 @@ impl BlifSimulator {
                 .info_mut().debug.val = node_value;
         }
 
-        for (_, s) in self.srams.iter_mut() {
-            s.update_cycle();
+        // Apply the writes of all the ports in slot order before the reads
+        for ((m, _), s) in self.srams.iter() {
+            let mem = self.mems.get_mut(&(*m, s.cfg.base_addr)).unwrap();
+            s.write_cycle(mem);
+        }
+        for ((m, _), s) in self.srams.iter_mut() {
+            let mem = self.mems.get(&(*m, s.cfg.base_addr)).unwrap();
+            s.read_cycle(mem);
         }
 
         self.cur_cycle += 1;
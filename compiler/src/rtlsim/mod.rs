@@ -1,4 +1,5 @@
 pub mod ref_rtlsim_testharness;
+pub mod ref_rtlsim;
 pub mod rtlsim_utils;
 pub mod vcdparser;
 pub mod blif_sim;
@@ -0,0 +1,166 @@
+use crate::common::config::RefSimulator;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// Name of the simulation binary under `<sim_dir>/build`
+pub const REF_RTLSIM_BINARY: &str = "rtlsim_binary";
+
+/// # RefRTLSimulator
+/// - Reference RTL simulator used to generate the golden waveform
+/// - `compile_cmd` runs from the simulation directory containing the
+///   testbench and the design, and should place the simulation binary in
+///   `build/`
+/// - `run_cmd` runs from `build/` so that `sim.vcd` is dumped there
+pub trait RefRTLSimulator {
+    /// Name of the simulator (used for error messages)
+    fn name(self: &Self) -> &'static str;
+
+    /// Command that compiles `tb_file` and `design_file` into `build/rtlsim_binary`
+    fn compile_cmd(self: &Self, sim_dir: &Path, tb_file: &str, design_file: &str) -> Command;
+
+    /// Command that runs the simulation binary
+    fn run_cmd(self: &Self, build_dir: &Path) -> Command;
+
+    /// Compile `tb_file` and `design_file` into `build/rtlsim_binary`
+    fn compile(self: &Self, sim_dir: &Path, tb_file: &str, design_file: &str) -> std::io::Result<()> {
+        let output = run_step(self.name(), "compile", self.compile_cmd(sim_dir, tb_file, design_file))?;
+        check_status(self.name(), "compile", output)?;
+        Ok(())
+    }
+
+    /// Run the simulation binary and return its stdout
+    fn run(self: &Self, build_dir: &Path) -> std::io::Result<Vec<u8>> {
+        let output = run_step(self.name(), "run", self.run_cmd(build_dir))?;
+        Ok(check_status(self.name(), "run", output)?.stdout)
+    }
+}
+
+/// Runs `cmd`, and reports a missing simulator instead of a bare "No such file"
+fn run_step(name: &str, step: &str, mut cmd: Command) -> std::io::Result<Output> {
+    match cmd.output() {
+        Ok(output) => Ok(output),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            Err(std::io::Error::new(ErrorKind::NotFound, format!(
+                "{} {} failed: `{}` was not found. Install {} or select another simulator with --ref-sim",
+                name,
+                step,
+                cmd.get_program().to_string_lossy(),
+                name)))
+        }
+        Err(e) => Err(e)
+    }
+}
+
+fn check_status(name: &str, step: &str, output: Output) -> std::io::Result<Output> {
+    if output.status.success() {
+        Ok(output)
+    } else {
+        Err(std::io::Error::other(format!(
+            "{} {} failed ({}):\n{}",
+            name,
+            step,
+            output.status,
+            String::from_utf8_lossy(&output.stderr))))
+    }
+}
+
+/// Synopsys VCS
+pub struct VCS;
+
+impl RefRTLSimulator for VCS {
+    fn name(self: &Self) -> &'static str {
+        "vcs"
+    }
+
+    fn compile_cmd(self: &Self, sim_dir: &Path, tb_file: &str, design_file: &str) -> Command {
+        let mut cmd = Command::new("vcs");
+        cmd.current_dir(sim_dir)
+            .arg("-sverilog")
+            .arg("-full64")
+            .arg("+notimingchecks")
+            .arg(tb_file)
+            .arg(design_file)
+            .arg("-o")
+            .arg(format!("build/{}", REF_RTLSIM_BINARY));
+        return cmd;
+    }
+
+    fn run_cmd(self: &Self, build_dir: &Path) -> Command {
+        let mut cmd = Command::new(format!("./{}", REF_RTLSIM_BINARY));
+        cmd.current_dir(build_dir);
+        return cmd;
+    }
+}
+
+/// Verilator (requires v5+ for `--binary` and `--timing`)
+pub struct Verilator;
+
+impl RefRTLSimulator for Verilator {
+    fn name(self: &Self) -> &'static str {
+        "verilator"
+    }
+
+    fn compile_cmd(self: &Self, sim_dir: &Path, tb_file: &str, design_file: &str) -> Command {
+        let mut cmd = Command::new("verilator");
+        cmd.current_dir(sim_dir)
+            .arg("--binary")
+            .arg("--timing")
+            .arg("--trace")
+            .arg("-Wno-fatal")
+            .arg("--top-module")
+            .arg("testharness")
+            .arg("--Mdir")
+            .arg("build")
+            .arg("-o")
+            .arg(REF_RTLSIM_BINARY)
+            .arg(tb_file)
+            .arg(design_file);
+        return cmd;
+    }
+
+    fn run_cmd(self: &Self, build_dir: &Path) -> Command {
+        let mut cmd = Command::new(format!("./{}", REF_RTLSIM_BINARY));
+        cmd.current_dir(build_dir);
+        return cmd;
+    }
+}
+
+/// Icarus Verilog
+pub struct Icarus;
+
+impl RefRTLSimulator for Icarus {
+    fn name(self: &Self) -> &'static str {
+        "icarus"
+    }
+
+    fn compile_cmd(self: &Self, sim_dir: &Path, tb_file: &str, design_file: &str) -> Command {
+        let mut cmd = Command::new("iverilog");
+        cmd.current_dir(sim_dir)
+            .arg("-g2012")
+            .arg("-s")
+            .arg("testharness")
+            .arg("-o")
+            .arg(format!("build/{}", REF_RTLSIM_BINARY))
+            .arg(tb_file)
+            .arg(design_file);
+        return cmd;
+    }
+
+    fn run_cmd(self: &Self, build_dir: &Path) -> Command {
+        let mut cmd = Command::new("vvp");
+        cmd.current_dir(build_dir)
+            .arg("-n")
+            .arg(REF_RTLSIM_BINARY);
+        return cmd;
+    }
+}
+
+/// Returns the reference simulator backend for `sim`
+pub fn ref_rtl_simulator(sim: &RefSimulator) -> Box<dyn RefRTLSimulator> {
+    match sim {
+        RefSimulator::VCS       => Box::new(VCS),
+        RefSimulator::Verilator => Box::new(Verilator),
+        RefSimulator::Icarus    => Box::new(Icarus),
+    }
+}
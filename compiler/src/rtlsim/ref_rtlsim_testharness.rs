@@ -1,16 +1,124 @@
 use crate::rtlsim::rtlsim_utils::*;
-use crate::common::utils;
+use crate::rtlsim::ref_rtlsim::ref_rtl_simulator;
+use crate::common::{utils, config::RefSimulator};
+use serde::{Serialize, Deserialize};
+use indexmap::IndexMap;
 use std::cmp::max;
 use std::io::Write;
 use std::path::Path;
 use std::process::Command;
 use std::{env, fs};
 
+pub const START_SIMULATION_TAG: &str = "** Start Simulation **";
+pub const END_SIMULATION_TAG: &str = "** End Simulation **";
+pub const TESTBENCH_TIMING_TAG: &str = "** Testbench Timing **";
+
+/// # TestbenchTiming
+/// - Timing of the generated testbench. The testbench prints this before
+///   starting the simulation so that the simulation output and the VCD can be
+///   mapped back to target cycles without assuming a clock period
+/// - All times are in testbench time units (`time_unit_ps` picoseconds)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestbenchTiming {
+    /// Testbench time unit in picoseconds
+    pub time_unit_ps: u64,
+
+    /// Clock period
+    pub clock_period: u64,
+
+    /// Number of clock periods that reset is held high
+    pub reset_cycles: u64,
+
+    /// Time at which target cycle 0 is sampled. The testbench waits until
+    /// then before sampling, so it should be a rising clock edge after reset
+    pub first_sample_time: u64,
+}
+
+impl Default for TestbenchTiming {
+    fn default() -> Self {
+        TestbenchTiming {
+            time_unit_ps: 1000,
+            clock_period: 20,
+            reset_cycles: 4,
+            first_sample_time: 80,
+        }
+    }
+}
+
+impl TestbenchTiming {
+    /// Time at which `cycle` is sampled
+    pub fn sample_time(self: &Self, cycle: u64) -> u64 {
+        self.first_sample_time + cycle * self.clock_period
+    }
+
+    /// Target cycle that was sampled at `time`
+    pub fn cycle(self: &Self, time: u64) -> Option<u64> {
+        if time < self.first_sample_time {
+            None
+        } else {
+            Some((time - self.first_sample_time) / self.clock_period)
+        }
+    }
+
+    /// Path of the metadata file that accompanies `vcd_path`
+    pub fn metadata_path(vcd_path: &str) -> String {
+        format!("{}.timing.json", vcd_path)
+    }
+
+    pub fn save(self: &Self, vcd_path: &str) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(TestbenchTiming::metadata_path(vcd_path), json)?;
+        Ok(())
+    }
+
+    /// Load the timing metadata of `vcd_path` if there is one
+    pub fn load(vcd_path: &str) -> std::io::Result<Option<TestbenchTiming>> {
+        let path = TestbenchTiming::metadata_path(vcd_path);
+        if !Path::new(&path).exists() {
+            return Ok(None);
+        }
+        let json = fs::read_to_string(path)?;
+        let timing: TestbenchTiming = serde_json::from_str(&json)?;
+        Ok(Some(timing))
+    }
+
+    /// Parse the line printed by the testbench, e.g.
+    /// `0 ** Testbench Timing ** time_unit_ps 1000 clock_period 20 reset_cycles 4 first_sample_time 80`
+    pub fn from_line(line: &str) -> Option<TestbenchTiming> {
+        let idx = line.find(TESTBENCH_TIMING_TAG)?;
+        let words: Vec<&str> = line[idx + TESTBENCH_TIMING_TAG.len()..]
+            .split(' ')
+            .filter(|x| *x != "")
+            .collect();
+
+        let mut timing = TestbenchTiming::default();
+        for kv in words.chunks(2) {
+            if kv.len() != 2 {
+                return None;
+            }
+            let val: u64 = kv[1].parse().ok()?;
+            match kv[0] {
+                "time_unit_ps"      => timing.time_unit_ps = val,
+                "clock_period"      => timing.clock_period = val,
+                "reset_cycles"      => timing.reset_cycles = val,
+                "first_sample_time" => timing.first_sample_time = val,
+                _ => return None,
+            }
+        }
+        Some(timing)
+    }
+}
+
 /// Generates a testharness String
+/// - `clocks` are the clock inputs that are slower than `clock` along with
+///   their periods in `clock` cycles. Their rising edges line up with the
+///   `clock` edges that end every `period`-th target cycle
 fn generate_testbench_string(
     input_stimuli: &InputStimuliMap,
     io: Vec<Port>,
     top: String,
+    timing: &TestbenchTiming,
+    clocks: &IndexMap<String, u32>,
 ) -> String {
     let mut testbench = "
 `timescale 1 ns/10 ps
@@ -32,22 +140,51 @@ module testharness;
             ));
         }
     }
+    let mut slow_clocks = "".to_string();
+    for (clk, period) in clocks.iter().filter(|(_, p)| **p > 1) {
+        slow_clocks.push_str(&format!(
+            "
+initial begin
+  {} = 1'b1;
+  #(FIRST_SAMPLE_TIME % (T*{}));
+  forever #(T*{}/2) {} <= ~{};
+end
+",
+            clk, period, period, clk, clk
+        ));
+    }
     testbench.push_str(&format!(
         "
-localparam T=20;
+localparam T={};
+localparam RESET_CYCLES={};
+localparam FIRST_SAMPLE_TIME={};
+
 always begin
   #(T/2) clock <= ~clock;
 end
-
+{}
 initial begin
   clock  = 1'b1;
   reset = 1'b1;
 
-  #(T/2*8) reset = 1'b1;
+  $display($time, \" {} time_unit_ps {} clock_period %0d reset_cycles %0d first_sample_time %0d\",
+           T, RESET_CYCLES, FIRST_SAMPLE_TIME);
 
-  $display($time, \" ** Start Simulation **\");
+  #(T*RESET_CYCLES) reset = 1'b1;
 
-"
+  // Target cycle 0 is sampled at the rising edge at FIRST_SAMPLE_TIME
+  if (FIRST_SAMPLE_TIME > $time) #(FIRST_SAMPLE_TIME - $time);
+
+  $display($time, \" {}\");
+
+",
+        timing.clock_period,
+        timing.reset_cycles,
+        timing.first_sample_time,
+        slow_clocks,
+        TESTBENCH_TIMING_TAG,
+        timing.time_unit_ps,
+        START_SIMULATION_TAG
     ));
     let cycles = input_stimuli.values().fold(0, |x, y| max(x, y.len()));
     for cycle in 0..cycles {
@@ -68,8 +205,8 @@ initial begin
         }
         poke_str.push_str(&format!(");\n"));
 
-        // poke inputs
-        for key in input_stimuli.keys() {
+        // poke inputs (the slower clocks are generated above)
+        for key in input_stimuli.keys().filter(|k| !clocks.contains_key(*k)) {
             let val = input_stimuli[key].get(cycle);
             match val {
                 Some(b) => {
@@ -117,11 +254,20 @@ end
 
 /// Reads from a verilog file, and returns the generated testharness String
 /// when successfull
-fn generate_testbench(
+pub fn generate_testbench(
     file_path: &str,
     top_mod: &str,
     input_stimuli: &InputStimuliMap,
+    timing: &TestbenchTiming,
+    clocks: &IndexMap<String, u32>,
 ) -> Result<String, String> {
+    if timing.first_sample_time < timing.reset_cycles * timing.clock_period ||
+       timing.first_sample_time % timing.clock_period != 0 {
+        return Err(format!(
+            "first_sample_time {} should be a rising clock edge after reset ({} cycles of {})",
+            timing.first_sample_time, timing.reset_cycles, timing.clock_period));
+    }
+
     let verilog_str = match fs::read_to_string(file_path) {
         Ok(content) => content,
         Err(e) => {
@@ -130,7 +276,7 @@ fn generate_testbench(
     };
 
     let ports = get_io(verilog_str.to_string(), top_mod.to_string());
-    let tb = generate_testbench_string(input_stimuli, ports, top_mod.to_string());
+    let tb = generate_testbench_string(input_stimuli, ports, top_mod.to_string(), timing, clocks);
     Ok(tb)
 }
 
@@ -140,9 +286,12 @@ pub fn run_rtl_simulation(
     input_stimuli_path: &str,
     sim_dir: &str,
     sim_output_file: &str,
-) -> std::io::Result<()> {
+    ref_sim: &RefSimulator,
+    clocks: &IndexMap<String, u32>,
+) -> std::io::Result<TestbenchTiming> {
     let input_stimuli = get_input_stimuli(input_stimuli_path);
-    let tb = match generate_testbench(sv_file_path, top_mod, &input_stimuli) {
+    let tb_timing = TestbenchTiming::default();
+    let tb = match generate_testbench(sv_file_path, top_mod, &input_stimuli, &tb_timing, clocks) {
         Ok(x) => x,
         Err(e) => {
             return Err(std::io::Error::other(format!("{}", e)));
@@ -165,72 +314,97 @@ pub fn run_rtl_simulation(
 
     Command::new("mv").arg(&tb_name).arg(&cwd).status()?;
 
-    Command::new("mkdir")
-        .current_dir(&cwd)
-        .arg("build")
-        .status()?;
+    fs::create_dir_all(cwd.join("build"))?;
 
-    Command::new("vcs")
-        .current_dir(&cwd)
-        .arg("-sverilog")
-        .arg("-full64")
-        .arg("+notimingchecks")
-        .arg(&tb_name)
-        .arg(verilog_file.file_name().unwrap())
-        .arg("-o")
-        .arg("build/rtlsim_binary")
-        .status()?;
+    let simulator = ref_rtl_simulator(ref_sim);
+    simulator.compile(
+        &cwd,
+        &tb_name,
+        verilog_file.file_name().unwrap().to_str().unwrap())?;
 
-    let stdout = Command::new("./rtlsim_binary")
-        .current_dir(cwd.join("build"))
-        .output()?
-        .stdout;
+    let stdout = simulator.run(&cwd.join("build"))?;
 
     let output = match String::from_utf8(stdout) {
         Ok(o) => o,
         _ => {
-            return Err(std::io::Error::other(
-                "Output from RTL simulation corrupted",
-            ));
+            return Err(rtl_simulation_corrupted());
         }
     };
 
-    let start_simulation_tag = "** Start Simulation **";
-    let end_simulation_tag = "** End Simulation **";
+    let (timing, output_str) = parse_rtl_simulation_output(&output)?;
+    timing.save(cwd.join("build/sim.vcd").to_str().unwrap())?;
+
+    let mut sim_out_file =
+        fs::File::create(format!("{}/{}", cwd.to_str().unwrap(), sim_output_file))?;
+    sim_out_file.write(output_str.as_bytes())?;
+
+    Ok(timing)
+}
+
+fn rtl_simulation_corrupted() -> std::io::Error {
+    std::io::Error::other("Output from RTL simulation corrupted")
+}
+
+/// Parses the stdout of the reference RTL simulation.
+/// - Returns the testbench timing and the sampled outputs with the
+///   timestamps replaced by target cycles
+/// - Lines between the start and end tags that are not samples of a target
+///   cycle, or a missing testbench timing, are reported as corrupted output
+pub fn parse_rtl_simulation_output(output: &str) -> std::io::Result<(TestbenchTiming, String)> {
+    let mut timing: Option<TestbenchTiming> = None;
     let mut start_collecting = false;
     let mut output_str = "".to_string();
 
     for line in output.lines() {
-        if start_collecting && line.contains(end_simulation_tag) {
-            output_str.push_str(end_simulation_tag);
+        if start_collecting && line.contains(END_SIMULATION_TAG) {
+            output_str.push_str(END_SIMULATION_TAG);
             output_str.push_str("\n");
             break;
         } else if start_collecting {
             let mut words: Vec<&str> = line.split(' ').filter(|x| *x != "").collect();
-            let timestamp: u64 = words[0].parse().unwrap();
+            let timestamp: u64 = match words.first().map(|w| w.parse()) {
+                Some(Ok(t)) => t,
+                _ => {
+                    return Err(rtl_simulation_corrupted());
+                }
+            };
             words.remove(0);
 
-            // TODO: fix hardcoded rtl sim period
-            let cycle = (timestamp - 80) / 20;
+            let cycle = match timing.as_ref().and_then(|t| t.cycle(timestamp)) {
+                Some(c) => c,
+                None => {
+                    return Err(rtl_simulation_corrupted());
+                }
+            };
             output_str.push_str(&format!("{} ", cycle));
             output_str.push_str(&words.join(" "));
             output_str.push_str("\n");
-        } else if line.contains(start_simulation_tag) {
+        } else if line.contains(TESTBENCH_TIMING_TAG) {
+            timing = TestbenchTiming::from_line(line);
+            if timing.is_none() {
+                return Err(std::io::Error::other(
+                    format!("Failed to parse testbench timing: {}", line)));
+            }
+            output_str.push_str(&line[line.find(TESTBENCH_TIMING_TAG).unwrap()..]);
+            output_str.push_str("\n");
+        } else if line.contains(START_SIMULATION_TAG) {
+            if timing.is_none() {
+                return Err(rtl_simulation_corrupted());
+            }
             start_collecting = true;
-            output_str.push_str(start_simulation_tag);
+            output_str.push_str(START_SIMULATION_TAG);
             output_str.push_str("\n");
         }
     }
 
-    let mut sim_out_file =
-        fs::File::create(format!("{}/{}", cwd.to_str().unwrap(), sim_output_file))?;
-    sim_out_file.write(output_str.as_bytes())?;
-
-    Ok(())
+    match timing {
+        Some(t) => Ok((t, output_str)),
+        None => Err(rtl_simulation_corrupted())
+    }
 }
 
 pub fn output_value_fmt(values: &InputStimuliMap) -> String {
-    let mut output_str = "** Start Simulation **\n".to_string();
+    let mut output_str = format!("{}\n", START_SIMULATION_TAG);
     let cycles = values.values().fold(0, |x, y| max(x, y.len()));
     for cycle in 0..cycles {
         output_str.push_str(&format!("{}", cycle));
@@ -239,6 +413,6 @@ pub fn output_value_fmt(values: &InputStimuliMap) -> String {
         }
         output_str.push_str("\n");
     }
-    output_str.push_str("** End Simulation **\n");
+    output_str.push_str(&format!("{}\n", END_SIMULATION_TAG));
     return output_str;
 }
@@ -207,6 +207,42 @@ impl WaveformDB {
         return ret;
     }
 
+    /// Number of waveform time units in `time_unit_ps` picoseconds
+    fn waveform_time_units(self: &Self, time_unit_ps: u64) -> f64 {
+        match self.header.hierarchy.timescale() {
+            Some(ts) => {
+                let exp = ts.unit.to_exponent().unwrap_or(-12) as i32;
+                let unit_ps = (ts.factor as f64) * 10f64.powi(exp + 12);
+                (time_unit_ps as f64) / unit_ps
+            }
+            None => 1.0
+        }
+    }
+
+    /// Returns the index into the time table of the last value change at or
+    /// before `time` (in `time_unit_ps` picoseconds)
+    pub fn time_index(self: &Self, time: u64, time_unit_ps: u64) -> Option<u32> {
+        let wtime = ((time as f64) * self.waveform_time_units(time_unit_ps)).round() as u64;
+        match self.body.time_table.partition_point(|t| *t <= wtime) {
+            0 => None,
+            n => Some((n - 1) as u32)
+        }
+    }
+
+    /// Same as `signal_values_at_cycle_rebase_top`, but indexed by time
+    /// (in `time_unit_ps` picoseconds) instead of a time table index
+    pub fn signal_values_at_time_rebase_top(
+        self: &mut Self,
+        time: u64,
+        time_unit_ps: u64,
+        instance_path: String
+    ) -> IndexMap<String, FourStateBit> {
+        match self.time_index(time, time_unit_ps) {
+            Some(idx) => self.signal_values_at_cycle_rebase_top(idx, instance_path),
+            None => IndexMap::new()
+        }
+    }
+
     pub fn signal_values_at_cycle_rebase_top(self: &mut Self, cycle: u32, instance_path: String) -> IndexMap<String, FourStateBit> {
         let ref_signals = self.signal_values_at_cycle(cycle);
         let instance_depth = instance_path.split(".").collect_vec().len();
@@ -2,23 +2,40 @@ use indexmap::IndexMap;
 use std::cmp::max;
 use indicatif::ProgressBar;
 use petgraph::graph::NodeIndex;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::common::primitive::*;
 use crate::common::config::*;
+use crate::common::circuit::Circuit;
+use crate::common::error::CompileError;
+use crate::common::clocks::add_clock_enable_stimuli;
 use crate::common::utils::save_graph_pdf;
 use crate::fsim::board::*;
+use crate::fsim::lanes::*;
 use crate::rtlsim::rtlsim_utils::*;
 use crate::rtlsim::blif_sim::*;
 use crate::testing::try_new_circuit;
 
+/// Compiles the netlist for a comparison against the blif simulator. The blif
+/// simulator runs on the netlist which compiled designs don't keep, so
+/// `--compiled` is rejected
+fn compile_for_blif_sim(args: &Args) -> Result<Circuit, CompileError> {
+    if args.compiled.is_some() {
+        return Err(CompileError::invalid_config(
+            "compiled", &args.compiled, "the blif simulator needs the netlist, compile the blif file instead"));
+    }
+    return try_new_circuit(args);
+}
+
 pub fn compare_blif_sim_to_fsim(args: Args) -> std::io::Result<()> {
-    let circuit = try_new_circuit(&args)?;
-    let input_stimuli_blasted = get_input_stimuli_blasted(
+    let circuit = compile_for_blif_sim(&args)?;
+    let mut input_stimuli_blasted = get_input_stimuli_blasted(
         &args.top_mod,
         &args.input_stimuli_path,
         &args.sv_file_path)?;
+    add_clock_enable_stimuli(&mut input_stimuli_blasted, &circuit.compiler_cfg.clocks);
 
-    let mut board = Board::from(&circuit);
+    let mut board: Board = Board::from(&circuit);
     let mut bsim  = BlifSimulator::new(circuit.clone(), input_stimuli_blasted.clone());
 
     let cycles = input_stimuli_blasted.values().fold(0, |x, y| max(x, y.len()));
@@ -132,3 +149,140 @@ pub fn compare_blif_sim_to_fsim(args: Args) -> std::io::Result<()> {
 
     return Ok(());
 }
+
+/// Runs `LaneVec::LANES` random stimuli at once on a bit-parallel `Board`
+/// and compares every lane against its own blif simulator.
+/// - Lane `l` drives random values generated from `seed + l` on the inputs.
+///   Resets and clocks follow `args.input_stimuli_path` in every lane
+/// - Reports the first mismatch of each lane and fails if any lane mismatches
+pub fn compare_blif_sim_to_fsim_lanes(args: Args, seed: u64) -> std::io::Result<()> {
+    let circuit = compile_for_blif_sim(&args)?;
+    let input_stimuli_blasted = get_input_stimuli_blasted(
+        &args.top_mod,
+        &args.input_stimuli_path,
+        &args.sv_file_path)?;
+
+    let cycles = input_stimuli_blasted.values().fold(0, |x, y| max(x, y.len()));
+    assert!(cycles > 1, "No point in running {}", cycles);
+
+    let lanes = LaneVec::LANES as usize;
+    let mut lane_stimuli: Vec<InputStimuliMap> = vec![];
+    for l in 0..lanes {
+        let mut rng = StdRng::seed_from_u64(seed + l as u64);
+        let mut stimuli = InputStimuliMap::new();
+        for (sig, vals) in input_stimuli_blasted.iter() {
+            if is_reset_signal(sig) || is_debug_reset(sig) || is_clock_signal(sig) {
+                stimuli.insert(sig.clone(), vals.clone());
+            } else {
+                stimuli.insert(sig.clone(), vals.iter().map(|_| rng.gen_range(0..2)).collect());
+            }
+        }
+        add_clock_enable_stimuli(&mut stimuli, &circuit.compiler_cfg.clocks);
+        lane_stimuli.push(stimuli);
+    }
+
+    let mut board: Board<LaneVec> = Board::from(&circuit);
+    let mut bsims: Vec<BlifSimulator> = lane_stimuli.iter()
+        .map(|stimuli| BlifSimulator::new(circuit.clone(), stimuli.clone()))
+        .collect();
+
+    // First mismatch of each lane
+    let mut mismatches: Vec<Option<String>> = vec![None; lanes];
+
+    let bar = ProgressBar::new(cycles as u64);
+    let mut printed_compared_cnt = false;
+    for cycle in 0..(cycles-1) {
+        bar.inc(1);
+
+        // Collect input stimuli of every lane for the current cycle by name
+        let mut input_stimuli_by_name: IndexMap<String, LaneVec> = IndexMap::new();
+        for (l, stimuli) in lane_stimuli.iter().enumerate() {
+            for (key, vals) in stimuli.iter() {
+                match vals.get(cycle) {
+                    Some(b) => {
+                        let val = input_stimuli_by_name.entry(key.to_string()).or_insert(0);
+                        val.set_lane(l as u32, *b as Bit);
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        // Resets are the same in every lane
+        let mut has_reset = false;
+        for (s, b) in input_stimuli_by_name.iter() {
+            if !is_debug_reset(s) && is_reset_signal(s) && *b > 0 {
+                has_reset = true;
+                break;
+            }
+        }
+
+        let mut input_stimuli_by_step: IndexMap<u32, Vec<(&str, LaneVec)>> = IndexMap::new();
+        for (sig, val) in input_stimuli_by_name.iter() {
+            match board.nodeindex(sig) {
+                Some(nidx) => {
+                    let pc = circuit.graph.node_weight(nidx).unwrap().info().pc;
+                    let step = pc + circuit.platform_cfg.fetch_decode_lat();
+                    if input_stimuli_by_step.get(&step) == None {
+                        input_stimuli_by_step.insert(step, vec![]);
+                    }
+                    input_stimuli_by_step.get_mut(&step).unwrap().push((sig, *val));
+                }
+                None => {
+                }
+            }
+        }
+
+        // Run emulator & blif simulators
+        board.run_cycle(&input_stimuli_by_step);
+        for bsim in bsims.iter_mut() {
+            bsim.run_cycle();
+        }
+
+        if has_reset {
+            continue;
+        }
+
+        let mut compared_cnt = 0;
+        for nidx in bsims[0].circuit.graph.node_indices() {
+            let name = bsims[0].circuit.graph.node_weight(nidx).unwrap().name();
+            let emul_val = match board.peek(name) {
+                Some(val) => val,
+                None => continue
+            };
+            compared_cnt += 1;
+            for (l, bsim) in bsims.iter().enumerate() {
+                let bsim_val = bsim.circuit.graph.node_weight(nidx).unwrap().info().debug.val;
+                if mismatches[l].is_none() && bsim_val != emul_val.lane(l as u32) {
+                    mismatches[l] = Some(format!("cycle {} node {} blif sim val {} emul sim val {}",
+                        cycle, name, bsim_val, emul_val.lane(l as u32)));
+                }
+            }
+        }
+
+        if !printed_compared_cnt {
+            println!("Compared {} nodes in {} lanes", compared_cnt, lanes);
+            printed_compared_cnt = true;
+        }
+
+        if mismatches.iter().all(|m| m.is_some()) {
+            break;
+        }
+    }
+    bar.finish();
+
+    for (l, mismatch) in mismatches.iter().enumerate() {
+        match mismatch {
+            Some(msg) => { println!("lane {} (seed {}): {}", l, seed + l as u64, msg); }
+            None => {}
+        }
+    }
+
+    let failed = mismatches.iter().filter(|m| m.is_some()).count();
+    println!("{} of {} lanes match the blif simulator (seeds {}..{})",
+        lanes - failed, lanes, seed, seed + lanes as u64);
+    if failed > 0 {
+        return Err(std::io::Error::other(format!("Simulation mismatch in {} lanes", failed)));
+    }
+    return Ok(());
+}
@@ -0,0 +1,805 @@
+use crate::common::{
+    circuit::Circuit,
+    config::*,
+    error::CompileError,
+    instruction::*,
+    network::*,
+    primitive::*,
+    topology::Topology,
+    verify::{global_senders, verify_program},
+};
+use crate::passes::blif_to_circuit::blif_to_circuit;
+use crate::testing::try_new_circuit;
+use indexmap::IndexMap;
+use petgraph::{
+    graph::NodeIndex,
+    visit::EdgeRef,
+    Direction::{Incoming, Outgoing}
+};
+use std::collections::VecDeque;
+use std::fmt::Display;
+use varisat::{ExtendFormula, Lit, Solver};
+
+/// Netlist LUTs with more inputs are not encoded (the truth table has 2^n rows)
+const MAX_LUT_INPUTS: usize = 16;
+
+/// Data memory slot: (processor, LDM or SDM, address)
+type Slot = (Coordinate, bool, u32);
+
+/// Truth table of a `Lut` given the rows that evaluate to one
+fn table_from_rows(rows: &Vec<Vec<u8>>, nins: usize) -> Vec<bool> {
+    let mut table = vec![false; 1 << nins];
+    for row in rows.iter() {
+        let mut e = 0;
+        for (i, b) in row.iter().enumerate() {
+            e |= (*b as usize) << i;
+        }
+        table[e] = true;
+    }
+    return table;
+}
+
+/// Truth table of an instruction LUT over its first `nins` operands
+fn table_from_bits(lut: u64, nins: usize) -> Vec<bool> {
+    (0..(1usize << nins)).map(|e| (lut >> e) & 1 == 1).collect()
+}
+
+/// Truth table with input `i` fixed to `val`
+fn cofactor(table: &Vec<bool>, i: usize, val: bool) -> Vec<bool> {
+    let mut ret = Vec::with_capacity(table.len() / 2);
+    for e in 0..(table.len() / 2) {
+        let low = e & ((1 << i) - 1);
+        let high = e >> i;
+        ret.push(table[low | ((val as usize) << i) | (high << (i + 1))]);
+    }
+    return ret;
+}
+
+/// Truth table with input `i` inverted
+fn negate_input(table: &Vec<bool>, i: usize) -> Vec<bool> {
+    (0..table.len()).map(|e| table[e ^ (1 << i)]).collect()
+}
+
+/// Truth table with input `i` removed and tied to input `j` (`j < i`)
+fn merge_inputs(table: &Vec<bool>, j: usize, i: usize) -> Vec<bool> {
+    let mut ret = Vec::with_capacity(table.len() / 2);
+    for e in 0..(table.len() / 2) {
+        let low = e & ((1 << i) - 1);
+        let high = e >> i;
+        let bj = (e >> j) & 1;
+        ret.push(table[low | (bj << i) | (high << (i + 1))]);
+    }
+    return ret;
+}
+
+/// Truth table whose input `p` is input `order[p]` of `table`
+fn permute_inputs(table: &Vec<bool>, order: &Vec<usize>) -> Vec<bool> {
+    let mut ret = Vec::with_capacity(table.len());
+    for e in 0..table.len() {
+        let mut old = 0;
+        for (p, o) in order.iter().enumerate() {
+            old |= ((e >> p) & 1) << o;
+        }
+        ret.push(table[old]);
+    }
+    return ret;
+}
+
+/// # Encoder
+/// - Tseitin encoding of LUT networks into the SAT solver
+/// - LUTs are normalized (constant, inverted, duplicate and unused inputs are
+///   folded, inputs are sorted) and structurally hashed. Logic that is left
+///   untouched by the compiler maps to the same literal on both sides of the
+///   miter and is never handed to the solver
+struct Encoder {
+    solver: Solver<'static>,
+
+    /// Literal that is always true
+    one: Lit,
+
+    /// Normalized (inputs, truth table) of the LUTs encoded so far
+    luts: IndexMap<(Vec<Lit>, Vec<bool>), Lit>
+}
+
+impl Encoder {
+    fn new() -> Self {
+        let mut solver = Solver::new();
+        let one = solver.new_lit();
+        solver.add_clause(&[one]);
+        Encoder {
+            solver: solver,
+            one: one,
+            luts: IndexMap::new()
+        }
+    }
+
+    fn constant(self: &Self, val: bool) -> Lit {
+        if val { self.one } else { !self.one }
+    }
+
+    fn fresh(self: &mut Self) -> Lit {
+        self.solver.new_lit()
+    }
+
+    fn lut(self: &mut Self, mut inputs: Vec<Lit>, mut table: Vec<bool>) -> Lit {
+        // Fold constant inputs and make every input positive
+        let mut i = 0;
+        while i < inputs.len() {
+            let lit = inputs[i];
+            if lit == self.one || lit == !self.one {
+                table = cofactor(&table, i, lit == self.one);
+                inputs.remove(i);
+                continue;
+            }
+            if lit.is_negative() {
+                table = negate_input(&table, i);
+                inputs[i] = !lit;
+            }
+            i += 1;
+        }
+
+        // Merge duplicate inputs and remove the ones the output does not depend on
+        let mut i = 0;
+        while i < inputs.len() {
+            match (0..i).find(|j| inputs[*j] == inputs[i]) {
+                Some(j) => {
+                    table = merge_inputs(&table, j, i);
+                    inputs.remove(i);
+                    continue;
+                }
+                None => {}
+            }
+            let t0 = cofactor(&table, i, false);
+            if t0 == cofactor(&table, i, true) {
+                table = t0;
+                inputs.remove(i);
+                continue;
+            }
+            i += 1;
+        }
+
+        let mut order: Vec<usize> = (0..inputs.len()).collect();
+        order.sort_by_key(|i| inputs[*i]);
+        table = permute_inputs(&table, &order);
+        let inputs: Vec<Lit> = order.iter().map(|i| inputs[*i]).collect();
+
+        if inputs.len() == 0 {
+            return self.constant(table[0]);
+        } else if inputs.len() == 1 {
+            return if table[1] { inputs[0] } else { !inputs[0] };
+        }
+
+        let key = (inputs, table);
+        match self.luts.get(&key) {
+            Some(lit) => { return *lit; }
+            None => {}
+        }
+
+        let (inputs, table) = &key;
+        let y = self.fresh();
+        for (e, val) in table.iter().enumerate() {
+            let mut clause: Vec<Lit> = inputs.iter().enumerate()
+                .map(|(i, x)| if (e >> i) & 1 == 1 { !*x } else { *x })
+                .collect();
+            clause.push(if *val { y } else { !y });
+            self.solver.add_clause(&clause);
+        }
+        self.luts.insert(key, y);
+        return y;
+    }
+
+    fn xor(self: &mut Self, a: Lit, b: Lit) -> Lit {
+        self.lut(vec![a, b], vec![false, true, true, false])
+    }
+}
+
+/// A netlist output or register that computes a different value in the
+/// compiled program
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub signal: String,
+
+    /// The next value of a register differs (rather than an output)
+    pub next_state: bool,
+
+    pub netlist: Bit,
+    pub compiled: Bit
+}
+
+/// Input values and register state of a target cycle in which the netlist
+/// and the compiled program disagree
+#[derive(Debug, Clone, Default)]
+pub struct Counterexample {
+    pub inputs: IndexMap<String, Bit>,
+
+    /// Register values at the start of the target cycle
+    pub state: IndexMap<String, Bit>,
+
+    pub mismatches: Vec<Mismatch>
+}
+
+impl Display for Counterexample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Inputs:")?;
+        for (name, bit) in self.inputs.iter() {
+            writeln!(f, "  {} = {}", name, bit)?;
+        }
+        writeln!(f, "Register state:")?;
+        for (name, bit) in self.state.iter() {
+            writeln!(f, "  {} = {}", name, bit)?;
+        }
+        writeln!(f, "Mismatches:")?;
+        for m in self.mismatches.iter() {
+            let what = if m.next_state { "next state of" } else { "output" };
+            writeln!(f, "  {} {}: netlist {} compiled {}", what, m.signal, m.netlist, m.compiled)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of `check_equivalence`
+#[derive(Debug, Clone, Default)]
+pub struct EquivReport {
+    /// Number of outputs compared
+    pub outputs: usize,
+
+    /// Number of register next-state functions compared
+    pub registers: usize,
+
+    /// Netlist outputs without a counterpart in the compiled program.
+    /// The program is not equivalent when there are any
+    pub unmatched_outputs: Vec<String>,
+
+    /// Netlist registers without a register instruction in the compiled
+    /// program. Registers removed as dead code end up here. Their next-state
+    /// functions are not compared
+    pub unmatched_registers: Vec<String>,
+
+    /// `None` when the functions match for all inputs and register states
+    pub counterexample: Option<Counterexample>
+}
+
+impl EquivReport {
+    pub fn equivalent(self: &Self) -> bool {
+        self.counterexample.is_none() && self.unmatched_outputs.is_empty()
+    }
+
+    pub fn print(self: &Self) {
+        println!("Compared {} outputs and {} register next-state functions",
+            self.outputs, self.registers);
+        if !self.unmatched_registers.is_empty() {
+            println!("{} netlist registers without a compiled register: {:?}",
+                self.unmatched_registers.len(), self.unmatched_registers);
+        }
+        if !self.unmatched_outputs.is_empty() {
+            println!("Not equivalent. {} netlist outputs without a compiled output: {:?}",
+                self.unmatched_outputs.len(), self.unmatched_outputs);
+        }
+        match &self.counterexample {
+            Some(cex) => {
+                println!("Not equivalent. Counterexample:");
+                print!("{}", cex);
+            }
+            None => {
+                if self.unmatched_outputs.is_empty() {
+                    println!("Equivalent");
+                }
+            }
+        }
+    }
+}
+
+/// Both sides of the miter
+struct Miter<'a> {
+    enc: Encoder,
+    netlist: &'a Circuit,
+    compiled: &'a Circuit,
+
+    /// Input literals shared by both sides, indexed by name
+    inputs: IndexMap<String, Lit>,
+
+    /// Names of the compiled `Input` instructions
+    input_names: IndexMap<(Coordinate, u32), String>,
+
+    /// Value written to each data memory slot in the target cycle
+    cur: IndexMap<Slot, Lit>,
+
+    /// Value of each data memory slot at the start of the target cycle.
+    /// Registers read these, so they are the state of the compiled program
+    prev: IndexMap<Slot, Lit>
+}
+
+impl<'a> Miter<'a> {
+    fn input(self: &mut Self, name: &str) -> Lit {
+        match self.inputs.get(name) {
+            Some(lit) => *lit,
+            None => {
+                let lit = self.enc.fresh();
+                self.inputs.insert(name.to_string(), lit);
+                lit
+            }
+        }
+    }
+
+    fn prev_slot(self: &mut Self, slot: Slot) -> Lit {
+        match self.prev.get(&slot) {
+            Some(lit) => *lit,
+            None => {
+                let lit = self.enc.fresh();
+                self.prev.insert(slot, lit);
+                lit
+            }
+        }
+    }
+
+    /// Bit that `op` reads at `pc`. Slots written in this target cycle are
+    /// visible `fetch_decode_lat + dmem_wr_lat` steps after they are written,
+    /// before that the slot still holds the value of the previous cycle.
+    /// With `next`, reads the slots as the instruction will see them in the
+    /// next target cycle
+    fn read(self: &mut Self, coord: Coordinate, op: &Operand, pc: u32, next: bool) -> Lit {
+        let pcfg = &self.compiled.platform_cfg;
+        let slot = (coord, op.local, op.rs);
+        let visible = op.rs + pcfg.fetch_decode_lat() + pcfg.dmem_wr_lat <= pc;
+        match (visible, next, self.cur.get(&slot).copied()) {
+            (true,  false, Some(lit)) => lit,
+            (false, true,  Some(lit)) => lit,
+            (_,     false, _)         => self.prev_slot(slot),
+            (_,     true,  _)         => self.enc.fresh()
+        }
+    }
+
+    /// Bit computed by `inst` at `pc` (see `Processor::compute`)
+    fn eval(self: &mut Self, coord: Coordinate, pc: u32, inst: &Instruction, next: bool) -> Lit {
+        let lut_inputs = self.compiled.platform_cfg.lut_inputs;
+
+        // Missing operands read LDM slot 0
+        let mut ops = vec![];
+        for i in 0..lut_inputs {
+            let op = match inst.operands.get(i as usize) {
+                Some(op) => op.clone(),
+                None => Operand { rs: 0, local: true, idx: i }
+            };
+            ops.push(op);
+        }
+
+        match inst.opcode {
+            Opcode::NOP | Opcode::ConstLut => {
+                return self.enc.constant(false);
+            }
+            Opcode::Input => {
+                match self.input_names.get(&(coord, pc)) {
+                    Some(name) => {
+                        let name = name.clone();
+                        return self.input(&name);
+                    }
+                    None => {
+                        return self.enc.fresh();
+                    }
+                }
+            }
+            Opcode::Lut | Opcode::Gate => {
+                let mut ins = vec![];
+                for op in ops.iter() {
+                    ins.push(self.read(coord, op, pc, next));
+                }
+                return self.enc.lut(ins, table_from_bits(inst.lut, lut_inputs as usize));
+            }
+            Opcode::Output | Opcode::Latch | Opcode::SRAMIn => {
+                return self.read(coord, &ops[0], pc, next);
+            }
+            Opcode::SRAMOut => {
+                return self.enc.fresh();
+            }
+        }
+    }
+
+    /// Symbolically executes one target cycle of the compiled program
+    fn encode_program(self: &mut Self) {
+        let compiled = self.compiled;
+        let pcfg = &compiled.platform_cfg;
+        let senders = global_senders(pcfg);
+
+        let mut procs = vec![];
+        for (m, mmap) in compiled.emul.module_mappings.iter() {
+            for (p, pmap) in mmap.proc_mappings.iter() {
+                procs.push((Coordinate { module: *m, proc: *p }, pmap));
+            }
+        }
+
+        for pc in 0..compiled.emul.host_steps {
+            for (coord, pmap) in procs.iter() {
+                let inst = &pmap.instructions[pc as usize];
+                let out = self.eval(*coord, pc, inst, false);
+                self.cur.insert((*coord, true, pc), out);
+            }
+
+            // The bit received at `pc` was sent `path_lat` steps earlier.
+            // Senders drive either their output or the bit they received in
+            // the previous step (`sinfo.fwd`)
+            for (coord, pmap) in procs.iter() {
+                let sinfo = &pmap.instructions[pc as usize].sinfo;
+                let sender = if !sinfo.local_set {
+                    None
+                } else if sinfo.local {
+                    Some(Coordinate { module: coord.module, proc: sinfo.idx })
+                } else {
+                    match senders.get(coord) {
+                        Some(s) => s.iter().find(|s| s.proc == sinfo.idx).copied(),
+                        None => None
+                    }
+                };
+                let lat = match sender {
+                    Some(src) => pcfg.topology.path_lat(&NetworkPath::new(src, *coord), pcfg),
+                    None => 0
+                };
+                let sent = match (sender, pc.checked_sub(lat)) {
+                    (Some(src), Some(send_pc)) => {
+                        let sinst = &compiled.emul.module_mappings[&src.module]
+                            .proc_mappings[&src.proc]
+                            .instructions[send_pc as usize];
+                        if !sinst.sinfo.fwd {
+                            self.cur.get(&(src, true, send_pc)).copied()
+                        } else if send_pc > 0 {
+                            self.cur.get(&(src, false, send_pc - 1)).copied()
+                        } else {
+                            None
+                        }
+                    }
+                    _ => None
+                };
+                let bit = match sent {
+                    Some(lit) => lit,
+                    None => self.enc.fresh()
+                };
+                self.cur.insert((*coord, false, pc), bit);
+            }
+        }
+    }
+
+    /// Value of each netlist node in the target cycle given the register
+    /// state `regs`
+    fn encode_netlist(self: &mut Self, regs: &IndexMap<String, Lit>) -> Result<IndexMap<NodeIndex, Lit>, CompileError> {
+        let netlist = self.netlist;
+        let graph = &netlist.graph;
+        let mut vals: IndexMap<NodeIndex, Lit> = IndexMap::new();
+        let is_comb = |nidx: NodeIndex| {
+            let p = graph.node_weight(nidx).unwrap().is();
+            p == Primitive::Lut || p == Primitive::Output
+        };
+
+        let mut indeg: IndexMap<NodeIndex, u32> = IndexMap::new();
+        for nidx in graph.node_indices() {
+            let node = graph.node_weight(nidx).unwrap();
+            match &node.prim {
+                CircuitPrimitive::Input { name } => {
+                    let lit = self.input(name);
+                    vals.insert(nidx, lit);
+                }
+                CircuitPrimitive::ConstLut { val, .. } => {
+                    vals.insert(nidx, self.enc.constant(*val == 1));
+                }
+                CircuitPrimitive::Latch { .. } | CircuitPrimitive::Gate { .. } => {
+                    vals.insert(nidx, regs[node.name()]);
+                }
+                CircuitPrimitive::Lut { .. } | CircuitPrimitive::Output { .. } => {
+                    let n = graph.edges_directed(nidx, Incoming)
+                        .filter(|e| is_comb(e.source()))
+                        .count();
+                    indeg.insert(nidx, n as u32);
+                }
+                _ => {}
+            }
+        }
+
+        let mut q: VecDeque<NodeIndex> = indeg.iter()
+            .filter(|(_, d)| **d == 0)
+            .map(|(n, _)| *n)
+            .collect();
+        let mut done = 0;
+        while let Some(nidx) = q.pop_front() {
+            done += 1;
+            let node = graph.node_weight(nidx).unwrap();
+            let lit = match &node.prim {
+                CircuitPrimitive::Lut { inputs, table, .. } => {
+                    if inputs.len() > MAX_LUT_INPUTS {
+                        return Err(CompileError::invalid_config("netlist LUT", node.name(),
+                            &format!("{} inputs, the equivalence check supports up to {}",
+                                inputs.len(), MAX_LUT_INPUTS)));
+                    }
+                    let mut ins = vec![];
+                    for net in inputs.iter() {
+                        ins.push(self.net_value(nidx, net, &vals));
+                    }
+                    self.enc.lut(ins, table_from_rows(table, inputs.len()))
+                }
+                _ => {
+                    match graph.edges_directed(nidx, Incoming).next() {
+                        Some(e) => self.net_value(nidx, e.weight().signal.name(), &vals),
+                        None => self.enc.fresh()
+                    }
+                }
+            };
+            vals.insert(nidx, lit);
+
+            for e in graph.edges_directed(nidx, Outgoing) {
+                match indeg.get_mut(&e.target()) {
+                    Some(d) => {
+                        *d -= 1;
+                        if *d == 0 {
+                            q.push_back(e.target());
+                        }
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        if done != indeg.len() {
+            let stuck = indeg.iter().find(|(_, d)| **d != 0).unwrap().0;
+            return Err(CompileError::invalid_config("netlist", graph.node_weight(*stuck).unwrap().name(),
+                "is on a combinational loop"));
+        }
+        return Ok(vals);
+    }
+
+    /// Value of `net` read by `nidx`. Undriven nets are unconstrained
+    fn net_value(self: &mut Self, nidx: NodeIndex, net: &str, vals: &IndexMap<NodeIndex, Lit>) -> Lit {
+        let graph = &self.netlist.graph;
+        let driver = graph.edges_directed(nidx, Incoming)
+            .find(|e| e.weight().signal.name() == net)
+            .map(|e| e.source());
+        match driver {
+            Some(src) if vals.contains_key(&src) => vals[&src],
+            _ => self.enc.fresh()
+        }
+    }
+
+    fn model_bit(model: &Vec<bool>, lit: Lit) -> Bit {
+        let val = match model.get(lit.var().index()) {
+            Some(v) => *v,
+            None => false
+        };
+        return (val != lit.is_negative()) as Bit;
+    }
+}
+
+/// # check_equivalence
+/// - Proves that the compiled program of `compiled` computes the same output
+///   and register next-state functions as `netlist`, the `HWGraph` straight
+///   out of `blif_to_circuit`, for every input and register state
+/// - One target cycle of the compiled program is executed symbolically. Its
+///   state are the data memory slots read by the Latch and Gate instructions.
+///   Each netlist register is bound to the output of the compiled register
+///   instruction with the same name, so the check is inductive: matching
+///   initial states stay matching forever
+/// - Designs with SRAMs or slow clock domains are not supported
+pub fn check_equivalence(netlist: &Circuit, compiled: &Circuit) -> Result<EquivReport, CompileError> {
+    for nidx in netlist.graph.node_indices() {
+        let node = netlist.graph.node_weight(nidx).unwrap();
+        match node.is() {
+            Primitive::NOP | Primitive::Input | Primitive::Output | Primitive::Lut |
+            Primitive::ConstLut | Primitive::Gate | Primitive::Latch => {}
+            _ => {
+                return Err(CompileError::invalid_config("netlist", node.name(),
+                    "SRAMs are not supported by the equivalence check"));
+            }
+        }
+    }
+    for (clock, period) in compiled.compiler_cfg.clocks.iter() {
+        if *period > 1 {
+            return Err(CompileError::invalid_config("clocks", clock,
+                "slow clock domains are not supported by the equivalence check"));
+        }
+    }
+
+    let violations = verify_program(&compiled.emul, &compiled.platform_cfg);
+    if !violations.is_empty() {
+        return Err(CompileError::InvalidProgram { violations: violations });
+    }
+
+    // Instructions of each signal
+    let mut signals: IndexMap<String, Vec<(Coordinate, u32, Opcode)>> = IndexMap::new();
+    let mut input_names: IndexMap<(Coordinate, u32), String> = IndexMap::new();
+    for (m, mmap) in compiled.emul.module_mappings.iter() {
+        for (p, pmap) in mmap.proc_mappings.iter() {
+            let coord = Coordinate { module: *m, proc: *p };
+            for (name, nmi) in pmap.signal_map.iter() {
+                let opcode = pmap.instructions[nmi.info.pc as usize].opcode;
+                if opcode == Opcode::Input {
+                    input_names.insert((coord, nmi.info.pc), name.clone());
+                }
+                if !signals.contains_key(name) {
+                    signals.insert(name.clone(), vec![]);
+                }
+                signals.get_mut(name).unwrap().push((coord, nmi.info.pc, opcode));
+            }
+        }
+    }
+    let find_signal = |name: &str, opcodes: &[Opcode]| -> Option<(Coordinate, u32, Opcode)> {
+        match signals.get(name) {
+            Some(insts) => {
+                match insts.iter().find(|(_, _, op)| opcodes.contains(op)) {
+                    Some(x) => Some(*x),
+                    None => insts.first().copied()
+                }
+            }
+            None => None
+        }
+    };
+
+    let mut miter = Miter {
+        enc: Encoder::new(),
+        netlist: netlist,
+        compiled: compiled,
+        inputs: IndexMap::new(),
+        input_names: input_names,
+        cur: IndexMap::new(),
+        prev: IndexMap::new()
+    };
+    println!("Encoding the compiled program");
+    miter.encode_program();
+
+    let mut report = EquivReport::default();
+
+    // (signal, next state, netlist literal, compiled literal)
+    let mut pairs: Vec<(String, bool, Lit, Lit)> = vec![];
+
+    // Registers of the netlist hold the value of their compiled counterpart
+    let mut regs: IndexMap<String, Lit> = IndexMap::new();
+    let mut reg_next: IndexMap<String, Lit> = IndexMap::new();
+    for nidx in netlist.graph.node_indices() {
+        let node = netlist.graph.node_weight(nidx).unwrap();
+        if node.is() != Primitive::Latch && node.is() != Primitive::Gate {
+            continue;
+        }
+        let name = node.name().to_string();
+        match find_signal(&name, &[Opcode::Latch, Opcode::Gate]) {
+            Some((coord, pc, opcode)) => {
+                regs.insert(name.clone(), miter.cur[&(coord, true, pc)]);
+                if opcode == Opcode::Latch || opcode == Opcode::Gate {
+                    let inst = compiled.emul.module_mappings[&coord.module]
+                        .proc_mappings[&coord.proc]
+                        .instructions[pc as usize].clone();
+                    let next = miter.eval(coord, pc, &inst, true);
+                    reg_next.insert(name.clone(), next);
+                } else {
+                    report.unmatched_registers.push(name.clone());
+                }
+            }
+            None => {
+                let lit = miter.enc.fresh();
+                regs.insert(name.clone(), lit);
+                report.unmatched_registers.push(name.clone());
+            }
+        }
+    }
+
+    println!("Encoding the netlist");
+    let vals = miter.encode_netlist(&regs)?;
+
+    for nidx in netlist.graph.node_indices() {
+        let node = netlist.graph.node_weight(nidx).unwrap();
+        let name = node.name().to_string();
+        match &node.prim {
+            CircuitPrimitive::Output { .. } => {
+                match find_signal(&name, &[Opcode::Output]) {
+                    Some((coord, pc, _)) => {
+                        pairs.push((name, false, vals[&nidx], miter.cur[&(coord, true, pc)]));
+                        report.outputs += 1;
+                    }
+                    None => {
+                        report.unmatched_outputs.push(name);
+                    }
+                }
+            }
+            CircuitPrimitive::Latch { input, .. } => {
+                match reg_next.get(&name) {
+                    Some(next) => {
+                        let d = miter.net_value(nidx, input, &vals);
+                        pairs.push((name, true, d, *next));
+                        report.registers += 1;
+                    }
+                    None => {}
+                }
+            }
+            CircuitPrimitive::Gate { q, .. } => {
+                match reg_next.get(&name) {
+                    Some(next) => {
+                        let mut ins = vec![];
+                        for net in node.prim.gate_operands().iter() {
+                            if net == q {
+                                ins.push(regs[q]);
+                            } else {
+                                ins.push(miter.net_value(nidx, net, &vals));
+                            }
+                        }
+                        let table = table_from_rows(&node.prim.gate_table(), ins.len());
+                        let d = miter.enc.lut(ins, table);
+                        pairs.push((name, true, d, *next));
+                        report.registers += 1;
+                    }
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Miter: at least one pair differs
+    let mut diffs = vec![];
+    for (_, _, a, b) in pairs.iter() {
+        let d = miter.enc.xor(*a, *b);
+        if d != miter.enc.constant(false) {
+            diffs.push(d);
+        }
+    }
+    if diffs.is_empty() {
+        return Ok(report);
+    }
+    miter.enc.solver.add_clause(&diffs);
+
+    println!("Solving the miter of {} outputs and {} registers", report.outputs, report.registers);
+    let sat = match miter.enc.solver.solve() {
+        Ok(sat) => sat,
+        Err(e) => {
+            return Err(CompileError::Internal { pass: "check_equivalence".to_string(), msg: format!("{}", e) });
+        }
+    };
+    if !sat {
+        return Ok(report);
+    }
+
+    let mut model: Vec<bool> = vec![];
+    for lit in miter.enc.solver.model().unwrap().iter() {
+        let idx = lit.var().index();
+        if model.len() <= idx {
+            model.resize(idx + 1, false);
+        }
+        model[idx] = lit.is_positive();
+    }
+
+    let mut cex = Counterexample::default();
+    for nidx in netlist.graph.node_indices() {
+        let node = netlist.graph.node_weight(nidx).unwrap();
+        if node.is() == Primitive::Input {
+            cex.inputs.insert(node.name().to_string(), Miter::model_bit(&model, vals[&nidx]));
+        }
+    }
+    for (name, lit) in regs.iter() {
+        cex.state.insert(name.clone(), Miter::model_bit(&model, *lit));
+    }
+    for (name, next_state, a, b) in pairs.iter() {
+        let (va, vb) = (Miter::model_bit(&model, *a), Miter::model_bit(&model, *b));
+        if va != vb {
+            cex.mismatches.push(Mismatch {
+                signal: name.clone(),
+                next_state: *next_state,
+                netlist: va,
+                compiled: vb
+            });
+        }
+    }
+    report.counterexample = Some(cex);
+    return Ok(report);
+}
+
+/// Compiles `args.blif_file_path` (or loads `--compiled`) and proves that the
+/// compiled program is equivalent to the netlist. Prints a counterexample
+/// stimulus and fails when they are not
+pub fn prove_equivalence(args: Args) -> std::io::Result<()> {
+    let compiled = try_new_circuit(&args)?;
+    let netlist = match blif_to_circuit(&args.blif_file_path) {
+        Ok(c) => c,
+        Err(e) => {
+            return Err(CompileError::Parse { path: args.blif_file_path.clone(), msg: e }.into());
+        }
+    };
+
+    let report = check_equivalence(&netlist, &compiled)?;
+    report.print();
+    if !report.equivalent() {
+        return Err(std::io::Error::other("Equivalence check failed"));
+    }
+    return Ok(());
+}
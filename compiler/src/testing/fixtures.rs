@@ -0,0 +1,49 @@
+use clap::Parser;
+use crate::common::config::*;
+use crate::testing::blifsim::compare_blif_sim_to_fsim;
+
+/// # example_args
+/// - Arguments for compiling the example `top_mod` from `../examples` (tests
+///   run in the `compiler` directory) into `sim_dir`
+/// - `extra` flags are appended to these defaults and must not repeat them
+pub fn example_args(top_mod: &str, sim_dir: &str, extra: &[&str]) -> Args {
+    let sv    = format!("../examples/{}.sv", top_mod);
+    let input = format!("../examples/{}.input", top_mod);
+    let blif  = format!("../examples/{}.lut.blif", top_mod);
+    let no_dbg = u32::MAX.to_string(); // don't print debug graph when testing
+    let mut argv: Vec<&str> = vec![
+        "bee",
+        "--sv-file-path",       &sv,
+        "--top-mod",            top_mod,
+        "--input-stimuli-path", &input,
+        "--blif-file-path",     &blif,
+        "--sim-dir",            sim_dir,
+        "--max-steps",          "65536",
+        "--dbg-tail-length",    &no_dbg,
+        "--dbg-tail-threshold", &no_dbg,
+    ];
+    argv.extend_from_slice(extra);
+    return Args::parse_from(argv);
+}
+
+/// Runs the blif simulator against the functional simulator and fails the
+/// test with the error of the first mismatch
+pub fn assert_blif_sim_matches(args: Args) {
+    match compare_blif_sim_to_fsim(args) {
+        Ok(_)  => { }
+        Err(e) => { assert!(false, "{}", e); }
+    }
+}
+
+/// Examples and module counts the compiled program tests run on
+pub const EXAMPLE_DESIGNS: [(&str, u32); 2] = [("Adder", 1), ("GCD", 3)];
+
+/// Arguments for compiling the example `top_mod` onto `num_mods` modules of
+/// 8 processors into the sim directory `<prefix>-<top_mod>-<num_mods>`
+pub fn compile_args(prefix: &str, top_mod: &str, num_mods: u32) -> Args {
+    let num_mods_str = num_mods.to_string();
+    return example_args(top_mod, &format!("{}-{}-{}", prefix, top_mod, num_mods), &[
+        "--num-mods",  &num_mods_str,
+        "--num-procs", "8",
+    ]);
+}
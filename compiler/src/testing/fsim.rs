@@ -5,6 +5,7 @@ use indicatif::ProgressBar;
 use crate::common::primitive::*;
 use crate::common::config::*;
 use crate::common::circuit::*;
+use crate::common::clocks::add_clock_enable_stimuli;
 use crate::common::utils::save_graph_pdf;
 use crate::fsim::board::*;
 use crate::rtlsim::rtlsim_utils::*;
@@ -25,6 +26,7 @@ fn compare_signals(
     board: &mut Board,
     board_lag: &mut Board,
     input_stimuli_by_step: &IndexMap<u32, Vec<(&str, Bit)>>,
+    tb_timing: &Option<TestbenchTiming>,
     args: &Args,
     cycle: usize,
 ) -> std::io::Result<ReturnCode> {
@@ -34,12 +36,26 @@ fn compare_signals(
     let mut at_least_one_compare = false;
     let mut found_mismatch = false;
 
-    let offset = if args.clock_start_low { 1 } else { 0 };
-    let waveform_time = (args.timesteps_per_cycle as usize) * (cycle + args.ref_skip_cycles as usize) + offset;
-    let ref_signals = waveform_db.signal_values_at_cycle_rebase_top(waveform_time as u32, args.instance_path.clone());
+    let ref_signals = match tb_timing {
+        Some(timing) => {
+            waveform_db.signal_values_at_time_rebase_top(
+                timing.sample_time(cycle as u64),
+                timing.time_unit_ps,
+                args.instance_path.clone())
+        }
+        None => {
+            // No testbench timing metadata: fall back to the user provided
+            // waveform format
+            let offset = if args.clock_start_low { 1 } else { 0 };
+            let waveform_time = (args.timesteps_per_cycle as usize) * (cycle + args.ref_skip_cycles as usize) + offset;
+            waveform_db.signal_values_at_cycle_rebase_top(waveform_time as u32, args.instance_path.clone())
+        }
+    };
 
     for (signal_name, four_state_bit) in ref_signals.iter() {
-        if is_clock_signal(&signal_name) || is_clock_tap(&signal_name) {
+        if is_clock_signal(&signal_name) ||
+           is_clock_tap(&signal_name)    ||
+           circuit.compiler_cfg.clocks.contains_key(signal_name) {
             continue;
         }
 
@@ -54,6 +70,7 @@ fn compare_signals(
                         cycle, signal_name, ref_bit, bit
                     );
 
+                    // Loaded compiled designs only keep the IO nodes
                     match board.nodeindex(&signal_name) {
                         Some(nodeidx) => {
                             save_graph_pdf(
@@ -98,6 +115,7 @@ fn run_test_cycle(
     board: &mut Board,
     board_lag: &mut Board,
     waveform_db: &mut WaveformDB,
+    tb_timing: &Option<TestbenchTiming>,
     args: &Args,
     has_reset: &bool,
     input_stimuli_by_step: &IndexMap<u32, Vec<(&str, Bit)>>,
@@ -115,7 +133,7 @@ fn run_test_cycle(
     if (cycle as u32) < args.no_check_cycles || *has_reset || !check_period {
         board_lag.run_cycle(&input_stimuli_by_step);
     } else {
-        let rc = compare_signals(circuit, waveform_db, board, board_lag, input_stimuli_by_step, args, cycle);
+        let rc = compare_signals(circuit, waveform_db, board, board_lag, input_stimuli_by_step, tb_timing, args, cycle);
         match rc {
             Ok(ReturnCode::TestSuccess) => {
                 board_lag.run_cycle(&input_stimuli_by_step);
@@ -138,6 +156,7 @@ fn run_test(
     board_lag: &mut Board,
     input_stimuli_blasted: &InputStimuliMap,
     waveform_db: &mut WaveformDB,
+    tb_timing: &Option<TestbenchTiming>,
     args: &Args
 ) -> std::io::Result<ReturnCode> {
     let cycles = input_stimuli_blasted.values().fold(0, |x, y| max(x, y.len()));
@@ -189,6 +208,7 @@ fn run_test(
             board,
             board_lag,
             waveform_db,
+            tb_timing,
             args,
             &has_reset,
             &input_stimuli_by_step,
@@ -217,41 +237,45 @@ pub fn test_emulator(
 
 // circuit.save_graph("final")?;
 
-    let input_stimuli_blasted = get_input_stimuli_blasted(
+    let mut input_stimuli_blasted = get_input_stimuli_blasted(
         &args.top_mod,
         &args.input_stimuli_path,
         &args.sv_file_path)?;
+    add_clock_enable_stimuli(&mut input_stimuli_blasted, &circuit.compiler_cfg.clocks);
 
-    let waveform_path = match &args.vcd {
+    let (waveform_path, tb_timing) = match &args.vcd {
         Some(vcd) => {
-            vcd
+            (vcd.clone(), TestbenchTiming::load(vcd)?)
         }
         None => {
             // No reference waveform provided: run reference RTL simulation
             let sim_output_file = format!("{}-simulation.out", args.top_mod);
-            run_rtl_simulation(
+            let timing = run_rtl_simulation(
                 &args.sv_file_path,
                 &args.top_mod,
                 &args.input_stimuli_path,
                 &out_dir,
                 &sim_output_file,
+                &args.ref_sim,
+                &circuit.compiler_cfg.clocks,
             )?;
             println!("Reference RTL simulation finished");
 
             let mut waveform_path = out_dir.clone();
             waveform_path.push_str("/build/sim.vcd");
-            &waveform_path.clone()
+            (waveform_path, Some(timing))
         }
     };
-    let mut waveform_db = WaveformDB::new(waveform_path);
+    let mut waveform_db = WaveformDB::new(&waveform_path);
 
-    let mut board     = Board::from(&circuit);
-    let mut board_lag = Board::from(&circuit);
+    let mut board:     Board = Board::from(&circuit);
+    let mut board_lag: Board = Board::from(&circuit);
 
     return run_test(&mut circuit,
         &mut board,
         &mut board_lag,
         &input_stimuli_blasted,
         &mut waveform_db,
+        &tb_timing,
         &args);
 }
@@ -1,26 +1,51 @@
 pub mod fsim;
 pub mod blifsim;
+pub mod equiv;
+
+#[cfg(test)]
+pub mod fixtures;
 
 use std::env;
 use std::process::Command;
 use crate::common::config::*;
 use crate::common::circuit::*;
+use crate::common::primitive::CircuitPrimitive;
+use crate::common::sram_image::*;
+use crate::common::clocks::parse_clock_args;
+use crate::common::compiled::{CompiledDesign, netlist_hash};
+use crate::common::topology::GlobalNetworkTopology;
+use crate::common::error::CompileError;
 use crate::passes::blif_to_circuit::blif_to_circuit;
+use crate::passes::check_program::check_program;
 use crate::passes::runner::run_compiler_passes;
 
-pub fn try_new_circuit(args: &Args) -> std::io::Result<Circuit> {
+pub fn try_new_circuit(args: &Args) -> Result<Circuit, CompileError> {
+    return compile_circuit(args, false);
+}
+
+fn compile_circuit(args: &Args, hw: bool) -> Result<Circuit, CompileError> {
     let mut cwd = env::current_dir()?;
     cwd.push(args.sim_dir.clone());
     Command::new("mkdir").arg(&cwd).status()?;
 
+    match &args.compiled {
+        Some(path) => {
+            return load_compiled_circuit(args, path, cwd.to_str().unwrap());
+        }
+        None => {}
+    }
+
     println!("Parsing blif file");
     let res = blif_to_circuit(&args.blif_file_path);
     let mut circuit = match res {
         Ok(c) => c,
         Err(e) => {
-            return Err(std::io::Error::other(format!("{}", e)));
+            return Err(CompileError::Parse { path: args.blif_file_path.clone(), msg: e });
         }
     };
+    if hw {
+        check_hw_sram_ports(&args.blif_file_path, &circuit)?;
+    }
 
     circuit.set_cfg(
         PlatformConfig {
@@ -40,21 +65,95 @@ pub fn try_new_circuit(args: &Args) -> std::io::Result<Circuit> {
             sram_rd_lat:        args.sram_rd_lat,
             sram_wr_lat:        args.sram_wr_lat,
             sram_ip_pl:         args.sram_ip_pl,
-            topology: GlobalNetworkTopology::new(args.num_mods, args.num_procs)
+            sram_slots:         args.sram_slots,
+            topology: GlobalNetworkTopology::new(args.topology, args.num_mods, args.num_procs)?
         },
         CompilerConfig {
             top_module: args.top_mod.clone(),
             output_dir: cwd.to_str().unwrap().to_string(),
             dbg_tail_length: args.dbg_tail_length,
             dbg_tail_threshold: args.dbg_tail_threshold,
+            sram_init: parse_sram_init_args(&args.sram_init)?,
+            clocks: parse_clock_args(&args.clocks)?,
+            lut_map: args.lut_map,
+            partition_constraints: args.partition_constraints.clone(),
+            partitioner: args.partitioner,
+            repartition_iters: args.repartition_iters,
+            repartition_time_limit: args.repartition_time_limit,
+            pass_cfg: args.pass_cfg.clone()
         }
     );
+    circuit.kaminpar_cfg = args.kaminpar_cfg.clone();
 
     println!("Running compiler passes with config: {:#?}", &circuit.platform_cfg);
-    run_compiler_passes(&mut circuit);
+    run_compiler_passes(&mut circuit)?;
     println!("Compiler pass finished");
 
+    // Make sure that the SRAM images can be loaded before running any simulations
+    sram_init_contents(&circuit.compiler_cfg, &circuit.emul)?;
+
     circuit.save_emulator_instructions()?;
     circuit.save_emulator_sigmap()?;
+
+    let compiled_path = format!("{}/{}.compiled.json",
+        circuit.compiler_cfg.output_dir, circuit.compiler_cfg.top_module);
+    CompiledDesign::from(&circuit, netlist_hash(&args.blif_file_path)?).save(&compiled_path)?;
+    return Ok(circuit);
+}
+
+/// Compile `args` for the emulator hardware and its drivers (metasim, swsim, alveo).
+/// - The SRAMProcessor in the emulator RTL holds a single target SRAM, so
+///   packing SRAMs with `--sram-slots` is only supported by the functional
+///   and blif simulators and is rejected here
+/// - The SRAMProcessor has no logic to serialize the ports of a multi-ported
+///   target SRAM, so only 1R1W and single port SRAM blackboxes are accepted
+pub fn try_new_hw_circuit(args: &Args) -> Result<Circuit, CompileError> {
+    check_hw_sram_slots(args.sram_slots)?;
+    let circuit = compile_circuit(args, true)?;
+
+    // A compiled design that is loaded with `--compiled` keeps its own config
+    check_hw_sram_slots(circuit.platform_cfg.sram_slots)?;
+    return Ok(circuit);
+}
+
+fn check_hw_sram_slots(sram_slots: u32) -> Result<(), CompileError> {
+    if sram_slots > 1 {
+        return Err(CompileError::invalid_config("sram_slots", sram_slots,
+            "the emulator SRAM processor holds a single target SRAM, use --sram-slots 1"));
+    }
+    return Ok(());
+}
+
+fn check_hw_sram_ports(blif_file_path: &str, circuit: &Circuit) -> Result<(), CompileError> {
+    for node in circuit.graph.node_weights() {
+        match &node.prim {
+            CircuitPrimitive::SRAMNode { name, port, .. } if !port.is_empty() => {
+                return Err(CompileError::invalid_config("blif_file_path", blif_file_path,
+                    &format!("SRAM {} has multiple ports, which only the functional and blif simulators emulate", name)));
+            }
+            _ => { }
+        }
+    }
+    return Ok(());
+}
+
+/// Load the compiled design at `path` instead of compiling `args.blif_file_path`.
+/// When `args.blif_file_path` is set, the design must be compiled from the same netlist
+fn load_compiled_circuit(args: &Args, path: &str, output_dir: &str) -> Result<Circuit, CompileError> {
+    println!("Loading compiled design {}", path);
+    let design = CompiledDesign::load(path)?;
+
+    if !args.blif_file_path.is_empty() {
+        let hash = netlist_hash(&args.blif_file_path)?;
+        if hash != design.netlist_hash {
+            return Err(CompileError::invalid_config("compiled", path,
+                &format!("compiled from a different netlist than {}, recompile it",
+                    args.blif_file_path)));
+        }
+    }
+
+    let circuit = design.to_circuit(output_dir)?;
+    check_program(&circuit)?;
+    sram_init_contents(&circuit.compiler_cfg, &circuit.emul)?;
     return Ok(circuit);
 }
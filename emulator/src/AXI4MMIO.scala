@@ -148,4 +148,10 @@ object AXI4MMIOModule {
     idx += 1
     return idx - 1
   }
+
+  def bind_decoupled_write(enq: DecoupledIO[UInt], mmio: AXI4MMIOModule): Int = {
+    enq <> mmio.io.ctrl(idx).wr
+    idx += 1
+    return idx - 1
+  }
 }
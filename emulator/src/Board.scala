@@ -14,12 +14,19 @@ class BoardInstInitBundle(cfg: EmulatorConfig) extends Bundle {
   val midx = UInt(log2Ceil(cfg.num_mods).W)
 }
 
+class BoardDataMemInitBundle(cfg: EmulatorConfig) extends Bundle {
+  val init = new ModuleDataMemInitBundle(cfg)
+  val midx = UInt(log2Ceil(cfg.num_mods).W)
+}
+
 class BoardBundle(cfg: EmulatorConfig) extends Bundle {
   import cfg._
 
   val cfg_in = Vec(num_mods, Input(new EModuleConfigBundle(cfg)))
   val init = Output(Bool())
   val inst = Flipped(Decoupled(new BoardInstInitBundle(cfg)))
+  val sram_init = Vec(num_mods, Flipped(Decoupled(new SRAMInitWriteBundle(cfg))))
+  val dmem_init = Flipped(Decoupled(new BoardDataMemInitBundle(cfg)))
 
   val run = Input(Bool())
   val io = Vec(num_mods, new EModuleIOBitsBundle(cfg))
@@ -44,6 +51,7 @@ class Board(cfg: EmulatorConfig) extends Module {
       global_switch.io.ports(i)(j) <> mods(i).io.sw_glb(j)
     }
     mods(i).io.cfg_in := io.cfg_in(i)
+    mods(i).io.sram_init <> io.sram_init(i)
     mods(i).io.run := io.run
     mods(i).io.io <> io.io(i)
   }
@@ -60,6 +68,15 @@ class Board(cfg: EmulatorConfig) extends Module {
     mod.io.inst.ready && (inst_q.io.deq.bits.midx === i.U)
   }}).reduce(_ || _)
 
+  for (i <- 0 until num_mods) {
+    mods(i).io.dmem_init.bits  := io.dmem_init.bits.init
+    mods(i).io.dmem_init.valid := io.dmem_init.valid && (io.dmem_init.bits.midx === i.U)
+  }
+
+  io.dmem_init.ready := mods.zipWithIndex.map({ case(mod, i) => {
+    mod.io.dmem_init.ready && (io.dmem_init.bits.midx === i.U)
+  }}).reduce(_ || _)
+
   io.init := mods.map(_.io.init).reduce(_ && _)
   io.dbg.map(_.bdbg.zipWithIndex.map { case(dbg, i) => {
     dbg := mods(i).io.dbg.get
@@ -67,13 +67,13 @@ class MMIOIf(
   }
 }
 
-case class SRAMConfigAddr(ptype: Int, mask: Int, width: Int)
+case class SRAMConfigAddr(ptype: Int, mask: Int, width: Int, init: Int)
 
 class SRAMConfigVecIf(val cfgs: Seq[SRAMConfigAddr]) extends MMap {
   def str: String = {
     var ret = "vec![\n"
     cfgs.zipWithIndex.foreach({ case (cfg, i) => {
-      ret += s"          SRAMConfig::new(${cfg.ptype}, ${cfg.mask}, ${cfg.width})"
+      ret += s"          SRAMConfig::new(${cfg.ptype}, ${cfg.mask}, ${cfg.width}, ${cfg.init})"
       if (i != cfgs.length - 1) {
         ret += ",\n"
       }
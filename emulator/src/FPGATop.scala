@@ -176,7 +176,7 @@ class FPGATopImp(outer: FPGATop)(cfg: FPGATopParams) extends LazyModuleImp(outer
   val axil_addr_range = 1 << cfg.axil.axi4BundleParams.addrBits
   val axil_data_byts  = cfg.axil.axi4BundleParams.dataBits / 8
 
-  val max_mmio_regs = 4 * cfg.emul.num_mods + 26
+  val max_mmio_regs = 5 * cfg.emul.num_mods + 28
 
   val mmio = Module(new AXI4MMIOModule(max_mmio_regs, cfg.axil.axi4BundleParams))
   AXI4MMIOModule.tieoff(mmio)
@@ -203,8 +203,26 @@ class FPGATopImp(outer: FPGATop)(cfg: FPGATopParams) extends LazyModuleImp(outer
     val mask_idxs  = AXI4MMIOModule.bind_readwrite_reg_array(wmask_bits,      mmio)
     val width_idxs = AXI4MMIOModule.bind_readwrite_reg_array(width_bits,      mmio)
 
-    ptype_idxs.zip(mask_idxs).zip(width_idxs).foreach({ case((p, m), w) => {
-      mmap.ctrl.add_sram(SRAMConfigAddr(p << 2, m << 2, w << 2))
+    // Initial SRAM contents. The host shifts in the data of an entry
+    // (most significant word first) and then writes the address of the module
+    // SRAM processor which triggers the write
+    val sram_init_data = RegInit(0.U(cfg.emul.sram_width.W))
+    val sram_init_data_wr = Wire(Decoupled(UInt(cfg.axil.dataBits.W)))
+    sram_init_data_wr.ready := true.B
+    when (sram_init_data_wr.valid) {
+      sram_init_data := ((sram_init_data << cfg.axil.dataBits) | sram_init_data_wr.bits)(cfg.emul.sram_width - 1, 0)
+    }
+    mmap.ctrl.add_reg(new MMIOIf(
+      AXI4MMIOModule.bind_decoupled_write(sram_init_data_wr, mmio) << 2,
+      false,
+      true,
+      "sram_init_data"))
+
+    val sram_init_addr_wr = Seq.fill(cfg.emul.num_mods)(Wire(Decoupled(UInt(cfg.axil.dataBits.W))))
+    val init_idxs = sram_init_addr_wr.map(w => AXI4MMIOModule.bind_decoupled_write(w, mmio))
+
+    ptype_idxs.zip(mask_idxs).zip(width_idxs).zip(init_idxs).foreach({ case(((p, m), w), i) => {
+      mmap.ctrl.add_sram(SRAMConfigAddr(p << 2, m << 2, w << 2, i << 2))
     }})
 
     val fingerprint_reg = RegInit(BigInt("F00DCAFE", 16).U(32.W))
@@ -271,8 +289,38 @@ class FPGATopImp(outer: FPGATop)(cfg: FPGATopParams) extends LazyModuleImp(outer
       board.io.cfg_in(i).sram.single_port_ram := single_port_ram(i)
       board.io.cfg_in(i).sram.wmask_bits      := wmask_bits(i)
       board.io.cfg_in(i).sram.width_bits      := width_bits(i)
+
+      board.io.sram_init(i).valid     := sram_init_addr_wr(i).valid
+      board.io.sram_init(i).bits.addr := sram_init_addr_wr(i).bits
+      board.io.sram_init(i).bits.data := sram_init_data
+      sram_init_addr_wr(i).ready      := board.io.sram_init(i).ready
     }
 
+    // Initial register state. Each write sets a single data memory bit
+    // {midx, pidx, idx, sdm, bit} (LSB is the bit)
+    val proc_bits = log2Ceil(cfg.emul.num_procs)
+    val mod_bits  = log2Ceil(cfg.emul.num_mods)
+    require(cfg.axil.dataBits >= 2 + cfg.emul.index_bits + proc_bits + mod_bits)
+
+    val dmem_init_wr = Wire(Decoupled(UInt(cfg.axil.dataBits.W)))
+    mmap.ctrl.add_reg(new MMIOIf(
+      AXI4MMIOModule.bind_decoupled_write(dmem_init_wr, mmio) << 2,
+      false,
+      true,
+      "dmem_init"))
+
+    val dmem_init_idx_lo  = 2
+    val dmem_init_pidx_lo = dmem_init_idx_lo + cfg.emul.index_bits
+    val dmem_init_midx_lo = dmem_init_pidx_lo + proc_bits
+    val dmem_init = board.io.dmem_init
+    dmem_init.valid              := dmem_init_wr.valid
+    dmem_init.bits.init.init.bit := dmem_init_wr.bits(0)
+    dmem_init.bits.init.init.sdm := dmem_init_wr.bits(1)
+    dmem_init.bits.init.init.idx := dmem_init_wr.bits(dmem_init_pidx_lo - 1, dmem_init_idx_lo)
+    dmem_init.bits.init.pidx     := dmem_init_wr.bits >> dmem_init_pidx_lo
+    dmem_init.bits.midx          := dmem_init_wr.bits >> dmem_init_midx_lo
+    dmem_init_wr.ready           := dmem_init.ready
+
     val tot_insts_pushed = RegInit(0.U(log2Ceil(cfg.emul.insts_per_mod * cfg.emul.num_mods + 1).W))
 
     mmap.ctrl.add_reg(new MMIOIf(
@@ -24,10 +24,17 @@ class ModuleInstInitBundle(cfg: EmulatorConfig) extends Bundle {
   val pidx = UInt(log2Ceil(cfg.num_procs).W)
 }
 
+class ModuleDataMemInitBundle(cfg: EmulatorConfig) extends Bundle {
+  val init = new DataMemInitBundle(cfg)
+  val pidx = UInt(log2Ceil(cfg.num_procs).W)
+}
+
 class EModuleBundle(cfg: EmulatorConfig) extends Bundle {
   val cfg_in = Input(new EModuleConfigBundle(cfg))
   val init = Output(Bool())
   val inst = Flipped(Decoupled(new ModuleInstInitBundle(cfg)))
+  val sram_init = Flipped(Decoupled(new SRAMInitWriteBundle(cfg)))
+  val dmem_init = Flipped(Decoupled(new ModuleDataMemInitBundle(cfg)))
 
   val run  = Input(Bool())
   val io   = new EModuleIOBitsBundle(cfg)
@@ -84,6 +91,15 @@ class EModule(cfg: EmulatorConfig) extends Module {
     proc.io.inst.ready && (inst_q.io.deq.bits.pidx === i.U)
   }}).reduce(_ || _)
 
+  for (i <- 0 until num_procs) {
+    procs(i).io.dmem_init.bits  := io.dmem_init.bits.init
+    procs(i).io.dmem_init.valid := io.dmem_init.valid && (io.dmem_init.bits.pidx === i.U)
+  }
+
+  io.dmem_init.ready := procs.zipWithIndex.map({ case(proc, i) => {
+    proc.io.dmem_init.ready && (io.dmem_init.bits.pidx === i.U)
+  }}).reduce(_ || _)
+
   val procs_init = procs.map { p => {
     !p.io.inst.ready
   }}.reduce(_ && _)
@@ -93,6 +109,7 @@ class EModule(cfg: EmulatorConfig) extends Module {
   sram_proc.io.run := io.run
   sram_proc.io.host_steps := host_steps
   sram_proc.io.cfg_in := io.cfg_in.sram
+  sram_proc.io.init_wr <> io.sram_init
 
   for (i <- 0 until num_procs) {
     sram_proc.io.ports(i) <> procs(i).io.sram_port
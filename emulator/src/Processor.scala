@@ -14,12 +14,19 @@ class ProcInstInitBundle(cfg: EmulatorConfig) extends Bundle {
   val inst = Flipped(Decoupled(Instruction(cfg)))
 }
 
+class DataMemInitBundle(cfg: EmulatorConfig) extends Bundle {
+  val sdm = Bool()
+  val idx = UInt(cfg.index_bits.W)
+  val bit = UInt(cfg.num_bits.W)
+}
+
 class ProcessorBundle(cfg: EmulatorConfig) extends Bundle {
   import cfg._
   val run  = Input(Bool())
   val host_steps  = Input(UInt(index_bits.W))
 
   val inst = Flipped(Decoupled(Instruction(cfg)))
+  val dmem_init = Flipped(Decoupled(new DataMemInitBundle(cfg)))
 
   val sw_loc = new LocalSwitchPort(cfg)
   val sw_glb = new GlobalSwitchPort(cfg)
@@ -124,7 +131,8 @@ class Processor(cfg: EmulatorConfig) extends Module {
       f_out := ops(0)
     }
     is (Instruction.Gate.U) {
-      f_out := ops(0)
+      // Enable & reset logic of the register is folded into the LUT
+      f_out := de_inst.lut >> (lut_idx * num_bits.U)
     }
     is (Instruction.Latch.U) {
       f_out := ops(0)
@@ -175,4 +183,19 @@ class Processor(cfg: EmulatorConfig) extends Module {
     sdm.io.wr.idx := pc
     sdm.io.wr.bit := 0.U
   }
+
+  // Initial register state can be written by the host once the data memories
+  // are cleared
+  io.dmem_init.ready := init && !io.run
+  when (io.dmem_init.fire) {
+    when (io.dmem_init.bits.sdm) {
+      sdm.io.wr.en  := true.B
+      sdm.io.wr.idx := io.dmem_init.bits.idx
+      sdm.io.wr.bit := io.dmem_init.bits.bit
+    } .otherwise {
+      ldm.io.wr.en  := true.B
+      ldm.io.wr.idx := io.dmem_init.bits.idx
+      ldm.io.wr.bit := io.dmem_init.bits.bit
+    }
+  }
 }
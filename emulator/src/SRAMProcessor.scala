@@ -90,9 +90,15 @@ class SRAMProcessorConfigBundle(cfg: EmulatorConfig) extends Bundle {
   val width_bits      = UInt(cfg.sram_width_bits.W)
 }
 
+class SRAMInitWriteBundle(cfg: EmulatorConfig) extends Bundle {
+  val addr = UInt(cfg.sram_addr_bits.W)
+  val data = UInt(cfg.sram_width.W)
+}
+
 class SRAMProcessorBundle(cfg: EmulatorConfig) extends Bundle {
   val ports      = Vec(cfg.num_procs, new PerProcessorSRAMBundle(cfg))
   val cfg_in     = Input(new SRAMProcessorConfigBundle(cfg))
+  val init_wr    = Flipped(Decoupled(new SRAMInitWriteBundle(cfg)))
   val run        = Input(Bool())
   val host_steps = Input(UInt(cfg.index_bits.W))
   val init       = Output(Bool())
@@ -279,8 +285,12 @@ class SRAMProcessor(cfg: EmulatorConfig) extends Module {
   val wcond = io.run && wen && pc === cfg.sram_rd_lat.U
   val wport_waddr = Mux(wcond, waddr, pc)
   val wport_wdata = Mux(wcond, masked_wr_data.io.masked_wr_data, 0.U)
+  // Initial SRAM contents can be written by the host once the SRAM is cleared
+  io.init_wr.ready := init && !io.run
   when (wcond || !init) {
     sram.write(wport_waddr, wport_wdata)
+  } .elsewhen (io.init_wr.fire) {
+    sram.write(io.init_wr.bits.addr, io.init_wr.bits.data)
   }
 
   for (i <- 0 until num_procs) {
@@ -0,0 +1,19 @@
+module DupInput(
+  input  clock,
+  input  reset,
+  input  a,
+  input  b,
+  input  c,
+  input  d,
+  output y,
+  output z
+);
+
+  wire t = a & b;
+  assign y = t & b & d;
+
+  reg r;
+  always @(posedge clock)
+    r <= y;
+  assign z = t & ~r;
+endmodule
@@ -0,0 +1,35 @@
+module GateRegs(
+  input        clock,
+  input        reset,
+  input        io_en,
+  input        io_in,
+  output [1:0] io_cnt,
+  output       io_hold,
+  output       io_x
+);
+
+  reg [1:0] cnt;
+  always @(posedge clock) begin
+    if (reset)
+      cnt <= 2'h0;
+    else if (io_en)
+      cnt <= cnt + 2'h1;
+  end // always @(posedge)
+
+  reg hold;
+  always @(posedge clock) begin
+    if (io_en)
+      hold <= io_in;
+  end // always @(posedge)
+
+  reg x;
+  always @(posedge clock) begin
+    if (reset)
+      x <= 1'h0;
+    else
+      x <= io_in ^ hold;
+  end // always @(posedge)
+  assign io_cnt = cnt;
+  assign io_hold = hold;
+  assign io_x = x;
+endmodule
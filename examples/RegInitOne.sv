@@ -0,0 +1,13 @@
+module RegInitOne(
+  input        clock,
+  input        reset,
+  input  [1:0] io_a,
+  output [1:0] io_c
+);
+
+  reg [1:0] reg_0 = 2'h1;
+  always @(posedge clock) begin
+    reg_0 <= reg_0 + io_a;
+  end // always @(posedge)
+  assign io_c = reg_0;
+endmodule
@@ -0,0 +1,15 @@
+module RegInitShared(
+  input        clock,
+  input        reset,
+  input  [1:0] io_a,
+  output [1:0] io_c
+);
+
+  reg reg_0 = 1'h1;
+  reg reg_1 = 1'h0;
+  always @(posedge clock) begin
+    reg_0 <= io_a[0] ^ io_a[1];
+    reg_1 <= io_a[0] ^ io_a[1];
+  end // always @(posedge)
+  assign io_c = {reg_1, reg_0};
+endmodule
@@ -0,0 +1,69 @@
+module mem_4x2(
+  input  [1:0] R0_addr,
+  input        R0_en,
+  input        R0_clk,
+  output [1:0] R0_data,
+  input  [1:0] W0_addr,
+  input        W0_en,
+  input        W0_clk,
+  input  [1:0] W0_data
+);
+
+  reg [1:0] Memory[0:3];
+  reg       _R0_en_d0;
+  reg [1:0] _R0_addr_d0;
+  always @(posedge R0_clk) begin
+    _R0_en_d0 <= R0_en;
+    _R0_addr_d0 <= R0_addr;
+  end // always @(posedge)
+  always @(posedge W0_clk) begin
+    if (W0_en)
+      Memory[W0_addr] <= W0_data;
+  end // always @(posedge)
+  assign R0_data = _R0_en_d0 ? Memory[_R0_addr_d0] : 2'bx;
+endmodule
+
+// Counters & a SRAM in two clock domains. `slow_clock` is derived from
+// `clock`, e.g. --clocks slow_clock=2
+module TwoClocks(
+  input        clock,
+  input        slow_clock,
+  input        reset,
+  input        io_ren,
+  input        io_wen,
+  input  [1:0] io_addr,
+  input  [1:0] io_wdata,
+  output [1:0] io_fast,
+  output [1:0] io_slow,
+  output [1:0] io_rdata
+);
+
+  reg [1:0] fast;
+  always @(posedge clock) begin
+    if (reset)
+      fast <= 2'h0;
+    else
+      fast <= fast + 2'h1;
+  end // always @(posedge)
+
+  reg [1:0] slow;
+  always @(posedge slow_clock) begin
+    if (reset)
+      slow <= 2'h0;
+    else
+      slow <= slow + 2'h1;
+  end // always @(posedge)
+
+  mem_4x2 mem_ext (
+    .R0_addr (io_addr),
+    .R0_en   (io_ren),
+    .R0_clk  (slow_clock),
+    .R0_data (io_rdata),
+    .W0_addr (io_addr),
+    .W0_en   (io_wen),
+    .W0_clk  (slow_clock),
+    .W0_data (io_wdata)
+  );
+  assign io_fast = fast;
+  assign io_slow = slow;
+endmodule
@@ -0,0 +1,77 @@
+// Generated by CIRCT firtool-1.62.0
+// VCS coverage exclude_file
+module mem_8x4(
+  input  [2:0] R0_addr,
+  input        R0_en,
+  input        R0_clk,
+  output [3:0] R0_data,
+  input  [2:0] R1_addr,
+  input        R1_en,
+  input        R1_clk,
+  output [3:0] R1_data,
+  input  [2:0] W0_addr,
+  input        W0_en,
+  input        W0_clk,
+  input  [3:0] W0_data,
+  input  [1:0] W0_mask
+);
+
+  reg [3:0] Memory[0:7];
+  reg       _R0_en_d0;
+  reg [2:0] _R0_addr_d0;
+  always @(posedge R0_clk) begin
+    _R0_en_d0 <= R0_en;
+    _R0_addr_d0 <= R0_addr;
+  end // always @(posedge)
+  reg       _R1_en_d0;
+  reg [2:0] _R1_addr_d0;
+  always @(posedge R1_clk) begin
+    _R1_en_d0 <= R1_en;
+    _R1_addr_d0 <= R1_addr;
+  end // always @(posedge)
+  always @(posedge W0_clk) begin
+    if (W0_en & W0_mask[0])
+      Memory[W0_addr][32'h0 +: 2] <= W0_data[1:0];
+    if (W0_en & W0_mask[1])
+      Memory[W0_addr][32'h2 +: 2] <= W0_data[3:2];
+  end // always @(posedge)
+  assign R0_data = _R0_en_d0 ? Memory[_R0_addr_d0] : 4'bx;
+  assign R1_data = _R1_en_d0 ? Memory[_R1_addr_d0] : 4'bx;
+endmodule
+
+module TwoReadOneWritePortSRAM(
+  input        clock,
+  input        reset,
+  input        io_ren0,
+  input  [2:0] io_raddr0,
+  output [3:0] io_rdata0,
+  input        io_ren1,
+  input  [2:0] io_raddr1,
+  output [3:0] io_rdata1,
+  input        io_wen,
+  input  [2:0] io_waddr,
+  input  [3:0] io_wdata,
+  input  [1:0] io_wmask
+);
+
+  wire [3:0] _mem_ext_R0_data;
+  wire [3:0] _mem_ext_R1_data;
+  mem_8x4 mem_ext (
+    .R0_addr (io_raddr0),
+    .R0_en   (io_ren0),
+    .R0_clk  (clock),
+    .R0_data (_mem_ext_R0_data),
+    .R1_addr (io_raddr1),
+    .R1_en   (io_ren1),
+    .R1_clk  (clock),
+    .R1_data (_mem_ext_R1_data),
+    .W0_addr (io_waddr),
+    .W0_en   (io_wen),
+    .W0_clk  (clock),
+    .W0_data (io_wdata),
+    .W0_mask (io_wmask)
+  );
+  assign io_rdata0 = _mem_ext_R0_data;
+  assign io_rdata1 = _mem_ext_R1_data;
+endmodule
+
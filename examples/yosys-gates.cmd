@@ -0,0 +1,12 @@
+read_verilog Tile.sv
+hierarchy -check -top Tile
+proc; opt; memory; opt; fsm; opt; techmap; opt;
+async2sync;
+dfflegalize -cell $_DFF_P_ 01 -cell $_DFFE_PP_ 0 -cell $_SDFF_PP0_ 0 -cell $_SDFFE_PP0P_ 0; opt -nodffe -nosdff
+flatten
+opt_clean -purge
+abc -g AND,NAND,OR,NOR,XOR,XNOR,MUX
+techmap -map +/gate2lut.v -D LUT_WIDTH=3
+opt_clean -purge
+opt -nodffe -nosdff;
+write_blif Tile.gates.blif
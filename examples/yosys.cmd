@@ -1,8 +1,8 @@
 read_verilog Tile.sv
 hierarchy -check -top Tile
-proc; opt -nodffe -nosdff; memory; opt -nodffe -nosdff; fsm; opt -nodffe -nosdff; techmap; opt -nodffe -nosdff;
+proc; opt; memory; opt; fsm; opt; techmap; opt;
 async2sync;
-dffunmap; opt -nodffe -nosdff
+dfflegalize -cell $_DFF_P_ 01 -cell $_DFFE_PP_ 0 -cell $_SDFF_PP0_ 0 -cell $_SDFFE_PP0P_ 0; opt -nodffe -nosdff
 flatten
 opt_clean -purge
 abc -fast -lut 3
@@ -7,6 +7,7 @@ use std::{
 };
 use bee::{
     common::{
+        clocks::add_clock_enable_stimuli,
         config::Args,
         hwgraph::NodeMapInfo, instruction::*,
         mapping::SRAMMapping,
@@ -14,7 +15,7 @@ use bee::{
         primitive::Primitive
     },
     rtlsim::rtlsim_utils::get_input_stimuli_blasted,
-    testing::try_new_circuit
+    testing::try_new_hw_circuit
 };
 use simif::{
     simif::*,
@@ -91,7 +92,7 @@ fn main() -> Result<(), SimIfErr> {
         args.func,
     )?;
 
-    let circuit = try_new_circuit(&args.bee_args)?;
+    let circuit = try_new_hw_circuit(&args.bee_args)?;
 
     // Aggregate per module instructions
     let mut module_insts: IndexMap<u32, VecDeque<Instruction>> = IndexMap::new();
@@ -108,11 +109,12 @@ fn main() -> Result<(), SimIfErr> {
     }
 
     // Get the input stimuli
-    let input_stimuli_blasted =
+    let mut input_stimuli_blasted =
         get_input_stimuli_blasted(
             &args.bee_args.top_mod,
             &args.bee_args.input_stimuli_path,
             &args.bee_args.sv_file_path)?;
+    add_clock_enable_stimuli(&mut input_stimuli_blasted, &circuit.compiler_cfg.clocks);
 
     // Aggregate signal mappings
     let mut all_signal_map: IndexMap<String, NodeMapInfo> = IndexMap::new();
@@ -188,6 +190,7 @@ fn main() -> Result<(), SimIfErr> {
     board_reset(&mut driver, &fpga_top_cfg)?;
     test_dma_bridge(&mut driver, args.dma_test_iterations, &fpga_top_cfg)?;
     set_target_config_regs(&mut driver, &sram_cfgs, circuit.emul.host_steps)?;
+    push_sram_init(&mut driver, &circuit, &fpga_top_cfg)?;
     push_instructions(&mut driver, module_insts, circuit.emul.host_steps, &fpga_top_cfg)?;
 
     while driver.ctrl_bridge.init_done.read(&mut driver.simif)? == 0 {
@@ -195,6 +198,8 @@ fn main() -> Result<(), SimIfErr> {
         sleep(std::time::Duration::from_millis(1));
     }
 
+    push_dmem_init(&mut driver, &circuit, &fpga_top_cfg)?;
+
     println!("Simulation initialization finished");
 
     if args.trace_mode {
@@ -8,9 +8,12 @@ use bee::{
         network::Coordinate,
         config::PlatformConfig,
         circuit::Circuit,
+        error::CompileError,
         hwgraph::NodeMapInfo, instruction::*,
+        inst_image::{encode_inst_beat, inst_beat_bits},
         mapping::{SRAMMapping, SRAMPortType},
-        primitive::Bit
+        primitive::Bit,
+        sram_image::sram_init_contents
     },
     fsim::board::Board,
     rtlsim::rtlsim_utils::InputStimuliMap,
@@ -50,7 +53,7 @@ pub fn get_input_stimuli_by_step<'a>(
     for (sig, bit) in input_stimuli_by_name.iter() {
         match signal_map.get(*sig) {
             Some(nmap) => {
-                let pc = circuit.graph.node_weight(nmap.idx).unwrap().info().pc;
+                let pc = nmap.info.pc;
                 let step = pc + circuit.platform_cfg.fetch_decode_lat();
                 if input_stimuli_by_step.get(&step) == None {
                     input_stimuli_by_step.insert(step, vec![]);
@@ -211,6 +214,14 @@ pub fn set_target_config_regs(
 
     println!("Setting SRAM config registers");
     for (m, sram_cfg) in sram_cfgs.iter() {
+        if sram_cfg.mems.len() > 1 {
+            return Err(Box::new(CompileError::NotEnoughSRAMSlots {
+                sram: format!("packed into module {}", m),
+                slots: sram_cfg.mems.len() as u32,
+                sram_slots: 1
+            }));
+        }
+
         let single_port_sram = match sram_cfg.port_type {
             SRAMPortType::SinglePortSRAM     => { true }
             SRAMPortType::OneRdOneWrPortSRAM => { false }
@@ -240,6 +251,108 @@ pub fn set_target_config_regs(
     return Ok(());
 }
 
+/// Write the initial contents of the target SRAMs (`--sram-init`) to the
+/// SRAM processors. Must be called before `init_done` is set.
+pub fn push_sram_init(
+    driver: &mut Driver,
+    circuit: &Circuit,
+    cfg: &FPGATopConfig
+) -> Result<(), SimIfErr> {
+    println!("Start writing initial SRAM contents");
+
+    let contents = sram_init_contents(&circuit.compiler_cfg, &circuit.emul)?;
+    let word_bits = cfg.axil.data_bits;
+    let nwords = (cfg.emul.sram_width + word_bits - 1) / word_bits;
+    for (m, mcontents) in contents.iter() {
+        for (addr, bits) in mcontents.iter() {
+            assert!(driver.ctrl_bridge.init_done.read(&mut driver.simif)? == 0,
+                "Init set while writing SRAM contents, module {} addr 0x{:x}", m, addr);
+
+            // Shift in the entry, most significant word first
+            for w in (0..nwords).rev() {
+                let mut word = 0u32;
+                for i in 0..std::cmp::min(word_bits, 32) {
+                    match bits.get((w * word_bits + i) as usize) {
+                        Some(b) if *b != 0 => { word |= 1 << i; }
+                        _ => {}
+                    }
+                }
+                driver.ctrl_bridge.sram_init_data.write(&mut driver.simif, word)?;
+                driver.simif.step();
+            }
+
+            // Writing the address triggers the SRAM write
+            let sram_mmios: &SRAMConfig = driver.ctrl_bridge.sram.get(*m as usize).unwrap();
+            sram_mmios.init_addr.write(&mut driver.simif, *addr)?;
+            driver.simif.step();
+        }
+        println!("Wrote {} SRAM entries to module {}", mcontents.len(), m);
+    }
+
+    return Ok(());
+}
+
+/// Encode a data memory initialization write. Mirrors the decoding in `FPGATop`.
+/// Fails when the fields don't fit in the 32 bit MMIO word or are out of range
+pub fn dmem_init_word(
+    emul: &PlatformConfig,
+    m: u32,
+    p: u32,
+    sdm: bool,
+    addr: u32,
+    bit: Bit
+) -> Result<u32, SimIfErr> {
+    let idx_bits = emul.index_bits();
+    let pidx_lo = 2 + idx_bits;
+    let midx_lo = pidx_lo + emul.num_proc_bits();
+    let word_bits = midx_lo + emul.num_mod_bits();
+    if word_bits > u32::BITS {
+        return Err(format!("dmem init words need {} bits, the MMIO word has {}",
+            word_bits, u32::BITS).into());
+    }
+    if m >= emul.num_mods || p >= emul.num_procs || addr >= (1 << idx_bits) || bit > 1 {
+        return Err(format!("dmem init of module {} proc {} addr {} bit {} is out of range",
+            m, p, addr, bit).into());
+    }
+    return Ok((m << midx_lo) | (p << pidx_lo) | (addr << 2) | ((sdm as u32) << 1) | (bit as u32));
+}
+
+/// Write the initial register values to the processor data memories.
+/// Must be called after `init_done` is set and before the target starts running,
+/// as the data memories are cleared while the instructions are pushed.
+pub fn push_dmem_init(
+    driver: &mut Driver,
+    circuit: &Circuit,
+    cfg: &FPGATopConfig
+) -> Result<(), SimIfErr> {
+    println!("Start writing initial register values");
+
+    assert!(driver.ctrl_bridge.init_done.read(&mut driver.simif)? == 1,
+        "Init should be set before writing initial register values");
+
+    let mut cnt = 0;
+    for (m, mmap) in circuit.emul.module_mappings.iter() {
+        for (p, pmap) in mmap.proc_mappings.iter() {
+            let dmem_inits = [(false, &pmap.ldm_init), (true, &pmap.sdm_init)];
+            for (sdm, dmem_init) in dmem_inits.iter() {
+                for (addr, bit) in dmem_init.iter() {
+                    // Data memories are cleared during initialization
+                    if *bit == 0 {
+                        continue;
+                    }
+                    let word = dmem_init_word(&cfg.emul, *m, *p, *sdm, *addr, *bit)?;
+                    driver.ctrl_bridge.dmem_init.write(&mut driver.simif, word)?;
+                    driver.simif.step();
+                    cnt += 1;
+                }
+            }
+        }
+    }
+    println!("Wrote {} initial register values", cnt);
+
+    return Ok(());
+}
+
 pub fn push_instructions(
     driver: &mut Driver,
     module_insts: IndexMap<u32, VecDeque<Instruction>>,
@@ -264,33 +377,13 @@ pub fn push_instructions(
         for (inst_idx, inst) in insts.iter().enumerate() {
             let _p = inst_idx as u32 / host_steps;
 
-            let mut bitbuf = inst.to_bits(&cfg.emul);
-
-            assert!(bitbuf.len() < 8 * 8, "Instruction bits {} > 64", bitbuf.len());
+            assert!(cfg.emul.inst_bits() < 8 * 8, "Instruction bits {} > 64", cfg.emul.inst_bits());
+            assert!(inst_beat_bits(&cfg.emul) < cfg.axi.data_bits,
+                "Instruction beat bits {} >= {}", inst_beat_bits(&cfg.emul), cfg.axi.data_bits);
             assert!(driver.ctrl_bridge.init_done.read(&mut driver.simif)? == 0,
                 "Init set while pushing instructions, module {} inst {}", _m, inst_idx);
 
-            for x in 0..cfg.emul.num_proc_bits() {
-                let sl = cfg.emul.num_proc_bits() - x - 1;
-                bitbuf.push((_p >> sl) & 1 == 1);
-            }
-            for x in 0..cfg.emul.num_mod_bits() {
-                let sl = cfg.emul.num_mod_bits() - x - 1;
-                bitbuf.push((_m >> sl) & 1 == 1);
-            }
-            bitbuf.reverse();
-
-            assert!(bitbuf.len() < cfg.axi.data_bits as usize);
-
-            let mut bytebuf: Vec<u8> = vec![];
-            bytebuf.extend(bitbuf
-                .clone()
-                .into_vec()
-                .iter()
-                .flat_map(|&x| x.to_le_bytes())
-                .rev());
-            bytebuf.reverse();
-            bytebuf.resize(64 as usize, 0);
+            let bytebuf = encode_inst_beat(inst, &cfg.emul, *_m, _p);
 
             let dbg_init_cntr_mmio = driver.ctrl_bridge.dbg_init_cntrs.get(*_m as usize).unwrap();
             let dbg_init_cntr = dbg_init_cntr_mmio.read(&mut driver.simif)?;
@@ -442,7 +535,7 @@ pub fn run_from_trace(
     mapped_input_stimuli_blasted: &mut IndexMap<Coordinate, VecDeque<u64>>,
     fpga_top_cfg: &FPGATopConfig
 ) -> Result<bool, SimIfErr> {
-    let mut funct_sim = Board::from(&circuit);
+    let mut funct_sim: Board = Board::from(&circuit);
     let mut mismatch = false;
 
     let total_procs = circuit.platform_cfg.total_procs();
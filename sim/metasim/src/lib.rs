@@ -5,7 +5,9 @@ pub mod simif;
 pub mod driver;
 use bee::{
     common::{
+        clocks::add_clock_enable_stimuli,
         config::Args,
+        error::CompileError,
         hwgraph::NodeMapInfo, instruction::*,
         mapping::SRAMMapping,
         network::Coordinate,
@@ -13,7 +15,7 @@ use bee::{
     },
     fsim::board::Board,
     rtlsim::rtlsim_utils::get_input_stimuli_blasted,
-    testing::try_new_circuit
+    testing::try_new_hw_circuit
 };
 use indexmap::IndexMap;
 use std::{
@@ -53,6 +55,12 @@ impl From<Box<dyn std::error::Error>> for RTLSimError {
     }
 }
 
+impl From<CompileError> for RTLSimError {
+    fn from(err: CompileError) -> RTLSimError {
+        RTLSimError::IOError(Box::new(err))
+    }
+}
+
 impl From<String> for RTLSimError {
     fn from(err: String) -> RTLSimError {
         RTLSimError::SimError(err)
@@ -60,8 +68,8 @@ impl From<String> for RTLSimError {
 }
 
 pub fn start_test(args: &Args) -> Result<(), RTLSimError> {
-    let circuit = try_new_circuit(&args)?;
-    let mut funct_sim = Board::from(&circuit);
+    let circuit = try_new_hw_circuit(&args)?;
+    let mut funct_sim: Board = Board::from(&circuit);
 
     // Aggregate per module instructions
     let mut module_insts: IndexMap<u32, VecDeque<Instruction>> = IndexMap::new();
@@ -78,8 +86,9 @@ pub fn start_test(args: &Args) -> Result<(), RTLSimError> {
     }
 
     // Get the input stimuli
-    let input_stimuli_blasted =
+    let mut input_stimuli_blasted =
         get_input_stimuli_blasted(&args.top_mod, &args.input_stimuli_path, &args.sv_file_path)?;
+    add_clock_enable_stimuli(&mut input_stimuli_blasted, &circuit.compiler_cfg.clocks);
 
     // Aggregate signal mappings
     let mut all_signal_map: IndexMap<String, NodeMapInfo> = IndexMap::new();
@@ -181,6 +190,7 @@ pub fn start_test(args: &Args) -> Result<(), RTLSimError> {
         board_reset(&mut driver, &fpga_top_cfg)?;
         test_dma_bridge(&mut driver, 20, &fpga_top_cfg)?;
         set_target_config_regs(&mut driver, &sram_cfgs, host_steps)?;
+        push_sram_init(&mut driver, &circuit, &fpga_top_cfg)?;
         push_instructions(&mut driver, module_insts, host_steps, &fpga_top_cfg)?;
 
         // Wait until initialization is finished
@@ -188,6 +198,8 @@ pub fn start_test(args: &Args) -> Result<(), RTLSimError> {
             driver.simif.step();
         }
 
+        push_dmem_init(&mut driver, &circuit, &fpga_top_cfg)?;
+
         println!("Start simulation");
 
         let sim_bar = ProgressBar::new(target_cycles as u64);
@@ -3,7 +3,7 @@
 #[cfg(test)]
 pub mod fpgatop_test {
     use fpgatopsim::start_test;
-    use bee::common::config::Args;
+    use bee::common::config::{Args, KaMinParConfig, PassManagerConfig, PartitionerKind, RefSimulator, TopologyKind};
     use test_case::test_case;
 
     fn test_emulator_rtl(
@@ -20,6 +20,7 @@ pub mod fpgatop_test {
             input_stimuli_path:  input_stimuli_path.to_string(),
             blif_file_path:      blif_file_path.to_string(),
             vcd:                 None,
+            ref_sim:             RefSimulator::default(),
             instance_path:       "testharness.top".to_string(),
             clock_start_low:     false,
             timesteps_per_cycle: 2,
@@ -28,6 +29,7 @@ pub mod fpgatop_test {
             check_cycle_period:  1,
             num_mods:            17,
             num_procs:           64,
+            topology:            TopologyKind::Hub,
             max_steps:           1024,
             lut_inputs:          3,
             inter_proc_nw_lat:   1,
@@ -42,6 +44,17 @@ pub mod fpgatop_test {
             sram_rd_lat:         1,
             sram_wr_lat:         1,
             sram_ip_pl:          1,
+            sram_slots:          1,
+            sram_init:           vec![],
+            clocks:              vec![],
+            lut_map:             false,
+            partition_constraints: None,
+            partitioner:         PartitionerKind::default(),
+            repartition_iters:   0,
+            repartition_time_limit: None,
+            compiled:            None,
+            kaminpar_cfg:        KaMinParConfig::default(),
+            pass_cfg:            PassManagerConfig::default(),
             dbg_tail_length:     u32::MAX, // don't print debug graph when testing
             dbg_tail_threshold:  u32::MAX  // don't print debug graph when testing
         };
@@ -2,3 +2,4 @@ pub mod simif;
 pub mod mmioif;
 pub mod dmaif;
 pub mod driver_generated;
+pub mod swsim;
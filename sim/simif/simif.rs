@@ -21,14 +21,16 @@ pub struct SRAMConfig {
     pub ptype: WrMMIOIf,
     pub mask: WrMMIOIf,
     pub width: WrMMIOIf,
+    pub init_addr: WrMMIOIf,
 }
 
 impl SRAMConfig {
-    pub fn new(paddr: u32, maddr: u32, waddr: u32) -> Self {
+    pub fn new(paddr: u32, maddr: u32, waddr: u32, iaddr: u32) -> Self {
         Self {
             ptype: WrMMIOIf::new(paddr),
             mask: WrMMIOIf::new(maddr),
             width: WrMMIOIf::new(waddr),
+            init_addr: WrMMIOIf::new(iaddr),
         }
     }
 }
@@ -38,6 +40,8 @@ pub struct ControlIf {
     pub custom_resetn: WrMMIOIf,
 
     pub sram: Vec<SRAMConfig>,
+    pub sram_init_data: WrMMIOIf,
+    pub dmem_init: WrMMIOIf,
 
     pub fingerprint: RdWrMMIOIf,
     pub host_steps: RdWrMMIOIf,
@@ -0,0 +1,677 @@
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use indexmap::IndexMap;
+use bitvec::{order::Lsb0, vec::BitVec};
+use bee::{
+    common::{
+        config::PlatformConfig,
+        inst_image::decode_inst_beat,
+        instruction::Instruction,
+        mapping::{SRAMMapping, SRAMPortType},
+        primitive::Bit
+    },
+    fsim::board::Board
+};
+use crate::simif::simif::*;
+use crate::driver::driver::FPGATopConfig;
+
+/// Value of the control bridge fingerprint register after reset
+pub const SWSIM_CTRL_FINGERPRINT: u32 = 0xf00dcafe;
+
+/// Value of the clock wizard fingerprint register after reset
+pub const SWSIM_CLKWIZ_FINGERPRINT: u32 = 0xAAC0FFEE;
+
+/// Depth of the midx & pidx mismatch queues in FPGATop
+const MISMATCH_QUEUE_DEPTH: usize = 4;
+
+/// Number of AXI beats that each DMA stream queue can buffer
+const STREAM_QUEUE_BEATS: u32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SWStream {
+    IO,
+    Inst,
+    DMA,
+    Dbg
+}
+
+/// Registers exposed by FPGATop through MMIO
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SWReg {
+    CustomResetn,
+    SRAMPtype(u32),
+    SRAMMask(u32),
+    SRAMWidth(u32),
+    SRAMInitData,
+    SRAMInitAddr(u32),
+    DMemInit,
+    Fingerprint,
+    HostSteps,
+    HostStepsPrvDeq,
+    HostStepsPrvCnt,
+    HostStepsCurDeq,
+    HostStepsCurCnt,
+    InitDone,
+    TotInstsPushed,
+    PcsAreZero,
+    DbgProc0Init,
+    DbgProcNInit,
+    MidxMismatchDeq,
+    MidxMismatchCnt,
+    PidxMismatchDeq,
+    PidxMismatchCnt,
+    DbgInitCntr(u32),
+    TargetCycleLo,
+    TargetCycleHi,
+    StreamFilled(SWStream),
+    StreamEmpty(SWStream),
+    PllLocked,
+    PllReset,
+    FPGATopResetn,
+    ClkWizFingerprint,
+    PllResetCycle
+}
+
+/// Software model of FPGATop.
+/// Emulates the `ControlIf` & `ClockWizardControlIf` MMIO registers as well as
+/// the io, instruction, DMA loopback and debug streams on top of the
+/// functional simulator (`Board`). This can be used to exercise the driver
+/// without building the RTL simulator.
+/// The MMIO address map is taken from the generated `Driver`, so use
+/// `Driver::try_from_swsim` to construct a `Driver` backed by this model.
+pub struct SWSim {
+    cfg: FPGATopConfig,
+
+    /// MMIO address to register mapping
+    regs: IndexMap<u32, SWReg>,
+
+    /// DMA address to stream mapping
+    streams: IndexMap<u32, SWStream>,
+
+    /// Push the LDM & SDM write bits of every host step into the debug stream
+    debug: bool,
+
+    cycle: u64,
+
+    // ---------------- clock wizard ----------------
+    pll_locked: bool,
+    pll_reset_cycle: u32,
+    clkwiz_fingerprint: u32,
+    fpga_top_resetn: bool,
+
+    // ---------------- control bridge ----------------
+    fingerprint: u32,
+    host_steps: u32,
+    host_steps_prv_q: VecDeque<u32>,
+    host_steps_cur_q: VecDeque<u32>,
+    sram_cfgs: Vec<SRAMMapping>,
+
+    /// SRAM entry shifted in through `sram_init_data` (LSB first)
+    sram_init_data: Vec<Bit>,
+
+    /// Initial SRAM contents written by the host for each module
+    sram_inits: IndexMap<u32, Vec<(u32, Vec<Bit>)>>,
+
+    /// Initial register values written by the host as
+    /// (module, processor, sdm, address, bit)
+    dmem_inits: Vec<(u32, u32, bool, u32, Bit)>,
+    tot_insts_pushed: u32,
+    expect_midx: u32,
+    expect_pidx: u32,
+    inst_cntr: u32,
+    midx_mismatch_q: VecDeque<u32>,
+    pidx_mismatch_q: VecDeque<u32>,
+    target_cycle: u64,
+
+    /// Instructions received for each module and processor
+    insts: Vec<Vec<Vec<Instruction>>>,
+
+    /// Functional model of the emulator, created once initialization is done
+    board: Option<Board>,
+
+    // ---------------- streams ----------------
+    io_in_q:  VecDeque<u8>,
+    io_out_q: VecDeque<u8>,
+    dma_q:    VecDeque<u8>,
+    dbg_q:    VecDeque<u8>,
+}
+
+impl Debug for SWSim {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SWSim[cycle: {} target_cycle: {} host_steps: {} insts_pushed: {} init_done: {}]",
+            self.cycle, self.target_cycle, self.host_steps, self.tot_insts_pushed, self.init_done())
+    }
+}
+
+impl SWSim {
+    pub fn new(cfg: &FPGATopConfig) -> Self {
+        let emul = &cfg.emul;
+        SWSim {
+            cfg: cfg.clone(),
+            regs: IndexMap::new(),
+            streams: IndexMap::new(),
+            debug: false,
+            cycle: 0,
+            pll_locked: false,
+            pll_reset_cycle: 0,
+            clkwiz_fingerprint: SWSIM_CLKWIZ_FINGERPRINT,
+            fpga_top_resetn: true,
+            fingerprint: SWSIM_CTRL_FINGERPRINT,
+            host_steps: 0,
+            host_steps_prv_q: VecDeque::new(),
+            host_steps_cur_q: VecDeque::new(),
+            sram_cfgs: vec![SRAMMapping::default(); emul.num_mods as usize],
+            sram_init_data: vec![0; emul.sram_width as usize],
+            sram_inits: IndexMap::new(),
+            dmem_inits: vec![],
+            tot_insts_pushed: 0,
+            expect_midx: 0,
+            expect_pidx: 0,
+            inst_cntr: 0,
+            midx_mismatch_q: VecDeque::new(),
+            pidx_mismatch_q: VecDeque::new(),
+            target_cycle: 0,
+            insts: vec![vec![vec![]; emul.num_procs as usize]; emul.num_mods as usize],
+            board: None,
+            io_in_q:  VecDeque::new(),
+            io_out_q: VecDeque::new(),
+            dma_q:    VecDeque::new(),
+            dbg_q:    VecDeque::new(),
+        }
+    }
+
+    /// Bind the MMIO & DMA addresses used by `driver` to the model registers.
+    /// Per module registers of modules that are not in `cfg.emul` stay unmapped
+    fn bind(self: &mut Self, driver: &Driver) {
+        let ctrl = &driver.ctrl_bridge;
+        let clkwiz = &driver.clkwiz_ctrl;
+        let num_mods = self.emul().num_mods as usize;
+
+        self.regs.insert(ctrl.custom_resetn.addr,      SWReg::CustomResetn);
+        for (m, sram) in ctrl.sram.iter().take(num_mods).enumerate() {
+            self.regs.insert(sram.ptype.addr, SWReg::SRAMPtype(m as u32));
+            self.regs.insert(sram.mask.addr,  SWReg::SRAMMask(m as u32));
+            self.regs.insert(sram.width.addr, SWReg::SRAMWidth(m as u32));
+            self.regs.insert(sram.init_addr.addr, SWReg::SRAMInitAddr(m as u32));
+        }
+        self.regs.insert(ctrl.sram_init_data.addr,     SWReg::SRAMInitData);
+        self.regs.insert(ctrl.dmem_init.addr,          SWReg::DMemInit);
+        self.regs.insert(ctrl.fingerprint.addr,        SWReg::Fingerprint);
+        self.regs.insert(ctrl.host_steps.addr,         SWReg::HostSteps);
+        self.regs.insert(ctrl.host_steps_prv_deq.addr, SWReg::HostStepsPrvDeq);
+        self.regs.insert(ctrl.host_steps_prv_cnt.addr, SWReg::HostStepsPrvCnt);
+        self.regs.insert(ctrl.host_steps_cur_deq.addr, SWReg::HostStepsCurDeq);
+        self.regs.insert(ctrl.host_steps_cur_cnt.addr, SWReg::HostStepsCurCnt);
+        self.regs.insert(ctrl.init_done.addr,          SWReg::InitDone);
+        self.regs.insert(ctrl.tot_insts_pushed.addr,   SWReg::TotInstsPushed);
+        self.regs.insert(ctrl.pcs_are_zero.addr,       SWReg::PcsAreZero);
+        self.regs.insert(ctrl.dbg_proc_0_init.addr,    SWReg::DbgProc0Init);
+        self.regs.insert(ctrl.dbg_proc_n_init.addr,    SWReg::DbgProcNInit);
+        self.regs.insert(ctrl.midx_mismatch_deq.addr,  SWReg::MidxMismatchDeq);
+        self.regs.insert(ctrl.midx_mismatch_cnt.addr,  SWReg::MidxMismatchCnt);
+        self.regs.insert(ctrl.pidx_mismatch_deq.addr,  SWReg::PidxMismatchDeq);
+        self.regs.insert(ctrl.pidx_mismatch_cnt.addr,  SWReg::PidxMismatchCnt);
+        for (m, cntr) in ctrl.dbg_init_cntrs.iter().take(num_mods).enumerate() {
+            self.regs.insert(cntr.addr, SWReg::DbgInitCntr(m as u32));
+        }
+        self.regs.insert(ctrl.target_cycle_lo.addr,    SWReg::TargetCycleLo);
+        self.regs.insert(ctrl.target_cycle_hi.addr,    SWReg::TargetCycleHi);
+
+        self.regs.insert(clkwiz.pll_locked.addr,       SWReg::PllLocked);
+        self.regs.insert(clkwiz.pll_reset.addr,        SWReg::PllReset);
+        self.regs.insert(clkwiz.fpga_top_resetn.addr,  SWReg::FPGATopResetn);
+        self.regs.insert(clkwiz.fingerprint.addr,      SWReg::ClkWizFingerprint);
+        self.regs.insert(clkwiz.pll_reset_cycle.addr,  SWReg::PllResetCycle);
+
+        let bridges = [
+            (&driver.io_bridge,   SWStream::IO),
+            (&driver.inst_bridge, SWStream::Inst),
+            (&driver.dma_bridge,  SWStream::DMA),
+            (&driver.dbg_bridge,  SWStream::Dbg),
+        ];
+        for (bridge, stream) in bridges.iter() {
+            self.streams.insert(bridge.addr, *stream);
+            self.regs.insert(bridge.filled, SWReg::StreamFilled(*stream));
+            self.regs.insert(bridge.empty,  SWReg::StreamEmpty(*stream));
+        }
+    }
+
+    fn emul(self: &Self) -> &PlatformConfig {
+        &self.cfg.emul
+    }
+
+    fn stream_bytes(self: &Self, bits: u32) -> u32 {
+        let data_bits = self.cfg.axi.data_bits;
+        ((bits + data_bits - 1) / data_bits) * data_bits / 8
+    }
+
+    fn io_stream_bytes(self: &Self) -> u32 {
+        self.stream_bytes(self.emul().total_procs())
+    }
+
+    fn dbg_stream_bytes(self: &Self) -> u32 {
+        self.stream_bytes(2 * self.emul().total_procs())
+    }
+
+    fn inst_stream_bytes(self: &Self) -> u32 {
+        64
+    }
+
+    fn stream_capacity(self: &Self, stream: SWStream) -> u32 {
+        let beat_bytes = match stream {
+            SWStream::IO | SWStream::DMA => self.io_stream_bytes(),
+            SWStream::Inst               => self.inst_stream_bytes(),
+            SWStream::Dbg                => self.dbg_stream_bytes(),
+        };
+        return beat_bytes * STREAM_QUEUE_BEATS;
+    }
+
+    fn stream_queue(self: &mut Self, stream: SWStream) -> &mut VecDeque<u8> {
+        match stream {
+            SWStream::IO   => &mut self.io_out_q,
+            SWStream::DMA  => &mut self.dma_q,
+            SWStream::Dbg  => &mut self.dbg_q,
+            SWStream::Inst => panic!("Instruction stream cannot be pulled"),
+        }
+    }
+
+    fn proc_initialized(self: &Self, m: u32, p: u32) -> bool {
+        self.host_steps > 0 &&
+            self.insts[m as usize][p as usize].len() as u32 >= self.host_steps
+    }
+
+    fn init_cntr(self: &Self, m: u32) -> u32 {
+        (0..self.emul().num_procs)
+            .filter(|p| self.proc_initialized(m, *p))
+            .count() as u32
+    }
+
+    fn init_vec(self: &Self, p: u32) -> u32 {
+        (0..self.emul().num_mods)
+            .filter(|m| self.proc_initialized(*m, p))
+            .fold(0, |vec, m| vec | (1 << m))
+    }
+
+    fn init_done(self: &Self) -> bool {
+        (0..self.emul().num_mods).all(|m| self.init_cntr(m) == self.emul().num_procs)
+    }
+
+    /// Reset the emulator state (custom_resetn)
+    fn reset(self: &mut Self) {
+        let cfg = self.cfg.clone();
+        let regs = std::mem::take(&mut self.regs);
+        let streams = std::mem::take(&mut self.streams);
+        let debug = self.debug;
+        let cycle = self.cycle;
+        let pll_locked = self.pll_locked;
+        let pll_reset_cycle = self.pll_reset_cycle;
+        let clkwiz_fingerprint = self.clkwiz_fingerprint;
+
+        *self = SWSim::new(&cfg);
+        self.regs = regs;
+        self.streams = streams;
+        self.debug = debug;
+        self.cycle = cycle;
+        self.pll_locked = pll_locked;
+        self.pll_reset_cycle = pll_reset_cycle;
+        self.clkwiz_fingerprint = clkwiz_fingerprint;
+    }
+
+    fn push_inst(self: &mut Self, beat: &Vec<u8>) -> Result<(), SimIfErr> {
+        let (inst, pidx, midx) = decode_inst_beat(beat, self.emul())?;
+
+        if midx != self.expect_midx && self.midx_mismatch_q.len() < MISMATCH_QUEUE_DEPTH {
+            self.midx_mismatch_q.push_back(midx);
+        }
+        if pidx != self.expect_pidx && self.pidx_mismatch_q.len() < MISMATCH_QUEUE_DEPTH {
+            self.pidx_mismatch_q.push_back(pidx);
+        }
+
+        self.tot_insts_pushed += 1;
+        if self.inst_cntr + 1 >= self.host_steps {
+            self.inst_cntr = 0;
+            if self.expect_pidx == self.emul().num_procs - 1 {
+                self.expect_pidx = 0;
+                self.expect_midx += 1;
+            } else {
+                self.expect_pidx += 1;
+            }
+        } else {
+            self.inst_cntr += 1;
+        }
+
+        match self.insts.get_mut(midx as usize).and_then(|m| m.get_mut(pidx as usize)) {
+            Some(pinsts) => {
+                pinsts.push(inst);
+                return Ok(());
+            }
+            None => {
+                return Err(format!("SWSim: instruction for module {} proc {} out of range", midx, pidx).into());
+            }
+        }
+    }
+
+    /// Build the functional simulator from the received instructions
+    fn build_board(self: &mut Self) {
+        let mut insts: IndexMap<u32, Vec<Vec<Instruction>>> = IndexMap::new();
+        let mut sram_cfgs: IndexMap<u32, SRAMMapping> = IndexMap::new();
+        for (m, minsts) in self.insts.iter().enumerate() {
+            insts.insert(m as u32, minsts.clone());
+            sram_cfgs.insert(m as u32, self.sram_cfgs[m].clone());
+        }
+        let mut board: Board = Board::from_instructions(
+                self.emul(), self.host_steps, &insts, &sram_cfgs);
+        board.init_srams(&self.sram_inits);
+        for (m, p, sdm, addr, bit) in self.dmem_inits.iter() {
+            board.init_dmem_bit(*m, *p, *sdm, *addr, *bit);
+        }
+        self.board = Some(board);
+    }
+
+    /// Consume a single io stream beat and run a target cycle
+    fn run_target_cycle(self: &mut Self) {
+        if self.board.is_none() {
+            self.build_board();
+        }
+
+        let io_bytes = self.io_stream_bytes() as usize;
+        let dbg_bytes = self.dbg_stream_bytes() as usize;
+        let ibeat: Vec<u8> = self.io_in_q.drain(0..io_bytes).collect();
+        let ibits: BitVec<u8, Lsb0> = BitVec::from_vec(ibeat);
+
+        let num_mods = self.emul().num_mods;
+        let num_procs = self.emul().num_procs;
+        let host_steps = self.host_steps;
+        let debug = self.debug;
+
+        let mut dbg_beats: Vec<Vec<u8>> = vec![];
+        let board = self.board.as_mut().unwrap();
+        for m in 0..num_mods {
+            for p in 0..num_procs {
+                let id = (m * num_procs + p) as usize;
+                board.set_io_i(m, p, ibits[id] as Bit);
+            }
+        }
+
+        let no_input = IndexMap::new();
+        for step in 0..host_steps {
+            let state = board.step_with_input(step, &no_input);
+            if debug {
+                let mut dbits: BitVec<u8, Lsb0> = BitVec::repeat(false, dbg_bytes * 8);
+                for (m, mstate) in state.iter().enumerate() {
+                    for (p, (ldm, sdm)) in mstate.iter().enumerate() {
+                        let id = m * num_procs as usize + p;
+                        dbits.set(2 * id,     *ldm != 0);
+                        dbits.set(2 * id + 1, *sdm != 0);
+                    }
+                }
+                dbg_beats.push(dbits.into_vec());
+            }
+        }
+
+        let mut obits: BitVec<u8, Lsb0> = BitVec::repeat(false, io_bytes * 8);
+        for m in 0..num_mods {
+            for p in 0..num_procs {
+                let id = (m * num_procs + p) as usize;
+                obits.set(id, board.get_io_o(m, p) != 0);
+            }
+        }
+
+        for beat in dbg_beats.iter() {
+            self.dbg_q.extend(beat.iter());
+        }
+        self.io_out_q.extend(obits.into_vec().iter());
+        self.target_cycle += 1;
+    }
+
+    fn read_reg(self: &mut Self, reg: SWReg) -> u32 {
+        match reg {
+            SWReg::CustomResetn          => 1,
+            SWReg::SRAMPtype(m)          => match self.sram_cfgs[m as usize].port_type {
+                SRAMPortType::OneRdOneWrPortSRAM => 0,
+                SRAMPortType::SinglePortSRAM     => 1,
+            },
+            SWReg::SRAMMask(m)           => self.sram_cfgs[m as usize].wmask_bits,
+            SWReg::SRAMWidth(m)          => self.sram_cfgs[m as usize].width_bits,
+            SWReg::SRAMInitData          => 0,
+            SWReg::SRAMInitAddr(_)       => 0,
+            SWReg::DMemInit              => 0,
+            SWReg::Fingerprint           => self.fingerprint,
+            SWReg::HostSteps             => self.host_steps,
+            SWReg::HostStepsPrvDeq       => self.host_steps_prv_q.pop_front().unwrap_or(0),
+            SWReg::HostStepsPrvCnt       => self.host_steps_prv_q.len() as u32,
+            SWReg::HostStepsCurDeq       => self.host_steps_cur_q.pop_front().unwrap_or(0),
+            SWReg::HostStepsCurCnt       => self.host_steps_cur_q.len() as u32,
+            SWReg::InitDone              => self.init_done() as u32,
+            SWReg::TotInstsPushed        => self.tot_insts_pushed,
+            SWReg::PcsAreZero            => ((1u64 << self.emul().num_mods) - 1) as u32,
+            SWReg::DbgProc0Init          => self.init_vec(0),
+            SWReg::DbgProcNInit          => self.init_vec(self.emul().num_procs - 1),
+            SWReg::MidxMismatchDeq       => self.midx_mismatch_q.pop_front().unwrap_or(0),
+            SWReg::MidxMismatchCnt       => self.midx_mismatch_q.len() as u32,
+            SWReg::PidxMismatchDeq       => self.pidx_mismatch_q.pop_front().unwrap_or(0),
+            SWReg::PidxMismatchCnt       => self.pidx_mismatch_q.len() as u32,
+            SWReg::DbgInitCntr(m)        => self.init_cntr(m),
+            SWReg::TargetCycleLo         => (self.target_cycle & 0xffffffff) as u32,
+            SWReg::TargetCycleHi         => (self.target_cycle >> 32) as u32,
+            SWReg::StreamFilled(SWStream::Inst) => 0,
+            SWReg::StreamFilled(stream)  => self.stream_queue(stream).len() as u32,
+            SWReg::StreamEmpty(SWStream::IO)    => self.stream_capacity(SWStream::IO).saturating_sub(self.io_in_q.len() as u32),
+            SWReg::StreamEmpty(SWStream::Inst)  => self.stream_capacity(SWStream::Inst),
+            SWReg::StreamEmpty(stream)   => self.stream_capacity(stream).saturating_sub(self.stream_queue(stream).len() as u32),
+            SWReg::PllLocked             => self.pll_locked as u32,
+            SWReg::PllReset              => 0,
+            SWReg::FPGATopResetn         => self.fpga_top_resetn as u32,
+            SWReg::ClkWizFingerprint     => self.clkwiz_fingerprint,
+            SWReg::PllResetCycle         => self.pll_reset_cycle,
+        }
+    }
+
+    fn write_reg(self: &mut Self, reg: SWReg, data: u32) -> Result<(), SimIfErr> {
+        match reg {
+            SWReg::CustomResetn => {
+                if data == 0 {
+                    self.reset();
+                }
+            }
+            SWReg::SRAMPtype(m) => {
+                self.sram_cfgs[m as usize].port_type = if data != 0 {
+                    SRAMPortType::SinglePortSRAM
+                } else {
+                    SRAMPortType::OneRdOneWrPortSRAM
+                };
+            }
+            SWReg::SRAMMask(m)  => { self.sram_cfgs[m as usize].wmask_bits = data; }
+            SWReg::SRAMWidth(m) => { self.sram_cfgs[m as usize].width_bits = data; }
+            SWReg::SRAMInitData => {
+                // Shift in the next (less significant) word of the entry
+                let word_bits = self.cfg.axil.data_bits as usize;
+                let mut bits: Vec<Bit> = (0..word_bits)
+                    .map(|i| if i < 32 { ((data >> i) & 1) as Bit } else { 0 })
+                    .collect();
+                bits.extend(self.sram_init_data.iter());
+                bits.truncate(self.emul().sram_width as usize);
+                self.sram_init_data = bits;
+            }
+            SWReg::SRAMInitAddr(m) => {
+                let entry = (data, self.sram_init_data.clone());
+                match self.sram_inits.get_mut(&m) {
+                    Some(inits) => { inits.push(entry); }
+                    None => { self.sram_inits.insert(m, vec![entry]); }
+                }
+            }
+            SWReg::DMemInit => {
+                // {midx, pidx, idx, sdm, bit}. Mirrors `dmem_init_word`
+                let emul = self.emul();
+                let idx_bits  = emul.index_bits();
+                let proc_bits = emul.num_proc_bits();
+                let pidx_lo = 2 + idx_bits;
+                let midx_lo = pidx_lo + proc_bits;
+                let bit  = (data & 1) as Bit;
+                let sdm  = (data >> 1) & 1 == 1;
+                let addr = (data >> 2) & ((1 << idx_bits) - 1);
+                let p    = (data >> pidx_lo) & ((1 << proc_bits) - 1);
+                let m    = data >> midx_lo;
+                if m >= emul.num_mods || p >= emul.num_procs {
+                    return Err(format!("SWSim: dmem init for module {} proc {} out of range", m, p).into());
+                }
+                match self.board.as_mut() {
+                    Some(board) => { board.init_dmem_bit(m, p, sdm, addr, bit); }
+                    None => {}
+                }
+                self.dmem_inits.push((m, p, sdm, addr, bit));
+            }
+            SWReg::Fingerprint  => { self.fingerprint = data; }
+            SWReg::HostSteps    => {
+                if data != self.host_steps {
+                    self.host_steps_prv_q.push_back(self.host_steps);
+                    self.host_steps_cur_q.push_back(data);
+                }
+                self.host_steps = data;
+            }
+            SWReg::PllReset => {
+                if data != 0 {
+                    self.pll_locked = false;
+                }
+            }
+            SWReg::FPGATopResetn => {
+                self.fpga_top_resetn = data != 0;
+                if !self.fpga_top_resetn {
+                    self.reset();
+                }
+            }
+            SWReg::ClkWizFingerprint => { self.clkwiz_fingerprint = data; }
+            SWReg::PllResetCycle     => { self.pll_reset_cycle = data; }
+            _ => {
+                return Err(format!("SWSim: write to read only register {:?}", reg).into());
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl SimIf for SWSim {
+    /// Locks the PLL
+    fn init(self: &mut Self) {
+        self.pll_locked = true;
+    }
+
+    fn finish(self: &mut Self) {
+    }
+
+    fn step(self: &mut Self) {
+        self.cycle += 1;
+        let io_bytes = self.io_stream_bytes() as usize;
+        while self.init_done() && self.io_in_q.len() >= io_bytes {
+            self.run_target_cycle();
+        }
+    }
+
+    fn step_debug(self: &mut Self) {
+        self.step();
+    }
+
+    fn push(self:  &mut Self, addr: u32, data: &Vec<u8>) -> Result<u32, SimIfErr> {
+        let stream = match self.streams.get(&addr) {
+            Some(s) => *s,
+            None => {
+                return Err(format!("SWSim: push to unmapped DMA address {:x}", addr).into());
+            }
+        };
+
+        match stream {
+            SWStream::IO  => { self.io_in_q.extend(data.iter()); }
+            SWStream::DMA => { self.dma_q.extend(data.iter()); }
+            SWStream::Inst => {
+                for beat in data.chunks(self.inst_stream_bytes() as usize) {
+                    self.push_inst(&beat.to_vec())?;
+                }
+            }
+            SWStream::Dbg => {
+                return Err("SWSim: debug stream cannot be pushed".into());
+            }
+        }
+        return Ok(data.len() as u32);
+    }
+
+    fn pull(self:  &mut Self, addr: u32, data: &mut Vec<u8>) -> Result<u32, SimIfErr> {
+        let stream = match self.streams.get(&addr) {
+            Some(s) => *s,
+            None => {
+                return Err(format!("SWSim: pull from unmapped DMA address {:x}", addr).into());
+            }
+        };
+        if stream == SWStream::Inst {
+            return Err("SWSim: instruction stream cannot be pulled".into());
+        }
+
+        let q = self.stream_queue(stream);
+        let size = std::cmp::min(data.len(), q.len());
+        for (i, b) in q.drain(0..size).enumerate() {
+            data[i] = b;
+        }
+        return Ok(size as u32);
+    }
+
+    fn read(self:  &mut Self, addr: u32) -> Result<u32, SimIfErr> {
+        match self.regs.get(&addr) {
+            Some(reg) => {
+                let reg = *reg;
+                Ok(self.read_reg(reg))
+            }
+            None => Err(format!("SWSim: read from unmapped MMIO address {:x}", addr).into())
+        }
+    }
+
+    fn write(self: &mut Self, addr: u32, data: u32) -> Result<(), SimIfErr> {
+        match self.regs.get(&addr) {
+            Some(reg) => {
+                let reg = *reg;
+                self.write_reg(reg, data)
+            }
+            None => Err(format!("SWSim: write to unmapped MMIO address {:x}", addr).into())
+        }
+    }
+}
+
+/// Placeholder `SimIf` of a `Driver` whose MMIO address map is being bound
+/// to a `SWSim`. Every access fails
+#[derive(Debug)]
+struct SWSimUnbound;
+
+impl SimIf for SWSimUnbound {
+    fn init(self: &mut Self) {}
+    fn finish(self: &mut Self) {}
+    fn step(self: &mut Self) {}
+    fn step_debug(self: &mut Self) {}
+
+    fn push(self:  &mut Self, _addr: u32, _data: &Vec<u8>) -> Result<u32, SimIfErr> {
+        Err("SWSim: driver is not bound yet".into())
+    }
+
+    fn pull(self:  &mut Self, _addr: u32, _data: &mut Vec<u8>) -> Result<u32, SimIfErr> {
+        Err("SWSim: driver is not bound yet".into())
+    }
+
+    fn read(self:  &mut Self, _addr: u32) -> Result<u32, SimIfErr> {
+        Err("SWSim: driver is not bound yet".into())
+    }
+
+    fn write(self: &mut Self, _addr: u32, _data: u32) -> Result<(), SimIfErr> {
+        Err("SWSim: driver is not bound yet".into())
+    }
+}
+
+impl Driver {
+    /// Create a `Driver` that is backed by the software model of FPGATop.
+    /// When `debug` is set, the LDM & SDM write bits of every host step are
+    /// pushed into the `dbg_bridge` (the `emul_debug` build option).
+    pub fn try_from_swsim(cfg: &FPGATopConfig, debug: bool) -> Self {
+        let mut driver = Driver::try_from_simif(Box::new(SWSimUnbound));
+        let mut swsim = SWSim::new(cfg);
+        swsim.debug = debug;
+        swsim.bind(&driver);
+        driver.simif = Box::new(swsim);
+        return driver;
+    }
+}
@@ -0,0 +1,120 @@
+use std::env;
+use std::fs;
+
+/// Number of per module register sets (SRAM configs & debug counters) in the
+/// register map. `SWSim::bind` only maps the ones of the modules in use
+const NUM_MODS: u32 = 64;
+
+/// Hands out consecutive 4 byte MMIO addresses
+struct MMIOAlloc {
+    next: u32,
+}
+
+impl MMIOAlloc {
+    fn alloc(self: &mut Self) -> u32 {
+        let addr = self.next;
+        self.next += 4;
+        return addr;
+    }
+}
+
+/// Derive the `Driver` register map of FPGATop.
+/// `SWSim` binds its registers to whatever addresses the `Driver` uses, so the
+/// map only has to be unique and doesn't have to match the Chisel build
+fn driver_mmap() -> String {
+    let mut mmio = MMIOAlloc { next: 0 };
+    let mut dmas = String::new();
+    let bridges = ["io_bridge", "inst_bridge", "dma_bridge", "dbg_bridge"];
+    for (i, name) in bridges.iter().enumerate() {
+        let filled = mmio.alloc();
+        let empty  = mmio.alloc();
+        dmas += &format!("
+          {}: PushPullDMAIf::new({:#x}, {:#x}, {:#x}),", name, i * 0x1000, filled, empty);
+    }
+
+    let custom_resetn = mmio.alloc();
+    let mut srams = vec![];
+    for _ in 0..NUM_MODS {
+        srams.push(format!("SRAMConfig::new({}, {}, {}, {})",
+            mmio.alloc(), mmio.alloc(), mmio.alloc(), mmio.alloc()));
+    }
+    let mut dbg_init_cntrs = vec![];
+    for _ in 0..NUM_MODS {
+        dbg_init_cntrs.push(format!("RdMMIOIf::new({:#x})", mmio.alloc()));
+    }
+
+    let regs = [
+        ("sram_init_data",     "WrMMIOIf"),
+        ("dmem_init",          "WrMMIOIf"),
+        ("fingerprint",        "RdWrMMIOIf"),
+        ("host_steps",         "RdWrMMIOIf"),
+        ("host_steps_prv_deq", "RdMMIOIf"),
+        ("host_steps_prv_cnt", "RdMMIOIf"),
+        ("host_steps_cur_deq", "RdMMIOIf"),
+        ("host_steps_cur_cnt", "RdMMIOIf"),
+        ("init_done",          "RdMMIOIf"),
+        ("tot_insts_pushed",   "RdMMIOIf"),
+        ("pcs_are_zero",       "RdMMIOIf"),
+        ("dbg_proc_0_init",    "RdMMIOIf"),
+        ("dbg_proc_n_init",    "RdMMIOIf"),
+        ("midx_mismatch_deq",  "RdMMIOIf"),
+        ("midx_mismatch_cnt",  "RdMMIOIf"),
+        ("pidx_mismatch_deq",  "RdMMIOIf"),
+        ("pidx_mismatch_cnt",  "RdMMIOIf"),
+        ("target_cycle_lo",    "RdMMIOIf"),
+        ("target_cycle_hi",    "RdMMIOIf"),
+    ];
+    let mut ctrl_regs = String::new();
+    for (name, mmio_type) in regs.iter() {
+        ctrl_regs += &format!("
+            {}: {}::new({:#x}),", name, mmio_type, mmio.alloc());
+    }
+
+    return format!(r#"
+    impl Driver {{
+      pub fn try_from_simif(simif: Box<dyn SimIf>) -> Self {{
+        Self {{
+          simif: simif,{}
+          clkwiz_ctrl: ClockWizardControlIf {{
+            pll_locked:      RdMMIOIf::new(0x10000),
+            pll_reset:       WrMMIOIf::new(0x10004),
+            fpga_top_resetn: WrMMIOIf::new(0x10008),
+            fingerprint:     RdWrMMIOIf::new(0x1000c),
+            pll_reset_cycle: WrMMIOIf::new(0x10010),
+          }},
+          ctrl_bridge: ControlIf {{
+            custom_resetn: WrMMIOIf::new({:#x}),
+            sram: vec![
+              {}
+            ],
+            dbg_init_cntrs: vec![
+              {}
+            ],{}
+          }}
+        }}
+      }}
+    }}
+    "#, dmas, custom_resetn, srams.join(",\n              "), dbg_init_cntrs.join(",\n              "), ctrl_regs);
+}
+
+fn generate_driver_impl() -> std::io::Result<()> {
+    let dst_path = format!("{}/src/simif/driver_generated.rs", env::current_dir()?.to_str().unwrap());
+    let macro_code = format!(r#"
+    use crate::simif::simif::*;
+    use crate::simif::mmioif::*;
+    use crate::simif::dmaif::*;
+
+    {}
+    "#, driver_mmap());
+
+    fs::write(&dst_path, macro_code)?;
+    return Ok(());
+}
+
+fn main() -> std::io::Result<()> {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    generate_driver_impl()?;
+
+    return Ok(());
+}
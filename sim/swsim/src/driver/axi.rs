@@ -0,0 +1 @@
+../../../driver/axi.rs
\ No newline at end of file
@@ -0,0 +1 @@
+../../../driver/dram.rs
\ No newline at end of file
@@ -0,0 +1 @@
+../../../driver/driver.rs
\ No newline at end of file
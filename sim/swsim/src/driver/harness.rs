@@ -0,0 +1 @@
+../../../driver/harness.rs
\ No newline at end of file
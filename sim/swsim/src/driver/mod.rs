@@ -0,0 +1 @@
+../../../driver/mod.rs
\ No newline at end of file
@@ -0,0 +1 @@
+../../../driver/tsi.rs
\ No newline at end of file
@@ -0,0 +1,131 @@
+pub mod simif;
+pub mod driver;
+
+use bee::{
+    common::{
+        clocks::add_clock_enable_stimuli,
+        config::Args,
+        hwgraph::NodeMapInfo,
+        instruction::*,
+        mapping::SRAMMapping,
+        network::Coordinate,
+        primitive::Primitive
+    },
+    rtlsim::rtlsim_utils::get_input_stimuli_blasted,
+    testing::try_new_hw_circuit
+};
+use indexmap::IndexMap;
+use std::collections::VecDeque;
+use simif::{
+    simif::*,
+    mmioif::*
+};
+use driver::{
+    axi::*,
+    driver::*
+};
+
+/// Runs the same driver flow as the metasim `start_test`, but against the
+/// software model of FPGATop (`SWSim`) instead of the verilated RTL
+pub fn start_test_swsim(args: &Args) -> Result<(), SimIfErr> {
+    let circuit = try_new_hw_circuit(&args)?;
+
+    // Aggregate per module instructions
+    let mut module_insts: IndexMap<u32, VecDeque<Instruction>> = IndexMap::new();
+    let mut sram_cfgs: IndexMap<u32, SRAMMapping> = IndexMap::new();
+    for (m, mmap) in circuit.emul.module_mappings.iter() {
+        let mut insts: VecDeque<Instruction> = VecDeque::new();
+        let mut mmap_ = mmap.clone();
+        mmap_.proc_mappings.sort_keys();
+        for (_, pmap) in mmap_.proc_mappings.iter() {
+            insts.extend(pmap.instructions.clone());
+        }
+        module_insts.insert(*m, insts);
+        sram_cfgs.insert(*m, mmap.sram_mapping.clone());
+    }
+
+    // Get the input stimuli
+    let mut input_stimuli_blasted =
+        get_input_stimuli_blasted(&args.top_mod, &args.input_stimuli_path, &args.sv_file_path)?;
+    add_clock_enable_stimuli(&mut input_stimuli_blasted, &circuit.compiler_cfg.clocks);
+
+    // Aggregate signal mappings
+    let mut all_signal_map: IndexMap<String, NodeMapInfo> = IndexMap::new();
+    for (_, mmap) in circuit.emul.module_mappings.iter() {
+        for (_, pmap) in mmap.proc_mappings.iter() {
+            all_signal_map.extend(pmap.signal_map.clone());
+        }
+    }
+
+    // Map the input stimuli to a coordinate
+    let mut mapped_input_stimulti_blasted: IndexMap<Coordinate, VecDeque<u64>> = IndexMap::new();
+    for (sig, stim) in input_stimuli_blasted.iter() {
+        match all_signal_map.get(sig) {
+            Some(nmi) =>  {
+                let coord = nmi.info.coord;
+                mapped_input_stimulti_blasted.insert(coord, VecDeque::from(stim.clone()));
+            }
+            None =>  { println!("Input Signal {} not found", sig); }
+        }
+    }
+
+    let mut output_signals: IndexMap<String, Coordinate> = IndexMap::new();
+    for nidx in circuit.graph.node_indices() {
+        let node = circuit.graph.node_weight(nidx).unwrap();
+        if node.is() == Primitive::Output {
+            assert!(all_signal_map.contains_key(node.name()),
+                    "Output {} not found in signal map",
+                    node.name());
+            output_signals.insert(node.name().to_string(), node.info().coord);
+        }
+    }
+
+    let fpga_top_cfg = FPGATopConfig {
+        axi: AXI4Config {
+            addr_bits: 64,
+            id_bits: 4,
+            data_bits: 512
+        },
+        axil: AXI4Config {
+            addr_bits: 64,
+            id_bits: 4,
+            data_bits: 32
+        },
+        emul: circuit.platform_cfg.clone()
+    };
+
+    let host_steps = circuit.emul.host_steps;
+    let mut driver = Driver::try_from_swsim(&fpga_top_cfg, false);
+
+    pll_lock_and_fpga_top_reset(&mut driver)?;
+    board_reset(&mut driver, &fpga_top_cfg)?;
+    test_dma_bridge(&mut driver, 20, &fpga_top_cfg)?;
+    set_target_config_regs(&mut driver, &sram_cfgs, host_steps)?;
+    push_sram_init(&mut driver, &circuit, &fpga_top_cfg)?;
+    push_instructions(&mut driver, module_insts, host_steps, &fpga_top_cfg)?;
+
+    // Wait until initialization is finished
+    while driver.ctrl_bridge.init_done.read(&mut driver.simif)? == 0 {
+        driver.simif.step();
+    }
+
+    push_dmem_init(&mut driver, &circuit, &fpga_top_cfg)?;
+
+    println!("Start simulation");
+
+    let mismatch = run_from_trace(
+        &mut driver,
+        &circuit,
+        &input_stimuli_blasted,
+        &all_signal_map,
+        &output_signals,
+        &mut mapped_input_stimulti_blasted,
+        &fpga_top_cfg)?;
+    driver.simif.finish();
+
+    if mismatch {
+        Err("SWSim output mismatch".into())
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1 @@
+../../../simif/dmaif.rs
\ No newline at end of file
@@ -0,0 +1 @@
+../../../simif/mmioif.rs
\ No newline at end of file
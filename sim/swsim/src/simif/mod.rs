@@ -0,0 +1 @@
+../../../simif/mod.rs
\ No newline at end of file
@@ -0,0 +1 @@
+../../../simif/simif.rs
\ No newline at end of file
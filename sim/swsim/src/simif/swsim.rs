@@ -0,0 +1 @@
+../../../simif/swsim.rs
\ No newline at end of file
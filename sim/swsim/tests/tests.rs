@@ -0,0 +1,119 @@
+#[cfg(test)]
+pub mod swsim_test {
+    use swsim::start_test_swsim;
+    use bee::common::config::{Args, KaMinParConfig, PassManagerConfig, PartitionerKind, RefSimulator, TopologyKind};
+    use swsim::simif::simif::SimIfErr;
+    use test_case::test_case;
+
+    fn test_emulator_swsim(
+        sv_file_path: &str,
+        top_mod: &str,
+        input_stimuli_path: &str,
+        blif_file_path: &str,
+    ) -> Result<(), SimIfErr> {
+        let args = Args {
+            verbose:             false,
+            sim_dir:             format!("../sim-dir/swsim-{}", top_mod),
+            sv_file_path:        sv_file_path.to_string(),
+            top_mod:             top_mod.to_string(),
+            input_stimuli_path:  input_stimuli_path.to_string(),
+            blif_file_path:      blif_file_path.to_string(),
+            vcd:                 None,
+            ref_sim:             RefSimulator::default(),
+            instance_path:       "testharness.top".to_string(),
+            clock_start_low:     false,
+            timesteps_per_cycle: 2,
+            ref_skip_cycles:     4,
+            no_check_cycles:     0,
+            check_cycle_period:  1,
+            num_mods:            17,
+            num_procs:           64,
+            topology:            TopologyKind::Hub,
+            max_steps:           1024,
+            lut_inputs:          3,
+            inter_proc_nw_lat:   1,
+            inter_mod_nw_lat:    1,
+            imem_lat:            1,
+            dmem_rd_lat:         0,
+            dmem_wr_lat:         1,
+            sram_width:          256,
+            sram_entries:        16384,
+            sram_rd_ports:       1,
+            sram_wr_ports:       1,
+            sram_rd_lat:         1,
+            sram_wr_lat:         1,
+            sram_ip_pl:          1,
+            sram_slots:          1,
+            sram_init:           vec![],
+            clocks:              vec![],
+            lut_map:             false,
+            partition_constraints: None,
+            partitioner:         PartitionerKind::default(),
+            repartition_iters:   0,
+            repartition_time_limit: None,
+            compiled:            None,
+            kaminpar_cfg:        KaMinParConfig::default(),
+            pass_cfg:            PassManagerConfig::default(),
+            dbg_tail_length:     u32::MAX, // don't print debug graph when testing
+            dbg_tail_threshold:  u32::MAX  // don't print debug graph when testing
+        };
+
+        return start_test_swsim(&args);
+    }
+
+    #[test_case("Adder"; "Adder Test")]
+    #[test_case("TestRegInit"; "TestRegInit Test")]
+    #[test_case("RegInitOne"; "RegInitOne Test")]
+    #[test_case("Const"; "Const Test")]
+    #[test_case("GCD"; "GCD Test")]
+    #[test_case("ShiftReg"; "ShiftReg Test")]
+    #[test_case("Fir"; "Fir Test")]
+    #[test_case("MyQueue"; "MyQueue Test")]
+    #[test_case("PointerChasing"; "PointerChasing Test")]
+    #[test_case("SinglePortSRAM"; "SinglePortSRAM Test")]
+    #[test_case("OneReadOneWritePortSRAM"; "OneReadOneWritePortSRAM Test")]
+    pub fn test(top: &str) {
+        match test_emulator_swsim(
+            &format!("../../examples/{}.sv", top),
+            &top,
+            &format!("../../examples/{}.input", top),
+            &format!("../../examples/{}.lut.blif", top))
+        {
+            Ok(_) => {}
+            Err(e) => {
+                assert!(false, "{} failed on SWSim: {}", top, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod dmem_init_test {
+    use bee::common::config::PlatformConfig;
+    use swsim::driver::driver::dmem_init_word;
+
+    #[test]
+    pub fn test_dmem_init_word_fields() {
+        let mut emul = PlatformConfig::default();
+        emul.num_mods = 9;
+        emul.num_procs = 8;
+        let word = dmem_init_word(&emul, 8, 7, true, 3, 1).unwrap();
+        let pidx_lo = 2 + emul.index_bits();
+        assert_eq!(word & 0b11, 0b11);
+        assert_eq!((word >> 2) & ((1 << emul.index_bits()) - 1), 3);
+        assert_eq!((word >> pidx_lo) & 0b111, 7);
+        assert_eq!(word >> (pidx_lo + emul.num_proc_bits()), 8);
+
+        assert!(dmem_init_word(&emul, 9, 0, false, 0, 1).is_err());
+        assert!(dmem_init_word(&emul, 0, 0, false, emul.max_steps, 1).is_err());
+    }
+
+    #[test]
+    pub fn test_dmem_init_word_overflow() {
+        // 2 + 7 index bits + 12 proc bits + 12 module bits don't fit in 32 bits
+        let mut emul = PlatformConfig::default();
+        emul.num_mods = 1 << 12;
+        emul.num_procs = 1 << 12;
+        assert!(dmem_init_word(&emul, 0, 0, false, 0, 1).is_err());
+    }
+}